@@ -2,14 +2,118 @@ use std::env;
 use std::fs;
 use std::path::Path;
 
+/// Option values a request accepts, as (TypeScript name, source file, Rust enum with a `parse` fn)
+const OPTION_ENUMS: &[(&str, &str, &str)] = &[
+    ("BodyCompression", "compress.rs", "BodyEncoding"),
+    ("BodyHashAlgorithm", "body_hash.rs", "HashAlgorithm"),
+    ("BodyParser", "parse.rs", "BodyParser"),
+    ("CookiePolicy", "cookie_jar.rs", "CookiePolicy"),
+    ("IpStrategy", "dns.rs", "IpStrategy"),
+    ("ProxyPoolStrategy", "proxy_pool.rs", "ProxyStrategy"),
+    ("RequestCacheMode", "http_cache.rs", "CacheMode"),
+];
+
+/// Option objects a request accepts, as (source file, Rust struct deriving `Deserialize`)
+const OPTION_STRUCTS: &[(&str, &str)] = &[("client.rs", "RedirectPolicy"), ("retry.rs", "RetryPolicy")];
+
+/// Objects passed between JS and Rust, as (TypeScript name, source file, Rust struct, fn that builds the
+/// JS object, if Rust is the side that builds it)
+const NATIVE_STRUCTS: &[(&str, &str, &str, Option<&str>)] = &[
+    ("NativeConnectionOptions", "options.rs", "ConnectionInput", None),
+    ("NativeRequestOptions", "options.rs", "RequestInput", None),
+    ("NativeResponse", "client.rs", "Response", Some("response_to_js_object")),
+];
+
+/// TypeScript types of the Rust field types in `NATIVE_STRUCTS` that `typescript_type` doesn't cover
+const NATIVE_TYPES: &[(&str, &str)] = &[
+    ("&'static str", "string"),
+    ("&'static Encoding", "string"),
+    ("Vec<u8>", "Buffer"),
+    ("Vec<(String, String)>", "HeaderTuple[]"),
+    ("Vec<(usize, Vec<u8>)>", "[number, Buffer][]"),
+    ("IndexMap<String, String>", "Record<string, string>"),
+    ("Vec<ResponseCookie>", "NativeCookie[]"),
+    ("Vec<RedirectHop>", "NativeRedirectHop[]"),
+    ("Vec<Decoding>", "BodyDecoding[]"),
+    ("Timings", "Timings"),
+    ("ConnectionInfo", "NativeConnectionInfo"),
+    ("PeerCertificate", "NativeTlsInfo"),
+    ("fingerprint::Report", "ConnectionFingerprint"),
+    ("ChallengeInfo", "ChallengeInfo"),
+    ("BodyHash", "string"),
+    ("serde_json::Value", "unknown"),
+];
+
+/// Names a TypeScript type may use without importing them
+const GLOBAL_TYPES: &[&str] = &["Buffer", "Pick", "Promise", "Record"];
+
+/// A field of an option object as JS sees it
+struct OptionField {
+    key: String,
+    ts_type: String,
+    doc: String,
+    default: Option<String>,
+}
+
 fn main() {
     // Dynamically extract all browser profiles from wreq-util by reading the source
-    let profiles = extract_profiles_from_source();
+    let emulation_source = read_emulation_source();
+    let profiles = extract_profiles(&emulation_source);
 
     println!("cargo:warning=Found {} browser profiles", profiles.len());
 
-    // Generate TypeScript type definition
-    let ts_type = generate_typescript_type(&profiles);
+    // Read the error codes and their descriptions from the ErrorKind enum
+    let error_codes = extract_error_codes();
+
+    // Interfaces of the objects passed between JS and Rust, which take the types this file doesn't
+    // define from ./types
+    let mut defined = vec!["BrowserProfile", "EmulationOs", "ErrorCode"];
+    defined.extend(OPTION_ENUMS.iter().map(|(ts_name, _, _)| *ts_name));
+    defined.extend(OPTION_STRUCTS.iter().map(|(_, rust_name)| *rust_name));
+    defined.extend(NATIVE_STRUCTS.iter().map(|(ts_name, _, _, _)| *ts_name));
+
+    let mut native_types = String::new();
+    let mut imports = Vec::new();
+
+    for (ts_name, file, rust_name, builder) in NATIVE_STRUCTS {
+        let (content, ts_types) = generate_native_interface(ts_name, file, rust_name, *builder);
+        native_types.push_str(&content);
+        for name in ts_types.iter().flat_map(|ts| ts.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')) {
+            if name.starts_with(|c: char| c.is_ascii_uppercase())
+                && !defined.contains(&name)
+                && !GLOBAL_TYPES.contains(&name)
+                && !imports.iter().any(|import| import == name)
+            {
+                imports.push(name.to_string());
+            }
+        }
+    }
+    imports.sort();
+
+    // Generate TypeScript type definitions
+    let mut ts_type = generate_typescript_type(&profiles, &imports);
+
+    let emulation_os: Vec<(String, String)> = extract_emulation_os(&emulation_source)
+        .into_iter()
+        .map(|os| (os, String::new()))
+        .collect();
+    let emulation_os_doc = "Operating system a browser profile presents as, in its User-Agent, client hints \
+        and platform headers";
+    ts_type.push_str(&generate_union_type("EmulationOs", emulation_os_doc, &emulation_os));
+
+    for (ts_name, file, rust_name) in OPTION_ENUMS {
+        let (doc, values) = extract_option_enum(file, rust_name);
+        ts_type.push_str(&generate_union_type(ts_name, &doc, &values));
+    }
+
+    for (file, rust_name) in OPTION_STRUCTS {
+        let (doc, fields) = extract_option_struct(file, rust_name);
+        ts_type.push_str(&generate_interface(rust_name, &doc, &fields));
+    }
+
+    ts_type.push_str(&native_types);
+
+    ts_type.push_str(&generate_error_code_type(&error_codes));
 
     // Generate Rust profiles array
     let rust_profiles = generate_rust_profiles(&profiles);
@@ -28,11 +132,13 @@ fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 }
 
-fn generate_typescript_type(profiles: &[String]) -> String {
+fn generate_typescript_type(profiles: &[String], imports: &[String]) -> String {
     let mut ts_content = String::from(
         "/**\n * Auto-generated from Rust build script\n * DO NOT EDIT MANUALLY\n */\n\n"
     );
 
+    ts_content.push_str(&generate_imports(imports));
+
     ts_content.push_str("/**\n * Browser profile names supported\n */\n");
     ts_content.push_str("export type BrowserProfile =\n");
 
@@ -48,6 +154,187 @@ fn generate_typescript_type(profiles: &[String]) -> String {
     ts_content
 }
 
+fn generate_error_code_type(codes: &[(String, String)]) -> String {
+    let mut ts_content = String::from("\n/**\n * Failure class of a {@link RequestError}.\n *\n");
+
+    for (code, description) in codes {
+        ts_content.push_str(&format!(" * - `{}` - {}\n", code, description));
+    }
+
+    ts_content.push_str(" */\nexport type ErrorCode =\n");
+
+    for (i, (code, _)) in codes.iter().enumerate() {
+        let end = if i == codes.len() - 1 { ";" } else { "" };
+        ts_content.push_str(&format!("  | '{}'{}\n", code, end));
+    }
+
+    // A runtime list too, for telling native error codes from anything else
+    ts_content.push_str("\n/**\n * Every {@link ErrorCode}, in declaration order\n */\n");
+    ts_content.push_str("export const ERROR_CODES: readonly ErrorCode[] = [\n");
+
+    for (code, _) in codes {
+        ts_content.push_str(&format!("  '{}',\n", code));
+    }
+
+    ts_content.push_str("];\n");
+
+    ts_content
+}
+
+fn generate_union_type(name: &str, doc: &str, values: &[(String, String)]) -> String {
+    let mut ts_content = format!("\n/**\n{}", doc_comment_lines(doc, ""));
+
+    // Only the values with a description of their own are listed
+    let described: Vec<_> = values.iter().filter(|(_, description)| !description.is_empty()).collect();
+    if !described.is_empty() {
+        ts_content.push_str(" *\n");
+    }
+    for (value, description) in described {
+        ts_content.push_str(&format!(" * - `'{}'` - {}\n", value, description));
+    }
+
+    ts_content.push_str(&format!(" */\nexport type {} =\n", name));
+
+    for (i, (value, _)) in values.iter().enumerate() {
+        let end = if i == values.len() - 1 { ";" } else { "" };
+        ts_content.push_str(&format!("  | '{}'{}\n", value, end));
+    }
+
+    ts_content
+}
+
+fn generate_interface(name: &str, doc: &str, fields: &[OptionField]) -> String {
+    let mut ts_content = format!("\n/**\n{} */\nexport interface {} {{\n", doc_comment_lines(doc, ""), name);
+
+    for field in fields {
+        ts_content.push_str("  /**\n");
+        ts_content.push_str(&doc_comment_lines(&field.doc, "  "));
+        if let Some(default) = &field.default {
+            ts_content.push_str(&format!("   * @default {}\n", default));
+        }
+        ts_content.push_str("   */\n");
+        ts_content.push_str(&format!("  {}?: {};\n", field.key, field.ts_type));
+    }
+
+    ts_content.push_str("}\n");
+
+    ts_content
+}
+
+fn generate_imports(names: &[String]) -> String {
+    let mut ts_content = String::from("import type {\n");
+
+    for name in names {
+        ts_content.push_str(&format!("  {},\n", name));
+    }
+
+    ts_content.push_str("} from './types';\n\n");
+
+    ts_content
+}
+
+// An interface for an object passed between JS and Rust, with the TypeScript types of its fields
+fn generate_native_interface(
+    ts_name: &str,
+    file: &str,
+    rust_name: &str,
+    builder: Option<&str>,
+) -> (String, Vec<String>) {
+    let content = read_source(file);
+    let lines: Vec<&str> = content.lines().collect();
+
+    // Structs holding JS handles carry a lifetime, like: pub struct RequestInput<'a> {
+    let headers = [format!("pub struct {} {{", rust_name), format!("pub struct {}<'a> {{", rust_name)];
+    let start = lines.iter().position(|line| headers.iter().any(|header| line.trim() == header))
+        .unwrap_or_else(|| panic!("{} struct not found in src/{}", rust_name, file));
+
+    // Keys the builder sets, and whether it sets them only sometimes
+    let built = builder.map(|name| extract_js_keys(name)).unwrap_or_default();
+
+    let mut extends = Vec::new();
+    let mut fields = String::new();
+    let mut ts_types = Vec::new();
+
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim();
+        if line == "}" {
+            break;
+        }
+        let Some((field, rest)) = line.strip_prefix("pub ").and_then(|rest| rest.split_once(": ")) else {
+            continue;
+        };
+        // A trailing comment gives the TypeScript type, like: pub ca: Option<Handle<'a, JsValue>>, // string | Buffer
+        let (rust_type, comment) = match rest.split_once(", // ") {
+            Some((rust_type, comment)) => (rust_type, Some(comment)),
+            None => (rest.trim_end_matches(','), None),
+        };
+
+        // A field holding another of these structs stands for all of its fields
+        let base = rust_type.split('<').next().unwrap_or_default();
+        if let Some((parent, _, _, _)) = NATIVE_STRUCTS.iter().find(|(_, _, name, _)| *name == base) {
+            extends.push(*parent);
+            continue;
+        }
+
+        let key = to_camel_case(field);
+        let optional = match builder {
+            Some(name) => match built.iter().find(|(built_key, _)| *built_key == key) {
+                Some((_, sometimes)) => *sometimes,
+                None => panic!("{} doesn't set {} of {}", name, key, rust_name),
+            },
+            None => rust_type.starts_with("Option<"),
+        };
+        let ts_type = native_type(rust_type, comment);
+
+        let doc = doc_lines_above(&lines, i);
+        if doc.is_empty() {
+            panic!("No doc for {}::{} in src/{}", rust_name, field, file);
+        }
+        fields.push_str("  /**\n");
+        for doc_line in doc {
+            fields.push_str(format!("   * {}", doc_line).trim_end());
+            fields.push('\n');
+        }
+        fields.push_str("   */\n");
+        fields.push_str(&format!("  {}{}: {};\n", key, if optional { "?" } else { "" }, ts_type));
+        ts_types.push(ts_type);
+    }
+
+    let mut ts_content = String::from("\n/**\n");
+    for doc_line in doc_lines_above(&lines, start) {
+        ts_content.push_str(format!(" * {}", doc_line).trim_end());
+        ts_content.push('\n');
+    }
+    ts_content.push_str(" *\n * @internal\n */\n");
+    ts_content.push_str(&format!("export interface {}", ts_name));
+    if !extends.is_empty() {
+        ts_content.push_str(&format!(" extends {}", extends.join(", ")));
+    }
+    ts_content.push_str(&format!(" {{\n{}}}\n", fields));
+
+    (ts_content, ts_types)
+}
+
+// Doc text as ` * ` lines of at most 100 columns
+fn doc_comment_lines(text: &str, indent: &str) -> String {
+    let mut lines = String::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && indent.len() + line.len() + word.len() + 4 > 100 {
+            lines.push_str(&format!("{} * {}\n", indent, line));
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    lines.push_str(&format!("{} * {}\n", indent, line));
+    lines
+}
+
 fn generate_rust_profiles(profiles: &[String]) -> String {
     let mut rust_content = String::from(
         "// Auto-generated from build script\n// DO NOT EDIT MANUALLY\n\n"
@@ -64,7 +351,305 @@ fn generate_rust_profiles(profiles: &[String]) -> String {
     rust_content
 }
 
-fn extract_profiles_from_source() -> Vec<String> {
+// Read a file of this crate's source, rebuilding whenever it changes
+fn read_source(file: &str) -> String {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/{}", file);
+
+    fs::read_to_string(Path::new(&manifest_dir).join("src").join(file))
+        .unwrap_or_else(|_| panic!("Failed to read src/{}", file))
+}
+
+// The `///` lines above `lines[index]`, past any attributes, joined into one line
+fn doc_above(lines: &[&str], index: usize) -> String {
+    let mut doc = Vec::new();
+
+    for line in lines[..index].iter().rev().map(|line| line.trim()) {
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.trim());
+        } else if !line.starts_with("#[") {
+            break;
+        }
+    }
+
+    doc.reverse();
+    doc.join(" ")
+}
+
+// The `///` lines above `lines[index]`, past any attributes, as they are written
+fn doc_lines_above(lines: &[&str], index: usize) -> Vec<String> {
+    let mut doc = Vec::new();
+
+    for line in lines[..index].iter().rev().map(|line| line.trim()) {
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.strip_prefix(' ').unwrap_or(text).trim_end().to_string());
+        } else if !line.starts_with("#[") {
+            break;
+        }
+    }
+
+    doc.reverse();
+    doc
+}
+
+// The `#[...]` lines above `lines[index]`, past any doc comments
+fn attributes_above<'a>(lines: &[&'a str], index: usize) -> Vec<&'a str> {
+    lines[..index]
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .take_while(|line| line.starts_with("///") || line.starts_with("#["))
+        .filter(|line| line.starts_with("#["))
+        .collect()
+}
+
+// "The request exceeded..." reads as "- `ERR_TIMEOUT` - the request exceeded...", but "IPv4..." stays
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if !chars.clone().next().is_some_and(char::is_uppercase) => {
+            first.to_lowercase().chain(chars).collect()
+        }
+        _ => text.to_string(),
+    }
+}
+
+fn extract_option_enum(file: &str, name: &str) -> (String, Vec<(String, String)>) {
+    let content = read_source(file);
+    let lines: Vec<&str> = content.lines().collect();
+
+    let header = format!("pub enum {} {{", name);
+    let start = lines.iter().position(|line| line.trim() == header)
+        .unwrap_or_else(|| panic!("{} enum not found in src/{}", name, file));
+
+    // Variants and their doc lines, in declaration order
+    let mut variants = Vec::new();
+
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim();
+        if line == "}" {
+            break;
+        }
+        if line.starts_with("//") {
+            continue;
+        }
+        if let Some(variant) = line.strip_suffix(',') {
+            variants.push((variant.to_string(), lowercase_first(&doc_above(&lines, i))));
+        }
+    }
+
+    // Values from the arms of the `parse` fn, like: "no-store" => Some(Self::NoStore),
+    let arms = content.split(&format!("impl {} {{", name)).nth(1)
+        .and_then(|rest| rest.split("_ => None").next())
+        .unwrap_or_else(|| panic!("No {}::parse in src/{}", name, file));
+
+    let mut values = Vec::new();
+
+    for line in arms.lines().map(str::trim) {
+        let Some((value, arm)) = line.split_once("\" => Some(") else {
+            continue;
+        };
+        let variant = arm.trim_end_matches("),").rsplit("::").next().unwrap_or_default();
+        let description = variants.iter()
+            .find(|(variant_name, _)| variant_name == variant)
+            .map(|(_, description)| description.clone())
+            .unwrap_or_else(|| panic!("No variant {}::{} in src/{}", name, variant, file));
+        values.push((value.trim_start_matches('"').to_string(), description));
+    }
+
+    if values.is_empty() {
+        panic!("No values found for {} in src/{}!", name, file);
+    }
+
+    (doc_above(&lines, start), values)
+}
+
+fn extract_option_struct(file: &str, name: &str) -> (String, Vec<OptionField>) {
+    let content = read_source(file);
+    let lines: Vec<&str> = content.lines().collect();
+
+    let header = format!("pub struct {} {{", name);
+    let start = lines.iter().position(|line| line.trim() == header)
+        .unwrap_or_else(|| panic!("{} struct not found in src/{}", name, file));
+
+    // JS keys follow the struct's serde attributes
+    let camel_case = attributes_above(&lines, start).iter().any(|attr| attr.contains("rename_all = \"camelCase\""));
+
+    let defaults = extract_defaults(&content, name);
+    let mut fields = Vec::new();
+
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim();
+        if line == "}" {
+            break;
+        }
+        let Some((field, rust_type)) = line.strip_prefix("pub ").and_then(|rest| rest.split_once(": ")) else {
+            continue;
+        };
+
+        let rename = attributes_above(&lines, i).iter()
+            .find_map(|attr| attr.split_once("rename = \"").and_then(|(_, rest)| rest.split('"').next()))
+            .map(str::to_string);
+        let key = match rename {
+            Some(key) => key,
+            None if camel_case => to_camel_case(field),
+            None => field.to_string(),
+        };
+
+        fields.push(OptionField {
+            key,
+            ts_type: typescript_type(rust_type.trim_end_matches(',')),
+            doc: doc_above(&lines, i),
+            default: defaults.iter().find(|(default_field, _)| default_field == field).map(|(_, value)| value.clone()),
+        });
+    }
+
+    if fields.is_empty() {
+        panic!("No fields found for {} in src/{}!", name, file);
+    }
+
+    (doc_above(&lines, start), fields)
+}
+
+// Field values of `impl Default`, as TypeScript literals, like: ("statuses", "[429, 502]")
+fn extract_defaults(content: &str, name: &str) -> Vec<(String, String)> {
+    let Some(body) = content.split(&format!("impl Default for {} {{", name)).nth(1)
+        .and_then(|rest| rest.split("-> Self {").nth(1))
+        .and_then(|rest| rest.split("Self {").nth(1))
+        .and_then(|rest| rest.split('}').next())
+    else {
+        return Vec::new();
+    };
+
+    // Split on the commas between fields, not the ones inside `vec![...]`
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+
+    for c in body.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                entries.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    entries.push(current);
+
+    entries.iter()
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(field, value)| {
+            let value = value.trim().trim_start_matches("vec!");
+            let value = if value.chars().all(|c| c.is_ascii_digit() || c == '_') {
+                value.replace('_', "")
+            } else {
+                value.to_string()
+            };
+            (field.trim().to_string(), value)
+        })
+        .collect()
+}
+
+fn typescript_type(rust_type: &str) -> String {
+    if let Some(item) = rust_type.strip_prefix("Vec<").and_then(|rest| rest.strip_suffix('>')) {
+        return format!("{}[]", typescript_type(item));
+    }
+
+    match rust_type {
+        "bool" => "boolean".to_string(),
+        "String" => "string".to_string(),
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i32" | "i64" | "f64" => "number".to_string(),
+        other => panic!("No TypeScript type for {}", other),
+    }
+}
+
+// TypeScript type of a field in `NATIVE_STRUCTS`, which its trailing comment gives if it has one
+fn native_type(rust_type: &str, comment: Option<&str>) -> String {
+    if let Some(ts_type) = comment {
+        return ts_type.to_string();
+    }
+
+    let rust_type = rust_type.strip_prefix("Option<").and_then(|rest| rest.strip_suffix('>')).unwrap_or(rust_type);
+    NATIVE_TYPES.iter()
+        .find(|(rust, _)| *rust == rust_type)
+        .map(|(_, ts)| ts.to_string())
+        .unwrap_or_else(|| typescript_type(rust_type))
+}
+
+// Keys a fn in lib.rs sets on the object it builds, and whether it sets them only under a condition,
+// like: ("trailers", true) from `    if ... {\n        obj.set(cx, "trailers", trailers)?;`
+fn extract_js_keys(name: &str) -> Vec<(String, bool)> {
+    let content = read_source("lib.rs");
+    let body = content.split(&format!("fn {}", name)).nth(1)
+        .and_then(|rest| rest.split("\n}\n").next())
+        .unwrap_or_else(|| panic!("No {} in src/lib.rs", name));
+
+    body.lines()
+        .filter_map(|line| {
+            let key = line.trim_start().strip_prefix("obj.set(cx, \"")?.split('"').next()?;
+            let indent = line.len() - line.trim_start().len();
+            Some((key.to_string(), indent > 4))
+        })
+        .collect()
+}
+
+fn to_camel_case(field: &str) -> String {
+    let mut parts = field.split('_');
+    let mut key = parts.next().unwrap_or_default().to_string();
+
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            key.extend(first.to_uppercase().chain(chars));
+        }
+    }
+
+    key
+}
+
+fn extract_error_codes() -> Vec<(String, String)> {
+    let content = read_source("error.rs");
+
+    // Variants and their doc lines, in declaration order
+    let body = content.split("pub enum ErrorKind {").nth(1)
+        .and_then(|rest| rest.split('}').next())
+        .expect("ErrorKind enum not found in src/error.rs");
+
+    let mut variants = Vec::new();
+    let mut doc = String::new();
+
+    for line in body.lines().map(str::trim) {
+        if let Some(text) = line.strip_prefix("///") {
+            doc = text.trim().to_string();
+        } else if let Some(variant) = line.strip_suffix(',') {
+            variants.push((variant.to_string(), std::mem::take(&mut doc)));
+        }
+    }
+
+    // Codes from the arms of ErrorKind::code, like: ErrorKind::Timeout => "ERR_TIMEOUT",
+    let mut codes = Vec::new();
+
+    for (variant, doc) in variants {
+        let arm = format!("ErrorKind::{} => \"", variant);
+        let code = content.lines()
+            .find_map(|line| line.split_once(&arm).and_then(|(_, rest)| rest.split('"').next()))
+            .unwrap_or_else(|| panic!("No error code for ErrorKind::{}", variant));
+
+        codes.push((code.to_string(), lowercase_first(&doc)));
+    }
+
+    if codes.is_empty() {
+        panic!("No error codes found in src/error.rs!");
+    }
+
+    codes
+}
+
+fn read_emulation_source() -> String {
     // Find wreq-util in cargo metadata
     let metadata = std::process::Command::new("cargo")
         .args(&["metadata", "--format-version", "1"])
@@ -93,9 +678,11 @@ fn extract_profiles_from_source() -> Vec<String> {
 
     // Read the emulation mod.rs file
     let emulation_file = wreq_util_dir.join("src").join("emulation").join("mod.rs");
-    let content = fs::read_to_string(&emulation_file)
-        .expect("Failed to read wreq-util emulation/mod.rs");
+    fs::read_to_string(&emulation_file)
+        .expect("Failed to read wreq-util emulation/mod.rs")
+}
 
+fn extract_profiles(content: &str) -> Vec<String> {
     // Extract serde rename values from the file
     // Look for patterns like: => ("profile_name", ...)
     let mut profiles = Vec::new();
@@ -116,3 +703,21 @@ fn extract_profiles_from_source() -> Vec<String> {
 
     profiles
 }
+
+fn extract_emulation_os(content: &str) -> Vec<String> {
+    // The define_enum! arms after the EmulationOS name and default, like: MacOS => "macos",
+    let arms = content.split("EmulationOS, ").nth(1)
+        .and_then(|rest| rest.split(");").next())
+        .expect("EmulationOS enum not found in wreq-util source");
+
+    let os_names: Vec<String> = arms.lines()
+        .filter_map(|line| line.split_once("=> \"").and_then(|(_, rest)| rest.split('"').next()))
+        .map(str::to_string)
+        .collect();
+
+    if os_names.is_empty() {
+        panic!("No emulation OS names found in wreq-util source!");
+    }
+
+    os_names
+}
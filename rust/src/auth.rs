@@ -0,0 +1,424 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use boring2::hash::{hash, MessageDigest};
+use boring2::nid::Nid;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Challenge-response schemes answered automatically when a server replies `401`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// RFC 7616, MD5 or SHA-256 with `qop=auth`
+    Digest,
+    /// NTLMv2, negotiated over a single HTTP/1.1 connection
+    Ntlm,
+}
+
+impl AuthScheme {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "digest" => Some(AuthScheme::Digest),
+            "ntlm" => Some(AuthScheme::Ntlm),
+            _ => None,
+        }
+    }
+}
+
+/// Credentials for answering a server's authentication challenge.
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub scheme: AuthScheme,
+    pub username: String,
+    pub password: String,
+    /// NTLM domain; `DOMAIN\user` usernames are split when this is absent
+    pub domain: Option<String>,
+}
+
+/// `Authorization` value for Basic credentials. RFC 7617 only defines UTF-8 as a charset,
+/// which is what browsers send, so non-ASCII credentials are encoded as UTF-8 before base64.
+pub fn basic_authorization(user: &str, pass: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("{}:{}", user, pass)))
+}
+
+/// Whether `value` fits the token68 syntax Bearer tokens use (RFC 6750).
+pub fn is_token68(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let end = take_while(bytes, 0, is_token68_char);
+    end > 0 && skip(bytes, end, b"=") == bytes.len()
+}
+
+/// One challenge from a `WWW-Authenticate` header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Challenge {
+    scheme: String,
+    /// The single opaque value some schemes (NTLM, Negotiate) use instead of parameters
+    token: Option<String>,
+    /// Parameter names lowercased, values unquoted
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn is_tchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+fn is_token68_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~+/".contains(&byte)
+}
+
+fn skip(bytes: &[u8], mut pos: usize, set: &[u8]) -> usize {
+    while bytes.get(pos).is_some_and(|byte| set.contains(byte)) {
+        pos += 1;
+    }
+    pos
+}
+
+fn take_while(bytes: &[u8], mut pos: usize, accept: impl Fn(u8) -> bool) -> usize {
+    while bytes.get(pos).is_some_and(|byte| accept(*byte)) {
+        pos += 1;
+    }
+    pos
+}
+
+// A quoted-string starting at `pos`, unescaped, and the position after its closing quote
+fn quoted(bytes: &[u8], mut pos: usize) -> (String, usize) {
+    let mut value = Vec::new();
+    pos += 1;
+    while let Some(&byte) = bytes.get(pos) {
+        pos += 1;
+        match byte {
+            b'"' => break,
+            b'\\' => {
+                if let Some(&escaped) = bytes.get(pos) {
+                    value.push(escaped);
+                    pos += 1;
+                }
+            }
+            _ => value.push(byte),
+        }
+    }
+    (String::from_utf8_lossy(&value).into_owned(), pos)
+}
+
+/// Parse the challenges in `WWW-Authenticate` header values. A header may carry several,
+/// separated by commas like the parameters within each.
+fn parse_challenges<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<Challenge> {
+    let mut challenges = Vec::new();
+
+    for value in values {
+        let bytes = value.as_bytes();
+        let mut pos = 0;
+
+        loop {
+            pos = skip(bytes, pos, b" \t,");
+            let end = take_while(bytes, pos, is_tchar);
+            if end == pos {
+                break;
+            }
+            let mut challenge = Challenge {
+                scheme: value[pos..end].to_string(),
+                ..Challenge::default()
+            };
+            pos = skip(bytes, end, b" \t");
+
+            // A token68 runs to the end of the challenge; anything else is the first parameter
+            let end = skip(bytes, take_while(bytes, pos, is_token68_char), b"=");
+            let after = skip(bytes, end, b" \t");
+            if end > pos && bytes[pos] != b'=' && matches!(bytes.get(after), None | Some(b',')) {
+                challenge.token = Some(value[pos..end].to_string());
+                challenges.push(challenge);
+                pos = after;
+                continue;
+            }
+
+            loop {
+                let start = skip(bytes, pos, b" \t,");
+                let name_end = take_while(bytes, start, is_tchar);
+                let equals = skip(bytes, name_end, b" \t");
+                if name_end == start || bytes.get(equals) != Some(&b'=') {
+                    break;
+                }
+                let value_start = skip(bytes, equals + 1, b" \t");
+                let (param, next) = if bytes.get(value_start) == Some(&b'"') {
+                    quoted(bytes, value_start)
+                } else {
+                    let value_end = take_while(bytes, value_start, is_tchar);
+                    (value[value_start..value_end].to_string(), value_end)
+                };
+                challenge.params.push((value[start..name_end].to_ascii_lowercase(), param));
+                pos = next;
+            }
+            challenges.push(challenge);
+        }
+    }
+
+    challenges
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    std::array::from_fn(|_| fastrand::u8(..))
+}
+
+// Quote a value for a Digest parameter
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The `Authorization` header answering the strongest Digest challenge among
+/// `www_authenticate`, or `None` when none of them uses a supported algorithm and qop.
+/// `uri` is the request target the challenge came back for.
+pub fn digest_authorization<'a>(
+    config: &AuthConfig,
+    www_authenticate: impl IntoIterator<Item = &'a str>,
+    method: &str,
+    uri: &str,
+) -> Result<Option<String>> {
+    let candidates = parse_challenges(www_authenticate).into_iter().filter_map(|challenge| {
+        if !challenge.scheme.eq_ignore_ascii_case("Digest") {
+            return None;
+        }
+        let algorithm = challenge.param("algorithm").unwrap_or("MD5").to_string();
+        let (digest, session, rank) = match algorithm.to_ascii_uppercase().as_str() {
+            "SHA-256" => (MessageDigest::sha256(), false, 3),
+            "SHA-256-SESS" => (MessageDigest::sha256(), true, 2),
+            "MD5" => (MessageDigest::md5(), false, 1),
+            "MD5-SESS" => (MessageDigest::md5(), true, 0),
+            _ => return None,
+        };
+        // Without a qop the challenge predates RFC 2617; auth-int alone isn't supported
+        let qop = match challenge.param("qop") {
+            None => false,
+            Some(qop) if qop.split(',').any(|value| value.trim().eq_ignore_ascii_case("auth")) => true,
+            Some(_) => return None,
+        };
+        Some((rank, challenge, algorithm, digest, session, qop))
+    });
+    let Some((_, challenge, algorithm, digest, session, qop)) = candidates.max_by_key(|candidate| candidate.0) else {
+        return Ok(None);
+    };
+
+    let h = |data: String| -> Result<String> { Ok(hex(&hash(digest, data.as_bytes())?)) };
+    let realm = challenge.param("realm").unwrap_or_default();
+    let nonce = challenge.param("nonce").context("Digest challenge has no nonce")?;
+    let method = if method.is_empty() { "GET" } else { method };
+    let cnonce = hex(&random_bytes::<16>());
+    let nc = "00000001";
+
+    let mut ha1 = h(format!("{}:{}:{}", config.username, realm, config.password))?;
+    if session {
+        ha1 = h(format!("{}:{}:{}", ha1, nonce, cnonce))?;
+    }
+    let ha2 = h(format!("{}:{}", method, uri))?;
+    let response = if qop {
+        h(format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2))?
+    } else {
+        h(format!("{}:{}:{}", ha1, nonce, ha2))?
+    };
+
+    let mut header = format!(
+        "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response={}",
+        quote(&config.username),
+        quote(realm),
+        quote(nonce),
+        quote(uri),
+        algorithm,
+        quote(&response)
+    );
+    if qop {
+        header.push_str(&format!(", qop=auth, nc={}, cnonce={}", nc, quote(&cnonce)));
+    }
+    if let Some(opaque) = challenge.param("opaque") {
+        header.push_str(&format!(", opaque={}", quote(opaque)));
+    }
+    Ok(Some(header))
+}
+
+const NTLM_SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+// UNICODE | OEM | REQUEST_TARGET | NTLM | ALWAYS_SIGN | EXTENDED_SESSIONSECURITY
+// | TARGET_INFO | 128 | 56
+const NTLM_FLAGS: u32 = 0xA088_8207;
+const NTLM_UNICODE: u32 = 0x0000_0001;
+const NTLM_OEM: u32 = 0x0000_0002;
+
+/// Whether any of the `WWW-Authenticate` values offers NTLM.
+pub fn offers_ntlm<'a>(www_authenticate: impl IntoIterator<Item = &'a str>) -> bool {
+    parse_challenges(www_authenticate)
+        .iter()
+        .any(|challenge| challenge.scheme.eq_ignore_ascii_case("NTLM"))
+}
+
+/// The `Authorization` header opening an NTLM handshake (the Type 1 message).
+pub fn ntlm_negotiate() -> String {
+    let mut message = Vec::with_capacity(32);
+    message.extend_from_slice(NTLM_SIGNATURE);
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NTLM_FLAGS.to_le_bytes());
+    // Empty domain and workstation
+    message.extend_from_slice(&[0; 16]);
+    format!("NTLM {}", STANDARD.encode(message))
+}
+
+/// The `Authorization` header completing an NTLM handshake (the Type 3 message), answering
+/// the server's Type 2 challenge. `None` when the server sent no challenge to answer.
+pub fn ntlm_authenticate<'a>(
+    config: &AuthConfig,
+    www_authenticate: impl IntoIterator<Item = &'a str>,
+) -> Result<Option<String>> {
+    let token = parse_challenges(www_authenticate)
+        .into_iter()
+        .find(|challenge| challenge.scheme.eq_ignore_ascii_case("NTLM"))
+        .and_then(|challenge| challenge.token);
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let message = STANDARD.decode(token.trim()).context("Invalid NTLM challenge")?;
+    if message.len() < 32 || &message[..8] != NTLM_SIGNATURE || le_u32(&message, 8) != 2 {
+        bail!("Invalid NTLM challenge");
+    }
+    let server_flags = le_u32(&message, 20);
+    let server_challenge = &message[24..32];
+    let target_info = if message.len() >= 48 {
+        security_buffer(&message, 40).context("Invalid NTLM challenge")?
+    } else {
+        &[]
+    };
+
+    let (domain, username) = match (&config.domain, config.username.split_once('\\')) {
+        (Some(domain), _) => (domain.as_str(), config.username.as_str()),
+        (None, Some((domain, username))) => (domain, username),
+        (None, None) => ("", config.username.as_str()),
+    };
+    let workstation = "";
+
+    // NTLMv2: an HMAC-MD5 keyed with the password hash over the server's challenge and a blob
+    // carrying the client's own challenge, a timestamp and the server's target info
+    let nt_hash = hash(
+        MessageDigest::from_nid(Nid::MD4).context("MD4 is unavailable")?,
+        &utf16le(&config.password),
+    )?;
+    let v2_hash = hmac_md5(&nt_hash, &utf16le(&format!("{}{}", username.to_uppercase(), domain)))?;
+    let client_challenge = random_bytes::<8>();
+    let server_timestamp = av_timestamp(target_info);
+    let timestamp = server_timestamp.unwrap_or_else(filetime_now);
+
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.to_le_bytes());
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let mut nt_response = hmac_md5(&v2_hash, &[server_challenge, &blob[..]].concat())?;
+    nt_response.extend_from_slice(&blob);
+    // When the server sends a timestamp, the LM response is left as zeros (MS-NLMP 3.1.5.1.2)
+    let lm_response = if server_timestamp.is_some() {
+        vec![0; 24]
+    } else {
+        let mut lm = hmac_md5(&v2_hash, &[server_challenge, &client_challenge[..]].concat())?;
+        lm.extend_from_slice(&client_challenge);
+        lm
+    };
+
+    let flags = (server_flags & NTLM_FLAGS | NTLM_UNICODE) & !NTLM_OEM;
+    let payloads = [
+        lm_response,
+        nt_response,
+        utf16le(domain),
+        utf16le(username),
+        utf16le(workstation),
+        Vec::new(),
+    ];
+
+    let mut message = Vec::new();
+    message.extend_from_slice(NTLM_SIGNATURE);
+    message.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = 64u32;
+    for payload in &payloads {
+        let len = payload.len() as u16;
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&len.to_le_bytes());
+        message.extend_from_slice(&offset.to_le_bytes());
+        offset += u32::from(len);
+    }
+    message.extend_from_slice(&flags.to_le_bytes());
+    for payload in &payloads {
+        message.extend_from_slice(payload);
+    }
+
+    Ok(Some(format!("NTLM {}", STANDARD.encode(message))))
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// The bytes an NTLM security buffer (length, capacity, offset) at `at` points to
+fn security_buffer(message: &[u8], at: usize) -> Option<&[u8]> {
+    let len = usize::from(le_u16(message, at));
+    let offset = le_u32(message, at + 4) as usize;
+    message.get(offset..offset.checked_add(len)?)
+}
+
+// MsvAvTimestamp from the server's target info, a list of (id, length, value) pairs
+fn av_timestamp(mut target_info: &[u8]) -> Option<u64> {
+    const MSV_AV_EOL: u16 = 0;
+    const MSV_AV_TIMESTAMP: u16 = 7;
+
+    while target_info.len() >= 4 {
+        let id = le_u16(target_info, 0);
+        let len = usize::from(le_u16(target_info, 2));
+        let value = target_info.get(4..4 + len)?;
+        match id {
+            MSV_AV_EOL => break,
+            MSV_AV_TIMESTAMP if len == 8 => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+            _ => target_info = &target_info[4 + len..],
+        }
+    }
+    None
+}
+
+// Now as a Windows FILETIME: 100ns intervals since 1601-01-01
+fn filetime_now() -> u64 {
+    const EPOCH_DIFFERENCE: u64 = 11_644_473_600;
+    let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since.as_secs() + EPOCH_DIFFERENCE) * 10_000_000 + u64::from(since.subsec_nanos() / 100)
+}
+
+fn utf16le(value: &str) -> Vec<u8> {
+    value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+// boring2 only exposes HMAC for the SHA family, so HMAC-MD5 is built from the hash (RFC 2104)
+fn hmac_md5(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    const BLOCK: usize = 64;
+
+    let mut padded = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        padded[..16].copy_from_slice(&hash(MessageDigest::md5(), key)?);
+    } else {
+        padded[..key.len()].copy_from_slice(key);
+    }
+
+    let inner_key = padded.map(|byte| byte ^ 0x36);
+    let outer_key = padded.map(|byte| byte ^ 0x5c);
+    let inner = hash(MessageDigest::md5(), &[&inner_key[..], data].concat())?;
+    let outer = hash(MessageDigest::md5(), &[&outer_key[..], &inner[..]].concat())?;
+    Ok(outer.to_vec())
+}
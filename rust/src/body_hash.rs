@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use boring2::hash::{Hasher, MessageDigest};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Digest computed over a response body as it is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Md5,
+    /// 64-bit XXH3: not cryptographic, but fast enough to hash anything the network delivers
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "md5" => Some(HashAlgorithm::Md5),
+            "xxh3" => Some(HashAlgorithm::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Hash a body that is already in memory, e.g. one served from the HTTP cache.
+    pub fn digest(self, data: &[u8]) -> Result<BodyHash> {
+        let mut hasher = BodyHasher::new(self)?;
+        hasher.update(data)?;
+        hasher.finish()
+    }
+}
+
+/// A body's digest as lowercase hex, with the algorithm that produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyHash {
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+}
+
+/// Running digest, fed one chunk at a time so the body never has to be hashed twice.
+pub enum BodyHasher {
+    Digest(HashAlgorithm, Hasher),
+    Xxh3(Box<Xxh3>),
+}
+
+impl BodyHasher {
+    pub fn new(algorithm: HashAlgorithm) -> Result<Self> {
+        let digest = match algorithm {
+            HashAlgorithm::Sha256 => MessageDigest::sha256(),
+            HashAlgorithm::Md5 => MessageDigest::md5(),
+            HashAlgorithm::Xxh3 => return Ok(BodyHasher::Xxh3(Box::default())),
+        };
+        let hasher = Hasher::new(digest).context("Failed to start the body hash")?;
+        Ok(BodyHasher::Digest(algorithm, hasher))
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            BodyHasher::Digest(_, hasher) => hasher.update(data).context("Failed to hash the response body"),
+            BodyHasher::Xxh3(hasher) => {
+                hasher.update(data);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<BodyHash> {
+        match self {
+            BodyHasher::Digest(algorithm, mut hasher) => {
+                let digest = hasher.finish().context("Failed to hash the response body")?;
+                Ok(BodyHash {
+                    algorithm,
+                    hex: hex(&digest),
+                })
+            }
+            BodyHasher::Xxh3(hasher) => Ok(BodyHash {
+                algorithm: HashAlgorithm::Xxh3,
+                hex: format!("{:016x}", hasher.digest()),
+            }),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,69 @@
+/// Why a response looks like a bot-protection challenge rather than the page asked for.
+#[derive(Debug, Clone)]
+pub struct ChallengeInfo {
+    /// "cloudflare", "akamai", "perimeterx" or "datadome"
+    pub provider: &'static str,
+    /// What gave it away, e.g. `header cf-mitigated` or `body captcha-delivery.com`
+    pub signals: Vec<String>,
+}
+
+// Headers only these vendors send with a challenge or block, and the value they carry
+const HEADER_MARKERS: &[(&str, &str, Option<&str>)] = &[
+    ("cloudflare", "cf-mitigated", Some("challenge")),
+    ("datadome", "x-datadome", None),
+    ("datadome", "x-dd-b", None),
+];
+
+// Scripts, hosts and titles of the vendors' interstitial pages. Only looked for in error
+// responses, since the same strings can appear in ordinary pages behind the same vendor
+const BODY_MARKERS: &[(&str, &str)] = &[
+    ("cloudflare", "/cdn-cgi/challenge-platform/"),
+    ("cloudflare", "_cf_chl_opt"),
+    ("cloudflare", "<title>Just a moment...</title>"),
+    ("cloudflare", "Attention Required! | Cloudflare"),
+    ("akamai", "/_sec/cp_challenge/"),
+    ("akamai", "bm-verify"),
+    ("akamai", "errors.edgesuite.net"),
+    ("perimeterx", "px-captcha"),
+    ("perimeterx", "_pxAppId"),
+    ("perimeterx", "captcha.px-cdn.net"),
+    ("datadome", "captcha-delivery.com"),
+];
+
+/// Look for challenge markers in a response. `body` is `None` when it hasn't been read,
+/// as for streamed responses, which are then judged by their headers alone.
+pub fn detect(status: u16, headers: &[(String, String)], body: Option<&str>) -> Option<ChallengeInfo> {
+    let mut found: Vec<(&'static str, String)> = Vec::new();
+
+    for &(provider, name, expected) in HEADER_MARKERS {
+        let matched = headers.iter().any(|(key, value)| {
+            key.eq_ignore_ascii_case(name)
+                && expected.is_none_or(|expected| value.to_ascii_lowercase().contains(expected))
+        });
+        if matched {
+            found.push((provider, format!("header {}", name)));
+        }
+    }
+
+    if let Some(body) = body.filter(|_| status >= 400) {
+        for &(provider, marker) in BODY_MARKERS {
+            if body.contains(marker) {
+                found.push((provider, format!("body {}", marker)));
+            }
+        }
+    }
+
+    // The vendor with the most signals; ties go to the one listed first
+    let mut best: Option<ChallengeInfo> = None;
+    for &(provider, _) in &found {
+        let signals: Vec<String> = found
+            .iter()
+            .filter(|(candidate, _)| *candidate == provider)
+            .map(|(_, signal)| signal.clone())
+            .collect();
+        if best.as_ref().is_none_or(|best| signals.len() > best.signals.len()) {
+            best = Some(ChallengeInfo { provider, signals });
+        }
+    }
+    best
+}
@@ -0,0 +1,77 @@
+use encoding_rs::{Encoding, UTF_8};
+use mime::Mime;
+
+/// How far into an HTML document the spec's prescan looks for a `<meta>` charset
+const PRESCAN_BYTES: usize = 1024;
+
+/// The encoding a body is decoded with: a byte order mark, then the `Content-Type` charset,
+/// then for HTML and XML a `<meta>` tag or XML declaration near the start, then UTF-8.
+pub fn detect(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    let mime = content_type.and_then(|value| value.parse::<Mime>().ok());
+    let declared = mime
+        .as_ref()
+        .and_then(|mime| mime.get_param(mime::CHARSET))
+        .and_then(|charset| Encoding::for_label(charset.as_str().as_bytes()));
+    if let Some(encoding) = declared {
+        return encoding;
+    }
+
+    // Without a Content-Type the body may still be markup
+    let markup = mime.as_ref().is_none_or(|mime| {
+        mime.subtype() == mime::HTML || mime.subtype() == mime::XML || mime.suffix() == Some(mime::XML)
+    });
+    markup.then(|| sniff_markup(bytes)).flatten().unwrap_or(UTF_8)
+}
+
+/// Decode a body with `forced`, or with the encoding detected for it; a leading BOM is dropped.
+pub fn decode(bytes: &[u8], content_type: Option<&str>, forced: Option<&'static Encoding>) -> (String, &'static Encoding) {
+    let encoding = forced.unwrap_or_else(|| detect(bytes, content_type));
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    (text.into_owned(), encoding)
+}
+
+// An XML declaration's encoding, or the first <meta> charset (either the `charset` attribute
+// or the one inside `content` of an http-equiv tag)
+fn sniff_markup(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = bytes[..bytes.len().min(PRESCAN_BYTES)].to_ascii_lowercase();
+
+    if head.starts_with(b"<?xml") {
+        let end = head.iter().position(|&byte| byte == b'>').unwrap_or(head.len());
+        if let Some(encoding) = attribute_value(&head[..end], b"encoding=").and_then(Encoding::for_label) {
+            return Some(encoding.output_encoding());
+        }
+    }
+
+    let mut rest = head.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        let tag = &rest[start..];
+        let end = tag.iter().position(|&byte| byte == b'>').unwrap_or(tag.len());
+        // A page can't declare itself UTF-16, since the declaration itself was read as ASCII
+        if let Some(encoding) = attribute_value(&tag[..end], b"charset=").and_then(Encoding::for_label) {
+            return Some(encoding.output_encoding());
+        }
+        rest = &tag[end..];
+    }
+    None
+}
+
+// The value following `key`: up to the closing quote, or up to a space, `;` or quote when unquoted
+fn attribute_value<'a>(tag: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let value = &tag[find(tag, key)? + key.len()..];
+    if let Some(&quote @ (b'"' | b'\'')) = value.first() {
+        return value[1..].split(|&byte| byte == quote).next();
+    }
+    let end = value
+        .iter()
+        .position(|&byte| byte.is_ascii_whitespace() || matches!(byte, b';' | b'"' | b'\'' | b'/'))
+        .unwrap_or(value.len());
+    Some(&value[..end])
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
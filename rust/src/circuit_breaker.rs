@@ -0,0 +1,209 @@
+use crate::concurrency::request_host;
+use crate::error::{CircuitOpen, ErrorKind};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Per-host circuit breaker settings, set from Node with `setCircuitBreaker`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Failures in a row that open a host's circuit
+    pub threshold: u32,
+    /// How long an open circuit turns requests away before letting a probe through
+    pub cooldown: Duration,
+    /// Response statuses that count as failures, besides connection errors and timeouts
+    pub statuses: Vec<u16>,
+}
+
+/// Circuits are pruned once this many have piled up, dropping the healthy ones.
+const MAX_CIRCUITS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Closed,
+    Open {
+        until: Instant,
+    },
+    /// The cooldown is over; a single probe request finds out whether the host recovered
+    HalfOpen {
+        probing: bool,
+    },
+}
+
+#[derive(Default)]
+struct Circuit {
+    state: State,
+    consecutive_failures: u32,
+}
+
+/// One host's circuit, as reported by `circuitBreakerStats`.
+#[derive(Debug, Clone)]
+pub struct CircuitStats {
+    pub host: String,
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    /// Milliseconds since the Unix epoch at which an open circuit lets a probe through
+    pub open_until: Option<f64>,
+}
+
+struct Breaker {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl Breaker {
+    // Whether an attempt's outcome counts against its host. Proxy failures are the proxy's
+    // fault, and cancelled or invalid requests say nothing about the host.
+    fn is_failure(&self, outcome: Result<u16, &anyhow::Error>) -> bool {
+        match outcome {
+            Ok(status) => self.config.statuses.contains(&status),
+            Err(error) => matches!(
+                ErrorKind::classify(error),
+                ErrorKind::Timeout | ErrorKind::ConnectError | ErrorKind::DnsFailure | ErrorKind::TlsError
+            ),
+        }
+    }
+}
+
+static BREAKER: Lazy<RwLock<Option<Arc<Breaker>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Replace the circuit breaker, closing every circuit, or remove it with `None`.
+pub fn configure(config: Option<CircuitBreakerConfig>) {
+    let breaker = config.map(|config| {
+        Arc::new(Breaker {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        })
+    });
+    *BREAKER.write().unwrap() = breaker;
+}
+
+/// An attempt let through by its host's circuit, to be recorded once its outcome is known.
+pub struct Attempt {
+    breaker: Arc<Breaker>,
+    host: String,
+    probe: bool,
+    recorded: bool,
+}
+
+/// Check the circuit of the host `url` points at before an attempt is sent. An open
+/// circuit whose cooldown is over lets this one attempt through as its probe.
+pub fn admit(url: &str) -> Result<Option<Attempt>, CircuitOpen> {
+    let Some(breaker) = BREAKER.read().unwrap().clone() else {
+        return Ok(None);
+    };
+    let Some(host) = request_host(url) else {
+        return Ok(None);
+    };
+
+    let now = Instant::now();
+    let probe = {
+        let mut circuits = breaker.circuits.lock().unwrap();
+        if circuits.len() >= MAX_CIRCUITS && !circuits.contains_key(&host) {
+            circuits.retain(|_, circuit| circuit.state != State::Closed || circuit.consecutive_failures > 0);
+        }
+
+        let circuit = circuits.entry(host.clone()).or_default();
+        match circuit.state {
+            State::Closed => false,
+            State::Open { until } if until > now => {
+                return Err(CircuitOpen {
+                    host,
+                    retry_in: until - now,
+                });
+            }
+            State::HalfOpen { probing: true } => {
+                return Err(CircuitOpen {
+                    host,
+                    retry_in: Duration::ZERO,
+                });
+            }
+            State::Open { .. } | State::HalfOpen { probing: false } => {
+                circuit.state = State::HalfOpen { probing: true };
+                true
+            }
+        }
+    };
+
+    Ok(Some(Attempt {
+        breaker,
+        host,
+        probe,
+        recorded: false,
+    }))
+}
+
+impl Attempt {
+    /// A failure opens the circuit once `threshold` have come in a row, or straight
+    /// away for a probe; a success closes it.
+    pub fn record(mut self, outcome: Result<u16, &anyhow::Error>) {
+        self.recorded = true;
+        let failed = self.breaker.is_failure(outcome);
+        let config = &self.breaker.config;
+        let mut circuits = self.breaker.circuits.lock().unwrap();
+        let circuit = circuits.entry(self.host.clone()).or_default();
+
+        if !failed {
+            circuit.consecutive_failures = 0;
+            circuit.state = State::Closed;
+            return;
+        }
+
+        circuit.consecutive_failures += 1;
+        let trips = self.probe || circuit.consecutive_failures >= config.threshold;
+        if trips && !matches!(circuit.state, State::Open { .. }) {
+            circuit.state = State::Open {
+                until: Instant::now() + config.cooldown,
+            };
+            tracing::warn!(host = %self.host, failures = circuit.consecutive_failures, "circuit opened");
+        }
+    }
+}
+
+impl Drop for Attempt {
+    // A probe that never finished, e.g. because the request was cancelled, hands the job on
+    fn drop(&mut self) {
+        if !self.probe || self.recorded {
+            return;
+        }
+        let mut circuits = self.breaker.circuits.lock().unwrap();
+        let probing = circuits
+            .get_mut(&self.host)
+            .filter(|circuit| circuit.state == State::HalfOpen { probing: true });
+        if let Some(circuit) = probing {
+            circuit.state = State::HalfOpen { probing: false };
+        }
+    }
+}
+
+/// Every host the breaker has seen fail, with its circuit's state.
+pub fn stats() -> Vec<CircuitStats> {
+    let Some(breaker) = BREAKER.read().unwrap().clone() else {
+        return Vec::new();
+    };
+
+    let now = Instant::now();
+    let wall = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let circuits = breaker.circuits.lock().unwrap();
+    circuits
+        .iter()
+        .filter(|(_, circuit)| circuit.state != State::Closed || circuit.consecutive_failures > 0)
+        .map(|(host, circuit)| CircuitStats {
+            host: host.clone(),
+            state: match circuit.state {
+                State::Closed => "closed",
+                State::Open { until } if until > now => "open",
+                State::Open { .. } | State::HalfOpen { .. } => "half-open",
+            },
+            consecutive_failures: circuit.consecutive_failures,
+            open_until: match circuit.state {
+                State::Open { until } if until > now => Some((wall + (until - now)).as_secs_f64() * 1000.0),
+                _ => None,
+            },
+        })
+        .collect()
+}
@@ -1,191 +1,1576 @@
+use crate::auth::{self, AuthConfig, AuthScheme};
+use crate::body_hash::{BodyHash, BodyHasher, HashAlgorithm};
+use crate::challenge::{self, ChallengeInfo};
+use crate::charset;
+use crate::circuit_breaker;
+use crate::compress::{self, BodyEncoding};
+use crate::concurrency::{self, request_host};
+use crate::conditional;
+use crate::cookie_jar::{self, CookiePolicy};
+use crate::decoding::{self, Decoding};
+use crate::dns::DnsConfig;
+use crate::emulation;
+use crate::har;
+use crate::header_order::{self, HeaderPosition};
+use crate::hooks::{Outgoing, SendHook};
+use crate::http_cache::{self, CacheMode, Lookup};
+use crate::metrics;
+use crate::extract::{self, ExtractRule};
+use crate::parse::BodyParser;
+use crate::error::{BodyTooLarge, DeadlineExceeded, ErrorKind, TooManyRefreshes};
+use crate::events::Trace;
+use crate::fingerprint::{self, Http2Fingerprint};
+use crate::pool::PoolConfig;
+use crate::proxy_pool::{self, ProxyPool};
+use crate::proxy_routes::{self, RouteTarget};
+use crate::ranges::{self, ContentRange, ResumeState};
+use crate::rate_limit;
+use crate::refresh;
+use crate::retry::RetryPolicy;
+use crate::session::{
+    basic_auth_header, build_proxy, managed_cookie_jar, managed_session_proxy, proxy_credentials, SessionConfig,
+    SESSION_MANAGER,
+};
+use crate::sigv4::{self, SigV4Config};
+use crate::single_flight;
+use crate::stream::{store_stream, DEFAULT_HIGH_WATER_MARK};
+use crate::telemetry;
+use crate::timings::{measure_connect, ConnectTimings, Timings};
+use crate::tls::{verify_pins, PeerCertificate, TlsConfig};
+use crate::trailers::{self, WithTrailers};
 use anyhow::{Context, Result};
+use cookie::{Cookie as RawCookie, SameSite};
+use encoding_rs::Encoding;
+use http_body_util::BodyExt;
 use indexmap::IndexMap;
-use moka::sync::Cache;
-use once_cell::sync::Lazy;
-use serde_json::Value;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::runtime::Runtime;
+use serde::Deserialize;
+use std::io::SeekFrom;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{Instrument, Span};
 use uuid::Uuid;
-use wreq::{Client as HttpClient, Proxy};
-use wreq_util::Emulation;
-
-pub static HTTP_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .expect("Failed to create shared HTTP runtime")
-});
-
-static SESSION_MANAGER: Lazy<SessionManager> = Lazy::new(SessionManager::new);
+use wreq::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+    COOKIE, LOCATION, SET_COOKIE, TRAILER, WWW_AUTHENTICATE,
+};
+use wreq::multipart::{Form, Part};
+use wreq::tls::TlsInfo;
+use wreq::{redirect, Extension, Method, RequestBuilder, StatusCode, Uri, Version};
+use wreq_util::{Emulation, EmulationOS};
 
 #[derive(Debug, Clone)]
 pub struct RequestOptions {
     pub url: String,
     pub emulation: Emulation,
-    pub headers: IndexMap<String, String>,
+    /// Operating system the profile presents as, instead of its own default
+    pub emulation_os: Option<EmulationOS>,
+    /// Request headers in the order given, repeated names included
+    pub headers: Vec<(String, String)>,
+    /// Lowercased names of emulation default headers to leave out
+    pub removed_headers: Vec<String>,
+    /// Caller headers placed before or after another header instead of at the end
+    pub header_positions: Vec<(String, HeaderPosition)>,
     pub method: String,
-    pub body: Option<String>,
+    pub body: Option<Vec<u8>>,
+    pub multipart: Option<Vec<MultipartField>>,
+    /// JSON body; sets `Content-Type: application/json` unless a header overrides it
+    pub json: Option<serde_json::Value>,
+    /// Body streamed from this file rather than read into memory first
+    pub body_file: Option<PathBuf>,
+    /// Trailer fields sent after `body_file`, which then goes out chunked
+    pub trailers: Vec<(String, String)>,
+    /// Compress `body` or `json` with this coding before sending, setting `Content-Encoding`
+    pub compress_body: Option<BodyEncoding>,
     pub proxy: Option<String>,
-    pub timeout: u64,
+    pub proxy_auth: Option<ProxyAuth>,
+    pub proxy_headers: Vec<(String, String)>,
+    /// Pool each attempt draws its proxy from, in place of `proxy`
+    pub proxy_pool: Option<Arc<ProxyPool>>,
+    /// Connect without a proxy, even when the session was created with one
+    pub direct: bool,
+    /// Credentials for answering Digest or NTLM challenges
+    pub auth: Option<AuthConfig>,
+    /// Sign each attempt with AWS Signature Version 4
+    pub aws_sigv4: Option<SigV4Config>,
+    /// Source address for new connections, for hosts with several egress IPs
+    pub local_address: Option<IpAddr>,
+    /// Network interface new connections are bound to (`SO_BINDTODEVICE` and friends)
+    pub interface: Option<String>,
+    /// Unix domain socket to connect to instead of the URL's host, which still goes in `Host`
+    pub unix_socket: Option<PathBuf>,
+    /// Limit on each attempt, from connecting until the body has been read
+    pub timeout: Option<Duration>,
+    /// Limit on opening a new connection, proxy and TLS handshakes included
+    pub connect_timeout: Option<Duration>,
+    /// Longest wait for the response head, and then between body chunks
+    pub read_timeout: Option<Duration>,
+    /// Limit on the whole request, retries and their backoff included
+    pub deadline: Option<Duration>,
     pub session_id: String,
     pub ephemeral: bool,
+    /// Leave an ephemeral session in place after the request, for the caller to drop once it is
+    /// done with it
+    pub keep_session: bool,
     pub disable_default_headers: bool,
+    pub redirect: RedirectPolicy,
+    pub retry: RetryPolicy,
+    /// Protocol to enforce instead of negotiating via ALPN
+    pub http_version: Option<Version>,
+    /// Abort the download once the (decompressed) body grows past this many bytes
+    pub max_body_bytes: Option<u64>,
+    /// When false, bodies are returned exactly as sent, `Content-Encoding` and all
+    pub decompress: bool,
+    /// Set for buffered bodies, which are decoded here once read rather than by wreq as they
+    /// arrive, so stacked and mislabelled codings can be dealt with
+    pub decode_after_read: bool,
+    /// Overrides the emulation's `Accept-Encoding` header
+    pub accept_encoding: Option<String>,
+    /// Encoding to decode the body text with instead of the detected one
+    pub charset: Option<&'static Encoding>,
+    /// Share the response of an identical request already in flight instead of sending another
+    pub dedupe: bool,
+    /// Client certificate and trust settings; part of the session's client configuration
+    pub tls: TlsConfig,
+    /// HTTP/2 settings layered over the emulation's; also part of the session's client configuration
+    pub http2: Option<Http2Fingerprint>,
+    /// Connection reuse and keepalive settings; like `tls`, part of the session's client configuration
+    pub pool: PoolConfig,
+    /// Hostname overrides; also part of the session's client configuration
+    pub dns: DnsConfig,
+    /// File the session's cookies are loaded from and saved to; part of the client configuration
+    pub cookie_store_path: Option<PathBuf>,
+    /// Whether the session's cookies are sent and the response's stored; the session's client is used either way
+    pub cookies: CookiePolicy,
+    /// SHA-256 hashes of acceptable leaf certificates or public keys; empty disables pinning
+    pub pinned_sha256: Vec<[u8; 32]>,
+    /// How the HTTP cache is consulted, when one is configured
+    pub cache: CacheMode,
+    /// Revalidate against the last response seen for the URL, answering a `304` from it
+    pub conditional: bool,
+    /// Follow `Refresh` headers, meta refreshes and script redirects like HTTP redirects
+    pub follow_refresh: bool,
+    /// Caller's trace or correlation ID, recorded on the request's span
+    pub trace_id: Option<String>,
+    /// Tags the request's span, lifecycle events, HAR entry, error and response; the caller's or a UUID
+    pub request_id: String,
+    /// Report the fingerprint the connection presents on the response
+    pub inspect_fingerprint: bool,
+    /// Reduce the buffered body to structured data in Rust and return that instead
+    pub parse: Option<BodyParser>,
+    /// CSS selectors run over the buffered body, whose matches are returned instead of it
+    pub extract: Vec<ExtractRule>,
+    /// Hash the body as it is read and return the digest with the response
+    pub hash_body: Option<HashAlgorithm>,
+    /// Bytes of a streamed body read ahead of the consumer before reads from the socket pause
+    pub high_water_mark: Option<u64>,
+    /// `onSend` middleware, shown each send with its final headers and able to replace them
+    pub before_send: Option<SendHook>,
 }
 
+/// One field of a multipart/form-data body.
+#[derive(Debug, Clone)]
+pub struct MultipartField {
+    pub name: String,
+    pub value: MultipartValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum MultipartValue {
+    Text(String),
+    File {
+        source: FileSource,
+        filename: Option<String>,
+        content_type: Option<String>,
+    },
+}
+
+/// Where a file part's contents come from.
+#[derive(Debug, Clone)]
+pub enum FileSource {
+    Path(String),
+    Bytes(Vec<u8>),
+}
+
+/// Credentials sent to the proxy as Basic `Proxy-Authorization`.
+#[derive(Debug, Clone)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+/// How redirects are handled for a single request.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RedirectPolicy {
+    /// Whether 3xx responses with a Location header are followed
+    pub follow: bool,
+    /// Maximum number of hops to follow before failing
+    pub max: u32,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self { follow: true, max: 10 }
+    }
+}
+
+// build.rs generates `NativeResponse` from these fields, in camelCase and optional where
+// `response_to_js_object` may leave them out
+/// A finished request, as JS gets it
 #[derive(Debug, Clone)]
 pub struct Response {
+    /// HTTP status code, e.g. 200 or 404
     pub status: u16,
-    pub headers: IndexMap<String, String>,
+    /// Protocol the response arrived over, e.g. "HTTP/2"
+    pub version: &'static str,
+    /// Response headers in wire order, repeated names included
+    pub headers: Vec<(String, String)>,
+    /// Values that aren't valid UTF-8, by index into `headers`, which holds them decoded as Latin-1
+    pub raw_header_values: Vec<(usize, Vec<u8>)>,
+    /// Trailer fields that followed the body, e.g. `grpc-status`
+    pub trailers: Vec<(String, String)>,
+    /// ID of the request this answered, as in its logs, events and HAR entry
+    pub request_id: String,
+    /// Body decoded as text, with the charset in `charset`
     pub body: String,
+    /// Body as it arrived, after any content codings were removed
+    pub body_bytes: Vec<u8>,
+    /// Encoding `body` was decoded with; for a streamed body, only one the request forced
+    pub charset: Option<&'static Encoding>,
+    /// Cookies the response set, by name
     pub cookies: IndexMap<String, String>,
+    /// Every cookie the response set, with its attributes, in header order
+    pub set_cookies: Vec<ResponseCookie>,
+    /// Final URL, after any redirects
     pub url: String,
+    /// URLs that answered with a redirect, in the order they were visited
+    pub redirects: Vec<String>,
+    /// The same redirects with their status, `Location` and cookies
+    pub redirect_hops: Vec<RedirectHop>,
+    /// Handle to pull the body with, when it was left unread for streaming
+    pub stream_id: Option<u64>,
+    /// Number of times the request was sent, retries and proxy failovers included
+    pub attempts: u32,
+    /// Phase durations of the final attempt
+    pub timings: Timings,
+    /// Proxy the final attempt went through, when the request or its proxy pool named one
+    pub proxy: Option<String>,
+    /// Connection the final attempt went out on; cached responses have none
+    pub connection: Option<ConnectionInfo>,
+    /// Leaf certificate of the connection the response arrived on
+    pub tls: Option<PeerCertificate>,
+    /// Body served from the HTTP cache, either fresh or after a `304` revalidation
+    pub from_cache: bool,
+    /// JA3/JA4, ALPN and HTTP/2 preface of the connection, when asked for
+    pub fingerprint: Option<fingerprint::Report>,
+    /// What `parse` made of the body, which is then left empty
+    pub parsed: Option<serde_json::Value>,
+    /// Matches of the `extract` rules by name; the body is left empty as with `parsed`
+    pub extracted: Option<serde_json::Value>, // ExtractedValues
+    /// Signs that this is a bot-protection challenge rather than the page asked for
+    pub challenge: Option<ChallengeInfo>,
+    /// Digest of the body, when the request asked for one; streamed bodies have theirs once read
+    pub body_hash: Option<BodyHash>,
+    /// Content codings removed from a buffered body, outermost first
+    pub decodings: Vec<Decoding>,
 }
 
-#[derive(Clone)]
-struct SessionConfig {
-    emulation: Emulation,
-    label: String,
+// A response whose head has arrived, plus what it took to get there
+struct Sent {
+    response: wreq::Response,
+    attempts: u32,
+    timings: Timings,
     proxy: Option<String>,
+    connection: ConnectionInfo,
 }
 
-impl SessionConfig {
-    fn from_request(options: &RequestOptions) -> Self {
-        Self {
-            emulation: options.emulation.clone(),
-            label: emulation_label(&options.emulation),
-            proxy: options.proxy.clone(),
+/// A cookie parsed from one `Set-Cookie` header, attributes included.
+#[derive(Debug, Clone)]
+pub struct ResponseCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    /// Expiry as milliseconds since the Unix epoch
+    pub expires: Option<f64>,
+    pub max_age: Option<u64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<&'static str>,
+}
+
+/// The connection a response arrived on, for checking that pooling works as configured.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// No new connection was opened for the request, redirects included
+    pub reused: bool,
+    pub local_addr: Option<SocketAddr>,
+    pub remote_addr: Option<SocketAddr>,
+    /// Session, origin, proxy and source binding; only requests with the same key share connections
+    pub pool_key: String,
+}
+
+/// One redirect the client followed on the way to a response.
+#[derive(Debug, Clone)]
+pub struct RedirectHop {
+    /// URL that answered with the redirect
+    pub url: String,
+    pub status: u16,
+    pub location: Option<String>,
+    /// Cookies the redirect set, which the session jar has already taken
+    pub set_cookies: Vec<ResponseCookie>,
+}
+
+pub async fn make_request(mut options: RequestOptions) -> Result<Response> {
+    // A shared response carries the ID of the request that went out, so each caller relabels its copy
+    let request_id = options.request_id.clone();
+    let parser = options.parse;
+    let hash_body = options.hash_body;
+    let extract = std::mem::take(&mut options.extract);
+    let result = if options.follow_refresh && options.redirect.follow {
+        follow_refreshes(options).await
+    } else {
+        dispatch(options).await
+    };
+    let mut response = result?;
+    response.request_id = request_id;
+    // Cached bodies and copies of a deduplicated response weren't hashed as they were read
+    if let Some(algorithm) = hash_body {
+        if response.body_hash.as_ref().is_none_or(|hash| hash.algorithm != algorithm) {
+            response.body_hash = Some(algorithm.digest(&response.body_bytes)?);
         }
     }
+    if parser.is_some() || !extract.is_empty() {
+        reduce_body(&mut response, parser, &extract)?;
+    }
+    Ok(response)
+}
 
-    fn new(emulation: Emulation, proxy: Option<String>) -> Self {
-        let label = emulation_label(&emulation);
-        Self {
-            emulation,
-            label,
+// Send a buffered request, or share the response of an identical one already in flight
+async fn dispatch(options: RequestOptions) -> Result<Response> {
+    if options.dedupe && single_flight::eligible(&options) {
+        let key = single_flight::key(&options);
+        single_flight::run(key, observed_request(options)).await
+    } else {
+        observed_request(options).await
+    }
+}
+
+// Follow refreshes as GET redirects, each hop a request of its own with its own events and
+// HAR entry. Refreshes and HTTP redirects share the `maxRedirects` budget, and every hop
+// goes out in the same session, so the cookies an interstitial sets reach the next page
+async fn follow_refreshes(mut options: RequestOptions) -> Result<Response> {
+    // Hops stay ephemeral, so the HTTP cache and dedupe key them as such
+    let ephemeral = options.ephemeral;
+    options.keep_session = true;
+    let session_id = options.session_id.clone();
+    let max = options.redirect.max;
+    let mut hops = Vec::new();
+
+    let result = loop {
+        let mut response = match dispatch(options.clone()).await {
+            Ok(response) => response,
+            Err(e) => break Err(e),
+        };
+        hops.append(&mut response.redirect_hops);
+        let Some(target) = refresh::target(&response) else {
+            response.redirects = hops.iter().map(|hop| hop.url.clone()).collect();
+            response.redirect_hops = hops;
+            break Ok(response);
+        };
+        if hops.len() >= max as usize {
+            break Err(TooManyRefreshes { max }.into());
+        }
+        // The ephemeral jar now holds cookies, which the shared cache entries don't account for
+        if ephemeral && !response.set_cookies.is_empty() {
+            options.cache = CacheMode::NoStore;
+        }
+
+        hops.push(RedirectHop {
+            url: response.url,
+            status: response.status,
+            location: Some(target.clone()),
+            set_cookies: response.set_cookies,
+        });
+        options.url = target;
+        options.method = "GET".to_string();
+        options.body = None;
+        options.multipart = None;
+        options.json = None;
+        options.body_file = None;
+        options.trailers.clear();
+        options.redirect.max = max - hops.len() as u32;
+    };
+
+    if ephemeral {
+        SESSION_MANAGER.drop_session(&session_id);
+    }
+    result
+}
+
+// Replace the body with what `parse` and `extract` make of it, so only that crosses into JS
+fn reduce_body(response: &mut Response, parser: Option<BodyParser>, extract: &[ExtractRule]) -> Result<()> {
+    if let Some(parser) = parser {
+        response.parsed = Some(parser.run(&response.body_bytes, &response.body, &response.url)?);
+    }
+    if !extract.is_empty() {
+        response.extracted = Some(extract::run(extract, &response.body));
+    }
+    response.body = String::new();
+    response.body_bytes = Vec::new();
+    Ok(())
+}
+
+/// A fresh ID for a request the caller didn't name.
+pub fn generate_request_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+async fn observed_request(mut options: RequestOptions) -> Result<Response> {
+    let observed = Observed::start(&mut options);
+    let result = buffered_request(options, &observed.trace)
+        .instrument(observed.span.clone())
+        .await;
+    let received = result.as_ref().ok().map(|response| response.body_bytes.len() as u64);
+    observed.finish(result.as_ref(), received);
+    result
+}
+
+async fn buffered_request(mut options: RequestOptions, trace: &Trace) -> Result<Response> {
+    options.decode_after_read = options.decompress;
+    let decode = options.decode_after_read;
+    let session_id = options.session_id.clone();
+    let ephemeral = options.ephemeral && !options.keep_session;
+    let max_body_bytes = options.max_body_bytes;
+    let charset = options.charset;
+    let hash_body = options.hash_body;
+    let inspected = inspected_emulation(&options);
+    let har = har::recorder().map(|recorder| {
+        let pending = recorder.pending(&options);
+        (recorder, pending)
+    });
+
+    // Validators added here make the HTTP cache pass the request straight through
+    let revalidation = conditional::prepare(&mut options);
+
+    // Fresh cached responses skip the network, along with the concurrency and rate limits
+    let cache = match http_cache::lookup(&mut options).await {
+        Lookup::Hit(mut response) => {
+            redecode(&mut response, charset);
+            if let Some((recorder, pending)) = har {
+                recorder.record(pending, Vec::new(), Ok(&response), Some(&response.body_bytes));
+            }
+            return Ok(response);
+        }
+        Lookup::Send(pending) => pending,
+    };
+
+    // Held until the body has been read, since that is when the connection frees up
+    let _permit = concurrency::acquire(&options.url).await;
+    let mut hops = Vec::new();
+    let result = match send_with_retry(options, trace).await {
+        Ok(Sent {
+            response,
+            attempts,
+            mut timings,
             proxy,
+            connection,
+        }) => {
+            if har.is_some() {
+                hops = har::redirect_hops(&response);
+            }
+            let download_started = Instant::now();
+            read_response(response, max_body_bytes, charset, hash_body, decode).await.map(|mut result| {
+                timings.finish(download_started.elapsed());
+                result.attempts = attempts;
+                result.timings = timings;
+                result.proxy = proxy;
+                result.connection = Some(connection);
+                result.fingerprint = inspected.map(|emulation| fingerprint_report(&emulation, &result));
+                result
+            })
         }
+        Err(e) => Err(e),
+    };
+
+    let result = match (result, cache) {
+        (Ok(response), Some(pending)) => {
+            let mut response = pending.complete(response).await;
+            redecode(&mut response, charset);
+            Ok(response)
+        }
+        (result, _) => result,
+    };
+    let result = match (result, revalidation) {
+        (Ok(response), Some(revalidation)) => Ok(revalidation.complete(response)),
+        (result, _) => result,
+    };
+
+    if let Some((recorder, pending)) = har {
+        let body = result.as_ref().ok().map(|response| response.body_bytes.as_slice());
+        recorder.record(pending, hops, result.as_ref(), body);
     }
 
-    fn matches(&self, other: &SessionConfig) -> bool {
-        self.label == other.label && self.proxy == other.proxy
+    if ephemeral {
+        SESSION_MANAGER.drop_session(&session_id);
     }
+
+    result
 }
 
-#[derive(Clone)]
-struct SessionEntry {
-    client: Arc<HttpClient>,
-    config: SessionConfig,
+/// Send a request and hand the body back as a stream instead of buffering it.
+/// The returned `Response` has an empty body and a `stream_id` to pull chunks with.
+pub async fn make_request_stream(mut options: RequestOptions) -> Result<Response> {
+    let observed = Observed::start(&mut options);
+    let request_id = options.request_id.clone();
+    let result = streamed_request(options, &observed.trace)
+        .instrument(observed.span.clone())
+        .await
+        .map(|mut head| {
+            head.request_id = request_id;
+            head
+        });
+    // The body is read later, on demand, so the request counts as complete once its head is in
+    observed.finish(result.as_ref(), None);
+    result
 }
 
-struct SessionManager {
-    cache: Cache<String, Arc<SessionEntry>>,
+async fn streamed_request(options: RequestOptions, trace: &Trace) -> Result<Response> {
+    let session_id = options.session_id.clone();
+    let ephemeral = options.ephemeral;
+    let max_body_bytes = options.max_body_bytes;
+    let charset = options.charset;
+    let hash_body = options.hash_body;
+    let high_water_mark = options.high_water_mark.unwrap_or(DEFAULT_HIGH_WATER_MARK);
+    let inspected = inspected_emulation(&options);
+    let har = har::recorder().map(|recorder| {
+        let pending = recorder.pending(&options);
+        (recorder, pending)
+    });
+
+    let permit = concurrency::acquire(&options.url).await;
+    let result = send_with_retry(options, trace).await;
+
+    if ephemeral {
+        SESSION_MANAGER.drop_session(&session_id);
+    }
+
+    let Sent {
+        response,
+        attempts,
+        timings,
+        proxy,
+        connection,
+    } = match result {
+        Ok(sent) => sent,
+        Err(e) => {
+            if let Some((recorder, pending)) = har {
+                recorder.record(pending, Vec::new(), Err(&e), None);
+            }
+            return Err(e);
+        }
+    };
+    if let Some(limit) = max_body_bytes {
+        check_declared_length(&response, limit)?;
+    }
+
+    let mut head = response_head(&response);
+    head.attempts = attempts;
+    head.timings = timings;
+    head.proxy = proxy;
+    head.connection = Some(connection);
+    head.charset = charset;
+    head.fingerprint = inspected.map(|emulation| fingerprint_report(&emulation, &head));
+    // Only the head is known at this point; the body is read later, on demand
+    if let Some((recorder, pending)) = har {
+        recorder.record(pending, har::redirect_hops(&response), Ok(&head), None);
+    }
+    // The stream keeps the request's concurrency slot until its body is read or cancelled
+    head.stream_id = Some(store_stream(response, max_body_bytes, hash_body, high_water_mark, permit)?);
+    Ok(head)
 }
 
-impl SessionManager {
-    fn new() -> Self {
-        Self {
-            cache: Cache::builder()
-                .time_to_idle(Duration::from_secs(300))
-                .build(),
+// Captured before the request moves into the retry loop; the response completes the report
+fn inspected_emulation(options: &RequestOptions) -> Option<wreq::Emulation> {
+    options.inspect_fingerprint.then(|| {
+        fingerprint::emulation_with(
+            &options.emulation,
+            options.emulation_os,
+            options.tls.fingerprint.as_ref(),
+            options.http2.as_ref(),
+        )
+    })
+}
+
+// The final URL decides TLS and SNI, so a redirect to plain HTTP or an IP is described as such
+fn fingerprint_report(emulation: &wreq::Emulation, response: &Response) -> fingerprint::Report {
+    let uri = response.url.parse::<Uri>().ok();
+    let https = uri.as_ref().and_then(Uri::scheme_str) == Some("https");
+    let host_is_ip = uri
+        .as_ref()
+        .and_then(Uri::host)
+        .is_some_and(|host| host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok());
+    fingerprint::Report::new(emulation, https, host_is_ip, response.version)
+}
+
+/// How often download progress is reported while the body is arriving.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Download progress; `total` is the size of the whole file, when the server declared it.
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadProgress {
+    /// Bytes in the file so far, including any a resumed download started from
+    pub received: u64,
+    pub total: Option<u64>,
+    /// Average bytes per second since the body started arriving
+    pub rate: f64,
+}
+
+/// How `download` may use range requests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadPlan {
+    /// Continue a partial file left by an earlier `resume` download, and leave this one's
+    /// partial file behind for the next if it fails
+    pub resume: bool,
+    /// Fetch this many ranges of the body in parallel when the server supports them
+    pub segments: u32,
+}
+
+/// A finished download: the response head, with an empty body, and how the file was written.
+#[derive(Debug)]
+pub struct Downloaded {
+    pub head: Response,
+    /// Bytes this download wrote
+    pub written: u64,
+    /// Size of the partial file it continued; 0 when it started from scratch
+    pub resumed_from: u64,
+}
+
+/// Send a request and write its body straight to `dest` without buffering it.
+/// Progress is reported every `PROGRESS_INTERVAL` and once more when the body is complete.
+pub async fn make_download<F>(
+    mut options: RequestOptions,
+    dest: &Path,
+    plan: DownloadPlan,
+    on_progress: F,
+) -> Result<Downloaded>
+where
+    F: FnMut(DownloadProgress) + Send,
+{
+    let observed = Observed::start(&mut options);
+    let request_id = options.request_id.clone();
+    let session_id = options.session_id.clone();
+    let ephemeral = options.ephemeral;
+    let result = download_request(options, dest, plan, on_progress, &observed.trace)
+        .instrument(observed.span.clone())
+        .await
+        .map(|mut downloaded| {
+            downloaded.head.request_id = request_id;
+            downloaded
+        });
+    // Segments go out after the first response, so the ephemeral session outlives it
+    if ephemeral {
+        SESSION_MANAGER.drop_session(&session_id);
+    }
+    let received = result.as_ref().ok().map(|downloaded| downloaded.written);
+    observed.finish(result.as_ref().map(|downloaded| &downloaded.head), received);
+    result
+}
+
+// Where the first response's body goes, decided from how it answered the request's Range
+enum Placement {
+    /// Write the body from the start of the file
+    Whole,
+    /// Append the body to the partial file, which holds this many bytes
+    Append(u64),
+    /// Leave the partial file as it is, since the server answered the resume with an error
+    Keep,
+    /// Leave the file as it is, since it was already complete at this size
+    Complete(u64),
+    /// Fetch the body in ranges of a file this long, with this `If-Range` validator
+    Segments(u64, Option<String>),
+}
+
+async fn download_request<F>(
+    mut options: RequestOptions,
+    dest: &Path,
+    plan: DownloadPlan,
+    on_progress: F,
+    trace: &Trace,
+) -> Result<Downloaded>
+where
+    F: FnMut(DownloadProgress) + Send,
+{
+    let url = options.url.clone();
+    let max_body_bytes = options.max_body_bytes;
+    let mut hasher = options.hash_body.map(BodyHasher::new).transpose()?;
+    let har = har::recorder().map(|recorder| {
+        let pending = recorder.pending(&options);
+        (recorder, pending)
+    });
+
+    // A caller's own Range header goes out as is, and its answer is written like any other
+    let caller_range = options.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("range"));
+    let resume = match plan.resume && !caller_range {
+        true => ranges::load(dest, &url).await,
+        false => None,
+    };
+    let segmented = plan.segments > 1 && !caller_range && resume.is_none();
+    if (plan.resume && !caller_range) || segmented {
+        // Ranges count the bytes as sent, so ask for them unencoded, resumable first attempts included
+        options.accept_encoding = Some("identity".to_string());
+    }
+    let segment_options = segmented.then(|| options.clone());
+    match &resume {
+        Some((offset, state)) => {
+            options.headers.push(("Range".to_string(), format!("bytes={}-", offset)));
+            options.headers.push(("If-Range".to_string(), state.validator().to_string()));
         }
+        // One byte shows whether the server takes ranges, and how long the body is
+        None if segmented => options.headers.push(("Range".to_string(), "bytes=0-0".to_string())),
+        None => {}
     }
 
-    fn client_for(&self, session_id: &str, config: SessionConfig) -> Result<Arc<HttpClient>> {
-        if let Some(entry) = self.cache.get(session_id) {
-            if entry.config.matches(&config) {
-                return Ok(entry.client.clone());
-            } else {
+    let permit = concurrency::acquire(&url).await;
+    let Sent {
+        mut response,
+        attempts,
+        mut timings,
+        proxy,
+        connection,
+    } = match send_with_retry(options, trace).await {
+        Ok(sent) => sent,
+        Err(e) => {
+            if let Some((recorder, pending)) = har {
+                recorder.record(pending, Vec::new(), Err(&e), None);
+            }
+            return Err(e);
+        }
+    };
+    let hops = har.as_ref().map(|_| har::redirect_hops(&response)).unwrap_or_default();
+
+    let mut head = response_head(&response);
+    head.attempts = attempts;
+    head.proxy = proxy;
+    head.connection = Some(connection);
+
+    let status = response.status();
+    let served = ContentRange::from_headers(response.headers());
+    let encoded = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| !encoding.as_bytes().eq_ignore_ascii_case(b"identity"));
+    let placement = match &resume {
+        Some((offset, state)) if status == StatusCode::PARTIAL_CONTENT => {
+            if served.and_then(|served| served.range).map(|(first, _)| first) != Some(*offset)
+                || !state.matches(response.headers())
+                || encoded
+            {
+                // The file can't be trusted to continue, so the next download starts over
+                ranges::remove(dest).await;
+                let _ = tokio::fs::remove_file(dest).await;
+                anyhow::bail!(
+                    "The server resumed '{}' from a different point or version; its partial file was removed",
+                    dest.display()
+                );
+            }
+            Placement::Append(*offset)
+        }
+        Some((offset, state)) if status == StatusCode::RANGE_NOT_SATISFIABLE => {
+            let total = served.and_then(|served| served.total).or(state.total());
+            ranges::remove(dest).await;
+            if total != Some(*offset) {
+                let _ = tokio::fs::remove_file(dest).await;
                 anyhow::bail!(
-                    "Session '{}' was created with different browser/proxy configuration",
-                    session_id
+                    "The server has no bytes past the end of '{}'; its partial file was removed",
+                    dest.display()
                 );
             }
+            Placement::Complete(*offset)
+        }
+        Some(_) if !status.is_success() => Placement::Keep,
+        None if segmented && status == StatusCode::PARTIAL_CONTENT => {
+            let total = served.filter(|served| served.range == Some((0, 0))).and_then(|served| served.total);
+            match total {
+                Some(total) if !encoded => {
+                    let validator = ResumeState::from_headers(&url, response.headers())
+                        .map(|state| state.validator().to_string());
+                    Placement::Segments(total, validator)
+                }
+                _ => anyhow::bail!("The server answered the range probe for '{}' with an unusable range", url),
+            }
+        }
+        // The only range an empty body can't satisfy is its first byte
+        None if segmented
+            && status == StatusCode::RANGE_NOT_SATISFIABLE
+            && served.and_then(|served| served.total) == Some(0) =>
+        {
+            Placement::Segments(0, None)
+        }
+        _ => Placement::Whole,
+    };
+
+    let started = Instant::now();
+    let (written, resumed_from) = match placement {
+        Placement::Keep => {
+            hasher = None;
+            (0, resume.map_or(0, |(offset, _)| offset))
+        }
+        Placement::Complete(len) => {
+            if let Some(hasher) = hasher.as_mut() {
+                hash_file(dest, len, hasher).await?;
+            }
+            (0, len)
         }
+        Placement::Segments(total, validator) => {
+            if let Some(limit) = max_body_bytes.filter(|limit| total > *limit) {
+                return Err(BodyTooLarge { limit }.into());
+            }
+            // The head describes the whole file rather than the probe's single byte
+            head.status = 200;
+            head.headers.retain(|(name, _)| {
+                !name.eq_ignore_ascii_case("content-range") && !name.eq_ignore_ascii_case("content-length")
+            });
+            head.headers.push(("content-length".to_string(), total.to_string()));
+            drop(response);
+            // Each segment waits for a slot of its own; holding this one too would deadlock a limit of one
+            drop(permit);
 
-        let entry = self.build_entry(config)?;
-        self.cache.insert(session_id.to_string(), entry.clone());
-        Ok(entry.client.clone())
+            let reporter = StdMutex::new(ProgressReporter::new(on_progress, 0, Some(total), started));
+            let options = segment_options.expect("segment options are kept for segmented downloads");
+            let written = download_segments(options, trace, dest, total, plan.segments, validator, &reporter).await;
+            if let Err(e) = written {
+                let _ = tokio::fs::remove_file(dest).await;
+                return Err(e);
+            }
+            if let Some(hasher) = hasher.as_mut() {
+                hash_file(dest, total, hasher).await?;
+            }
+            reporter.into_inner().unwrap().finish(total);
+            (total, 0)
+        }
+        placement @ (Placement::Whole | Placement::Append(_)) => {
+            let offset = match placement {
+                Placement::Append(offset) => offset,
+                _ => 0,
+            };
+            if let Some(limit) = max_body_bytes {
+                check_declared_length(&response, limit.saturating_sub(offset))?;
+            }
+            // Only bodies with a validator can be resumed later. A 206 need not repeat them,
+            // and the state the file was resumed with still describes it
+            let state = match plan.resume && !caller_range && status.is_success() {
+                true if offset > 0 => resume.map(|(_, state)| state),
+                true => ResumeState::from_headers(&url, response.headers()),
+                false => None,
+            };
+            match &state {
+                Some(state) => ranges::save(dest, state).await?,
+                None => ranges::remove(dest).await,
+            }
+
+            let mut file = if offset > 0 {
+                if let Some(hasher) = hasher.as_mut() {
+                    hash_file(dest, offset, hasher).await?;
+                }
+                let mut file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .open(dest)
+                    .await
+                    .with_context(|| format!("Failed to open '{}'", dest.display()))?;
+                file.set_len(offset)
+                    .await
+                    .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+                file.seek(SeekFrom::Start(offset))
+                    .await
+                    .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+                file
+            } else {
+                tokio::fs::File::create(dest)
+                    .await
+                    .with_context(|| format!("Failed to create '{}'", dest.display()))?
+            };
+
+            let total = match served {
+                Some(served) if offset > 0 => served.total,
+                _ => response.content_length(),
+            };
+            let mut reporter = ProgressReporter::new(on_progress, offset, total, started);
+            let received = write_body(&mut response, &mut file, dest, max_body_bytes, hasher.as_mut(), &mut reporter)
+                .instrument(tracing::debug_span!("body-read", dest = %dest.display()))
+                .await;
+
+            // Never leave a truncated file behind that could pass for a complete download,
+            // unless it is kept to be resumed
+            let received = match received {
+                Ok(received) => received,
+                Err(e) => {
+                    if state.is_some() {
+                        // What arrived stays on disk for the next download to continue from
+                        let _ = file.flush().await;
+                    } else {
+                        drop(file);
+                        let _ = tokio::fs::remove_file(dest).await;
+                    }
+                    return Err(e);
+                }
+            };
+            if state.is_some() {
+                ranges::remove(dest).await;
+            }
+            reporter.finish(received);
+            (received - offset, offset)
+        }
+    };
+
+    timings.finish(started.elapsed());
+    head.timings = timings;
+    head.body_hash = hasher.map(BodyHasher::finish).transpose()?;
+    // The body went to disk, so the entry only notes its size
+    if let Some((recorder, pending)) = har {
+        recorder.record(pending, hops, Ok(&head), None);
     }
+    Ok(Downloaded {
+        head,
+        written,
+        resumed_from,
+    })
+}
+
+// Progress of one download, shared by its segments
+struct ProgressReporter<F> {
+    on_progress: F,
+    /// Bytes already in the file when the body started arriving
+    offset: u64,
+    total: Option<u64>,
+    started: Instant,
+    last_report: Instant,
+}
 
-    fn build_entry(&self, config: SessionConfig) -> Result<Arc<SessionEntry>> {
-        let client = Arc::new(build_client(&config)?);
-        Ok(Arc::new(SessionEntry { client, config }))
+impl<F: FnMut(DownloadProgress)> ProgressReporter<F> {
+    fn new(on_progress: F, offset: u64, total: Option<u64>, started: Instant) -> Self {
+        Self {
+            on_progress,
+            offset,
+            total,
+            started,
+            last_report: started,
+        }
     }
 
-    fn create_session(&self, session_id: String, config: SessionConfig) -> Result<String> {
-        let entry = self.build_entry(config)?;
-        self.cache.insert(session_id.clone(), entry);
-        Ok(session_id)
+    fn update(&mut self, received: u64) {
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.finish(received);
+            self.last_report = Instant::now();
+        }
     }
 
-    fn clear_session(&self, session_id: &str) -> Result<()> {
-        let existing = self
-            .cache
-            .get(session_id)
-            .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
-        let config = existing.config.clone();
-        let entry = self.build_entry(config)?;
-        self.cache.insert(session_id.to_string(), entry);
-        Ok(())
+    fn finish(&mut self, received: u64) {
+        let elapsed = self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        (self.on_progress)(DownloadProgress {
+            received,
+            total: self.total,
+            rate: (received - self.offset) as f64 / elapsed,
+        });
     }
+}
+
+// Write a body to the file, returning the size the file has reached
+async fn write_body<F: FnMut(DownloadProgress)>(
+    response: &mut wreq::Response,
+    file: &mut tokio::fs::File,
+    dest: &Path,
+    max_body_bytes: Option<u64>,
+    mut hasher: Option<&mut BodyHasher>,
+    reporter: &mut ProgressReporter<F>,
+) -> Result<u64> {
+    let mut received = reporter.offset;
+    while let Some(chunk) = response.chunk().await.context("Failed to read response body")? {
+        received += chunk.len() as u64;
+        if let Some(limit) = max_body_bytes.filter(|limit| received > *limit) {
+            return Err(BodyTooLarge { limit }.into());
+        }
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk)?;
+        }
 
-    fn drop_session(&self, session_id: &str) {
-        self.cache.invalidate(session_id);
+        file.write_all(&chunk)
+            .await
+            .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+        reporter.update(received);
     }
+
+    file.flush()
+        .await
+        .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+    Ok(received)
 }
 
-pub async fn make_request(options: RequestOptions) -> Result<Response> {
-    let session_id = options.session_id.clone();
-    let ephemeral = options.ephemeral;
+// Split a body of `total` bytes into ranges fetched in parallel, each written at its offset
+async fn download_segments<F>(
+    options: RequestOptions,
+    trace: &Trace,
+    dest: &Path,
+    total: u64,
+    segments: u32,
+    validator: Option<String>,
+    reporter: &StdMutex<ProgressReporter<F>>,
+) -> Result<()>
+where
+    F: FnMut(DownloadProgress) + Send,
+{
+    let file = tokio::fs::File::create(dest)
+        .await
+        .with_context(|| format!("Failed to create '{}'", dest.display()))?;
+    file.set_len(total)
+        .await
+        .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+    drop(file);
 
-    let result = make_request_inner(options).await;
+    let count = u64::from(segments).clamp(1, total.max(1));
+    let size = total.div_ceil(count);
+    let received = AtomicU64::new(0);
+    let requests = (0..count).map(|index| index * size).filter(|first| *first < total).map(|first| {
+        let range = (first, (first + size).min(total) - 1);
+        let segment = Segment {
+            dest,
+            range,
+            validator: validator.as_deref(),
+            received: &received,
+        };
+        download_segment(options.clone(), trace, segment, reporter)
+    });
+    futures_util::future::try_join_all(requests).await?;
+    Ok(())
+}
 
-    if ephemeral {
-        SESSION_MANAGER.drop_session(&session_id);
+// One range of a segmented download
+struct Segment<'a> {
+    dest: &'a Path,
+    /// First and last byte, inclusive
+    range: (u64, u64),
+    validator: Option<&'a str>,
+    /// Bytes received by every segment of the download
+    received: &'a AtomicU64,
+}
+
+async fn download_segment<F>(
+    mut options: RequestOptions,
+    trace: &Trace,
+    segment: Segment<'_>,
+    reporter: &StdMutex<ProgressReporter<F>>,
+) -> Result<()>
+where
+    F: FnMut(DownloadProgress) + Send,
+{
+    let (first, last) = segment.range;
+    let dest = segment.dest;
+    options.headers.push(("Range".to_string(), format!("bytes={}-{}", first, last)));
+    // If the body changed since the probe, the server sends all of it instead of the range
+    if let Some(validator) = segment.validator {
+        options.headers.push(("If-Range".to_string(), validator.to_string()));
     }
 
-    result
+    // A segment is a request like any other, so it counts against the concurrency limits
+    let _permit = concurrency::acquire(&options.url).await;
+    let Sent { mut response, .. } = send_with_retry(options, trace).await?;
+    let served = ContentRange::from_headers(response.headers()).and_then(|served| served.range);
+    if response.status() != StatusCode::PARTIAL_CONTENT || served != Some(segment.range) {
+        anyhow::bail!(
+            "The server answered bytes {}-{} of '{}' with status {} instead of that range; the body may have changed",
+            first,
+            last,
+            dest.display(),
+            response.status().as_u16()
+        );
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(dest)
+        .await
+        .with_context(|| format!("Failed to open '{}'", dest.display()))?;
+    file.seek(SeekFrom::Start(first))
+        .await
+        .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+
+    let mut position = first;
+    while let Some(chunk) = response.chunk().await.context("Failed to read response body")? {
+        position += chunk.len() as u64;
+        if position > last + 1 {
+            anyhow::bail!("The server sent more than bytes {}-{} of '{}'", first, last, dest.display());
+        }
+        file.write_all(&chunk)
+            .await
+            .with_context(|| format!("Failed to write '{}'", dest.display()))?;
+        let received = segment.received.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+        reporter.lock().unwrap().update(received);
+    }
+    if position != last + 1 {
+        anyhow::bail!("The server ended bytes {}-{} of '{}' early", first, last, dest.display());
+    }
+
+    file.flush()
+        .await
+        .with_context(|| format!("Failed to write '{}'", dest.display()))
+}
+
+// Feed the first `len` bytes of a file to a hasher, for bodies that weren't written in order
+async fn hash_file(path: &Path, len: u64, hasher: &mut BodyHasher) -> Result<()> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open '{}'", path.display()))?;
+    let mut reader = file.take(len);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        if read == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..read])?;
+    }
+}
+
+// What is reported about one request: lifecycle events, metrics and its tracing span
+struct Observed {
+    trace: Trace,
+    span: Span,
+    started: Instant,
+    sent: Option<u64>,
+}
+
+impl Observed {
+    // A W3C trace ID also goes out as a `traceparent` header, unless the caller set one
+    fn start(options: &mut RequestOptions) -> Self {
+        let method = if options.method.is_empty() { "GET" } else { &options.method };
+        let span = tracing::info_span!(
+            "request",
+            method = %method,
+            url = %options.url,
+            session = %options.session_id,
+            request_id = %options.request_id,
+            trace_id = tracing::field::Empty,
+            span_id = tracing::field::Empty,
+        );
+
+        if let Some(trace_id) = &options.trace_id {
+            span.record("trace_id", trace_id.as_str());
+            let propagated = options.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("traceparent"));
+            if telemetry::is_w3c_trace_id(trace_id) && !propagated {
+                let (header, span_id) = telemetry::traceparent(trace_id);
+                span.record("span_id", span_id.as_str());
+                options.headers.push(("traceparent".to_string(), header));
+            }
+        }
+
+        Self {
+            trace: Trace::start(&options.method, &options.url, &options.request_id),
+            span,
+            started: Instant::now(),
+            sent: request_body_size(options),
+        }
+    }
+
+    fn finish(&self, result: Result<&Response, &anyhow::Error>, received: Option<u64>) {
+        metrics::record(result, self.started.elapsed(), self.sent, received);
+
+        let _entered = self.span.enter();
+        match result {
+            Ok(response) => {
+                tracing::info!(
+                    status = response.status,
+                    attempts = response.attempts,
+                    from_cache = response.from_cache,
+                    bytes = received,
+                    "response"
+                );
+                self.trace.complete(response, received);
+            }
+            Err(e) => {
+                tracing::warn!(code = ErrorKind::classify(e).code(), error = %format!("{:#}", e), "request failed");
+                self.trace.error(e);
+            }
+        }
+    }
+}
+
+// Size of the request body, when it is known up front; multipart bodies aren't
+fn request_body_size(options: &RequestOptions) -> Option<u64> {
+    if let Some(body) = &options.body {
+        Some(body.len() as u64)
+    } else if let Some(json) = &options.json {
+        serde_json::to_vec(json).ok().map(|json| json.len() as u64)
+    } else {
+        let path = options.body_file.as_ref()?;
+        std::fs::metadata(path).ok().map(|metadata| metadata.len())
+    }
+}
+
+// Send the request, retrying retryable statuses and network errors per the request's policy.
+// Timings describe the final attempt only.
+async fn send_with_retry(mut options: RequestOptions, trace: &Trace) -> Result<Sent> {
+    let policy = options.retry.clone();
+
+    // Routing rules override the proxy the request, or its session, chose for this host
+    let pool = match proxy_routes::route(&options.url) {
+        Some(RouteTarget::Direct) => {
+            options.proxy = None;
+            options.direct = true;
+            None
+        }
+        Some(RouteTarget::Proxy(proxy)) => {
+            options.proxy = Some(proxy);
+            None
+        }
+        Some(RouteTarget::Pool(pool)) => Some(pool),
+        None => options.proxy_pool.clone(),
+    };
+    let deadline = options.deadline.map(|deadline| (deadline, Instant::now() + deadline));
+    let attempt_timeout = options.timeout;
+    let mut attempt = 1;
+    // Proxies that failed this request, and how many times it was sent on through another
+    let mut failed_proxies = Vec::new();
+    let mut failovers = 0;
+
+    loop {
+        let last_attempt = attempt >= policy.max_attempts;
+
+        // A pool draws a proxy for every attempt, so a retry can go out through another one
+        if let Some(pool) = &pool {
+            options.proxy = Some(pool.pick(request_host(&options.url).as_deref(), &failed_proxies));
+        }
+
+        // An open circuit turns the attempt away before it takes a rate limit token
+        let circuit = circuit_breaker::admit(&options.url)?;
+
+        // Every attempt counts against the rate limit, since each one reaches the server
+        rate_limit::throttle(&options.url, options.proxy.as_deref()).await?;
+
+        // Each attempt gets what is left of the deadline when that is less than its own timeout;
+        // wreq applies the limit until the body has been read, so it covers the download too
+        if let Some((deadline, at)) = deadline {
+            let remaining = at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(DeadlineExceeded { deadline }.into());
+            }
+            options.timeout = Some(attempt_timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+        }
+
+        let started = Instant::now();
+        let sending = cookie_jar::scope(options.cookies, send_authenticated(options.clone()));
+        let sending = measure_connect(options.connect_timeout, sending);
+        let (result, connection) = trace.scope(sending).await;
+        if let Some(circuit) = circuit {
+            circuit.record(result.as_ref().map(|response| response.status().as_u16()));
+        }
+        if let (Some(pool), Some(proxy)) = (&pool, &options.proxy) {
+            let outcome = result.as_ref().map(|response| response.status().as_u16());
+            let failed = proxy_pool::is_proxy_failure(outcome);
+            pool.report(proxy, failed);
+
+            // Another of the pool's proxies gets the request straight away, outside the retry policy
+            let untried = failed_proxies.len() + 1 < pool.size();
+            let in_time = deadline.is_none_or(|(_, at)| Instant::now() < at);
+            if failed && failovers < pool.failover() && untried && in_time {
+                failed_proxies.push(proxy.clone());
+                failovers += 1;
+                drop(result);
+                tracing::debug!(failovers, "sending through another proxy");
+                continue;
+            }
+        }
+        if let Ok(response) = &result {
+            trace.first_byte(response.status().as_u16(), attempt + failovers);
+        }
+
+        let delay = match &result {
+            Ok(response) if policy.should_retry_status(response.status().as_u16()) => {
+                Some(policy.retry_after(response).unwrap_or_else(|| policy.backoff(attempt)))
+            }
+            Err(e) if policy.should_retry_error(e) => Some(policy.backoff(attempt)),
+            _ => None,
+        };
+        // A retry that could only start after the deadline isn't made; this outcome stands instead
+        let delay = delay.filter(|delay| !last_attempt && deadline.is_none_or(|(_, at)| Instant::now() + *delay < at));
+        let Some(delay) = delay else {
+            return result.map(|response| Sent {
+                connection: connection_info(&response, connection, &options),
+                response,
+                attempts: attempt + failovers,
+                timings: Timings::head(connection, started.elapsed()),
+                proxy: options.proxy,
+            });
+        };
+
+        drop(result);
+        tracing::debug!(attempt, delay_ms = delay.as_millis() as u64, "retrying");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// Where the response came from, and the pool its connection belongs to
+fn connection_info(response: &wreq::Response, timings: ConnectTimings, options: &RequestOptions) -> ConnectionInfo {
+    let origin = options
+        .url
+        .parse::<Uri>()
+        .ok()
+        .and_then(|uri| {
+            let scheme = uri.scheme_str()?.to_ascii_lowercase();
+            let port = uri.port_u16().unwrap_or(if scheme == "https" { 443 } else { 80 });
+            Some(format!("{}://{}:{}", scheme, uri.host()?, port))
+        })
+        .unwrap_or_else(|| options.url.clone());
+
+    let mut pool_key = format!("{} {}", options.session_id, origin);
+    let proxy = if let Some(path) = &options.unix_socket {
+        Some(format!("unix:{}", path.display()))
+    } else if options.direct {
+        None
+    } else {
+        options.proxy.clone().or_else(|| managed_session_proxy(&options.session_id))
+    };
+    if let Some(proxy) = proxy {
+        // Credentials stay out of the key, which ends up in logs
+        let proxy = match proxy.split_once("://") {
+            Some((scheme, rest)) => {
+                let host = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+                format!("{}://{}", scheme, host)
+            }
+            None => proxy,
+        };
+        pool_key.push_str(&format!(" via {}", proxy));
+    }
+    if let Some(address) = options.local_address {
+        pool_key.push_str(&format!(" from {}", address));
+    }
+    if let Some(interface) = &options.interface {
+        pool_key.push_str(&format!(" on {}", interface));
+    }
+
+    ConnectionInfo {
+        reused: !timings.opened(),
+        local_addr: response.local_addr(),
+        remote_addr: response.remote_addr(),
+        pool_key,
+    }
 }
 
-async fn make_request_inner(options: RequestOptions) -> Result<Response> {
-    let client = {
+/// Most of a challenge response body that is read so its connection can be reused.
+const CHALLENGE_BODY_LIMIT: u64 = 64 * 1024;
+
+// Send the request, answering a `401` Digest or NTLM challenge when credentials were given.
+// The answer goes to the URL that was challenged, which differs from the one requested
+// only when redirects were followed.
+async fn send_authenticated(mut options: RequestOptions) -> Result<wreq::Response> {
+    let Some(config) = options.auth.clone() else {
+        return send_request(options).await;
+    };
+    // NTLM authenticates a connection rather than a request, so the handshake stays on HTTP/1.1
+    if config.scheme == AuthScheme::Ntlm && options.http_version.is_none() {
+        options.http_version = Some(Version::HTTP_11);
+    }
+
+    let response = send_request(options.clone()).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+    options.url = response.uri().to_string();
+
+    let (response, authorization) = match config.scheme {
+        AuthScheme::Digest => {
+            let uri = response.uri().path_and_query().map_or("/", |target| target.as_str()).to_string();
+            let challenges = www_authenticate(&response);
+            let authorization = auth::digest_authorization(&config, challenges, &options.method, &uri)?;
+            (response, authorization)
+        }
+        AuthScheme::Ntlm => {
+            if !auth::offers_ntlm(www_authenticate(&response)) {
+                return Ok(response);
+            }
+            drain(response).await;
+            let response = send_request(with_authorization(options.clone(), auth::ntlm_negotiate())).await?;
+            if response.status() != StatusCode::UNAUTHORIZED {
+                return Ok(response);
+            }
+            let authorization = auth::ntlm_authenticate(&config, www_authenticate(&response))?;
+            (response, authorization)
+        }
+    };
+
+    let Some(authorization) = authorization else {
+        return Ok(response);
+    };
+    drain(response).await;
+    send_request(with_authorization(options, authorization)).await
+}
+
+fn www_authenticate(response: &wreq::Response) -> impl Iterator<Item = &str> {
+    response
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+}
+
+fn with_authorization(mut options: RequestOptions, authorization: String) -> RequestOptions {
+    options.headers.retain(|(key, _)| !key.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+    options.headers.push((AUTHORIZATION.to_string(), authorization));
+    options
+}
+
+// Read a challenge's body so the connection goes back to the pool for the next leg
+async fn drain(response: wreq::Response) {
+    let _ = read_body(response, Some(CHALLENGE_BODY_LIMIT)).await;
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "solaris",
+    target_os = "tvos",
+    target_os = "visionos",
+    target_os = "watchos",
+))]
+fn bind_interface(request: RequestBuilder, interface: String) -> Result<RequestBuilder> {
+    Ok(request.interface(interface))
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "solaris",
+    target_os = "tvos",
+    target_os = "visionos",
+    target_os = "watchos",
+)))]
+fn bind_interface(_request: RequestBuilder, interface: String) -> Result<RequestBuilder> {
+    anyhow::bail!("Binding to network interface '{}' is not supported on this platform", interface)
+}
+
+#[cfg(unix)]
+fn unix_socket_proxy(path: PathBuf) -> Result<wreq::Proxy> {
+    wreq::Proxy::unix(path).context("Invalid Unix socket path")
+}
+
+#[cfg(not(unix))]
+fn unix_socket_proxy(path: PathBuf) -> Result<wreq::Proxy> {
+    anyhow::bail!("Unix socket '{}' is not supported on this platform", path.display())
+}
+
+async fn send_request(mut options: RequestOptions) -> Result<wreq::Response> {
+    compress::apply(&mut options)?;
+
+    // What middleware returns goes out as is, in its order, instead of being merged with the
+    // emulation's defaults. Removing `Cookie` doesn't keep the jar's cookies off; `cookies` does
+    let mut hooked = false;
+    if let Some(hook) = options.before_send.clone() {
+        let method = if options.method.is_empty() { "GET" } else { options.method.as_str() };
+        let outgoing = Outgoing {
+            url: options.url.clone(),
+            method: method.to_string(),
+            headers: outgoing_headers(&options),
+        };
+        if let Some(outgoing) = hook.run(outgoing).await? {
+            options.url = outgoing.url;
+            options.method = outgoing.method;
+            options.headers = outgoing.headers;
+            options.accept_encoding = None;
+            options.removed_headers.clear();
+            options.header_positions.clear();
+            options.disable_default_headers = true;
+            hooked = true;
+        }
+    }
+
+    // Signed last, over the headers and body this attempt actually sends
+    if let Some(config) = options.aws_sigv4.clone() {
+        sigv4::sign(&mut options, &config).await?;
+    }
+
+    let session = {
         let config = SessionConfig::from_request(&options);
-        SESSION_MANAGER.client_for(&options.session_id, config)?
+        SESSION_MANAGER.session_for(&options.session_id, config)?
     };
+    let client = &session.client;
+
+    // wreq can't leave out single emulation headers, so a request that removes some sends
+    // the rest of the defaults itself, in the emulation's order. Cookies stay with the store
+    let removing = !options.removed_headers.is_empty() && !options.disable_default_headers;
+    if removing {
+        let caller_cookie = options.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(COOKIE.as_str()));
+        let mut headers = outgoing_headers(&options);
+        if !caller_cookie {
+            headers.retain(|(key, _)| !key.eq_ignore_ascii_case(COOKIE.as_str()));
+        }
+        options.headers = headers;
+        options.disable_default_headers = true;
+    }
+
+    // Positioned headers pin the whole wire order, emulation defaults included
+    let wire_order = (removing || hooked || !options.header_positions.is_empty())
+        .then(|| header_order::orig_headers(&outgoing_headers(&options)));
 
     let RequestOptions {
         url,
         headers,
         method,
         body,
+        multipart,
+        json,
+        body_file,
+        trailers,
+        proxy,
+        proxy_auth,
+        proxy_headers,
+        direct,
+        local_address,
+        interface,
+        unix_socket,
         timeout,
+        read_timeout,
         disable_default_headers,
+        redirect,
+        http_version,
+        decompress,
+        decode_after_read,
+        accept_encoding,
+        pinned_sha256,
         ..
     } = options;
 
+    // Any RFC 9110 token is a valid method, so extension methods (PROPFIND, REPORT, ...) pass through
     let method = if method.is_empty() {
-        "GET".to_string()
+        Method::GET
     } else {
-        method
+        Method::from_bytes(method.as_bytes()).map_err(|_| anyhow::anyhow!("Invalid HTTP method: {}", method))?
     };
 
-    let method_upper = method.to_uppercase();
-
     // Build request
-    let mut request = match method_upper.as_str() {
-        "GET" => client.get(&url),
-        "POST" => client.post(&url),
-        "PUT" => client.put(&url),
-        "DELETE" => client.delete(&url),
-        "PATCH" => client.patch(&url),
-        "HEAD" => client.head(&url),
-        _ => return Err(anyhow::anyhow!("Unsupported HTTP method: {}", method_upper)),
-    };
+    let mut request = client.request(method.clone(), &url);
 
-    // Apply custom headers
+    // Apply custom headers, appending so repeated names are all sent
     for (key, value) in headers.iter() {
-        request = request.header(key, value);
+        request = request.header_append(key, value);
+    }
+
+    if let Some(order) = wire_order {
+        request = request.orig_headers(order);
     }
 
     // Disable default headers if requested to prevent emulation headers from being appended
@@ -193,88 +1578,493 @@ async fn make_request_inner(options: RequestOptions) -> Result<Response> {
         request = request.default_headers(false);
     }
 
+    // Turn off automatic decompression, leaving the raw encoded body and its headers, either
+    // for the caller or for `read_response` to decode
+    if !decompress || decode_after_read {
+        request = request.gzip(false).brotli(false).deflate(false).zstd(false);
+    }
+
+    // wreq's decoder would have asked for the codings itself when the emulation's headers are off
+    let asks = accept_encoding.is_some() || headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("accept-encoding"));
+    if decode_after_read && disable_default_headers && !asks {
+        request = request.header(ACCEPT_ENCODING, "gzip, deflate, br, zstd");
+    }
+
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header(ACCEPT_ENCODING, accept_encoding);
+    }
+
     // Apply body if present
     if let Some(body) = body {
         request = request.body(body);
     }
 
-    // Apply timeout
-    request = request.timeout(Duration::from_millis(timeout));
+    // Apply multipart form if present; wreq sets the boundary content type
+    if let Some(fields) = multipart {
+        request = request.multipart(build_form(fields).await?);
+    }
+
+    // Apply JSON body if present
+    if let Some(json) = json {
+        request = request.json(&json);
+    }
+
+    // Stream the body from disk. Each attempt reopens the file, so retries resend it from the start
+    if let Some(path) = body_file {
+        let file = tokio::fs::File::open(&path)
+            .await
+            .with_context(|| format!("Failed to open body file '{}'", path.display()))?;
+
+        if trailers.is_empty() {
+            // A streamed body would otherwise go out chunked, which some servers refuse
+            let declared = headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-length"));
+            if !declared {
+                if let Ok(metadata) = file.metadata().await {
+                    request = request.header(CONTENT_LENGTH, metadata.len());
+                }
+            }
+            request = request.body(file);
+        } else {
+            // Trailers only fit after a chunked body, announced up front in `Trailer`
+            let mut fields = HeaderMap::new();
+            for (name, value) in &trailers {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid trailer name: {}", name))?;
+                let value =
+                    HeaderValue::from_str(value).with_context(|| format!("Invalid trailer value for {}", name))?;
+                fields.append(name, value);
+            }
+            let names: Vec<&str> = fields.keys().map(HeaderName::as_str).collect();
+            request = request.header(TRAILER, names.join(", "));
+            request = request.body(wreq::Body::wrap(WithTrailers::new(wreq::Body::from(file), fields)));
+        }
+    }
+
+    // Apply proxy, falling back to the one the session was created with unless routed direct.
+    // A Unix socket takes the proxy's place, so none applies
+    let proxy_url = if direct || unix_socket.is_some() {
+        None
+    } else {
+        proxy.as_deref().or_else(|| session.default_proxy())
+    };
+    if let Some(proxy_url) = proxy_url {
+        request = request.proxy(build_proxy(proxy_url, proxy_auth.as_ref(), &proxy_headers)?);
+
+        // wreq only adds proxy credentials and headers to CONNECT tunnels for per-request
+        // proxies, so plain HTTP requests forwarded by an HTTP proxy need them set here
+        let forwarded = url.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+            && proxy_url.get(..4).is_some_and(|scheme| scheme.eq_ignore_ascii_case("http"));
+        if forwarded {
+            if let Some(auth) = proxy_credentials(proxy_url, proxy_auth.as_ref()) {
+                request = request.header("Proxy-Authorization", basic_auth_header(&auth));
+            }
+            for (key, value) in proxy_headers.iter() {
+                request = request.header_append(key, value);
+            }
+        }
+    }
+
+    if let Some(path) = unix_socket {
+        request = request.proxy(unix_socket_proxy(path)?);
+    }
+
+    // Bind the outgoing socket; wreq pools connections per source, so they aren't mixed up
+    if let Some(address) = local_address {
+        request = request.local_address(address);
+    }
+    if let Some(interface) = interface {
+        request = bind_interface(request, interface)?;
+    }
+
+    // Force a protocol version if requested
+    if let Some(version) = http_version {
+        request = request.version(version);
+    }
+
+    // Apply redirect policy
+    let policy = if redirect.follow {
+        redirect::Policy::limited(redirect.max as usize)
+    } else {
+        redirect::Policy::none()
+    };
+    request = request.redirect(policy);
+
+    // Apply timeouts; a slow body that keeps arriving only runs into the total one
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    if let Some(timeout) = read_timeout {
+        request = request.read_timeout(timeout);
+    }
 
     // Execute request
     let response = request
         .send()
         .await
-        .with_context(|| format!("{} {}", method_upper, url))?;
+        .with_context(|| format!("{} {}", method, url))?;
+
+    // Checked on the response head, so a mismatched server never gets its body read
+    if !pinned_sha256.is_empty() {
+        let der = response
+            .extension::<TlsInfo>()
+            .and_then(|Extension(info)| info.peer_certificate());
+        verify_pins(der, &pinned_sha256).with_context(|| format!("{} {}", method, url))?;
+    }
+
+    Ok(response)
+}
+
+/// The headers a request goes out with, as far as they are known before it is sent: the
+/// emulation's defaults, with the caller's headers taking the place of any that share a
+/// name, then the caller's remaining headers and the session's cookies. Positioned headers
+/// are then moved next to their anchors.
+pub fn outgoing_headers(options: &RequestOptions) -> Vec<(String, String)> {
+    let mut caller = options.headers.clone();
+    if let Some(accept_encoding) = &options.accept_encoding {
+        caller.retain(|(key, _)| !key.eq_ignore_ascii_case(ACCEPT_ENCODING.as_str()));
+        caller.push((ACCEPT_ENCODING.to_string(), accept_encoding.clone()));
+    }
+    let has = |headers: &[(String, String)], name: &str| headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(name));
+    if options.json.is_some() && !has(&caller, CONTENT_TYPE.as_str()) {
+        caller.push((CONTENT_TYPE.to_string(), "application/json".to_string()));
+    }
+
+    let mut defaults = HeaderMap::new();
+    if !options.disable_default_headers {
+        defaults = std::mem::take(emulation::build(&options.emulation, options.emulation_os).headers_mut());
+        for name in &options.removed_headers {
+            defaults.remove(name.as_str());
+        }
+    }
 
-    // Extract response data
+    let mut headers = Vec::new();
+    for (name, value) in defaults.iter() {
+        if !has(&caller, name.as_str()) {
+            if let Ok(value) = value.to_str() {
+                headers.push((name.to_string(), value.to_string()));
+            }
+        } else if !has(&headers, name.as_str()) {
+            let overrides = caller.iter().filter(|(key, _)| key.eq_ignore_ascii_case(name.as_str()));
+            headers.extend(overrides.cloned());
+        }
+    }
+    let rest = caller.into_iter().filter(|(key, _)| !defaults.contains_key(key.to_ascii_lowercase().as_str()));
+    headers.extend(rest);
+
+    // The cookie store leaves requests that already carry a Cookie header alone
+    if options.cookies.uses_jar() && !has(&headers, COOKIE.as_str()) {
+        let cookies = options
+            .url
+            .parse::<Uri>()
+            .ok()
+            .and_then(|uri| Some(managed_cookie_jar(&options.session_id).ok()?.for_uri(&uri)))
+            .unwrap_or_default();
+        if !cookies.is_empty() {
+            let pairs = cookies.iter().map(|cookie| format!("{}={}", cookie.name, cookie.value));
+            headers.push((COOKIE.to_string(), pairs.collect::<Vec<_>>().join("; ")));
+        }
+    }
+
+    header_order::arrange(headers, &options.header_positions)
+}
+
+async fn build_form(fields: Vec<MultipartField>) -> Result<Form> {
+    let mut form = Form::new();
+
+    for MultipartField { name, value } in fields {
+        form = match value {
+            MultipartValue::Text(text) => form.text(name, text),
+            MultipartValue::File {
+                source,
+                filename,
+                content_type,
+            } => {
+                let mut part = match source {
+                    // Part::file infers the filename and mime type from the path
+                    FileSource::Path(path) => Part::file(&path)
+                        .await
+                        .with_context(|| format!("Failed to open multipart file '{}'", path))?,
+                    FileSource::Bytes(bytes) => Part::bytes(bytes).mime_str("application/octet-stream")?,
+                };
+                if let Some(filename) = filename {
+                    part = part.file_name(filename);
+                }
+                if let Some(content_type) = content_type {
+                    part = part
+                        .mime_str(&content_type)
+                        .with_context(|| format!("Invalid content type '{}' for field '{}'", content_type, name))?;
+                }
+                form.part(name, part)
+            }
+        };
+    }
+
+    Ok(form)
+}
+
+// Extract status, headers, cookies and final URL without touching the body
+fn response_head(response: &wreq::Response) -> Response {
     let status = response.status().as_u16();
+    let version = version_label(response.version());
     let final_url = response.uri().to_string();
 
-    // Extract headers
-    let mut response_headers = IndexMap::new();
+    // Extract headers as received; repeated ones (e.g. set-cookie) stay separate entries
+    let mut response_headers = Vec::with_capacity(response.headers().len());
+    let mut raw_header_values = Vec::new();
     for (key, value) in response.headers() {
-        if let Ok(value_str) = value.to_str() {
-            response_headers.insert(key.to_string(), value_str.to_string());
-        }
+        let value = match value.to_str() {
+            Ok(value) => value.to_string(),
+            Err(_) => {
+                raw_header_values.push((response_headers.len(), value.as_bytes().to_vec()));
+                value.as_bytes().iter().map(|&byte| byte as char).collect()
+            }
+        };
+        response_headers.push((key.to_string(), value));
     }
 
-    // Extract cookies
-    let mut cookies = IndexMap::new();
-    for cookie in response.cookies() {
-        cookies.insert(cookie.name().to_string(), cookie.value().to_string());
-    }
+    // Extract cookies from every set-cookie header
+    let set_cookies = parse_set_cookies(response.headers());
+    let cookies = set_cookies
+        .iter()
+        .map(|cookie| (cookie.name.clone(), cookie.value.clone()))
+        .collect();
 
-    // Get body
-    let body = response
-        .text()
-        .await
-        .context("Failed to read response body")?;
+    // Redirect chain recorded by the client
+    let redirect_hops: Vec<RedirectHop> = response
+        .extension::<Vec<redirect::History>>()
+        .map(|Extension(history)| {
+            history
+                .iter()
+                .map(|hop| RedirectHop {
+                    url: hop.previous().to_string(),
+                    status: hop.status().as_u16(),
+                    location: hop
+                        .headers()
+                        .get(LOCATION)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string),
+                    set_cookies: parse_set_cookies(hop.headers()),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let redirects = redirect_hops.iter().map(|hop| hop.url.clone()).collect();
+
+    // Judged by headers alone until the body has been read
+    let challenge = challenge::detect(status, &response_headers, None);
+
+    // Pooled connections carry their TLS info, so reused ones report it too
+    let tls = response
+        .extension::<TlsInfo>()
+        .and_then(|Extension(info)| info.peer_certificate())
+        .and_then(|der| PeerCertificate::from_der(der).ok());
 
-    Ok(Response {
+    Response {
         status,
+        version,
         headers: response_headers,
-        body,
+        raw_header_values,
+        trailers: Vec::new(),
+        request_id: String::new(),
+        body: String::new(),
+        body_bytes: Vec::new(),
+        charset: None,
         cookies,
+        set_cookies,
         url: final_url,
-    })
+        redirects,
+        redirect_hops,
+        stream_id: None,
+        attempts: 1,
+        timings: Timings::default(),
+        proxy: None,
+        connection: None,
+        tls,
+        from_cache: false,
+        fingerprint: None,
+        parsed: None,
+        extracted: None,
+        challenge,
+        body_hash: None,
+        decodings: Vec::new(),
+    }
 }
 
-fn build_client(config: &SessionConfig) -> Result<HttpClient> {
-    let mut client_builder = HttpClient::builder()
-        .emulation(config.emulation.clone())
-        .cookie_store(true);
+fn version_label(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
+
+// Every `Set-Cookie` in `headers` that parses, attributes included
+fn parse_set_cookies(headers: &HeaderMap) -> Vec<ResponseCookie> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| RawCookie::parse(value).ok())
+        .map(|cookie| ResponseCookie {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain: cookie.domain().map(str::to_string),
+            path: cookie.path().map(str::to_string),
+            expires: cookie
+                .expires_datetime()
+                .map(|at| (at.unix_timestamp_nanos() / 1_000_000) as f64),
+            max_age: cookie.max_age().map(|age| age.whole_seconds().max(0) as u64),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+            same_site: match cookie.same_site() {
+                Some(SameSite::Strict) => Some("Strict"),
+                Some(SameSite::Lax) => Some("Lax"),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+async fn read_response(
+    response: wreq::Response,
+    max_body_bytes: Option<u64>,
+    charset: Option<&'static Encoding>,
+    hash: Option<HashAlgorithm>,
+    decode: bool,
+) -> Result<Response> {
+    let mut result = response_head(&response);
 
-    if let Some(proxy_url) = config.proxy.as_deref() {
-        let proxy = Proxy::all(proxy_url).context("Failed to create proxy")?;
-        client_builder = client_builder.proxy(proxy);
+    // Get body as raw bytes; the text view is decoded from them so binary payloads stay intact
+    let mut hasher = hash.map(BodyHasher::new).transpose()?;
+    let (mut body_bytes, trailers) = read_body_with_trailers(response, max_body_bytes, hasher.as_mut())
+        .instrument(tracing::debug_span!("body-read"))
+        .await?;
+    result.body_hash = hasher.map(BodyHasher::finish).transpose()?;
+    result.trailers = trailers.as_ref().map(trailers::pairs).unwrap_or_default();
+    if decode {
+        body_bytes = decode_body(&mut result, body_bytes, max_body_bytes).await?;
+        // The digest was taken of the bytes as they arrived
+        if let Some(algorithm) = hash.filter(|_| !result.decodings.is_empty()) {
+            result.body_hash = Some(algorithm.digest(&body_bytes)?);
+        }
     }
+    let content_type = result
+        .headers
+        .iter()
+        .find(|(key, _)| key == "content-type")
+        .map(|(_, value)| value.as_str());
+    let (body, encoding) = charset::decode(&body_bytes, content_type, charset);
+    result.challenge = challenge::detect(result.status, &result.headers, Some(&body));
+    result.body = body;
+    result.body_bytes = body_bytes;
+    result.charset = Some(encoding);
 
-    client_builder
-        .build()
-        .context("Failed to build HTTP client")
+    Ok(result)
 }
 
-fn emulation_label(emulation: &Emulation) -> String {
-    match serde_json::to_value(emulation) {
-        Ok(Value::String(label)) => label,
-        _ => "chrome_142".to_string(),
+// Take a buffered body's content codings off, and the headers that described the encoded
+// bytes with them: `Content-Length`, and `Content-Encoding` unless a coding couldn't be removed
+async fn decode_body(response: &mut Response, body: Vec<u8>, limit: Option<u64>) -> Result<Vec<u8>> {
+    let header = |name: &str| {
+        let values: Vec<&str> = response
+            .headers
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    };
+    let content_encoding = header("content-encoding");
+    let content_type = header("content-type");
+    // Up to several layers of a body as large as the limit: too long to hold a runtime worker for
+    let encoding = content_encoding.clone();
+    let decoded = tokio::task::spawn_blocking(move || {
+        decoding::decode(body, encoding.as_deref(), content_type.as_deref(), limit)
+    })
+    .await
+    .context("Body decoding panicked")??;
+    if content_encoding.is_none() && decoded.applied.is_empty() {
+        return Ok(decoded.body);
+    }
+
+    let stale = |key: &str| key == "content-encoding" || (key == "content-length" && !decoded.applied.is_empty());
+    let mut headers = Vec::with_capacity(response.headers.len());
+    let mut raw_header_values = Vec::new();
+    for (index, entry) in std::mem::take(&mut response.headers).into_iter().enumerate() {
+        if stale(&entry.0) {
+            continue;
+        }
+        // Raw values are found by position, which moves up as headers are dropped
+        if let Some((_, bytes)) = response.raw_header_values.iter().find(|(at, _)| *at == index) {
+            raw_header_values.push((headers.len(), bytes.clone()));
+        }
+        headers.push(entry);
     }
+    if !decoded.remaining.is_empty() {
+        headers.push(("content-encoding".to_string(), decoded.remaining.join(", ")));
+    }
+    response.headers = headers;
+    response.raw_header_values = raw_header_values;
+    response.decodings = decoded.applied;
+    Ok(decoded.body)
 }
 
-pub fn create_managed_session(session_id: String, emulation: Emulation, proxy: Option<String>) -> Result<String> {
-    let config = SessionConfig::new(emulation, proxy);
-    SESSION_MANAGER.create_session(session_id, config)
+// Cached bodies were decoded when they were stored, before this request could force a charset
+fn redecode(response: &mut Response, charset: Option<&'static Encoding>) {
+    if let Some(encoding) = charset.filter(|encoding| response.from_cache && response.charset != Some(*encoding)) {
+        response.body = charset::decode(&response.body_bytes, None, Some(encoding)).0;
+        response.charset = Some(encoding);
+    }
 }
 
-pub fn clear_managed_session(session_id: &str) -> Result<()> {
-    SESSION_MANAGER.clear_session(session_id)
+// Read the whole body, giving up as soon as it is known to exceed `limit`. Dropping the
+// response mid-body closes the connection, so the rest is never downloaded.
+pub async fn read_body(response: wreq::Response, limit: Option<u64>) -> Result<Vec<u8>> {
+    Ok(read_body_with_trailers(response, limit, None).await?.0)
 }
 
-pub fn drop_managed_session(session_id: &str) {
-    SESSION_MANAGER.drop_session(session_id);
+// `read_body`, also returning the trailer fields that followed the body, if any, and
+// feeding each chunk to `hasher` as it arrives
+async fn read_body_with_trailers(
+    response: wreq::Response,
+    limit: Option<u64>,
+    mut hasher: Option<&mut BodyHasher>,
+) -> Result<(Vec<u8>, Option<HeaderMap>)> {
+    if let Some(limit) = limit {
+        check_declared_length(&response, limit)?;
+    }
+
+    let mut body = Vec::new();
+    let mut trailers = None;
+    let mut frames = wreq::Body::from(response);
+    while let Some(frame) = frames.frame().await {
+        let chunk = match frame.context("Failed to read response body")?.into_data() {
+            Ok(chunk) => chunk,
+            Err(frame) => {
+                trailers = frame.into_trailers().ok().or(trailers);
+                continue;
+            }
+        };
+        if let Some(limit) = limit.filter(|limit| (body.len() + chunk.len()) as u64 > *limit) {
+            return Err(BodyTooLarge { limit }.into());
+        }
+        if let Some(hasher) = hasher.as_deref_mut() {
+            hasher.update(&chunk)?;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok((body, trailers))
 }
 
-pub fn generate_session_id() -> String {
-    Uuid::new_v4().to_string()
+/// Reject a response up front when its `Content-Length` is already over the limit.
+fn check_declared_length(response: &wreq::Response, limit: u64) -> Result<()> {
+    if response.content_length().is_some_and(|length| length > limit) {
+        return Err(BodyTooLarge { limit }.into());
+    }
+    Ok(())
 }
+
@@ -0,0 +1,97 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps on requests in flight across every session; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    pub global: Option<usize>,
+    /// Applied separately to each hostname
+    pub per_host: Option<usize>,
+}
+
+/// Host semaphores are pruned once this many have piled up, keeping the ones in use.
+const MAX_IDLE_HOSTS: usize = 1024;
+
+struct Limiter {
+    config: ConcurrencyConfig,
+    global: Option<Arc<Semaphore>>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Limiter {
+    fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            config,
+            global: config.global.map(|limit| Arc::new(Semaphore::new(limit))),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn host_semaphore(&self, host: &str, limit: usize) -> Arc<Semaphore> {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(semaphore) = hosts.get(host) {
+            return semaphore.clone();
+        }
+
+        // Permits and pending acquires hold a reference, so a count of one means idle
+        if hosts.len() >= MAX_IDLE_HOSTS {
+            hosts.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        }
+
+        let semaphore = Arc::new(Semaphore::new(limit));
+        hosts.insert(host.to_string(), semaphore.clone());
+        semaphore
+    }
+}
+
+static LIMITER: Lazy<RwLock<Arc<Limiter>>> =
+    Lazy::new(|| RwLock::new(Arc::new(Limiter::new(ConcurrencyConfig::default()))));
+
+/// Slots a request holds while in flight; dropping it lets the next queued request go.
+#[derive(Debug, Default)]
+pub struct Permit {
+    _global: Option<OwnedSemaphorePermit>,
+    _host: Option<OwnedSemaphorePermit>,
+}
+
+/// Replace the limits. Requests already running or queued keep the limits they started
+/// under; requests made afterwards use the new ones.
+pub fn configure(config: ConcurrencyConfig) {
+    *LIMITER.write().unwrap() = Arc::new(Limiter::new(config));
+}
+
+pub fn current_config() -> ConcurrencyConfig {
+    LIMITER.read().unwrap().config
+}
+
+/// Wait until the request to `url` may start. The host slot is taken first so that
+/// requests queued behind a busy host don't sit on global slots other hosts could use.
+pub async fn acquire(url: &str) -> Permit {
+    let limiter = LIMITER.read().unwrap().clone();
+
+    let host = match (limiter.config.per_host, request_host(url)) {
+        (Some(limit), Some(host)) => {
+            let semaphore = limiter.host_semaphore(&host, limit);
+            semaphore.acquire_owned().await.ok()
+        }
+        _ => None,
+    };
+
+    let global = match &limiter.global {
+        Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+        None => None,
+    };
+
+    Permit {
+        _global: global,
+        _host: host,
+    }
+}
+
+/// Lowercased hostname of a request URL, the key for per-host limits.
+pub fn request_host(url: &str) -> Option<String> {
+    let uri: wreq::Uri = url.parse().ok()?;
+    uri.host().map(|host| host.to_ascii_lowercase())
+}
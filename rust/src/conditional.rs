@@ -0,0 +1,150 @@
+use crate::challenge;
+use crate::charset;
+use crate::client::{RequestOptions, Response};
+use encoding_rs::Encoding;
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+/// Most body bytes the store keeps before evicting the least recently used entries.
+const MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The last `200` seen for a URL that carried a validator.
+struct Entry {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Entry {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Unlike the HTTP cache, entries never count as fresh: every request goes out, and only
+// a `304` brings the stored body back
+static STORE: Lazy<Cache<String, Arc<Entry>>> = Lazy::new(|| {
+    Cache::builder()
+        .max_capacity(MAX_BYTES)
+        .weigher(|_key: &String, entry: &Arc<Entry>| entry.body.len().try_into().unwrap_or(u32::MAX))
+        .build()
+});
+
+/// A `conditional` request on its way out, holding the entry it may be answered from.
+pub struct Revalidation {
+    key: String,
+    stored: Option<Arc<Entry>>,
+    charset: Option<&'static Encoding>,
+}
+
+/// Add `If-None-Match`/`If-Modified-Since` from the last response stored for the URL.
+/// Only `GET`s are revalidated, and never when the caller set conditional headers itself.
+pub fn prepare(options: &mut RequestOptions) -> Option<Revalidation> {
+    let method = options.method.to_ascii_uppercase();
+    if !options.conditional || !(method.is_empty() || method == "GET") {
+        return None;
+    }
+    let caller_conditional = options.headers.iter().any(|(name, _)| {
+        let name = name.to_ascii_lowercase();
+        name.starts_with("if-") || name == "range"
+    });
+    if caller_conditional {
+        return None;
+    }
+
+    let key = store_key(options);
+    let stored = STORE.get(&key);
+    if let Some(entry) = &stored {
+        if let Some(etag) = entry.header("etag") {
+            options.headers.push(("If-None-Match".to_string(), etag.to_string()));
+        }
+        if let Some(last_modified) = entry.header("last-modified") {
+            options.headers.push(("If-Modified-Since".to_string(), last_modified.to_string()));
+        }
+    }
+
+    Some(Revalidation {
+        key,
+        stored,
+        charset: options.charset,
+    })
+}
+
+impl Revalidation {
+    /// Turn a `304` into the stored response, with the `304`'s headers folded in, or
+    /// remember a `200` that came with a validator.
+    pub fn complete(self, mut response: Response) -> Response {
+        if response.status == 304 {
+            let Some(stored) = self.stored else {
+                return response;
+            };
+            let mut headers = stored.headers.clone();
+            let refreshed = response.headers.iter().filter(|(name, _)| !is_body_header(name));
+            for (name, _) in refreshed.clone() {
+                headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+            }
+            headers.extend(refreshed.cloned());
+
+            let content_type = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                .map(|(_, value)| value.as_str());
+            let (body, encoding) = charset::decode(&stored.body, content_type, self.charset);
+            response.status = 200;
+            response.challenge = challenge::detect(200, &headers, Some(&body));
+            response.headers = headers;
+            response.raw_header_values = Vec::new();
+            response.body = body;
+            response.body_bytes = stored.body.clone();
+            response.charset = Some(encoding);
+            // The hash, if asked for, was of the empty 304 body
+            response.body_hash = None;
+            response.from_cache = true;
+            return response;
+        }
+
+        // A redirect's validators belong to the URL it ended at, not the one requested
+        if response.status != 200 || !response.redirects.is_empty() {
+            return response;
+        }
+        let validated = response
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("etag") || name.eq_ignore_ascii_case("last-modified"));
+        let no_store = response
+            .headers
+            .iter()
+            .any(|(name, value)| name.eq_ignore_ascii_case("cache-control") && value.contains("no-store"));
+        if validated && !no_store {
+            let entry = Entry {
+                headers: response.headers.clone(),
+                body: response.body_bytes.clone(),
+            };
+            STORE.insert(self.key, Arc::new(entry));
+        } else {
+            STORE.invalidate(&self.key);
+        }
+        response
+    }
+}
+
+/// Forget every stored response.
+pub fn clear() {
+    STORE.invalidate_all();
+}
+
+// Sessions keep their own validators, since what a URL returns can depend on its cookies;
+// requests outside a session share theirs. Fragments never reach the server
+fn store_key(options: &RequestOptions) -> String {
+    let url = options.url.split_once('#').map_or(options.url.as_str(), |(url, _)| url);
+    let session = if options.ephemeral { "" } else { options.session_id.as_str() };
+    format!("{}\0{}", session, url)
+}
+
+// A 304's framing headers describe its own empty body, not the stored one
+fn is_body_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("transfer-encoding")
+}
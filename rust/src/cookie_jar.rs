@@ -0,0 +1,532 @@
+use crate::runtime;
+use anyhow::{Context, Result};
+use cookie::{Cookie as RawCookie, SameSite};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wreq::cookie::CookieStore;
+use wreq::header::HeaderValue;
+use wreq::Uri;
+
+/// A cookie as the jar keeps it, with the domain it was set for and an absolute expiry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercased, without a leading dot
+    pub domain: String,
+    /// Set without a `Domain` attribute, so only sent to `domain` itself and not its subdomains
+    #[serde(default)]
+    pub host_only: bool,
+    pub path: String,
+    /// Milliseconds since the Unix epoch; `None` for session cookies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<String>,
+}
+
+impl StoredCookie {
+    /// Apply the storage rules of RFC 6265 §5.3 to a `Set-Cookie` received from `uri`.
+    /// `None` means the cookie is rejected, e.g. for naming a domain the host is not part of.
+    fn from_set_cookie(raw: &RawCookie<'_>, uri: &Uri, now: u64) -> Option<Self> {
+        let host = uri.host()?.to_ascii_lowercase();
+
+        let (domain, host_only) = match raw.domain().filter(|domain| !domain.is_empty()) {
+            Some(domain) => {
+                let domain = domain.to_ascii_lowercase();
+                // Without a public suffix list, at least refuse cookies for a bare TLD
+                if !domain_match(&host, &domain) || (!domain.contains('.') && domain != host) {
+                    return None;
+                }
+                (domain, false)
+            }
+            None => (host, true),
+        };
+
+        let path = match raw.path() {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => default_path(uri.path()).to_string(),
+        };
+
+        // Max-Age wins over Expires; both put an already expired cookie in the past
+        let expires = match (raw.max_age(), raw.expires_datetime()) {
+            (Some(max_age), _) => {
+                let millis = max_age.whole_milliseconds().clamp(0, u64::MAX as i128) as u64;
+                Some(now.saturating_add(millis))
+            }
+            (None, Some(at)) => Some((at.unix_timestamp().max(0) as u64).saturating_mul(1000)),
+            (None, None) => None,
+        };
+
+        let same_site = raw.same_site().map(|same_site| match same_site {
+            SameSite::Strict => "Strict".to_string(),
+            SameSite::Lax => "Lax".to_string(),
+            SameSite::None => "None".to_string(),
+        });
+
+        Some(Self {
+            name: raw.name().to_string(),
+            value: raw.value().to_string(),
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: raw.secure().unwrap_or(false),
+            http_only: raw.http_only().unwrap_or(false),
+            same_site,
+        })
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Cookies are replaced by name within the same domain and path.
+    fn same_slot(&self, other: &StoredCookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    /// Whether this cookie goes out with a request to `host` and `path`.
+    pub fn matches(&self, host: &str, path: &str, https: bool) -> bool {
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain_ok && path_match(path, &self.path) && (https || !self.secure)
+    }
+}
+
+/// RFC 6265 §5.1.3: the host is the domain or one of its subdomains.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// RFC 6265 §5.1.4: the request path is the cookie path or lies below it.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// RFC 6265 §5.1.4: the directory of the request path.
+fn default_path(request_path: &str) -> &str {
+    match request_path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &request_path[..index],
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// How a persisted jar is written: the Netscape `cookies.txt` format curl and wget use,
+/// picked for `.txt` paths, or a JSON array of [`StoredCookie`] for anything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Json,
+    Netscape,
+}
+
+impl FileFormat {
+    fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("txt") => Self::Netscape,
+            _ => Self::Json,
+        }
+    }
+}
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+fn parse_netscape(contents: &str) -> Vec<StoredCookie> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (http_only, line) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+                Some(rest) => (true, rest),
+                None if line.starts_with('#') => return None,
+                None => (false, line),
+            };
+
+            let fields = line.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+            let &[domain, subdomains, path, secure, expires, name, value] = fields.as_slice() else {
+                return None;
+            };
+
+            // Session cookies are written with an expiry of 0
+            let expires = expires.parse::<u64>().ok()?;
+            Some(StoredCookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+                host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+                path: path.to_string(),
+                expires: (expires > 0).then(|| expires.saturating_mul(1000)),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                http_only,
+                same_site: None,
+            })
+        })
+        .collect()
+}
+
+fn format_netscape(cookies: &[StoredCookie]) -> String {
+    let flag = |value: bool| if value { "TRUE" } else { "FALSE" };
+    let mut out = format!("{}\n\n", NETSCAPE_HEADER);
+    for cookie in cookies {
+        out.push_str(&format!(
+            "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only { HTTP_ONLY_PREFIX } else { "" },
+            if cookie.host_only { "" } else { "." },
+            cookie.domain,
+            flag(!cookie.host_only),
+            cookie.path,
+            flag(cookie.secure),
+            cookie.expires.map(|expires| expires / 1000).unwrap_or(0),
+            cookie.name,
+            cookie.value,
+        ));
+    }
+    out
+}
+
+/// The file a jar is loaded from and written back to.
+struct CookieFile {
+    path: PathBuf,
+    format: FileFormat,
+    /// Set while a write is scheduled, so a burst of changes is written once
+    dirty: AtomicBool,
+    /// Serializes writes so an older snapshot never lands after a newer one
+    write_lock: Mutex<()>,
+}
+
+struct Inner {
+    cookies: RwLock<Vec<StoredCookie>>,
+    file: Option<CookieFile>,
+}
+
+impl Inner {
+    fn write_file(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let _guard = file.write_lock.lock().unwrap();
+        file.dirty.store(false, Ordering::Release);
+
+        let now = now_millis();
+        let cookies = self
+            .cookies
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let contents = match file.format {
+            FileFormat::Json => serde_json::to_string_pretty(&cookies)?,
+            FileFormat::Netscape => format_netscape(&cookies),
+        };
+
+        if let Some(parent) = file.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cookie directory '{}'", parent.display()))?;
+        }
+
+        // Write beside the target and rename, so a crash never leaves half a jar behind
+        let temp = file.path.with_extension(format!("tmp-{}", fastrand::u64(..)));
+        std::fs::write(&temp, contents)
+            .with_context(|| format!("Failed to write cookie file '{}'", temp.display()))?;
+        if let Err(e) = std::fs::rename(&temp, &file.path) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to write cookie file '{}'", file.path.display()));
+        }
+        Ok(())
+    }
+}
+
+/// Whether a request uses its session's jar, from the request's `cookies` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CookiePolicy {
+    /// Send the jar's cookies and store the ones responses set
+    #[default]
+    Include,
+    /// Leave the jar alone: none of its cookies are sent and `Set-Cookie` isn't stored
+    Omit,
+    /// Leave the jar alone, sending the caller's own cookies instead
+    Manual,
+}
+
+impl CookiePolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "include" => Some(CookiePolicy::Include),
+            "omit" => Some(CookiePolicy::Omit),
+            "manual" => Some(CookiePolicy::Manual),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CookiePolicy::Include => "include",
+            CookiePolicy::Omit => "omit",
+            CookiePolicy::Manual => "manual",
+        }
+    }
+
+    pub fn uses_jar(self) -> bool {
+        self == CookiePolicy::Include
+    }
+}
+
+tokio::task_local! {
+    static POLICY: CookiePolicy;
+}
+
+/// Run `future` with `policy` deciding whether the session's jar is read and written. wreq's
+/// cookie layer runs on the request's task, hops of redirects included, so the jar can tell.
+pub async fn scope<F: Future>(policy: CookiePolicy, future: F) -> F::Output {
+    POLICY.scope(policy, future).await
+}
+
+// Outside a request, e.g. for `setCookie`, the jar is always in use
+fn in_use() -> bool {
+    POLICY.try_with(|policy| policy.uses_jar()).unwrap_or(true)
+}
+
+/// Cookie store behind every session's client. Unlike wreq's `Jar` it keeps each
+/// cookie's domain and expiry, so the jar can be written to disk and read back.
+pub struct CookieJar {
+    inner: Arc<Inner>,
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                cookies: RwLock::new(Vec::new()),
+                file: None,
+            }),
+        }
+    }
+}
+
+impl CookieJar {
+    /// A jar persisted to `path`, starting with the cookies already saved there.
+    /// A missing file is created on the first change.
+    pub fn open(path: &Path) -> Result<Self> {
+        let cookies = match std::fs::read_to_string(path) {
+            Ok(contents) if contents.trim_start().starts_with('[') => serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse cookie file '{}'", path.display()))?,
+            Ok(contents) => parse_netscape(&contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read cookie file '{}'", path.display())),
+        };
+
+        let now = now_millis();
+        let cookies = cookies
+            .into_iter()
+            .filter(|cookie: &StoredCookie| !cookie.is_expired(now))
+            .collect();
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                cookies: RwLock::new(cookies),
+                file: Some(CookieFile {
+                    path: path.to_path_buf(),
+                    format: FileFormat::for_path(path),
+                    dirty: AtomicBool::new(false),
+                    write_lock: Mutex::new(()),
+                }),
+            }),
+        })
+    }
+
+    /// Write the jar to its file now, if it has one. Used when a session is closed.
+    pub fn persist(&self) -> Result<()> {
+        self.inner.write_file()
+    }
+
+    pub fn clear(&self) {
+        self.inner.cookies.write().unwrap().clear();
+        self.changed();
+    }
+
+    /// Every cookie in the jar that has not expired, in the order they were set.
+    pub fn all(&self) -> Vec<StoredCookie> {
+        let now = now_millis();
+        let cookies = self.inner.cookies.read().unwrap();
+        cookies.iter().filter(|cookie| !cookie.is_expired(now)).cloned().collect()
+    }
+
+    /// The cookies a request to `uri` would carry, in the order they are sent.
+    pub fn for_uri(&self, uri: &Uri) -> Vec<StoredCookie> {
+        let Some(host) = uri.host().map(str::to_ascii_lowercase) else {
+            return Vec::new();
+        };
+        let https = uri.scheme_str().is_some_and(|scheme| scheme == "https" || scheme == "wss");
+        let now = now_millis();
+
+        let cookies = self.inner.cookies.read().unwrap();
+        let mut matching = cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(&host, uri.path(), https))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // RFC 6265 §5.4: longer paths first, otherwise in the order they were set
+        matching.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        matching
+    }
+
+    /// The `Cookie` header a request to `uri` would carry, or `None` when no cookie applies.
+    pub fn header_for(&self, uri: &Uri) -> Option<String> {
+        let matching = self.for_uri(uri);
+        if matching.is_empty() {
+            return None;
+        }
+
+        let header = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Some(header)
+    }
+
+    /// Store a cookie set from Node. It is checked here too, since a value that can't go in a
+    /// header would otherwise keep every cookie for its domain from being sent.
+    pub fn set(&self, mut cookie: StoredCookie) -> Result<()> {
+        let valid_name = !cookie.name.is_empty()
+            && cookie.name.bytes().all(|byte| byte.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&byte));
+        if !valid_name {
+            anyhow::bail!("Invalid cookie name '{}'", cookie.name);
+        }
+        if !cookie.value.bytes().all(|byte| (0x20..0x7f).contains(&byte) && byte != b';') {
+            anyhow::bail!("Invalid value for cookie '{}'", cookie.name);
+        }
+
+        cookie.domain = cookie.domain.trim_start_matches('.').to_ascii_lowercase();
+        if cookie.domain.is_empty() {
+            anyhow::bail!("Cookie '{}' needs a domain", cookie.name);
+        }
+        if !cookie.path.starts_with('/') {
+            anyhow::bail!("Cookie path must start with '/', got '{}'", cookie.path);
+        }
+
+        {
+            let mut cookies = self.inner.cookies.write().unwrap();
+            Self::insert(&mut cookies, cookie, now_millis());
+        }
+        self.changed();
+        Ok(())
+    }
+
+    /// Remove the cookies matching every filter given, returning how many went.
+    pub fn remove(&self, name: Option<&str>, domain: Option<&str>, path: Option<&str>) -> usize {
+        let domain = domain.map(|domain| domain.trim_start_matches('.').to_ascii_lowercase());
+        let removed = {
+            let mut cookies = self.inner.cookies.write().unwrap();
+            let before = cookies.len();
+            cookies.retain(|cookie| {
+                let selected = name.is_none_or(|name| cookie.name == name)
+                    && domain.as_deref().is_none_or(|domain| cookie.domain == domain)
+                    && path.is_none_or(|path| cookie.path == path);
+                !selected
+            });
+            before - cookies.len()
+        };
+
+        if removed > 0 {
+            self.changed();
+        }
+        removed
+    }
+
+    /// Store `cookie`, replacing the one with the same name, domain and path. An expired
+    /// cookie deletes that one instead, as servers do to log a session out.
+    fn insert(cookies: &mut Vec<StoredCookie>, cookie: StoredCookie, now: u64) {
+        let existing = cookies.iter().position(|stored| stored.same_slot(&cookie));
+        match (existing, cookie.is_expired(now)) {
+            (Some(index), true) => {
+                cookies.remove(index);
+            }
+            (Some(index), false) => cookies[index] = cookie,
+            (None, true) => {}
+            (None, false) => cookies.push(cookie),
+        }
+    }
+
+    // Schedule a write of the whole jar; changes made before it runs are written with it
+    fn changed(&self) {
+        let Some(file) = &self.inner.file else {
+            return;
+        };
+        if file.dirty.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        runtime::spawn_blocking(move || {
+            let _ = inner.write_file();
+        });
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, uri: &Uri) {
+        if !in_use() {
+            return;
+        }
+
+        let now = now_millis();
+        let parsed = cookie_headers
+            .filter_map(|header| header.to_str().ok())
+            .filter_map(|header| RawCookie::parse(header).ok())
+            .filter_map(|raw| StoredCookie::from_set_cookie(&raw, uri, now))
+            .collect::<Vec<_>>();
+        if parsed.is_empty() {
+            return;
+        }
+
+        {
+            let mut cookies = self.inner.cookies.write().unwrap();
+            for cookie in parsed {
+                Self::insert(&mut cookies, cookie, now);
+            }
+        }
+        self.changed();
+    }
+
+    fn cookies(&self, uri: &Uri) -> Vec<HeaderValue> {
+        if !in_use() {
+            return Vec::new();
+        }
+        let Some(header) = self.header_for(uri) else {
+            return Vec::new();
+        };
+
+        HeaderValue::from_str(&header).map(|value| vec![value]).unwrap_or_default()
+    }
+}
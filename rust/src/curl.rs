@@ -0,0 +1,228 @@
+use crate::client::{outgoing_headers, FileSource, MultipartValue, RequestOptions};
+use crate::proxy_routes::{self, RouteTarget};
+use crate::session::managed_session_proxy;
+use crate::tls::CaSource;
+use std::net::IpAddr;
+use wreq::{Uri, Version};
+
+/// Render a request as an equivalent `curl` command line, for reproducing it outside
+/// the library. Headers are the ones the request would go out with, emulation defaults
+/// and session cookies included. Binary bodies are piped in with `printf`. curl has its
+/// own TLS and HTTP/2 fingerprint, so servers that check those can still tell the two
+/// apart, and client certificates given as bytes have no file to point curl at.
+pub fn to_curl(options: &RequestOptions) -> String {
+    let mut args = vec!["curl".to_string()];
+    // Bytes piped to curl's stdin, for a body that can't be passed as an argument
+    let mut stdin: Option<&[u8]> = None;
+
+    let method = if options.method.is_empty() {
+        "GET"
+    } else {
+        options.method.as_str()
+    };
+    if method.eq_ignore_ascii_case("HEAD") {
+        args.push("--head".to_string());
+    } else if !method.eq_ignore_ascii_case("GET") {
+        args.push("-X".to_string());
+        args.push(quote(method));
+    }
+    args.push(quote(&options.url));
+
+    for (name, value) in outgoing_headers(options) {
+        args.push("-H".to_string());
+        args.push(quote(&format!("{}: {}", name, value)));
+    }
+
+    // curl signs the request itself, over the headers it sends
+    if let Some(aws) = &options.aws_sigv4 {
+        args.push("--aws-sigv4".to_string());
+        args.push(quote(&format!("aws:amz:{}:{}", aws.region, aws.service)));
+        args.push("--user".to_string());
+        args.push(quote(&format!("{}:{}", aws.access_key_id, aws.secret_access_key)));
+        if let Some(token) = &aws.session_token {
+            args.push("-H".to_string());
+            args.push(quote(&format!("X-Amz-Security-Token: {}", token)));
+        }
+    }
+
+    if let Some(json) = &options.json {
+        args.push("--data-raw".to_string());
+        args.push(quote(&json.to_string()));
+    } else if let Some(body) = &options.body {
+        match std::str::from_utf8(body) {
+            Ok(text) => {
+                args.push("--data-raw".to_string());
+                args.push(quote(text));
+            }
+            Err(_) => {
+                args.push("--data-binary".to_string());
+                args.push("@-".to_string());
+                stdin = Some(body);
+            }
+        }
+    } else if let Some(path) = &options.body_file {
+        args.push("--data-binary".to_string());
+        args.push(quote(&format!("@{}", path.display())));
+    } else if let Some(fields) = &options.multipart {
+        for field in fields {
+            let value = match &field.value {
+                MultipartValue::Text(text) => {
+                    args.push("--form-string".to_string());
+                    args.push(quote(&format!("{}={}", field.name, text)));
+                    continue;
+                }
+                // In-memory parts have no file to point at, so one of them can come from stdin
+                MultipartValue::File {
+                    source,
+                    filename,
+                    content_type,
+                } => {
+                    let mut value = match source {
+                        FileSource::Path(path) => format!("{}=@{}", field.name, path),
+                        FileSource::Bytes(bytes) if stdin.is_none() => {
+                            stdin = Some(bytes);
+                            format!("{}=@-", field.name)
+                        }
+                        FileSource::Bytes(_) => format!("{}=@/dev/null", field.name),
+                    };
+                    if let Some(filename) = filename {
+                        value.push_str(&format!(";filename={}", filename));
+                    }
+                    if let Some(content_type) = content_type {
+                        value.push_str(&format!(";type={}", content_type));
+                    }
+                    value
+                }
+            };
+            args.push("-F".to_string());
+            args.push(quote(&value));
+        }
+    }
+
+    // Like the request itself, routing rules come first, then the proxy the session was created with
+    let proxy = match proxy_routes::route(&options.url) {
+        Some(RouteTarget::Direct) => None,
+        Some(RouteTarget::Proxy(proxy)) => Some(proxy),
+        _ => options.proxy.clone().or_else(|| managed_session_proxy(&options.session_id)),
+    };
+    if let Some(proxy) = &proxy {
+        args.push("--proxy".to_string());
+        args.push(quote(proxy));
+        if let Some(auth) = &options.proxy_auth {
+            args.push("--proxy-user".to_string());
+            args.push(quote(&format!("{}:{}", auth.username, auth.password)));
+        }
+        for (name, value) in &options.proxy_headers {
+            args.push("--proxy-header".to_string());
+            args.push(quote(&format!("{}: {}", name, value)));
+        }
+    }
+
+    if let Some(interface) = &options.interface {
+        args.push("--interface".to_string());
+        args.push(quote(interface));
+    } else if let Some(address) = options.local_address {
+        args.push("--interface".to_string());
+        args.push(address.to_string());
+    }
+
+    // Pinned hostnames, at the port the URL connects to
+    if let Ok(uri) = options.url.parse::<Uri>() {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("http") { 80 } else { 443 });
+        if let Some(addrs) = options.dns.overrides.get(&host).filter(|addrs| !addrs.is_empty()) {
+            let addrs = addrs
+                .iter()
+                .map(|addr| match addr.ip() {
+                    IpAddr::V6(ip) => format!("[{}]", ip),
+                    ip => ip.to_string(),
+                })
+                .collect::<Vec<_>>();
+            args.push("--resolve".to_string());
+            args.push(quote(&format!("{}:{}:{}", host, port, addrs.join(","))));
+        }
+    }
+
+    match options.http_version {
+        Some(Version::HTTP_11) => args.push("--http1.1".to_string()),
+        Some(Version::HTTP_2) => args.push("--http2".to_string()),
+        _ => {}
+    }
+
+    if options.redirect.follow {
+        args.push("-L".to_string());
+        args.push("--max-redirs".to_string());
+        args.push(options.redirect.max.to_string());
+    }
+
+    if options.decompress {
+        args.push("--compressed".to_string());
+    }
+
+    if let Some(limit) = options.max_body_bytes {
+        args.push("--max-filesize".to_string());
+        args.push(limit.to_string());
+    }
+
+    // Only file-backed certificates can be handed to curl; in-memory ones are left out
+    for ca in &options.tls.ca {
+        if let CaSource::File(path) = ca {
+            args.push("--cacert".to_string());
+            args.push(quote(path));
+        }
+    }
+    if options.tls.danger_accept_invalid_certs {
+        args.push("--insecure".to_string());
+    }
+
+    // curl retries nothing here, so the deadline only tightens the one attempt's limit
+    let max_time = match (options.timeout, options.deadline) {
+        (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+        (timeout, deadline) => timeout.or(deadline),
+    };
+    if let Some(max_time) = max_time {
+        args.push("--max-time".to_string());
+        args.push(format!("{}", max_time.as_secs_f64()));
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        args.push("--connect-timeout".to_string());
+        args.push(format!("{}", connect_timeout.as_secs_f64()));
+    }
+
+    let command = args.join(" ");
+    match stdin {
+        Some(bytes) => format!("printf {} | {}", printf_format(bytes), command),
+        None => command,
+    }
+}
+
+// Quote for a POSIX shell; anything but plain words goes in single quotes
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_./:=@,+%".contains(&byte));
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+// Bytes as a single-quoted printf format, with everything but printable ASCII in octal
+fn printf_format(bytes: &[u8]) -> String {
+    let mut format = String::from("'");
+    for &byte in bytes {
+        match byte {
+            b'\\' => format.push_str(r"\\"),
+            b'%' => format.push_str("%%"),
+            b'\'' => format.push_str(r"\047"),
+            0x20..=0x7e => format.push(byte as char),
+            _ => format.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    format.push('\'');
+    format
+}
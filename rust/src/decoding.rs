@@ -0,0 +1,186 @@
+use crate::compress::BodyEncoding;
+use crate::error::{BodyParseFailed, BodyTooLarge};
+use anyhow::Result;
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// Most codings taken off one body, labelled or sniffed; past that it is a decompression bomb.
+const MAX_LAYERS: usize = 4;
+
+/// One content coding removed from a response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoding {
+    pub encoding: BodyEncoding,
+    /// Found from the body's leading bytes rather than taken from `Content-Encoding`
+    pub sniffed: bool,
+}
+
+/// A body with its codings removed.
+#[derive(Debug)]
+pub struct Decoded {
+    pub body: Vec<u8>,
+    /// In the order they were removed, outermost first
+    pub applied: Vec<Decoding>,
+    /// `Content-Encoding` tokens left in place, e.g. an unsupported `compress` and the ones under it
+    pub remaining: Vec<String>,
+}
+
+/// Remove a buffered body's content codings. Stacked codings (`gzip, br`) come off in reverse,
+/// a label the bytes contradict is replaced by what they start with, and a label on a text body
+/// that was never encoded is dropped. Once the labels run out, a textual body that still starts with
+/// a gzip, zlib or zstd signature has that removed too, since some CDNs compress twice or don't
+/// say. The limit applies to each decoded layer, so a small body can't inflate without bound.
+pub fn decode(
+    body: Vec<u8>,
+    content_encoding: Option<&str>,
+    content_type: Option<&str>,
+    limit: Option<u64>,
+) -> Result<Decoded> {
+    let mut tokens: Vec<String> = content_encoding
+        .unwrap_or_default()
+        .split(',')
+        .map(|token| token.trim().to_ascii_lowercase())
+        .filter(|token| !token.is_empty() && token != "identity")
+        .collect();
+
+    let mut decoded = Decoded {
+        body,
+        applied: Vec::new(),
+        remaining: Vec::new(),
+    };
+    while let Some(token) = tokens.last() {
+        // Codings under one that can't be removed can't be reached either
+        let Some(labelled) = parse_token(token) else {
+            break;
+        };
+        // Past the cap the rest stay labelled rather than being dropped from a body still encoded
+        if decoded.applied.len() == MAX_LAYERS {
+            break;
+        }
+        tokens.pop();
+        if decoded.body.is_empty() {
+            continue;
+        }
+
+        let found = sniff(&decoded.body);
+        let encoding = found.unwrap_or(labelled);
+        match inflate(encoding, &decoded.body, limit) {
+            Ok(body) => {
+                decoded.body = body;
+                decoded.applied.push(Decoding {
+                    encoding,
+                    sniffed: encoding != labelled,
+                });
+            }
+            // Anything but text that won't decode is cut short or corrupt, brotli and raw deflate
+            // included, since they have no signature to tell them by
+            Err(e) if e.is::<BodyTooLarge>() || !plain(&decoded.body) => return Err(e),
+            // Labelled, but sent as is
+            Err(_) => {}
+        }
+    }
+    decoded.remaining = tokens;
+
+    if decoded.remaining.is_empty() && textual(content_type) {
+        while decoded.applied.len() < MAX_LAYERS {
+            let Some(encoding) = sniff(&decoded.body) else {
+                break;
+            };
+            match inflate(encoding, &decoded.body, limit) {
+                Ok(body) => {
+                    decoded.body = body;
+                    decoded.applied.push(Decoding {
+                        encoding,
+                        sniffed: true,
+                    });
+                }
+                Err(e) if e.is::<BodyTooLarge>() => return Err(e),
+                Err(_) => break,
+            }
+        }
+    }
+    Ok(decoded)
+}
+
+fn parse_token(token: &str) -> Option<BodyEncoding> {
+    match token {
+        "x-gzip" => Some(BodyEncoding::Gzip),
+        token => BodyEncoding::parse(token),
+    }
+}
+
+// The coding a body's leading bytes belong to. Brotli has no signature, so it is only ever
+// taken from a label
+fn sniff(body: &[u8]) -> Option<BodyEncoding> {
+    match body {
+        [0x1f, 0x8b, ..] => Some(BodyEncoding::Gzip),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(BodyEncoding::Zstd),
+        // zlib: the deflate method with a window of at most 32 KiB, and a header check that is
+        // a multiple of 31 (RFC 1950)
+        [cmf, flg, ..] if cmf & 0x0f == 8 && cmf >> 4 <= 7 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => {
+            Some(BodyEncoding::Deflate)
+        }
+        _ => None,
+    }
+}
+
+// Whether a body starts out as UTF-8 text, which compressed data practically never does
+fn plain(body: &[u8]) -> bool {
+    let prefix = &body[..body.len().min(512)];
+    let text = match std::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // A character cut in two by the prefix
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return false,
+    };
+    !text.chars().any(|c| c.is_control() && !c.is_ascii_whitespace())
+}
+
+fn inflate(encoding: BodyEncoding, body: &[u8], limit: Option<u64>) -> Result<Vec<u8>> {
+    match encoding {
+        BodyEncoding::Gzip => read_limited(encoding, MultiGzDecoder::new(body), limit),
+        // `deflate` means zlib-wrapped, but plenty of servers send the raw stream
+        BodyEncoding::Deflate if sniff(body) == Some(BodyEncoding::Deflate) => {
+            read_limited(encoding, ZlibDecoder::new(body), limit)
+        }
+        BodyEncoding::Deflate => read_limited(encoding, DeflateDecoder::new(body), limit),
+        BodyEncoding::Brotli => read_limited(encoding, brotli::Decompressor::new(body, 4096), limit),
+        BodyEncoding::Zstd => {
+            let decoder = zstd::stream::read::Decoder::new(body).map_err(|e| failed(encoding, e))?;
+            read_limited(encoding, decoder, limit)
+        }
+    }
+}
+
+fn read_limited(encoding: BodyEncoding, reader: impl Read, limit: Option<u64>) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    // One byte past the limit is enough to know it was exceeded
+    let cap = limit.map_or(u64::MAX, |limit| limit.saturating_add(1));
+    reader
+        .take(cap)
+        .read_to_end(&mut decoded)
+        .map_err(|e| failed(encoding, e))?;
+    if let Some(limit) = limit.filter(|limit| decoded.len() as u64 > *limit) {
+        return Err(BodyTooLarge { limit }.into());
+    }
+    Ok(decoded)
+}
+
+fn failed(encoding: BodyEncoding, e: std::io::Error) -> BodyParseFailed {
+    BodyParseFailed {
+        format: encoding.token(),
+        reason: e.to_string(),
+    }
+}
+
+// Whether a body of this type is text, which a compression signature can't be part of
+fn textual(content_type: Option<&str>) -> bool {
+    let Some(mime) = content_type.and_then(|value| value.split(';').next()) else {
+        return false;
+    };
+    let mime = mime.trim().to_ascii_lowercase();
+    mime.starts_with("text/")
+        || ["/json", "+json", "/xml", "+xml", "javascript", "ecmascript"]
+            .iter()
+            .any(|kind| mime.ends_with(kind))
+}
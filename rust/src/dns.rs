@@ -0,0 +1,232 @@
+use crate::timings::{record_dns, TimingResolver};
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::TokioResolver;
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wreq::dns::{Addrs, Name, Resolve, Resolving};
+use wreq::ClientBuilder;
+
+/// Name resolution settings for a session's client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// Hostnames (lowercased) pinned to fixed addresses, bypassing the resolver
+    pub overrides: BTreeMap<String, Vec<SocketAddr>>,
+    /// Built-in resolver to use instead of the system's `getaddrinfo`
+    pub resolver: Option<ResolverSettings>,
+    /// Address families to connect over, and which to try first
+    pub ip_strategy: Option<IpStrategy>,
+}
+
+impl DnsConfig {
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        let resolver: Arc<dyn Resolve> = match &self.resolver {
+            Some(settings) => Arc::new(CachingResolver {
+                resolver: shared_resolver(settings),
+            }),
+            None => Arc::new(TimingResolver),
+        };
+        builder = match self.ip_strategy {
+            Some(strategy) => builder.dns_resolver(FilteredResolver { inner: resolver, strategy }),
+            None => builder.dns_resolver(resolver),
+        };
+
+        for (host, addrs) in &self.overrides {
+            builder = builder.resolve_to_addrs(host.clone(), addrs.iter().copied());
+        }
+
+        builder
+    }
+}
+
+/// Which resolved addresses to connect to. The connector tries the family of the
+/// first address, falling back to the other one if that is slow or fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpStrategy {
+    /// IPv4 addresses only
+    Ipv4Only,
+    /// IPv6 addresses only
+    Ipv6Only,
+    /// IPv4 first, IPv6 when it is slow or fails
+    PreferIpv4,
+    /// IPv6 first, IPv4 when it is slow or fails
+    PreferIpv6,
+}
+
+impl IpStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ipv4-only" => Some(Self::Ipv4Only),
+            "ipv6-only" => Some(Self::Ipv6Only),
+            "prefer-ipv4" => Some(Self::PreferIpv4),
+            "prefer-ipv6" => Some(Self::PreferIpv6),
+            _ => None,
+        }
+    }
+
+    fn select(self, addrs: impl Iterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+        let (v4, v6): (Vec<_>, Vec<_>) = addrs.partition(SocketAddr::is_ipv4);
+        match self {
+            Self::Ipv4Only => v4,
+            Self::Ipv6Only => v6,
+            Self::PreferIpv4 => v4.into_iter().chain(v6).collect(),
+            Self::PreferIpv6 => v6.into_iter().chain(v4).collect(),
+        }
+    }
+}
+
+/// Applies an [`IpStrategy`] to another resolver's answers.
+struct FilteredResolver {
+    inner: Arc<dyn Resolve>,
+    strategy: IpStrategy,
+}
+
+impl Resolve for FilteredResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let strategy = self.strategy;
+        let resolving = self.inner.resolve(name.clone());
+        Box::pin(async move {
+            let addrs = strategy.select(resolving.await?);
+            if addrs.is_empty() {
+                let family = if strategy == IpStrategy::Ipv6Only { "IPv6" } else { "IPv4" };
+                return Err(format!("dns error: no {} address found for {}", family, name).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Where the built-in resolver sends its queries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Upstream {
+    /// Nameservers from `/etc/resolv.conf` (or the platform equivalent)
+    #[default]
+    System,
+    /// Plain UDP/TCP DNS to these nameservers
+    Servers(Vec<SocketAddr>),
+    /// DNS-over-HTTPS to a public provider
+    Doh(DohProvider),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DohProvider {
+    Cloudflare,
+    Google,
+    Quad9,
+}
+
+impl DohProvider {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cloudflare" => Some(Self::Cloudflare),
+            "google" => Some(Self::Google),
+            "quad9" => Some(Self::Quad9),
+            _ => None,
+        }
+    }
+}
+
+/// Built-in resolver configuration. Sessions with equal settings share one
+/// resolver, and with it one cache of answers and failures.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ResolverSettings {
+    pub upstream: Upstream,
+    /// Records kept in the cache; 0 disables caching
+    pub cache_size: Option<u64>,
+    /// Cache answers for at least this long, even if their TTL is shorter
+    pub min_ttl: Option<Duration>,
+    /// Cache answers for at most this long, even if their TTL is longer
+    pub max_ttl: Option<Duration>,
+    /// How long a failed lookup (NXDOMAIN, no records) is remembered
+    pub negative_ttl: Option<Duration>,
+}
+
+static RESOLVERS: Lazy<Cache<ResolverSettings, TokioResolver>> =
+    Lazy::new(|| Cache::builder().max_capacity(64).build());
+
+fn shared_resolver(settings: &ResolverSettings) -> TokioResolver {
+    RESOLVERS.get_with(settings.clone(), || build_resolver(settings))
+}
+
+fn build_resolver(settings: &ResolverSettings) -> TokioResolver {
+    let provider = TokioConnectionProvider::default();
+    let mut builder = match &settings.upstream {
+        Upstream::System => TokioResolver::builder_tokio()
+            .unwrap_or_else(|_| TokioResolver::builder_with_config(ResolverConfig::default(), provider)),
+        Upstream::Servers(servers) => {
+            let mut group = NameServerConfigGroup::new();
+            for server in servers {
+                group.merge(NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true));
+            }
+            TokioResolver::builder_with_config(ResolverConfig::from_parts(None, vec![], group), provider)
+        }
+        Upstream::Doh(provider_name) => {
+            let config = match provider_name {
+                DohProvider::Cloudflare => ResolverConfig::cloudflare_https(),
+                DohProvider::Google => ResolverConfig::google_https(),
+                DohProvider::Quad9 => ResolverConfig::quad9_https(),
+            };
+            TokioResolver::builder_with_config(config, provider)
+        }
+    };
+
+    let options = builder.options_mut();
+    // Both families, so the connector can race them
+    options.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    if let Some(size) = settings.cache_size {
+        options.cache_size = size;
+    }
+    if let Some(ttl) = settings.min_ttl {
+        options.positive_min_ttl = Some(ttl);
+    }
+    if let Some(ttl) = settings.max_ttl {
+        options.positive_max_ttl = Some(ttl);
+    }
+    if let Some(ttl) = settings.negative_ttl {
+        options.negative_min_ttl = Some(ttl);
+        options.negative_max_ttl = Some(ttl);
+    }
+
+    builder.build()
+}
+
+/// Resolver backed by hickory's caching resolver, reporting lookup time like
+/// [`crate::timings::TimingResolver`]. Cache hits show up as near-zero DNS time.
+struct CachingResolver {
+    resolver: TokioResolver,
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.resolver.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let lookup = resolver.lookup_ip(name.as_str()).await?;
+            record_dns(started.elapsed());
+            let addrs: Vec<_> = lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Parse `1.2.3.4`, `1.2.3.4:443`, `::1` or `[::1]:443`. wreq always connects to the
+/// request URL's port, so a port given here is accepted but has no effect.
+pub fn parse_override_addr(value: &str) -> Option<SocketAddr> {
+    let value = value.trim();
+    value
+        .parse::<SocketAddr>()
+        .ok()
+        .or_else(|| value.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0)))
+}
+
+/// Parse a nameserver address, defaulting to port 53.
+pub fn parse_nameserver(value: &str) -> Option<SocketAddr> {
+    parse_override_addr(value).map(|addr| match addr.port() {
+        0 => SocketAddr::new(addr.ip(), 53),
+        _ => addr,
+    })
+}
@@ -0,0 +1,138 @@
+use crate::error::UnknownEmulation;
+use crate::generated_profiles::BROWSER_PROFILES;
+use serde_json::Value;
+use wreq::EmulationFactory;
+use wreq_util::{Emulation, EmulationOS, EmulationOption};
+
+/// A browser profile, described from its name.
+#[derive(Debug, Clone)]
+pub struct EmulationInfo {
+    pub name: &'static str,
+    /// "chrome", "edge", "opera", "safari", "firefox" or "okhttp"
+    pub family: &'static str,
+    pub version: &'static str,
+    /// Operating system the profile presents as, in wreq-util's terms: desktop profiles
+    /// default to "macos", mobile ones are "ios" or "android"
+    pub os: &'static str,
+}
+
+impl EmulationInfo {
+    fn from_name(name: &'static str) -> Self {
+        let mut parts = name.split('_');
+        let family = parts.next().unwrap_or(name);
+        let qualifiers = parts.clone().collect::<Vec<_>>();
+        let version = name.rsplit('_').next().unwrap_or(name);
+        let os = if family == "okhttp" || qualifiers.contains(&"android") {
+            "android"
+        } else if qualifiers.contains(&"ios") || qualifiers.contains(&"ipad") {
+            "ios"
+        } else {
+            "macos"
+        };
+
+        EmulationInfo {
+            name,
+            family,
+            version,
+            os,
+        }
+    }
+}
+
+/// Every profile this build knows, in the order wreq-util declares them.
+pub fn list() -> Vec<EmulationInfo> {
+    BROWSER_PROFILES.iter().copied().map(EmulationInfo::from_name).collect()
+}
+
+/// Look up a profile by its exact name, e.g. "chrome_142" or "safari_ios_17.2".
+pub fn parse(name: &str) -> Result<Emulation, UnknownEmulation> {
+    let unknown = || UnknownEmulation {
+        name: name.to_string(),
+        suggestions: close_matches(name),
+    };
+    if !BROWSER_PROFILES.contains(&name) {
+        return Err(unknown());
+    }
+    serde_json::from_value(Value::String(name.to_string())).map_err(|_| unknown())
+}
+
+/// The name a profile goes by, as accepted by [`parse`].
+pub fn label(emulation: &Emulation) -> String {
+    match serde_json::to_value(emulation) {
+        Ok(Value::String(label)) => label,
+        _ => format!("{:?}", emulation),
+    }
+}
+
+/// Look up an operating system by its wreq-util name: "windows", "macos", "linux",
+/// "android" or "ios".
+pub fn parse_os(name: &str) -> Option<EmulationOS> {
+    serde_json::from_value(Value::String(name.to_string())).ok()
+}
+
+/// A profile's emulation, presenting as `os` instead of the profile's own default when one
+/// is chosen. Profiles tied to one platform, like Safari on iOS or OkHttp, ignore it.
+pub fn build(emulation: &Emulation, os: Option<EmulationOS>) -> wreq::Emulation {
+    match os {
+        Some(os) => EmulationOption::builder()
+            .emulation(emulation.clone())
+            .emulation_os(os)
+            .build()
+            .emulation(),
+        None => emulation.clone().emulation(),
+    }
+}
+
+/// [`label`], followed by the chosen OS, e.g. "chrome_142/android". Two requests whose
+/// labels differ never share a client, a cached response or a single-flight result.
+pub fn profile_label(emulation: &Emulation, os: Option<EmulationOS>) -> String {
+    let Some(os) = os else {
+        return label(emulation);
+    };
+    match serde_json::to_value(os) {
+        Ok(Value::String(os)) => format!("{}/{}", label(emulation), os),
+        _ => format!("{}/{:?}", label(emulation), os),
+    }
+}
+
+// Up to three profiles within a few edits of the name, closest first. A bare family name
+// or a version that doesn't exist falls back to that family's newest profiles.
+fn close_matches(name: &str) -> Vec<&'static str> {
+    let normalized = name.trim().to_ascii_lowercase().replace(['-', ' '], "_");
+    let mut scored = BROWSER_PROFILES
+        .iter()
+        .enumerate()
+        .map(|(index, profile)| (edit_distance(&normalized, profile), index, *profile))
+        .filter(|(distance, _, _)| *distance <= 3)
+        .collect::<Vec<_>>();
+    // Later entries are newer, so they win ties
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    let mut matches = scored.into_iter().map(|(_, _, profile)| profile).take(3).collect::<Vec<_>>();
+
+    if matches.is_empty() {
+        let family = normalized.split('_').next().unwrap_or_default();
+        matches = BROWSER_PROFILES
+            .iter()
+            .rev()
+            .filter(|profile| profile.split('_').next() == Some(family))
+            .take(3)
+            .copied()
+            .collect();
+    }
+    matches
+}
+
+// Levenshtein distance over bytes
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, &left) in a.as_bytes().iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &right) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left != right);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
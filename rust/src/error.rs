@@ -0,0 +1,369 @@
+use neon::prelude::*;
+use std::fmt;
+use std::time::Duration;
+
+/// Failure classes surfaced to Node as `error.code`, so callers can branch on
+/// the kind of failure instead of matching message strings.
+///
+/// build.rs generates the TypeScript `ErrorCode` type from this enum: each variant's
+/// doc line describes its code there, so keep them to one line each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The request exceeded its `timeout`
+    Timeout,
+    /// The hostname could not be resolved
+    DnsFailure,
+    /// The connection was refused or reset
+    ConnectError,
+    /// The TLS handshake or certificate verification failed
+    TlsError,
+    /// The proxy rejected the connection or could not be reached
+    ProxyError,
+    /// `maxRedirects` was exceeded
+    TooManyRedirects,
+    /// The response body could not be read, decompressed or parsed
+    BodyDecodeError,
+    /// The response body exceeded `maxBodyBytes`
+    BodyTooLarge,
+    /// The server certificate matched none of `pinnedSha256`
+    PinningViolation,
+    /// The connection was torn down while the request was in flight
+    Aborted,
+    /// The rate limit was reached and set to `failFast`
+    RateLimited,
+    /// The host's circuit breaker is open after repeated failures
+    CircuitOpen,
+    /// `shutdown()` was under way, or its grace period ran out
+    Shutdown,
+    /// Anything else, such as invalid options
+    RequestError,
+}
+
+impl ErrorKind {
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "ERR_TIMEOUT",
+            ErrorKind::DnsFailure => "ERR_DNS",
+            ErrorKind::ConnectError => "ERR_CONNECT",
+            ErrorKind::TlsError => "ERR_TLS",
+            ErrorKind::ProxyError => "ERR_PROXY",
+            ErrorKind::TooManyRedirects => "ERR_TOO_MANY_REDIRECTS",
+            ErrorKind::BodyDecodeError => "ERR_BODY_DECODE",
+            ErrorKind::BodyTooLarge => "ERR_BODY_TOO_LARGE",
+            ErrorKind::PinningViolation => "ERR_PINNING",
+            ErrorKind::Aborted => "ERR_ABORTED",
+            ErrorKind::RateLimited => "ERR_RATE_LIMITED",
+            ErrorKind::CircuitOpen => "ERR_CIRCUIT_OPEN",
+            ErrorKind::Shutdown => "ERR_SHUTDOWN",
+            ErrorKind::RequestError => "ERR_REQUEST",
+        }
+    }
+
+    /// Classify an error by walking its source chain. wreq only exposes coarse
+    /// predicates, so connection failures are refined by their messages.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(shared) = cause.downcast_ref::<SharedFailure>() {
+                return shared.kind;
+            }
+            if cause.is::<BodyTooLarge>() {
+                return ErrorKind::BodyTooLarge;
+            }
+            if cause.is::<BodyParseFailed>() {
+                return ErrorKind::BodyDecodeError;
+            }
+            if cause.is::<TooManyRefreshes>() {
+                return ErrorKind::TooManyRedirects;
+            }
+            if cause.is::<PinningViolation>() {
+                return ErrorKind::PinningViolation;
+            }
+            if cause.is::<RateLimited>() {
+                return ErrorKind::RateLimited;
+            }
+            if cause.is::<CircuitOpen>() {
+                return ErrorKind::CircuitOpen;
+            }
+            if cause.is::<ShuttingDown>() {
+                return ErrorKind::Shutdown;
+            }
+            if cause.is::<ConnectTimedOut>() || cause.is::<DeadlineExceeded>() {
+                return ErrorKind::Timeout;
+            }
+            if let Some(e) = cause.downcast_ref::<wreq::Error>() {
+                if e.is_timeout() {
+                    return ErrorKind::Timeout;
+                }
+                if e.is_redirect() {
+                    return ErrorKind::TooManyRedirects;
+                }
+                if e.is_decode() || e.is_body() {
+                    return ErrorKind::BodyDecodeError;
+                }
+                if e.is_tls() {
+                    return ErrorKind::TlsError;
+                }
+            }
+            if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                if e.kind() == std::io::ErrorKind::TimedOut {
+                    return ErrorKind::Timeout;
+                }
+            }
+        }
+
+        let messages = error
+            .chain()
+            .map(|cause| cause.to_string().to_ascii_lowercase())
+            .collect::<Vec<_>>()
+            .join(": ");
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| messages.contains(needle));
+
+        if mentions(&["operation was canceled", "cancelled"]) {
+            ErrorKind::Aborted
+        } else if mentions(&["tunnel error", "socks error", "proxy"]) {
+            ErrorKind::ProxyError
+        } else if mentions(&[
+            "failed to lookup address",
+            "dns error",
+            "name or service not known",
+            "nodename nor servname",
+        ]) {
+            ErrorKind::DnsFailure
+        } else if mentions(&["certificate", "ssl", "tls", "handshake"]) {
+            ErrorKind::TlsError
+        } else if is_connect(error) {
+            ErrorKind::ConnectError
+        } else {
+            ErrorKind::RequestError
+        }
+    }
+}
+
+/// The response body (or its declared `Content-Length`) exceeded the request's limit.
+#[derive(Debug)]
+pub struct BodyTooLarge {
+    pub limit: u64,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Response body exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// The body couldn't be parsed the way the request's `parse` option asked.
+#[derive(Debug)]
+pub struct BodyParseFailed {
+    pub format: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for BodyParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Response body is not valid {}: {}", self.format, self.reason)
+    }
+}
+
+impl std::error::Error for BodyParseFailed {}
+
+/// A chain of redirects, counting the refreshes `followRefresh` follows, ran past the
+/// request's `maxRedirects`.
+#[derive(Debug)]
+pub struct TooManyRefreshes {
+    pub max: u32,
+}
+
+impl fmt::Display for TooManyRefreshes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many redirects: stopped after {} redirects and refreshes", self.max)
+    }
+}
+
+impl std::error::Error for TooManyRefreshes {}
+
+/// The server presented no certificate, or one whose certificate and public key
+/// hashes matched none of the request's pins.
+#[derive(Debug)]
+pub struct PinningViolation {
+    /// SHA-256 fingerprint of the certificate that was rejected
+    pub fingerprint256: Option<String>,
+}
+
+impl fmt::Display for PinningViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.fingerprint256 {
+            Some(fingerprint) => write!(f, "Certificate {} matches none of the pinned SHA-256 hashes", fingerprint),
+            None => write!(f, "No server certificate to check against the pinned SHA-256 hashes"),
+        }
+    }
+}
+
+impl std::error::Error for PinningViolation {}
+
+/// No token was available and the rate limit is set to fail fast.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub requests_per_second: f64,
+    /// Host whose bucket was empty, when buckets are per host
+    pub host: Option<String>,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limit of {} requests per second exceeded", self.requests_per_second)?;
+        match &self.host {
+            Some(host) => write!(f, " for {}", host),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Requests to the host failed too many times in a row, so its circuit breaker turns
+/// requests away until the cooldown is over and a probe request gets through.
+#[derive(Debug)]
+pub struct CircuitOpen {
+    pub host: String,
+    /// Time left until a probe is let through; zero while a probe is already in flight
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circuit breaker is open for {}", self.host)?;
+        if self.retry_in.is_zero() {
+            write!(f, " while a probe request checks it")
+        } else {
+            write!(f, "; requests resume in {} ms", self.retry_in.as_millis())
+        }
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// The failure of a deduplicated request, handed to every request that waited on it.
+/// It keeps the original's class, since the original error itself can't be shared.
+#[derive(Debug, Clone)]
+pub struct SharedFailure {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for SharedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SharedFailure {}
+
+/// The request arrived while the runtime was shutting down, or was still running when
+/// the shutdown's grace period ran out.
+#[derive(Debug)]
+pub struct ShuttingDown;
+
+impl fmt::Display for ShuttingDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The HTTP runtime is shutting down")
+    }
+}
+
+impl std::error::Error for ShuttingDown {}
+
+/// Establishing a new connection (TCP, proxy and TLS handshakes) took longer than
+/// the request's connect timeout.
+#[derive(Debug)]
+pub struct ConnectTimedOut {
+    pub limit: Duration,
+}
+
+impl fmt::Display for ConnectTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connecting took longer than {} ms", self.limit.as_millis())
+    }
+}
+
+impl std::error::Error for ConnectTimedOut {}
+
+/// The request's overall deadline passed before another attempt could be made.
+#[derive(Debug)]
+pub struct DeadlineExceeded {
+    pub deadline: Duration,
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Request deadline of {} ms exceeded", self.deadline.as_millis())
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// A browser profile name that matches none of the built-in profiles.
+#[derive(Debug)]
+pub struct UnknownEmulation {
+    pub name: String,
+    /// Closest known profile names, best first
+    pub suggestions: Vec<&'static str>,
+}
+
+impl fmt::Display for UnknownEmulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown browser profile '{}'", self.name)?;
+        if !self.suggestions.is_empty() {
+            write!(f, "; did you mean {}?", self.suggestions.join(", "))?;
+        }
+        write!(f, " See listEmulations() for every profile.")
+    }
+}
+
+impl std::error::Error for UnknownEmulation {}
+
+fn is_connect(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<wreq::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_connection_reset())
+            || cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ConnectionRefused
+                        | std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                )
+            })
+    })
+}
+
+/// Throw a JS `Error` carrying the full message chain and a `code` for its class.
+pub fn throw_classified<'a, C: Context<'a>, T>(cx: &mut C, error: &anyhow::Error) -> NeonResult<T> {
+    throw_with_kind(cx, ErrorKind::classify(error), format!("{:#}", error))
+}
+
+/// `throw_classified` for a failed request, also carrying the `requestId` its logs, events
+/// and HAR entry were tagged with.
+pub fn throw_request_failure<'a, C: Context<'a>, T>(
+    cx: &mut C,
+    error: &anyhow::Error,
+    request_id: &str,
+) -> NeonResult<T> {
+    let js_error = classified_error(cx, ErrorKind::classify(error), format!("{:#}", error))?;
+    let request_id = cx.string(request_id);
+    js_error.set(cx, "requestId", request_id)?;
+    cx.throw(js_error)
+}
+
+pub fn throw_with_kind<'a, C: Context<'a>, T>(cx: &mut C, kind: ErrorKind, message: String) -> NeonResult<T> {
+    let js_error = classified_error(cx, kind, message)?;
+    cx.throw(js_error)
+}
+
+fn classified_error<'a, C: Context<'a>>(cx: &mut C, kind: ErrorKind, message: String) -> JsResult<'a, JsError> {
+    let js_error = cx.error(message)?;
+    let code = cx.string(kind.code());
+    js_error.set(cx, "code", code)?;
+    Ok(js_error)
+}
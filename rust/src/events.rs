@@ -0,0 +1,237 @@
+use crate::client::Response;
+use crate::error::ErrorKind;
+use neon::prelude::*;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Stages of a request reported to the listener set with `setEventListener`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventType {
+    RequestStart,
+    DnsResolved,
+    Connected,
+    TlsEstablished,
+    /// Response headers arrived; once per attempt, so retries show up
+    FirstByte,
+    Complete,
+    Error,
+}
+
+impl EventType {
+    fn name(self) -> &'static str {
+        match self {
+            EventType::RequestStart => "request-start",
+            EventType::DnsResolved => "dns-resolved",
+            EventType::Connected => "connected",
+            EventType::TlsEstablished => "tls-established",
+            EventType::FirstByte => "first-byte",
+            EventType::Complete => "complete",
+            EventType::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Details {
+    /// Milliseconds the stage itself took, for DNS lookups and connects
+    duration: Option<f64>,
+    status: Option<u16>,
+    attempt: Option<u32>,
+    bytes: Option<u64>,
+    from_cache: Option<bool>,
+    error: Option<(&'static str, String)>,
+}
+
+struct Listener {
+    callback: Arc<Root<JsFunction>>,
+    channel: Channel,
+}
+
+static LISTENER: Lazy<RwLock<Option<Arc<Listener>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Replace the event listener, or remove it with `None`. The channel should be unref'd
+/// so a registered listener doesn't keep Node's event loop alive on its own.
+pub fn set_listener(listener: Option<(Root<JsFunction>, Channel)>) {
+    let listener = listener.map(|(callback, channel)| {
+        Arc::new(Listener {
+            callback: Arc::new(callback),
+            channel,
+        })
+    });
+    *LISTENER.write().unwrap() = listener;
+}
+
+struct TraceState {
+    listener: Arc<Listener>,
+    id: String,
+    method: String,
+    url: String,
+    started: Instant,
+}
+
+impl TraceState {
+    fn emit(&self, kind: EventType, details: Details) {
+        let callback = self.listener.callback.clone();
+        let id = self.id.clone();
+        let method = self.method.clone();
+        let url = self.url.clone();
+        let elapsed = self.started.elapsed().as_secs_f64() * 1000.0;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs_f64() * 1000.0)
+            .unwrap_or_default();
+
+        self.listener.channel.send(move |mut cx| {
+            let event = cx.empty_object();
+            let value = cx.string(kind.name());
+            event.set(&mut cx, "type", value)?;
+            let value = cx.string(id);
+            event.set(&mut cx, "requestId", value)?;
+            let value = cx.string(method);
+            event.set(&mut cx, "method", value)?;
+            let value = cx.string(url);
+            event.set(&mut cx, "url", value)?;
+            let value = cx.number(timestamp);
+            event.set(&mut cx, "timestamp", value)?;
+            let value = cx.number(elapsed);
+            event.set(&mut cx, "elapsed", value)?;
+
+            if let Some(duration) = details.duration {
+                let value = cx.number(duration);
+                event.set(&mut cx, "duration", value)?;
+            }
+            if let Some(status) = details.status {
+                let value = cx.number(status);
+                event.set(&mut cx, "status", value)?;
+            }
+            if let Some(attempt) = details.attempt {
+                let value = cx.number(attempt);
+                event.set(&mut cx, "attempt", value)?;
+            }
+            if let Some(bytes) = details.bytes {
+                let value = cx.number(bytes as f64);
+                event.set(&mut cx, "bytes", value)?;
+            }
+            if let Some(from_cache) = details.from_cache {
+                let value = cx.boolean(from_cache);
+                event.set(&mut cx, "fromCache", value)?;
+            }
+            if let Some((code, message)) = details.error {
+                let value = cx.string(code);
+                event.set(&mut cx, "code", value)?;
+                let value = cx.string(message);
+                event.set(&mut cx, "error", value)?;
+            }
+
+            let callback = callback.to_inner(&mut cx);
+            let this = cx.undefined();
+            callback.call(&mut cx, this, vec![event.upcast()])?;
+            Ok(())
+        });
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<TraceState>;
+}
+
+/// Lifecycle events for one request. Inert when no listener was set as the request started.
+#[derive(Clone)]
+pub struct Trace(Option<Arc<TraceState>>);
+
+impl Trace {
+    /// Start tracing a request, reporting `request-start`.
+    pub fn start(method: &str, url: &str, request_id: &str) -> Self {
+        let Some(listener) = LISTENER.read().unwrap().clone() else {
+            return Self(None);
+        };
+
+        let state = TraceState {
+            listener,
+            id: request_id.to_string(),
+            method: if method.is_empty() { "GET".to_string() } else { method.to_string() },
+            url: url.to_string(),
+            started: Instant::now(),
+        };
+        state.emit(EventType::RequestStart, Details::default());
+        Self(Some(Arc::new(state)))
+    }
+
+    /// Run `future` with this trace as the one DNS lookups and new connections report to.
+    /// Like connect timings, this relies on the pool opening connections on the request's task.
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        match &self.0 {
+            Some(state) => CURRENT.scope(state.clone(), future).await,
+            None => future.await,
+        }
+    }
+
+    pub fn first_byte(&self, status: u16, attempt: u32) {
+        if let Some(state) = &self.0 {
+            let details = Details {
+                status: Some(status),
+                attempt: Some(attempt),
+                ..Details::default()
+            };
+            state.emit(EventType::FirstByte, details);
+        }
+    }
+
+    /// The request finished with `response`; `bytes` is the body size, when it was read.
+    pub fn complete(&self, response: &Response, bytes: Option<u64>) {
+        if let Some(state) = &self.0 {
+            let details = Details {
+                status: Some(response.status),
+                attempt: Some(response.attempts),
+                bytes,
+                from_cache: Some(response.from_cache),
+                ..Details::default()
+            };
+            state.emit(EventType::Complete, details);
+        }
+    }
+
+    pub fn error(&self, error: &anyhow::Error) {
+        if let Some(state) = &self.0 {
+            let details = Details {
+                error: Some((ErrorKind::classify(error).code(), format!("{:#}", error))),
+                ..Details::default()
+            };
+            state.emit(EventType::Error, details);
+        }
+    }
+}
+
+fn with_current(report: impl FnOnce(&TraceState)) {
+    let _ = CURRENT.try_with(|state| report(state));
+}
+
+/// A lookup for a new connection finished.
+pub fn dns_resolved(duration: Duration) {
+    with_current(|state| {
+        let details = Details {
+            duration: Some(duration.as_secs_f64() * 1000.0),
+            ..Details::default()
+        };
+        state.emit(EventType::DnsResolved, details);
+    });
+}
+
+/// A new connection is ready. wreq performs the TCP, proxy and TLS handshakes as one
+/// step, so for HTTPS `tls-established` follows `connected` immediately.
+pub fn connected(duration: Duration) {
+    with_current(|state| {
+        let details = Details {
+            duration: Some(duration.as_secs_f64() * 1000.0),
+            ..Details::default()
+        };
+        state.emit(EventType::Connected, details.clone());
+
+        let https = state.url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+        if https {
+            state.emit(EventType::TlsEstablished, details);
+        }
+    });
+}
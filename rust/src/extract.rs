@@ -0,0 +1,48 @@
+use crate::parse::element_text;
+use scraper::{Html, Selector};
+use serde_json::{Map, Value};
+
+/// One named field of an `extract` option.
+#[derive(Debug, Clone)]
+pub struct ExtractRule {
+    pub name: String,
+    pub selector: Selector,
+    /// Attribute to read instead of the element's text
+    pub attr: Option<String>,
+    /// Every match as an array, instead of the first one or null
+    pub all: bool,
+}
+
+impl ExtractRule {
+    pub fn new(name: String, selector: &str, attr: Option<String>, all: bool) -> Result<Self, String> {
+        let selector = Selector::parse(selector).map_err(|e| e.to_string())?;
+        Ok(Self {
+            name,
+            selector,
+            attr,
+            all,
+        })
+    }
+}
+
+/// Run every rule over an HTML document, parsed once, into an object keyed by rule name.
+/// Matches without the rule's attribute are skipped.
+pub fn run(rules: &[ExtractRule], html: &str) -> Value {
+    let document = Html::parse_document(html);
+    let mut extracted = Map::new();
+
+    for rule in rules {
+        let mut values = document.select(&rule.selector).filter_map(|element| match &rule.attr {
+            Some(attr) => element.value().attr(attr).map(|value| value.trim().to_string()),
+            None => Some(element_text(element)),
+        });
+        let value = if rule.all {
+            Value::Array(values.map(Value::String).collect())
+        } else {
+            values.next().map_or(Value::Null, Value::String)
+        };
+        extracted.insert(rule.name.clone(), value);
+    }
+
+    Value::Object(extracted)
+}
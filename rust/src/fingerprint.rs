@@ -0,0 +1,848 @@
+use anyhow::{bail, Context, Result};
+use boring2::hash::{hash, MessageDigest};
+use std::borrow::Cow;
+use wreq::http2::{
+    Http2Options, Priorities, Priority, PseudoId, PseudoOrder, SettingId, SettingsOrder, StreamDependency, StreamId,
+};
+use wreq::tls::{AlpnProtocol, AlpsProtocol, CertificateCompressionAlgorithm, ExtensionType, TlsOptions, TlsVersion};
+use wreq_util::{Emulation, EmulationOS};
+
+/// A custom TLS ClientHello, taken from a JA3 string, a raw JA4 string or explicit lists.
+/// It is layered over the browser profile's TLS settings, so anything left empty keeps
+/// the profile's value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsFingerprint {
+    /// Cipher suites by IANA name, in ClientHello order
+    pub ciphers: Vec<&'static str>,
+    /// Extension code points in ClientHello order, GREASE left out
+    pub extensions: Vec<u16>,
+    /// Supported groups by name
+    pub curves: Vec<&'static str>,
+    /// Signature algorithms by name
+    pub sigalgs: Vec<&'static str>,
+    /// "h2" and/or "http/1.1"
+    pub alpn: Vec<&'static str>,
+    /// Highest version offered: TLS 1.3 when true, TLS 1.2 when false
+    pub tls13: Option<bool>,
+    /// Whether GREASE values are sprinkled into the lists
+    pub grease: Option<bool>,
+}
+
+const CIPHERS: &[(u16, &str)] = &[
+    (0x1301, "TLS_AES_128_GCM_SHA256"),
+    (0x1302, "TLS_AES_256_GCM_SHA384"),
+    (0x1303, "TLS_CHACHA20_POLY1305_SHA256"),
+    (0xc02b, "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+    (0xc02f, "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"),
+    (0xc02c, "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384"),
+    (0xc030, "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"),
+    (0xcca9, "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"),
+    (0xcca8, "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256"),
+    (0xc009, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA"),
+    (0xc00a, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA"),
+    (0xc013, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA"),
+    (0xc014, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA"),
+    (0xc023, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256"),
+    (0xc024, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384"),
+    (0xc027, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256"),
+    (0xc028, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384"),
+    (0xc008, "TLS_ECDHE_ECDSA_WITH_3DES_EDE_CBC_SHA"),
+    (0xc012, "TLS_ECDHE_RSA_WITH_3DES_EDE_CBC_SHA"),
+    (0x009c, "TLS_RSA_WITH_AES_128_GCM_SHA256"),
+    (0x009d, "TLS_RSA_WITH_AES_256_GCM_SHA384"),
+    (0x002f, "TLS_RSA_WITH_AES_128_CBC_SHA"),
+    (0x0035, "TLS_RSA_WITH_AES_256_CBC_SHA"),
+    (0x003c, "TLS_RSA_WITH_AES_128_CBC_SHA256"),
+    (0x003d, "TLS_RSA_WITH_AES_256_CBC_SHA256"),
+    (0x000a, "TLS_RSA_WITH_3DES_EDE_CBC_SHA"),
+];
+
+const CURVES: &[(u16, &str)] = &[
+    (29, "X25519"),
+    (23, "P-256"),
+    (24, "P-384"),
+    (25, "P-521"),
+    (256, "ffdhe2048"),
+    (257, "ffdhe3072"),
+    (4588, "X25519MLKEM768"),
+    (25497, "X25519Kyber768Draft00"),
+];
+
+const SIGALGS: &[(u16, &str)] = &[
+    (0x0403, "ecdsa_secp256r1_sha256"),
+    (0x0503, "ecdsa_secp384r1_sha384"),
+    (0x0603, "ecdsa_secp521r1_sha512"),
+    (0x0203, "ecdsa_sha1"),
+    (0x0804, "rsa_pss_rsae_sha256"),
+    (0x0805, "rsa_pss_rsae_sha384"),
+    (0x0806, "rsa_pss_rsae_sha512"),
+    (0x0401, "rsa_pkcs1_sha256"),
+    (0x0501, "rsa_pkcs1_sha384"),
+    (0x0601, "rsa_pkcs1_sha512"),
+    (0x0201, "rsa_pkcs1_sha1"),
+    (0x0807, "ed25519"),
+];
+
+// Extensions BoringSSL can send and order
+const EXTENSIONS: &[u16] = &[
+    0,     // server_name
+    5,     // status_request
+    10,    // supported_groups
+    11,    // ec_point_formats
+    13,    // signature_algorithms
+    16,    // application_layer_protocol_negotiation
+    18,    // signed_certificate_timestamp
+    21,    // padding
+    23,    // extended_master_secret
+    27,    // compress_certificate
+    28,    // record_size_limit
+    34,    // delegated_credentials
+    35,    // session_ticket
+    41,    // pre_shared_key
+    43,    // supported_versions
+    45,    // psk_key_exchange_modes
+    50,    // signature_algorithms_cert
+    51,    // key_share
+    17513, // application_settings
+    17613, // application_settings, new codepoint
+    65037, // encrypted_client_hello
+    65281, // renegotiation_info
+];
+
+const SERVER_NAME: u16 = 0;
+const ALPN: u16 = 16;
+
+// What Firefox offers for delegated credentials
+const DELEGATED_CREDENTIALS: &str = "ecdsa_secp256r1_sha256:ecdsa_secp384r1_sha384:ecdsa_secp521r1_sha512:ecdsa_sha1";
+
+impl TlsFingerprint {
+    /// Parse `version,ciphers,extensions,curves,point_formats` with dash-separated
+    /// decimal values, as JA3 records them. GREASE values turn GREASE on.
+    pub fn from_ja3(ja3: &str) -> Result<Self> {
+        let fields = ja3.trim().split(',').collect::<Vec<_>>();
+        let [_version, ciphers, extensions, curves, point_formats] = fields[..] else {
+            bail!("JA3 string must have 5 comma-separated fields, found {}", fields.len());
+        };
+
+        let mut grease = false;
+        let mut values = |field: &str, what: &str| -> Result<Vec<u16>> {
+            let mut values = Vec::new();
+            for value in field.split('-').filter(|value| !value.is_empty()) {
+                let value = value
+                    .parse::<u16>()
+                    .with_context(|| format!("Invalid JA3 {} value '{}'", what, value))?;
+                if is_grease(value) {
+                    grease = true;
+                } else {
+                    values.push(value);
+                }
+            }
+            Ok(values)
+        };
+        let ciphers = values(ciphers, "cipher")?;
+        let extensions = values(extensions, "extension")?;
+        let curves = values(curves, "curve")?;
+
+        if point_formats.split('-').any(|format| !format.is_empty() && format != "0") {
+            bail!("Only the uncompressed EC point format (0) is supported");
+        }
+
+        let mut fingerprint = TlsFingerprint {
+            tls13: Some(extensions.contains(&43)),
+            grease: Some(grease),
+            ..Default::default()
+        };
+        fingerprint.set_ciphers(&ciphers)?;
+        fingerprint.set_extensions(&extensions)?;
+        fingerprint.set_curves(&curves)?;
+        Ok(fingerprint)
+    }
+
+    /// Parse the raw JA4 forms, `JA4_r` and `JA4_ro`: a `t13d1516h2` style prefix, then
+    /// hex ciphers, extensions and signature algorithms separated by underscores. Hashed
+    /// JA4 strings can't be turned back into a ClientHello. `JA4_r` lists ciphers and
+    /// extensions sorted, so only `JA4_ro` keeps the original order.
+    pub fn from_ja4(ja4: &str) -> Result<Self> {
+        let parts = ja4.trim().split('_').collect::<Vec<_>>();
+        if parts.len() == 3 && parts[1].len() == 12 && !parts[1].contains(',') {
+            bail!("Hashed JA4 fingerprints can't be reversed; pass the raw JA4_r or JA4_ro form instead");
+        }
+        let [prefix, ciphers, extensions, sigalgs] = parts[..] else {
+            bail!("Raw JA4 string must have 4 underscore-separated parts, found {}", parts.len());
+        };
+        if prefix.len() != 10 || !prefix.is_ascii() {
+            bail!("Invalid JA4 prefix '{}'", prefix);
+        }
+
+        match &prefix[..1] {
+            "t" => {}
+            "q" => bail!("QUIC fingerprints are not supported; only TCP (t) ones are"),
+            other => bail!("Invalid JA4 protocol '{}'", other),
+        }
+        let tls13 = match &prefix[1..3] {
+            "13" => true,
+            "12" => false,
+            other => bail!("Unsupported JA4 TLS version '{}'", other),
+        };
+        let alpn = match &prefix[8..10] {
+            "h2" => vec!["h2", "http/1.1"],
+            "h1" => vec!["http/1.1"],
+            "00" => Vec::new(),
+            other => bail!("Unsupported JA4 ALPN '{}'", other),
+        };
+
+        let hex = |field: &str, what: &str| -> Result<Vec<u16>> {
+            field
+                .split(',')
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    u16::from_str_radix(value, 16).with_context(|| format!("Invalid JA4 {} value '{}'", what, value))
+                })
+                .collect()
+        };
+        let ciphers = hex(ciphers, "cipher")?;
+        let mut extensions = hex(extensions, "extension")?;
+        let sigalgs = hex(sigalgs, "signature algorithm")?;
+
+        // JA4_r leaves SNI and ALPN out of the list; the prefix says whether they were sent
+        if !alpn.is_empty() && !extensions.contains(&ALPN) {
+            extensions.insert(0, ALPN);
+        }
+        if &prefix[3..4] == "d" && !extensions.contains(&SERVER_NAME) {
+            extensions.insert(0, SERVER_NAME);
+        }
+
+        let mut fingerprint = TlsFingerprint {
+            alpn,
+            tls13: Some(tls13),
+            ..Default::default()
+        };
+        fingerprint.set_ciphers(&ciphers)?;
+        fingerprint.set_extensions(&extensions)?;
+        fingerprint.set_sigalgs(&sigalgs)?;
+        Ok(fingerprint)
+    }
+
+    pub fn set_ciphers(&mut self, ids: &[u16]) -> Result<()> {
+        self.ciphers = lookup(CIPHERS, ids, "cipher suite")?;
+        Ok(())
+    }
+
+    pub fn set_curves(&mut self, ids: &[u16]) -> Result<()> {
+        self.curves = lookup(CURVES, ids, "curve")?;
+        Ok(())
+    }
+
+    pub fn set_sigalgs(&mut self, ids: &[u16]) -> Result<()> {
+        self.sigalgs = lookup(SIGALGS, ids, "signature algorithm")?;
+        Ok(())
+    }
+
+    pub fn set_extensions(&mut self, ids: &[u16]) -> Result<()> {
+        self.extensions = ids.iter().copied().filter(|id| !is_grease(*id)).collect();
+        if let Some(id) = self.extensions.iter().find(|id| !EXTENSIONS.contains(id)) {
+            bail!("Unsupported TLS extension {}; BoringSSL can't send it", id);
+        }
+        Ok(())
+    }
+
+    /// Names are accepted alongside code points, e.g. "TLS_AES_128_GCM_SHA256" or "X25519".
+    pub fn cipher_id(name: &str) -> Result<u16> {
+        id_for(CIPHERS, name, "cipher suite")
+    }
+
+    pub fn curve_id(name: &str) -> Result<u16> {
+        id_for(CURVES, name, "curve")
+    }
+
+    pub fn sigalg_id(name: &str) -> Result<u16> {
+        id_for(SIGALGS, name, "signature algorithm")
+    }
+
+    pub fn set_alpn(&mut self, protocols: &[String]) -> Result<()> {
+        self.alpn = protocols
+            .iter()
+            .map(|protocol| match protocol.as_str() {
+                "h2" => Ok("h2"),
+                "http/1.1" => Ok("http/1.1"),
+                other => bail!("Unsupported ALPN protocol '{}', expected \"h2\" or \"http/1.1\"", other),
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    /// Override the profile's TLS options with the parts this fingerprint sets. An
+    /// extension list also switches the optional extensions on or off to match it.
+    pub fn apply(&self, options: &mut TlsOptions) {
+        if !self.ciphers.is_empty() {
+            options.cipher_list = Some(Cow::Owned(self.ciphers.join(":")));
+            options.preserve_tls13_cipher_list = Some(true);
+        }
+        if !self.curves.is_empty() {
+            options.curves_list = Some(Cow::Owned(self.curves.join(":")));
+        }
+        if !self.sigalgs.is_empty() {
+            options.sigalgs_list = Some(Cow::Owned(self.sigalgs.join(":")));
+        }
+        if !self.alpn.is_empty() {
+            let protocols = self
+                .alpn
+                .iter()
+                .map(|protocol| if *protocol == "h2" { AlpnProtocol::HTTP2 } else { AlpnProtocol::HTTP1 })
+                .collect::<Vec<_>>();
+            options.alpn_protocols = Some(Cow::Owned(protocols));
+        }
+        if let Some(tls13) = self.tls13 {
+            options.max_tls_version = Some(if tls13 { TlsVersion::TLS_1_3 } else { TlsVersion::TLS_1_2 });
+        }
+        if let Some(grease) = self.grease {
+            options.grease_enabled = Some(grease);
+        }
+
+        if self.extensions.is_empty() {
+            return;
+        }
+        let has = |id: u16| self.extensions.contains(&id);
+        options.extension_permutation = Some(Cow::Owned(
+            self.extensions.iter().map(|id| ExtensionType::from(*id)).collect(),
+        ));
+        options.permute_extensions = Some(false);
+        options.enable_ocsp_stapling = has(5);
+        options.enable_signed_cert_timestamps = has(18);
+        options.record_size_limit = has(28).then_some(0x4001);
+        options.session_ticket = has(35);
+        options.pre_shared_key = has(41);
+        options.psk_dhe_ke = has(45);
+        options.enable_ech_grease = has(65037);
+        options.renegotiation = has(65281);
+
+        if !has(27) {
+            options.certificate_compression_algorithms = None;
+        } else if options.certificate_compression_algorithms.is_none() {
+            options.certificate_compression_algorithms =
+                Some(Cow::Borrowed(&[CertificateCompressionAlgorithm::BROTLI]));
+        }
+        if !has(34) {
+            options.delegated_credentials = None;
+        } else if options.delegated_credentials.is_none() {
+            options.delegated_credentials = Some(Cow::Borrowed(DELEGATED_CREDENTIALS));
+        }
+        if has(17513) || has(17613) {
+            if options.alps_protocols.is_none() {
+                options.alps_protocols = Some(Cow::Borrowed(&[AlpsProtocol::HTTP2]));
+            }
+            options.alps_use_new_codepoint = has(17613);
+        } else {
+            options.alps_protocols = None;
+        }
+    }
+}
+
+/// A stream dependency as HTTP/2 PRIORITY data carries it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamPriority {
+    pub exclusive: bool,
+    /// Stream this one depends on, 0 for the root
+    pub depends_on: u32,
+    /// 1 to 256
+    pub weight: u16,
+}
+
+impl StreamPriority {
+    pub fn new(exclusive: bool, depends_on: u32, weight: u16) -> Result<Self> {
+        if !(1..=256).contains(&weight) {
+            bail!("HTTP/2 priority weight must be between 1 and 256, got {}", weight);
+        }
+        if depends_on > 0x7fff_ffff {
+            bail!("HTTP/2 stream IDs are 31 bits; {} is too large", depends_on);
+        }
+        Ok(Self {
+            exclusive,
+            depends_on,
+            weight,
+        })
+    }
+
+    fn dependency(self) -> StreamDependency {
+        StreamDependency::new(StreamId::from(self.depends_on), (self.weight - 1) as u8, self.exclusive)
+    }
+}
+
+/// Custom HTTP/2 connection settings, taken from an Akamai fingerprint or given one by one.
+/// They are layered over the browser profile's HTTP/2 options, so anything left out keeps
+/// the profile's value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Http2Fingerprint {
+    /// SETTINGS parameter values as `(id, value)`
+    pub settings: Vec<(u16, u32)>,
+    /// Order the SETTINGS parameters are sent in; ones left out follow in the default order
+    pub settings_order: Vec<u16>,
+    /// Send exactly `settings`, dropping the profile's other optional parameters
+    pub replace_settings: bool,
+    /// Increment of the connection-level WINDOW_UPDATE, 0 to send none
+    pub window_update: Option<u32>,
+    /// PRIORITY frames sent after the preface, by stream; empty to send none
+    pub priorities: Option<Vec<(u32, StreamPriority)>>,
+    pub pseudo_order: Vec<PseudoId>,
+    /// Dependency carried by every HEADERS frame
+    pub headers_priority: Option<StreamPriority>,
+}
+
+// SETTINGS parameters wreq can send
+const SETTINGS: &[u16] = &[1, 2, 3, 4, 5, 6, 8, 9];
+
+const PSEUDO_HEADERS: &[(PseudoId, &str, &str)] = &[
+    (PseudoId::Method, ":method", "m"),
+    (PseudoId::Authority, ":authority", "a"),
+    (PseudoId::Scheme, ":scheme", "s"),
+    (PseudoId::Path, ":path", "p"),
+];
+
+impl Http2Fingerprint {
+    /// Parse `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`, as Akamai and
+    /// fingerprinting services print it, e.g. `1:65536;2:0;4:6291456|15663105|0|m,a,s,p`.
+    /// The settings listed replace the profile's, in the order listed.
+    pub fn from_akamai(akamai: &str) -> Result<Self> {
+        let parts = akamai.trim().split('|').collect::<Vec<_>>();
+        let [settings, window_update, priorities, pseudo_order] = parts[..] else {
+            bail!("Akamai fingerprint must have 4 |-separated parts, found {}", parts.len());
+        };
+
+        let mut fingerprint = Http2Fingerprint {
+            replace_settings: true,
+            ..Default::default()
+        };
+        for setting in settings.split([';', ',']).filter(|setting| !setting.is_empty()) {
+            let parsed = setting
+                .split_once(':')
+                .and_then(|(id, value)| Some((id.parse::<u16>().ok()?, value.parse::<u32>().ok()?)));
+            let Some((id, value)) = parsed else {
+                bail!("Invalid HTTP/2 setting '{}', expected id:value", setting);
+            };
+            fingerprint.set_setting(id, value)?;
+            fingerprint.settings_order.push(id);
+        }
+
+        fingerprint.window_update = Some(match window_update {
+            "00" | "0" | "" => 0,
+            increment => increment
+                .parse::<u32>()
+                .with_context(|| format!("Invalid WINDOW_UPDATE increment '{}'", increment))?,
+        });
+
+        let mut frames = Vec::new();
+        for frame in priorities.split(',').filter(|frame| !frame.is_empty() && *frame != "0") {
+            let fields = frame.split(':').map(|field| field.parse::<u32>().ok()).collect::<Option<Vec<_>>>();
+            let Some(&[stream, exclusive, depends_on, weight]) = fields.as_deref() else {
+                bail!("Invalid PRIORITY frame '{}', expected stream:exclusive:dependency:weight", frame);
+            };
+            let weight = u16::try_from(weight).unwrap_or(u16::MAX);
+            frames.push((stream, StreamPriority::new(exclusive != 0, depends_on, weight)?));
+        }
+        fingerprint.priorities = Some(frames);
+
+        let names = pseudo_order.split(',').filter(|name| !name.is_empty()).collect::<Vec<_>>();
+        fingerprint.set_pseudo_order(&names)?;
+        Ok(fingerprint)
+    }
+
+    /// Set one SETTINGS parameter by its RFC 9113 id, keeping its place if already set.
+    pub fn set_setting(&mut self, id: u16, value: u32) -> Result<()> {
+        if !SETTINGS.contains(&id) {
+            bail!("HTTP/2 setting {} is not supported; known settings are 1-6, 8 and 9", id);
+        }
+        if id == 4 && value > 0x7fff_ffff {
+            bail!("HTTP/2 initial window size can be at most 2147483647, got {}", value);
+        }
+        if id == 5 && !(16_384..=16_777_215).contains(&value) {
+            bail!("HTTP/2 max frame size must be between 16384 and 16777215, got {}", value);
+        }
+        match self.settings.iter_mut().find(|(existing, _)| *existing == id) {
+            Some(setting) => setting.1 = value,
+            None => self.settings.push((id, value)),
+        }
+        Ok(())
+    }
+
+    pub fn set_settings_order(&mut self, ids: &[u16]) -> Result<()> {
+        if let Some(id) = ids.iter().find(|id| !SETTINGS.contains(id)) {
+            bail!("HTTP/2 setting {} is not supported; known settings are 1-6, 8 and 9", id);
+        }
+        self.settings_order = ids.to_vec();
+        Ok(())
+    }
+
+    /// Set the pseudo-header order from full names (`":method"`) or Akamai's letters (`"m"`).
+    /// Pseudo-headers left out follow in the default order.
+    pub fn set_pseudo_order<S: AsRef<str>>(&mut self, names: &[S]) -> Result<()> {
+        let mut order = Vec::new();
+        for name in names {
+            let name = name.as_ref();
+            let found = PSEUDO_HEADERS.iter().find(|(_, full, short)| name == *full || name == *short);
+            let Some(&(id, _, _)) = found else {
+                bail!("Unknown pseudo-header '{}', expected :method, :authority, :scheme or :path", name);
+            };
+            if order.contains(&id) {
+                bail!("Pseudo-header '{}' is listed twice", name);
+            }
+            order.push(id);
+        }
+        self.pseudo_order = order;
+        Ok(())
+    }
+
+    /// Layer these settings over a profile's HTTP/2 options.
+    pub fn apply(&self, options: &mut Http2Options) {
+        if self.replace_settings {
+            options.header_table_size = None;
+            options.enable_push = None;
+            options.max_concurrent_streams = None;
+            options.max_frame_size = None;
+            options.max_header_list_size = None;
+            options.enable_connect_protocol = None;
+            options.no_rfc7540_priorities = None;
+        }
+        for &(id, value) in &self.settings {
+            match SettingId::from(id) {
+                SettingId::HeaderTableSize => options.header_table_size = Some(value),
+                SettingId::EnablePush => options.enable_push = Some(value != 0),
+                SettingId::MaxConcurrentStreams => options.max_concurrent_streams = Some(value),
+                SettingId::InitialWindowSize => options.initial_window_size = value,
+                SettingId::MaxFrameSize => options.max_frame_size = Some(value),
+                SettingId::MaxHeaderListSize => options.max_header_list_size = Some(value),
+                SettingId::EnableConnectProtocol => options.enable_connect_protocol = Some(value != 0),
+                SettingId::NoRfc7540Priorities => options.no_rfc7540_priorities = Some(value != 0),
+                _ => {}
+            }
+        }
+        if !self.settings_order.is_empty() {
+            let order = self.settings_order.iter().map(|id| SettingId::from(*id));
+            options.settings_order = Some(SettingsOrder::builder().extend(order).build());
+        }
+
+        // The connection window starts at 65535; the WINDOW_UPDATE raises it by the increment
+        if let Some(increment) = self.window_update {
+            options.initial_conn_window_size = 65_535u32.saturating_add(increment);
+        }
+        if let Some(frames) = &self.priorities {
+            options.priorities = (!frames.is_empty()).then(|| {
+                frames
+                    .iter()
+                    .fold(Priorities::builder(), |builder, (stream, priority)| {
+                        builder.push(Priority::new(StreamId::from(*stream), priority.dependency()))
+                    })
+                    .build()
+            });
+        }
+        if !self.pseudo_order.is_empty() {
+            let order = PseudoOrder::builder().extend(self.pseudo_order.iter().copied()).build();
+            options.headers_pseudo_order = Some(order);
+        }
+        if let Some(priority) = self.headers_priority {
+            options.headers_stream_dependency = Some(priority.dependency());
+        }
+    }
+}
+
+/// The emulation a client is built with: the browser profile on the chosen OS, with the
+/// custom ClientHello and HTTP/2 settings (if any) layered over its own.
+pub fn emulation_with(
+    emulation: &Emulation,
+    os: Option<EmulationOS>,
+    fingerprint: Option<&TlsFingerprint>,
+    http2: Option<&Http2Fingerprint>,
+) -> wreq::Emulation {
+    let mut emulation = crate::emulation::build(emulation, os);
+    if let Some(fingerprint) = fingerprint {
+        fingerprint.apply(emulation.tls_options_mut().get_or_insert_with(TlsOptions::default));
+    }
+    if let Some(http2) = http2 {
+        http2.apply(emulation.http2_options_mut().get_or_insert_with(Http2Options::default));
+    }
+    emulation
+}
+
+// BoringSSL's extension order when nothing permutes it
+const DEFAULT_ORDER: &[u16] = &[0, 65037, 23, 65281, 10, 11, 35, 16, 5, 13, 18, 51, 45, 43, 27, 34, 17513, 17613, 28];
+
+/// What a connection presents to fingerprinting services, worked out from the settings the
+/// client was built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Full JA3 string; `None` when the profile shuffles its extensions on every connection
+    pub ja3: Option<String>,
+    /// MD5 of `ja3`, as fingerprinting services print it
+    pub ja3_hash: Option<String>,
+    /// Hashed JA4, which sorts what JA3 keeps in order and so stays stable under shuffling
+    pub ja4: Option<String>,
+    /// Protocol the server picked, "h2" or "http/1.1"; `None` over plain HTTP
+    pub alpn: Option<&'static str>,
+    /// Present when the response came over HTTP/2
+    pub http2: Option<Http2Report>,
+}
+
+/// The HTTP/2 connection preface, as the Akamai fingerprint describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Http2Report {
+    /// SETTINGS parameters as `(id, value)`, in the order they are sent
+    pub settings: Vec<(u16, u32)>,
+    /// Increment of the connection-level WINDOW_UPDATE, 0 when none is sent
+    pub window_update: u32,
+    /// Pseudo-header order, e.g. `["m", "a", "s", "p"]`
+    pub pseudo_order: Vec<&'static str>,
+    /// `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`
+    pub akamai: String,
+}
+
+impl Report {
+    /// Describe a request to `host` whose response arrived over `version` ("HTTP/2",
+    /// "HTTP/1.1", ...). Extensions BoringSSL only adds on some connections, padding and
+    /// pre_shared_key, are left out.
+    pub fn new(emulation: &wreq::Emulation, https: bool, host_is_ip: bool, version: &str) -> Self {
+        let mut emulation = emulation.clone();
+        let tls = emulation.tls_options_mut().clone().unwrap_or_default();
+        let http2 = emulation.http2_options_mut().clone().unwrap_or_default();
+
+        let hello = https.then(|| ClientHello::new(&tls, !host_is_ip));
+        let ja3 = hello.as_ref().and_then(ClientHello::ja3);
+        let alpn = match (https, version) {
+            (false, _) => None,
+            (true, "HTTP/2") => Some("h2"),
+            (true, _) => Some("http/1.1"),
+        };
+
+        Report {
+            ja3_hash: ja3.as_ref().and_then(|ja3| Some(hex(&hash(MessageDigest::md5(), ja3.as_bytes()).ok()?))),
+            ja3,
+            ja4: hello.as_ref().map(ClientHello::ja4),
+            alpn,
+            http2: (version == "HTTP/2").then(|| Http2Report::new(&http2)),
+        }
+    }
+}
+
+// The parts of a ClientHello JA3 and JA4 look at, GREASE left out as both leave it out
+struct ClientHello {
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    curves: Vec<u16>,
+    sigalgs: Vec<u16>,
+    alpn: Vec<&'static str>,
+    tls13: bool,
+    sni: bool,
+    permuted: bool,
+}
+
+impl ClientHello {
+    fn new(options: &TlsOptions, sni: bool) -> Self {
+        let ids = |list: &Option<Cow<'static, str>>, table: &[(u16, &'static str)]| -> Vec<u16> {
+            list.as_deref()
+                .unwrap_or_default()
+                .split(':')
+                .filter_map(|name| id_for(table, name, "").ok())
+                .collect()
+        };
+        let alpn = options
+            .alpn_protocols
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|protocol| match *protocol {
+                protocol if protocol == AlpnProtocol::HTTP2 => Some("h2"),
+                protocol if protocol == AlpnProtocol::HTTP1 => Some("http/1.1"),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let tls13 = options.max_tls_version != Some(TlsVersion::TLS_1_2);
+        let alps = options.alps_protocols.as_ref().is_some_and(|alps| !alps.is_empty());
+
+        let sent = |id: u16| match id {
+            0 => sni,
+            65037 => options.enable_ech_grease,
+            65281 => options.renegotiation,
+            35 => options.session_ticket,
+            16 => !alpn.is_empty(),
+            5 => options.enable_ocsp_stapling,
+            18 => options.enable_signed_cert_timestamps,
+            51 | 43 => tls13,
+            45 => tls13 && options.psk_dhe_ke,
+            27 => options.certificate_compression_algorithms.as_ref().is_some_and(|algs| !algs.is_empty()),
+            34 => options.delegated_credentials.is_some(),
+            17513 => alps && !options.alps_use_new_codepoint,
+            17613 => alps && options.alps_use_new_codepoint,
+            28 => options.record_size_limit.is_some(),
+            _ => true,
+        };
+        // A fixed permutation goes first; whatever it leaves out follows in the usual order
+        let mut extensions = Vec::new();
+        for extension in options.extension_permutation.as_deref().unwrap_or_default() {
+            if let Some(id) = EXTENSIONS.iter().copied().find(|id| ExtensionType::from(*id) == *extension) {
+                extensions.push(id);
+            }
+        }
+        let rest = DEFAULT_ORDER.iter().filter(|id| !extensions.contains(id)).copied().collect::<Vec<_>>();
+        extensions.extend(rest);
+        extensions.retain(|id| sent(*id));
+
+        ClientHello {
+            ciphers: ids(&options.cipher_list, CIPHERS),
+            extensions,
+            curves: ids(&options.curves_list, CURVES),
+            sigalgs: ids(&options.sigalgs_list, SIGALGS),
+            alpn,
+            tls13,
+            sni,
+            permuted: options.permute_extensions == Some(true) && options.extension_permutation.is_none(),
+        }
+    }
+
+    fn ja3(&self) -> Option<String> {
+        if self.permuted {
+            return None;
+        }
+        let join = |values: &[u16]| values.iter().map(u16::to_string).collect::<Vec<_>>().join("-");
+        Some(format!(
+            "771,{},{},{},0",
+            join(&self.ciphers),
+            join(&self.extensions),
+            join(&self.curves)
+        ))
+    }
+
+    // `t13d1516h2_<ciphers>_<extensions>`, each part the first 12 hex digits of a SHA-256
+    fn ja4(&self) -> String {
+        let alpn = match self.alpn.first() {
+            Some(&"h2") => "h2",
+            Some(_) => "h1",
+            None => "00",
+        };
+        let prefix = format!(
+            "t{}{}{:02}{:02}{}",
+            if self.tls13 { "13" } else { "12" },
+            if self.sni { "d" } else { "i" },
+            self.ciphers.len().min(99),
+            self.extensions.len().min(99),
+            alpn
+        );
+
+        let sorted_hex = |values: &[u16]| {
+            let mut values = values.iter().map(|value| format!("{:04x}", value)).collect::<Vec<_>>();
+            values.sort();
+            values.join(",")
+        };
+        let extensions = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|id| *id != SERVER_NAME && *id != ALPN)
+            .collect::<Vec<_>>();
+        let sigalgs = self.sigalgs.iter().map(|id| format!("{:04x}", id)).collect::<Vec<_>>().join(",");
+
+        format!(
+            "{}_{}_{}",
+            prefix,
+            truncated_sha256(&sorted_hex(&self.ciphers)),
+            truncated_sha256(&format!("{}_{}", sorted_hex(&extensions), sigalgs))
+        )
+    }
+}
+
+impl Http2Report {
+    fn new(options: &Http2Options) -> Self {
+        let mut settings = Vec::new();
+        for id in &options.settings_order.clone().unwrap_or_default() {
+            let value = match id {
+                SettingId::HeaderTableSize => options.header_table_size,
+                SettingId::EnablePush => options.enable_push.map(u32::from),
+                SettingId::MaxConcurrentStreams => options.max_concurrent_streams,
+                SettingId::InitialWindowSize => Some(options.initial_window_size),
+                SettingId::MaxFrameSize => options.max_frame_size,
+                SettingId::MaxHeaderListSize => options.max_header_list_size,
+                SettingId::EnableConnectProtocol => options.enable_connect_protocol.map(u32::from),
+                SettingId::NoRfc7540Priorities => options.no_rfc7540_priorities.map(u32::from),
+                _ => None,
+            };
+            if let Some(value) = value {
+                settings.push((u16::from(*id), value));
+            }
+        }
+
+        // The connection window starts at 65535; wreq raises it with one WINDOW_UPDATE
+        let window_update = options.initial_conn_window_size.saturating_sub(65535);
+        let pseudo_order = options
+            .headers_pseudo_order
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| match id {
+                PseudoId::Method => Some("m"),
+                PseudoId::Authority => Some("a"),
+                PseudoId::Scheme => Some("s"),
+                PseudoId::Path => Some("p"),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // PRIORITY frames as `stream:exclusive:dependency:weight`, read back from their encoding
+        let priorities = options
+            .priorities
+            .clone()
+            .into_iter()
+            .flatten()
+            .map(|priority| {
+                let mut frame = Vec::new();
+                priority.encode(&mut frame);
+                let stream = u32::from(priority.stream_id());
+                let dependency = u32::from_be_bytes([frame[9], frame[10], frame[11], frame[12]]);
+                let weight = u16::from(frame[13]) + 1;
+                format!("{}:{}:{}:{}", stream, dependency >> 31, dependency & 0x7fff_ffff, weight)
+            })
+            .collect::<Vec<_>>();
+
+        let akamai = format!(
+            "{}|{}|{}|{}",
+            settings.iter().map(|(id, value)| format!("{}:{}", id, value)).collect::<Vec<_>>().join(";"),
+            if window_update == 0 { "00".to_string() } else { window_update.to_string() },
+            if priorities.is_empty() { "0".to_string() } else { priorities.join(",") },
+            pseudo_order.join(",")
+        );
+
+        Http2Report {
+            settings,
+            window_update,
+            pseudo_order,
+            akamai,
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn truncated_sha256(value: &str) -> String {
+    let digest = hash(MessageDigest::sha256(), value.as_bytes()).map(|digest| hex(&digest)).unwrap_or_default();
+    digest.chars().take(12).collect()
+}
+
+// RFC 8701 reserves 0x0a0a, 0x1a1a, ... 0xfafa
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn lookup(table: &[(u16, &'static str)], ids: &[u16], what: &str) -> Result<Vec<&'static str>> {
+    ids.iter()
+        .filter(|id| !is_grease(**id))
+        .map(|id| match table.iter().find(|(known, _)| known == id) {
+            Some((_, name)) => Ok(*name),
+            None => bail!("Unsupported {} 0x{:04x}; BoringSSL can't offer it", what, id),
+        })
+        .collect()
+}
+
+fn id_for(table: &[(u16, &'static str)], name: &str, what: &str) -> Result<u16> {
+    match table.iter().find(|(_, known)| known.eq_ignore_ascii_case(name)) {
+        Some((id, _)) => Ok(*id),
+        None => bail!("Unknown {} '{}'", what, name),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::BodyParseFailed;
+
+// The flags byte of a gRPC-web frame: bit 7 marks the trailer frame, bit 0 a compressed message
+// (https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md)
+const TRAILER_FLAG: u8 = 0x80;
+const COMPRESSED_FLAG: u8 = 0x01;
+const HEADER_LEN: usize = 5;
+
+/// A gRPC-web response body split into its frames.
+#[derive(Debug, Default)]
+pub struct GrpcWebBody {
+    /// Message payloads, still protobuf-encoded
+    pub messages: Vec<Vec<u8>>,
+    /// Names lowercased, in the order the trailer frame lists them
+    pub trailers: Vec<(String, String)>,
+}
+
+/// Frame messages for a request body: a flags byte and a big-endian length before each.
+pub fn encode<'a>(messages: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    for message in messages {
+        let len = u32::try_from(message.len()).map_err(|_| "message is over 4 GiB".to_string())?;
+        body.push(0);
+        body.extend_from_slice(&len.to_be_bytes());
+        body.extend_from_slice(message);
+    }
+    Ok(body)
+}
+
+/// Split a response body into messages and the trailers sent in its last frame.
+pub fn decode(body: &[u8]) -> Result<GrpcWebBody, BodyParseFailed> {
+    let fail = |reason: String| BodyParseFailed {
+        format: "gRPC-web",
+        reason,
+    };
+
+    let mut decoded = GrpcWebBody::default();
+    let mut rest = body;
+    while !rest.is_empty() {
+        if rest.len() < HEADER_LEN {
+            return Err(fail(format!("truncated frame header at byte {}", body.len() - rest.len())));
+        }
+        let flags = rest[0];
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let Some(payload) = rest.get(HEADER_LEN..HEADER_LEN + len) else {
+            return Err(fail(format!(
+                "frame at byte {} declares {} bytes but only {} follow",
+                body.len() - rest.len(),
+                len,
+                rest.len() - HEADER_LEN
+            )));
+        };
+
+        if flags & TRAILER_FLAG != 0 {
+            decoded.trailers.extend(parse_trailers(payload));
+        } else if flags & COMPRESSED_FLAG != 0 {
+            return Err(fail("compressed messages are not supported".to_string()));
+        } else {
+            decoded.messages.push(payload.to_vec());
+        }
+        rest = &rest[HEADER_LEN + len..];
+    }
+    Ok(decoded)
+}
+
+// The trailer frame holds HTTP/1-style `name: value` lines separated by CRLF
+fn parse_trailers(payload: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(payload)
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
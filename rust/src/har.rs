@@ -0,0 +1,432 @@
+use crate::client::{outgoing_headers, RequestOptions, Response, ResponseCookie};
+use crate::timings::Timings;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wreq::{redirect, Extension};
+
+/// HAR recorder settings, set from Node with `startHarRecording`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HarConfig {
+    /// Request and response bodies are cut off after this many bytes
+    pub max_body_bytes: usize,
+    /// The oldest entries are dropped once the log holds this many
+    pub max_entries: usize,
+}
+
+impl Default for HarConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            max_entries: 10_000,
+        }
+    }
+}
+
+pub struct Recorder {
+    config: HarConfig,
+    entries: Mutex<VecDeque<Value>>,
+}
+
+static RECORDER: Lazy<RwLock<Option<Arc<Recorder>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Start recording into an empty log, replacing any recording in progress.
+pub fn start(config: HarConfig) {
+    let recorder = Recorder {
+        config,
+        entries: Mutex::new(VecDeque::new()),
+    };
+    *RECORDER.write().unwrap() = Some(Arc::new(recorder));
+}
+
+/// Stop recording and return what was captured, or `None` if nothing was being recorded.
+/// Requests still in flight are left out.
+pub fn stop() -> Option<String> {
+    let recorder = RECORDER.write().unwrap().take()?;
+    Some(recorder.export())
+}
+
+/// The log recorded so far, without stopping.
+pub fn export() -> Option<String> {
+    let recorder = RECORDER.read().unwrap().clone()?;
+    Some(recorder.export())
+}
+
+/// The active recorder, if any. Requests take it when they start, so a recording
+/// stopped mid-request doesn't pick the request up afterwards.
+pub fn recorder() -> Option<Arc<Recorder>> {
+    RECORDER.read().unwrap().clone()
+}
+
+/// What a request looked like before it was sent; the options themselves are consumed by sending.
+pub struct PendingEntry {
+    started: SystemTime,
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    post_data: Option<Value>,
+    request_id: String,
+}
+
+/// One hop of a followed redirect: the URL that answered, and how.
+pub struct RedirectHop {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// Redirects the client followed on the way to `response`.
+pub fn redirect_hops(response: &wreq::Response) -> Vec<RedirectHop> {
+    let Some(Extension(history)) = response.extension::<Vec<redirect::History>>() else {
+        return Vec::new();
+    };
+
+    history
+        .iter()
+        .map(|hop| RedirectHop {
+            url: hop.previous().to_string(),
+            status: hop.status().as_u16(),
+            headers: header_pairs(hop.headers()),
+        })
+        .collect()
+}
+
+impl Recorder {
+    pub fn pending(&self, options: &RequestOptions) -> PendingEntry {
+        let method = if options.method.is_empty() {
+            "GET".to_string()
+        } else {
+            options.method.clone()
+        };
+        let headers = outgoing_headers(options);
+
+        let post_data = if let Some(json) = &options.json {
+            let text = serde_json::to_string(json).unwrap_or_default();
+            Some(self.post_data("application/json", text.as_bytes()))
+        } else if let Some(body) = &options.body {
+            let mime_type = header_value(&headers, "content-type").unwrap_or("application/octet-stream");
+            Some(self.post_data(mime_type, body))
+        } else if options.multipart.is_some() {
+            Some(json!({
+                "mimeType": "multipart/form-data",
+                "text": "",
+                "comment": "Multipart body not captured",
+            }))
+        } else if let Some(path) = &options.body_file {
+            let mime_type = header_value(&headers, "content-type").unwrap_or("application/octet-stream");
+            Some(json!({
+                "mimeType": mime_type,
+                "text": "",
+                "comment": format!("Body streamed from {}", path.display()),
+            }))
+        } else {
+            None
+        };
+
+        PendingEntry {
+            started: SystemTime::now(),
+            method,
+            url: options.url.clone(),
+            headers,
+            post_data,
+            request_id: options.request_id.clone(),
+        }
+    }
+
+    /// Add the entries for a finished request: one per redirect hop, then the final response
+    /// or error. `body` is `None` when the body was streamed or saved to disk rather than read.
+    pub fn record(
+        &self,
+        pending: PendingEntry,
+        hops: Vec<RedirectHop>,
+        result: Result<&Response, &anyhow::Error>,
+        body: Option<&[u8]>,
+    ) {
+        let started = iso8601(pending.started);
+        let mut method = pending.method;
+        let mut post_data = pending.post_data;
+        let mut new_entries = Vec::with_capacity(hops.len() + 1);
+
+        for hop in hops {
+            let request = request_json(&method, &hop.url, &pending.headers, post_data.as_ref());
+            let location = header_value(&hop.headers, "location").unwrap_or_default().to_string();
+            let response = json!({
+                "status": hop.status,
+                "statusText": status_text(hop.status),
+                "httpVersion": "unknown",
+                "cookies": [],
+                "headers": headers_json(&hop.headers),
+                "content": { "size": 0, "mimeType": "x-unknown" },
+                "redirectURL": location,
+                "headersSize": -1,
+                "bodySize": -1,
+            });
+            new_entries.push(entry_json(&started, request, response, None));
+
+            // 301, 302 and 303 turn everything but HEAD into a bodiless GET, as the client does
+            if matches!(hop.status, 301..=303) && !method.eq_ignore_ascii_case("HEAD") {
+                method = "GET".to_string();
+                post_data = None;
+            }
+        }
+
+        let (url, response, timings) = match result {
+            Ok(response) => (response.url.clone(), self.response_json(response, body), Some(&response.timings)),
+            Err(e) => {
+                let response = json!({
+                    "status": 0,
+                    "statusText": "",
+                    "httpVersion": "unknown",
+                    "cookies": [],
+                    "headers": [],
+                    "content": { "size": 0, "mimeType": "x-unknown" },
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": -1,
+                    "_error": format!("{:#}", e),
+                });
+                (pending.url, response, None)
+            }
+        };
+        let request = request_json(&method, &url, &pending.headers, post_data.as_ref());
+        new_entries.push(entry_json(&started, request, response, timings));
+
+        // Redirect hops share the ID of the request that followed them
+        for entry in &mut new_entries {
+            entry["_requestId"] = pending.request_id.as_str().into();
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.extend(new_entries);
+        while entries.len() > self.config.max_entries {
+            entries.pop_front();
+        }
+    }
+
+    fn export(&self) -> String {
+        let entries = self.entries.lock().unwrap().iter().cloned().collect::<Vec<_>>();
+        let log = json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "wreq-js", "version": env!("CARGO_PKG_VERSION") },
+                "pages": [],
+                "entries": entries,
+            }
+        });
+        log.to_string()
+    }
+
+    fn post_data(&self, mime_type: &str, body: &[u8]) -> Value {
+        let mut post_data = json!({ "mimeType": mime_type });
+        let (text, encoding, truncated) = self.body_text(body);
+        post_data["text"] = text.into();
+        if let Some(encoding) = encoding {
+            post_data["encoding"] = encoding.into();
+        }
+        if truncated {
+            post_data["comment"] = format!("Truncated to {} bytes", self.config.max_body_bytes).into();
+        }
+        post_data
+    }
+
+    fn response_json(&self, response: &Response, body: Option<&[u8]>) -> Value {
+        let mime_type = header_value(&response.headers, "content-type").unwrap_or("x-unknown");
+        let content = match body {
+            Some(body) => {
+                let mut content = json!({ "size": body.len(), "mimeType": mime_type });
+                let (text, encoding, truncated) = self.body_text(body);
+                content["text"] = text.into();
+                if let Some(encoding) = encoding {
+                    content["encoding"] = encoding.into();
+                }
+                if truncated {
+                    content["comment"] = format!("Truncated to {} bytes", self.config.max_body_bytes).into();
+                }
+                content
+            }
+            None => json!({ "size": -1, "mimeType": mime_type, "comment": "Body not captured" }),
+        };
+
+        let mut entry = json!({
+            "status": response.status,
+            "statusText": status_text(response.status),
+            "httpVersion": response.version,
+            "cookies": response.set_cookies.iter().map(cookie_json).collect::<Vec<_>>(),
+            "headers": headers_json(&response.headers),
+            "content": content,
+            "redirectURL": header_value(&response.headers, "location").unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": body.map_or(-1, |body| body.len() as i64),
+        });
+        if response.from_cache {
+            entry["_fromCache"] = true.into();
+        }
+        entry
+    }
+
+    // Text bodies are kept as they are, anything else as base64, both cut at the size cap
+    fn body_text(&self, body: &[u8]) -> (String, Option<&'static str>, bool) {
+        let truncated = body.len() > self.config.max_body_bytes;
+        let body = &body[..body.len().min(self.config.max_body_bytes)];
+
+        match std::str::from_utf8(body) {
+            Ok(text) => (text.to_string(), None, truncated),
+            // A cut can land inside a multi-byte character; keep the text up to it
+            Err(e) if truncated && e.error_len().is_none() => {
+                (String::from_utf8_lossy(&body[..e.valid_up_to()]).into_owned(), None, truncated)
+            }
+            Err(_) => (STANDARD.encode(body), Some("base64"), truncated),
+        }
+    }
+}
+
+fn entry_json(started: &str, request: Value, response: Value, timings: Option<&Timings>) -> Value {
+    let timings = match timings {
+        Some(timings) => {
+            let dns = timings.dns.unwrap_or(-1.0);
+            let connect = timings.connect.unwrap_or(-1.0);
+            let wait = (timings.ttfb - dns.max(0.0) - connect.max(0.0)).max(0.0);
+            let receive = timings.download.unwrap_or(0.0);
+            json!({
+                "blocked": -1, "dns": dns, "connect": connect, "ssl": -1,
+                "send": 0, "wait": wait, "receive": receive,
+            })
+        }
+        None => json!({ "blocked": -1, "dns": -1, "connect": -1, "ssl": -1, "send": 0, "wait": 0, "receive": 0 }),
+    };
+    let time = ["dns", "connect", "send", "wait", "receive"]
+        .iter()
+        .filter_map(|phase| timings[phase].as_f64())
+        .filter(|ms| *ms > 0.0)
+        .sum::<f64>();
+
+    json!({
+        "startedDateTime": started,
+        "time": time,
+        "request": request,
+        "response": response,
+        "cache": {},
+        "timings": timings,
+    })
+}
+
+fn request_json(method: &str, url: &str, headers: &[(String, String)], post_data: Option<&Value>) -> Value {
+    let query = url
+        .split_once('?')
+        .map(|(_, query)| query.split_once('#').map_or(query, |(query, _)| query))
+        .filter(|query| !query.is_empty())
+        .map(|query| {
+            query
+                .split('&')
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    json!({ "name": name, "value": value })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let cookies = headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("cookie"))
+        .flat_map(|(_, value)| value.split(';'))
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            Some(json!({ "name": name, "value": value }))
+        })
+        .collect::<Vec<_>>();
+
+    let mut request = json!({
+        "method": method,
+        "url": url,
+        "httpVersion": "unknown",
+        "cookies": cookies,
+        "headers": headers_json(headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": post_data.map_or(0, |_| -1),
+    });
+    if let Some(post_data) = post_data {
+        request["postData"] = post_data.clone();
+    }
+    request
+}
+
+fn cookie_json(cookie: &ResponseCookie) -> Value {
+    let mut value = json!({
+        "name": cookie.name,
+        "value": cookie.value,
+        "httpOnly": cookie.http_only,
+        "secure": cookie.secure,
+    });
+    if let Some(path) = &cookie.path {
+        value["path"] = path.as_str().into();
+    }
+    if let Some(domain) = &cookie.domain {
+        value["domain"] = domain.as_str().into();
+    }
+    if let Some(expires) = cookie.expires {
+        value["expires"] = iso8601(UNIX_EPOCH + std::time::Duration::from_millis(expires as u64)).into();
+    }
+    value
+}
+
+fn headers_json(headers: &[(String, String)]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn header_pairs(headers: &wreq::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn status_text(status: u16) -> &'static str {
+    wreq::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("")
+}
+
+/// Format a time as ISO 8601 in UTC with milliseconds, as HAR timestamps are.
+pub fn iso8601(time: SystemTime) -> String {
+    let millis = time.duration_since(UNIX_EPOCH).map(|since| since.as_millis()).unwrap_or_default() as u64;
+    let (days, ms_of_day) = (millis / 86_400_000, millis % 86_400_000);
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000,
+    )
+}
@@ -0,0 +1,50 @@
+use wreq::header::OrigHeaderMap;
+use wreq::EmulationFactory;
+use wreq_util::Emulation;
+
+/// Where a caller's header goes relative to another header, named case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderPosition {
+    Before(String),
+    After(String),
+}
+
+/// The names of the headers a browser profile sends by default, in the order it sends them.
+pub fn template(emulation: &Emulation) -> Vec<String> {
+    let mut emulation = emulation.clone().emulation();
+    emulation.headers_mut().keys().map(|name| name.as_str().to_string()).collect()
+}
+
+/// Move each positioned header (every value under its name) next to its anchor. Headers
+/// whose anchor isn't among `headers` stay where they are, so a position written for one
+/// profile doesn't break requests made with another.
+pub fn arrange(mut headers: Vec<(String, String)>, positions: &[(String, HeaderPosition)]) -> Vec<(String, String)> {
+    for (name, position) in positions {
+        let (moved, rest): (Vec<_>, Vec<_>) = headers.into_iter().partition(|(key, _)| key.eq_ignore_ascii_case(name));
+        headers = rest;
+
+        let anchor = match position {
+            HeaderPosition::Before(anchor) => headers.iter().position(|(key, _)| key.eq_ignore_ascii_case(anchor)),
+            HeaderPosition::After(anchor) => headers
+                .iter()
+                .rposition(|(key, _)| key.eq_ignore_ascii_case(anchor))
+                .map(|index| index + 1),
+        };
+        let index = anchor.unwrap_or(headers.len());
+        headers.splice(index..index, moved);
+    }
+    headers
+}
+
+/// The wire order for a request, keeping the casing the caller wrote names in.
+pub fn orig_headers(headers: &[(String, String)]) -> OrigHeaderMap {
+    let mut order = OrigHeaderMap::with_capacity(headers.len());
+    let mut seen: Vec<&str> = Vec::new();
+    for (name, _) in headers {
+        if !seen.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+            seen.push(name);
+            order.insert(name.clone());
+        }
+    }
+    order
+}
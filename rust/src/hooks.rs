@@ -0,0 +1,123 @@
+use anyhow::{anyhow, Result};
+use neon::prelude::*;
+use neon::types::JsPromise;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// A request as the client is about to send it, as `onSend` middleware sees it.
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub url: String,
+    pub method: String,
+    /// In wire order: the profile's defaults, the caller's headers and the session's cookies
+    pub headers: Vec<(String, String)>,
+}
+
+/// The JS function that runs a request's `onSend` middleware, called from the runtime
+/// before every send, retries and authentication legs included.
+#[derive(Clone)]
+pub struct SendHook {
+    callback: Arc<Root<JsFunction>>,
+    channel: Channel,
+}
+
+impl std::fmt::Debug for SendHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SendHook")
+    }
+}
+
+impl SendHook {
+    pub fn new(callback: Root<JsFunction>, channel: Channel) -> Self {
+        Self {
+            callback: Arc::new(callback),
+            channel,
+        }
+    }
+
+    /// Hand the request to JS and wait for the middleware to finish with it. `None` means
+    /// nothing was changed; a hook that throws fails the request.
+    pub async fn run(&self, outgoing: Outgoing) -> Result<Option<Outgoing>> {
+        let callback = self.callback.clone();
+        let (sender, receiver) = oneshot::channel();
+        self.channel.send(move |mut cx| {
+            let called = cx.try_catch(|cx| {
+                let request = outgoing_to_js(cx, &outgoing)?;
+                let callback = callback.to_inner(cx);
+                let this = cx.undefined();
+                let promise = callback
+                    .call(cx, this, vec![request.upcast()])?
+                    .downcast_or_throw::<JsPromise, _>(cx)?;
+                promise.to_future(cx, |mut cx, result| {
+                    let settled = match result {
+                        Ok(value) => cx.try_catch(|cx| outgoing_from_js(cx, value)),
+                        Err(reason) => Err(reason),
+                    };
+                    Ok(settled.map_err(|reason| describe(&mut cx, reason)))
+                })
+            });
+            let called = called.map_err(|reason| describe(&mut cx, reason));
+            let _ = sender.send(called);
+            Ok(())
+        });
+
+        // The channel drops the call when Node is shutting down
+        let future = receiver
+            .await
+            .map_err(|_| anyhow!("onSend middleware could not be called"))?
+            .map_err(failed)?;
+        let settled = future
+            .await
+            .map_err(|e| anyhow!("onSend middleware could not be called: {}", e))?;
+        settled.map_err(failed)
+    }
+}
+
+fn failed(reason: String) -> anyhow::Error {
+    anyhow!("onSend middleware failed: {}", reason)
+}
+
+fn describe(cx: &mut TaskContext, value: Handle<JsValue>) -> String {
+    match value.to_string(cx) {
+        Ok(text) => text.value(cx),
+        Err(_) => "unknown error".to_string(),
+    }
+}
+
+fn outgoing_to_js<'a>(cx: &mut TaskContext<'a>, outgoing: &Outgoing) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let url = cx.string(&outgoing.url);
+    obj.set(cx, "url", url)?;
+    let method = cx.string(&outgoing.method);
+    obj.set(cx, "method", method)?;
+
+    let headers = cx.empty_array();
+    for (index, (name, value)) in outgoing.headers.iter().enumerate() {
+        let pair = cx.empty_array();
+        let name = cx.string(name);
+        pair.set(cx, 0, name)?;
+        let value = cx.string(value);
+        pair.set(cx, 1, value)?;
+        headers.set(cx, index as u32, pair)?;
+    }
+    obj.set(cx, "headers", headers)?;
+    Ok(obj)
+}
+
+// Anything but an object leaves the request as it was
+fn outgoing_from_js(cx: &mut TaskContext, value: Handle<JsValue>) -> NeonResult<Option<Outgoing>> {
+    let Ok(obj) = value.downcast::<JsObject, _>(cx) else {
+        return Ok(None);
+    };
+    let url = obj.get::<JsString, _, _>(cx, "url")?.value(cx);
+    let method = obj.get::<JsString, _, _>(cx, "method")?.value(cx);
+
+    let mut headers = Vec::new();
+    for entry in obj.get::<JsArray, _, _>(cx, "headers")?.to_vec(cx)? {
+        let pair = entry.downcast_or_throw::<JsArray, _>(cx)?;
+        let name = pair.get::<JsString, _, _>(cx, 0)?.value(cx);
+        let value = pair.get::<JsString, _, _>(cx, 1)?.value(cx);
+        headers.push((name, value));
+    }
+    Ok(Some(Outgoing { url, method, headers }))
+}
@@ -0,0 +1,623 @@
+use crate::challenge;
+use crate::charset;
+use crate::client::{RedirectHop, RequestOptions, Response};
+use crate::emulation;
+use crate::timings::Timings;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use boring2::hash::{hash, MessageDigest};
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where cached responses are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheStore {
+    /// In process, evicting the least recently used entries past `max_bytes` of bodies
+    Memory { max_bytes: u64 },
+    /// One JSON file per entry in this directory; entries are replaced, never evicted
+    Disk(PathBuf),
+}
+
+/// Per-request cache behaviour, mirroring `RequestInit.cache` from the Fetch standard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Serve fresh responses, revalidate stale ones
+    #[default]
+    Default,
+    /// Neither read nor update the cache
+    NoStore,
+    /// Skip the lookup, but store the response
+    Reload,
+    /// Revalidate a stored response even when it is fresh
+    NoCache,
+    /// Serve any stored response, however stale, before going to the network
+    ForceCache,
+}
+
+impl CacheMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "default" => Some(Self::Default),
+            "no-store" => Some(Self::NoStore),
+            "reload" => Some(Self::Reload),
+            "no-cache" => Some(Self::NoCache),
+            "force-cache" => Some(Self::ForceCache),
+            _ => None,
+        }
+    }
+}
+
+/// Statuses that may be cached without explicit freshness information (RFC 9110 §15.1).
+const HEURISTIC_STATUSES: &[u16] = &[200, 203, 204, 206, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Upper bound on heuristic freshness, derived from `Last-Modified`.
+const MAX_HEURISTIC_LIFETIME: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    status: u16,
+    version: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    #[serde(serialize_with = "serialize_base64", deserialize_with = "deserialize_base64")]
+    body: Vec<u8>,
+    redirects: Vec<String>,
+    /// Status and `Location` of each redirect; the cookies they set aren't kept
+    #[serde(default)]
+    redirect_hops: Vec<(u16, Option<String>)>,
+    /// Request headers named by `Vary` (lowercased) and the values they were sent with
+    vary: Vec<(String, Option<String>)>,
+    variant: Variant,
+    /// When the request was sent and when its response arrived, in seconds since the epoch
+    request_time: u64,
+    response_time: u64,
+}
+
+impl Entry {
+    fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    fn directives(&self) -> Directives {
+        Directives::parse(&self.headers)
+    }
+
+    fn date(&self) -> u64 {
+        self.header("date").and_then(parse_date).unwrap_or(self.response_time)
+    }
+
+    // RFC 9111 §4.2.1, as a shared cache: s-maxage, then max-age, then Expires, then a heuristic
+    fn freshness_lifetime(&self) -> u64 {
+        let directives = self.directives();
+        if let Some(lifetime) = directives.s_maxage.or(directives.max_age) {
+            return lifetime;
+        }
+
+        // An unparsable Expires means already expired
+        if let Some(expires) = self.header("expires") {
+            return parse_date(expires).map_or(0, |expires| expires.saturating_sub(self.date()));
+        }
+
+        match self.header("last-modified").and_then(parse_date) {
+            Some(modified) if HEURISTIC_STATUSES.contains(&self.status) => {
+                (self.date().saturating_sub(modified) / 10).min(MAX_HEURISTIC_LIFETIME)
+            }
+            _ => 0,
+        }
+    }
+
+    // RFC 9111 §4.2.3
+    fn current_age(&self, now: u64) -> u64 {
+        let apparent_age = self.response_time.saturating_sub(self.date());
+        let age_value = self.header("age").and_then(|age| age.trim().parse::<u64>().ok()).unwrap_or(0);
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        let corrected_initial_age = apparent_age.max(age_value + response_delay);
+        corrected_initial_age + now.saturating_sub(self.response_time)
+    }
+
+    fn matches(&self, request_headers: &[(String, String)], variant: &Variant) -> bool {
+        self.variant == *variant
+            && self
+                .vary
+                .iter()
+                .all(|(name, value)| joined_header(request_headers, name) == *value)
+    }
+
+    /// Fold in the headers of a `304 Not Modified` (RFC 9111 §3.2).
+    fn refreshed(&self, not_modified: &Response, request_time: u64, response_time: u64) -> Self {
+        let mut entry = self.clone();
+        for (name, _) in &not_modified.headers {
+            if name.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+            entry.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        }
+        entry.headers.extend(
+            not_modified
+                .headers
+                .iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("content-length"))
+                .cloned(),
+        );
+        entry.request_time = request_time;
+        entry.response_time = response_time;
+        entry
+    }
+
+    fn to_response(&self, now: u64) -> Response {
+        let mut headers = self.headers.clone();
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case("age"));
+        headers.push(("age".to_string(), self.current_age(now).to_string()));
+
+        let content_type = header(&headers, "content-type");
+        let (body, charset) = charset::decode(&self.body, content_type, None);
+        let challenge = challenge::detect(self.status, &headers, Some(&body));
+
+        Response {
+            status: self.status,
+            version: version_label(&self.version),
+            headers,
+            // Only the Latin-1 text of non-UTF-8 values is stored
+            raw_header_values: Vec::new(),
+            trailers: Vec::new(),
+            request_id: String::new(),
+            body,
+            body_bytes: self.body.clone(),
+            charset: Some(charset),
+            cookies: Default::default(),
+            set_cookies: Vec::new(),
+            url: self.url.clone(),
+            redirects: self.redirects.clone(),
+            redirect_hops: self
+                .redirects
+                .iter()
+                .zip(&self.redirect_hops)
+                .map(|(url, (status, location))| RedirectHop {
+                    url: url.clone(),
+                    status: *status,
+                    location: location.clone(),
+                    set_cookies: Vec::new(),
+                })
+                .collect(),
+            stream_id: None,
+            attempts: 0,
+            timings: Timings::default(),
+            proxy: None,
+            connection: None,
+            tls: None,
+            from_cache: true,
+            fingerprint: None,
+            parsed: None,
+            extracted: None,
+            challenge,
+            body_hash: None,
+            decodings: Vec::new(),
+        }
+    }
+}
+
+/// What a response depends on besides the request headers `Vary` names, compared along with them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Variant {
+    /// Browser profile of the request, whose headers vary too, `Accept-Encoding` among them
+    profile: String,
+    /// Whether the body was stored decoded, or as sent with its `Content-Encoding`
+    decompress: bool,
+    accept_encoding: Option<String>,
+}
+
+impl Variant {
+    fn of(options: &RequestOptions) -> Self {
+        Self {
+            profile: emulation::profile_label(&options.emulation, options.emulation_os),
+            decompress: options.decompress,
+            accept_encoding: options.accept_encoding.clone(),
+        }
+    }
+}
+
+/// The `Cache-Control` directives this cache acts on. Qualified forms such as
+/// `no-cache="Set-Cookie"` are treated like their unqualified versions.
+#[derive(Debug, Default)]
+struct Directives {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    must_revalidate: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl Directives {
+    fn parse(headers: &[(String, String)]) -> Self {
+        let mut directives = Self::default();
+        let values = headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("cache-control"))
+            .flat_map(|(_, value)| value.split(','));
+
+        for directive in values {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = argument.and_then(|argument| argument.parse::<u64>().ok());
+
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => directives.no_store = true,
+                "no-cache" => directives.no_cache = true,
+                "private" => directives.private = true,
+                "public" => directives.public = true,
+                "must-revalidate" | "proxy-revalidate" => directives.must_revalidate = true,
+                // A malformed max-age means the response is stale
+                "max-age" => directives.max_age = Some(seconds.unwrap_or(0)),
+                "s-maxage" => directives.s_maxage = Some(seconds.unwrap_or(0)),
+                _ => {}
+            }
+        }
+
+        directives
+    }
+}
+
+enum Backend {
+    Memory(Cache<String, Arc<Entry>>),
+    Disk(PathBuf),
+}
+
+impl Backend {
+    async fn get(&self, key: &str) -> Option<Arc<Entry>> {
+        match self {
+            Backend::Memory(cache) => cache.get(key),
+            Backend::Disk(dir) => {
+                let bytes = tokio::fs::read(entry_path(dir, key)).await.ok()?;
+                serde_json::from_slice(&bytes).ok().map(Arc::new)
+            }
+        }
+    }
+
+    async fn insert(&self, key: String, entry: Entry) -> Result<()> {
+        match self {
+            Backend::Memory(cache) => cache.insert(key, Arc::new(entry)),
+            Backend::Disk(dir) => {
+                // Write beside the target and rename, so readers never see half an entry
+                let path = entry_path(dir, &key);
+                let temp = path.with_extension(format!("tmp-{}", fastrand::u64(..)));
+                tokio::fs::write(&temp, serde_json::to_vec(&entry)?)
+                    .await
+                    .with_context(|| format!("Failed to write cache entry '{}'", temp.display()))?;
+                if let Err(e) = tokio::fs::rename(&temp, &path).await {
+                    let _ = tokio::fs::remove_file(&temp).await;
+                    return Err(e).with_context(|| format!("Failed to write cache entry '{}'", path.display()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) {
+        match self {
+            Backend::Memory(cache) => cache.invalidate(key),
+            Backend::Disk(dir) => {
+                let _ = tokio::fs::remove_file(entry_path(dir, key)).await;
+            }
+        }
+    }
+}
+
+static BACKEND: Lazy<RwLock<Option<Arc<Backend>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Turn the cache on with `store`, or off with `None`. Switching stores starts from
+/// an empty memory cache; a disk cache picks up the entries already in its directory.
+pub fn configure(store: Option<CacheStore>) -> Result<()> {
+    let backend = match store {
+        None => None,
+        Some(CacheStore::Memory { max_bytes }) => {
+            let cache = Cache::builder()
+                .max_capacity(max_bytes)
+                .weigher(|_key: &String, entry: &Arc<Entry>| entry.body.len().try_into().unwrap_or(u32::MAX))
+                .build();
+            Some(Arc::new(Backend::Memory(cache)))
+        }
+        Some(CacheStore::Disk(dir)) => {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create cache directory '{}'", dir.display()))?;
+            Some(Arc::new(Backend::Disk(dir)))
+        }
+    };
+
+    *BACKEND.write().unwrap() = backend;
+    Ok(())
+}
+
+/// Drop every stored response.
+pub fn clear() -> Result<()> {
+    let Some(backend) = BACKEND.read().unwrap().clone() else {
+        return Ok(());
+    };
+
+    match backend.as_ref() {
+        Backend::Memory(cache) => cache.invalidate_all(),
+        Backend::Disk(dir) => {
+            let entries = std::fs::read_dir(dir)
+                .with_context(|| format!("Failed to read cache directory '{}'", dir.display()))?;
+            for entry in entries.flatten() {
+                if entry.path().extension().is_some_and(|extension| extension == "json") {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The outcome of consulting the cache before a request is sent.
+pub enum Lookup {
+    /// A fresh stored response; nothing needs to be sent
+    Hit(Response),
+    /// Send the request, then hand the response to the pending entry, if any
+    Send(Option<Pending>),
+}
+
+/// Cache bookkeeping for a request that went to the network.
+pub struct Pending {
+    backend: Arc<Backend>,
+    key: String,
+    /// Unsafe methods only clear what is stored for their URL
+    invalidate: bool,
+    request_headers: Vec<(String, String)>,
+    /// Whether the client signs the request or answers an auth challenge after the lookup,
+    /// out of `request_headers`' sight
+    credentialed: bool,
+    variant: Variant,
+    request_time: u64,
+    /// The stale entry being revalidated with conditional headers
+    stored: Option<Arc<Entry>>,
+}
+
+/// Look the request up, adding `If-None-Match`/`If-Modified-Since` to `options` when a
+/// stored response needs revalidating. Only `GET` responses are cached.
+pub async fn lookup(options: &mut RequestOptions) -> Lookup {
+    let Some(backend) = BACKEND.read().unwrap().clone() else {
+        return Lookup::Send(None);
+    };
+    // A redirect returned as-is would otherwise be served to requests that follow it
+    if options.cache == CacheMode::NoStore || !options.redirect.follow {
+        return Lookup::Send(None);
+    }
+
+    let now = now_secs();
+    let mut pending = Pending {
+        backend,
+        key: cache_key(options),
+        invalidate: false,
+        request_headers: options.headers.clone(),
+        credentialed: options.aws_sigv4.is_some() || options.auth.is_some(),
+        variant: Variant::of(options),
+        request_time: now,
+        stored: None,
+    };
+
+    let method = options.method.to_ascii_uppercase();
+    if !method.is_empty() && method != "GET" {
+        pending.invalidate = !matches!(method.as_str(), "HEAD" | "OPTIONS" | "TRACE");
+        return Lookup::Send(pending.invalidate.then_some(pending));
+    }
+
+    // Conditional requests built by the caller go straight to the server
+    let request = Directives::parse(&options.headers);
+    let conditional = options.headers.iter().any(|(name, _)| {
+        let name = name.to_ascii_lowercase();
+        name.starts_with("if-") || name == "range"
+    });
+    if conditional || request.no_store {
+        return Lookup::Send(None);
+    }
+    if options.cache == CacheMode::Reload {
+        return Lookup::Send(Some(pending));
+    }
+
+    let Some(entry) = pending.backend.get(&pending.key).await else {
+        return Lookup::Send(Some(pending));
+    };
+    if !entry.matches(&options.headers, &pending.variant) {
+        return Lookup::Send(Some(pending));
+    }
+    if options.cache == CacheMode::ForceCache {
+        return Lookup::Hit(entry.to_response(now));
+    }
+
+    let pragma_no_cache = header(&options.headers, "pragma").is_some_and(|value| value.contains("no-cache"));
+    let must_validate = options.cache == CacheMode::NoCache
+        || request.no_cache
+        || pragma_no_cache
+        || entry.directives().no_cache;
+    let age = entry.current_age(now);
+    let fresh = age < entry.freshness_lifetime() && request.max_age.is_none_or(|max_age| age <= max_age);
+    if fresh && !must_validate {
+        return Lookup::Hit(entry.to_response(now));
+    }
+
+    let etag = entry.header("etag").map(str::to_string);
+    let last_modified = entry.header("last-modified").map(str::to_string);
+    if let Some(etag) = &etag {
+        options.headers.push(("If-None-Match".to_string(), etag.clone()));
+    }
+    if let Some(last_modified) = &last_modified {
+        options.headers.push(("If-Modified-Since".to_string(), last_modified.clone()));
+    }
+    if etag.is_some() || last_modified.is_some() {
+        pending.stored = Some(entry);
+    }
+
+    Lookup::Send(Some(pending))
+}
+
+impl Pending {
+    /// Store or invalidate based on the response, turning a `304` for a revalidated
+    /// entry into the stored response.
+    pub async fn complete(self, response: Response) -> Response {
+        let now = now_secs();
+
+        if self.invalidate {
+            if (200..400).contains(&response.status) {
+                self.backend.remove(&self.key).await;
+            }
+            return response;
+        }
+
+        if response.status == 304 {
+            let Some(stored) = &self.stored else {
+                return response;
+            };
+            let entry = stored.refreshed(&response, self.request_time, now);
+            let mut cached = entry.to_response(now);
+            cached.attempts = response.attempts;
+            cached.timings = response.timings;
+            cached.tls = response.tls;
+            cached.proxy = response.proxy;
+            cached.connection = response.connection;
+            let _ = self.backend.insert(self.key, entry).await;
+            return cached;
+        }
+
+        if storable(&response, &self.request_headers, self.credentialed) {
+            let vary = vary_names(&response.headers)
+                .map(|name| {
+                    let value = joined_header(&self.request_headers, &name);
+                    (name, value)
+                })
+                .collect();
+            let entry = Entry {
+                status: response.status,
+                version: response.version.to_string(),
+                url: response.url.clone(),
+                headers: response.headers.clone(),
+                body: response.body_bytes.clone(),
+                redirects: response.redirects.clone(),
+                redirect_hops: response
+                    .redirect_hops
+                    .iter()
+                    .map(|hop| (hop.status, hop.location.clone()))
+                    .collect(),
+                vary,
+                variant: self.variant,
+                request_time: self.request_time,
+                response_time: now,
+            };
+            // Without freshness or a validator the entry could never be used
+            let validatable = entry.header("etag").is_some() || entry.header("last-modified").is_some();
+            if entry.freshness_lifetime() > 0 || validatable {
+                let _ = self.backend.insert(self.key, entry).await;
+            }
+        }
+
+        response
+    }
+}
+
+// RFC 9111 §3, for a shared cache: requests outside a session share their entries
+fn storable(response: &Response, request_headers: &[(String, String)], credentialed: bool) -> bool {
+    if response.status < 200 || response.status == 206 || response.status == 304 {
+        return false;
+    }
+
+    let directives = Directives::parse(&response.headers);
+    if directives.no_store || directives.private {
+        return false;
+    }
+
+    // Cookies the caller sets pick out a user as much as credentials do; a session's own are
+    // added after this and kept apart by the key
+    let authorized = credentialed
+        || ["authorization", "cookie"]
+            .iter()
+            .any(|name| header(request_headers, name).is_some());
+    if authorized && !(directives.public || directives.s_maxage.is_some() || directives.must_revalidate) {
+        return false;
+    }
+
+    if vary_names(&response.headers).any(|name| name == "*") {
+        return false;
+    }
+
+    let explicit = directives.public
+        || directives.max_age.is_some()
+        || directives.s_maxage.is_some()
+        || header(&response.headers, "expires").is_some();
+    explicit || HEURISTIC_STATUSES.contains(&response.status)
+}
+
+fn vary_names(headers: &[(String, String)]) -> impl Iterator<Item = String> + '_ {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("vary"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn joined_header(headers: &[(String, String)], name: &str) -> Option<String> {
+    let values: Vec<_> = headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+// Sessions keep their own entries, since what a URL returns can depend on the cookies in
+// their jars; requests outside a session start with an empty jar and share theirs.
+// Fragments never reach the server, so they don't split entries
+fn cache_key(options: &RequestOptions) -> String {
+    let url = options.url.split_once('#').map_or(options.url.as_str(), |(url, _)| url);
+    let session = if options.ephemeral { "" } else { options.session_id.as_str() };
+    format!("{}\0{}", session, url)
+}
+
+fn entry_path(dir: &std::path::Path, key: &str) -> PathBuf {
+    let digest = hash(MessageDigest::sha256(), key.as_bytes()).map(|digest| digest.to_vec()).unwrap_or_default();
+    let name: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    dir.join(format!("{}.json", name))
+}
+
+fn parse_date(value: &str) -> Option<u64> {
+    let time = httpdate::parse_http_date(value.trim()).ok()?;
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+fn version_label(version: &str) -> &'static str {
+    match version {
+        "HTTP/0.9" => "HTTP/0.9",
+        "HTTP/1.0" => "HTTP/1.0",
+        "HTTP/2" => "HTTP/2",
+        "HTTP/3" => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+}
+
+fn serialize_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    STANDARD.decode(text).map_err(serde::de::Error::custom)
+}
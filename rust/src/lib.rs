@@ -1,34 +1,135 @@
+mod auth;
+mod body_hash;
+mod challenge;
+mod charset;
+mod circuit_breaker;
 mod client;
+mod compress;
+mod concurrency;
+mod conditional;
+mod cookie_jar;
+mod curl;
+mod decoding;
+mod dns;
+mod emulation;
+mod error;
+mod events;
+mod extract;
+mod fingerprint;
 mod generated_profiles;
+mod grpc_web;
+mod har;
+mod header_order;
+mod hooks;
+mod http_cache;
+mod metrics;
+mod ndjson;
+mod options;
+mod parse;
+mod pool;
+mod preview;
+mod proxy_check;
+mod proxy_pool;
+mod proxy_routes;
+mod query;
+mod ranges;
+mod rate_limit;
+mod refresh;
+mod retry;
+mod robots;
+mod runtime;
+mod session;
+mod sigv4;
+mod single_flight;
+mod sitemap;
+mod stream;
+mod telemetry;
+mod timings;
+mod tls;
+mod trailers;
 mod websocket;
 
+use auth::{AuthConfig, AuthScheme};
+use body_hash::HashAlgorithm;
+use challenge::ChallengeInfo;
+use circuit_breaker::CircuitBreakerConfig;
+use compress::BodyEncoding;
 use client::{
-    clear_managed_session, create_managed_session, drop_managed_session, generate_session_id, make_request,
-    RequestOptions, Response, HTTP_RUNTIME,
+    generate_request_id, make_download, make_request, make_request_stream, DownloadPlan, DownloadProgress,
+    FileSource, MultipartField, MultipartValue, ProxyAuth, RedirectPolicy, RequestOptions, Response, ResponseCookie,
 };
+use concurrency::ConcurrencyConfig;
+use cookie_jar::{CookiePolicy, StoredCookie};
+use dns::{
+    parse_nameserver, parse_override_addr, DnsConfig, DohProvider, IpStrategy, ResolverSettings, Upstream,
+};
+use encoding_rs::Encoding;
+use error::{throw_classified, throw_request_failure, throw_with_kind, ErrorKind};
+use extract::ExtractRule;
+use fingerprint::{Http2Fingerprint, StreamPriority, TlsFingerprint};
 use futures_util::StreamExt;
-use indexmap::IndexMap;
+use har::HarConfig;
+use header_order::HeaderPosition;
+use hooks::SendHook;
+use http_cache::{CacheMode, CacheStore};
+use parse::BodyParser;
+use pool::PoolConfig;
+use proxy_check::{ProxyCheck, ProxyCheckConfig};
+use proxy_pool::{ProxyPoolConfig, ProxyStrategy};
+use proxy_routes::{HostPattern, Route, RouteTarget};
+use query::{ArrayFormat, QueryValue};
+use rate_limit::{RateLimitConfig, RateLimitScope};
+use regex::RegexBuilder;
 use neon::prelude::*;
 use neon::types::{
     buffer::TypedArray, JsArray, JsBoolean, JsNull, JsObject, JsString, JsUndefined, JsValue,
 };
+use options::{ConnectionInput, RequestInput};
+use retry::RetryPolicy;
+use runtime::RuntimeConfig;
+use session::{
+    clear_managed_cache, clear_managed_session, configure_managed_cache, create_managed_session, drop_managed_session,
+    generate_session_id, managed_cache_stats, managed_cookie_jar, SessionConfig, SESSION_MANAGER,
+};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use sigv4::SigV4Config;
+use sitemap::SitemapEntry;
+use stream::{read_chunk, read_json_lines, read_sitemap_entries, remove_stream, take_hash, take_trailers};
+use telemetry::TracingConfig;
+use tls::{parse_pin, CaSource, ClientCert, PeerCertificate, TlsConfig};
 use tokio::sync::{mpsc, Semaphore};
 use websocket::{
     connect_websocket, get_connection, remove_connection, store_connection, WebSocketOptions,
     WS_RUNTIME,
 };
 use wreq::ws::message::Message;
-use wreq_util::Emulation;
+use wreq_util::{Emulation, EmulationOS};
 
 const WS_EVENT_BUFFER: usize = 64;
 
-// Parse browser string to Emulation enum using serde
-fn parse_emulation(browser: &str) -> Emulation {
-    // Use serde to deserialize the string into the enum
-    // If deserialization fails, default to Chrome142
-    serde_json::from_value(serde_json::Value::String(browser.to_string()))
-        .unwrap_or(Emulation::Chrome142)
+// Parse browser string to Emulation enum, throwing with the closest profile names when it is unknown
+fn parse_emulation<'a, C: Context<'a>>(cx: &mut C, browser: &str) -> NeonResult<Emulation> {
+    match emulation::parse(browser) {
+        Ok(emulation) => Ok(emulation),
+        Err(e) => cx.throw_type_error(e.to_string()),
+    }
+}
+
+// Parse the optional `emulationOs` option of a request, session or WebSocket
+fn parse_emulation_os(cx: &mut FunctionContext, name: Option<String>) -> NeonResult<Option<EmulationOS>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    match emulation::parse_os(&name) {
+        Some(os) => Ok(Some(os)),
+        None => cx.throw_type_error(format!(
+            "Unknown emulationOs '{}', expected windows, macos, linux, android or ios",
+            name
+        )),
+    }
 }
 
 fn coerce_header_value(cx: &mut FunctionContext, value: Handle<JsValue>) -> NeonResult<String> {
@@ -53,22 +154,21 @@ fn parse_header_tuple(cx: &mut FunctionContext, tuple: Handle<JsArray>) -> NeonR
     Ok((name, value))
 }
 
-fn parse_headers_from_array(cx: &mut FunctionContext, array: Handle<JsArray>) -> NeonResult<IndexMap<String, String>> {
-    let mut headers = IndexMap::new();
+fn parse_headers_from_array(cx: &mut FunctionContext, array: Handle<JsArray>) -> NeonResult<Vec<(String, String)>> {
+    let mut headers = Vec::with_capacity(array.len(cx) as usize);
     let len = array.len(cx);
 
     for i in 0..len {
         let element: Handle<JsValue> = array.get(cx, i)?;
         let tuple = element.downcast::<JsArray, _>(cx).or_throw(cx)?;
-        let (name, value) = parse_header_tuple(cx, tuple)?;
-        headers.insert(name, value);
+        headers.push(parse_header_tuple(cx, tuple)?);
     }
 
     Ok(headers)
 }
 
-fn parse_headers_from_object(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<IndexMap<String, String>> {
-    let mut headers = IndexMap::new();
+fn parse_headers_from_object(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<Vec<(String, String)>> {
+    let mut headers = Vec::new();
     let keys = obj.get_own_property_names(cx)?;
     let keys_vec = keys.to_vec(cx)?;
 
@@ -77,16 +177,16 @@ fn parse_headers_from_object(cx: &mut FunctionContext, obj: Handle<JsObject>) ->
             let key = key_str.value(cx);
             let value = obj.get(cx, key.as_str())?;
             let value = coerce_header_value(cx, value)?;
-            headers.insert(key, value);
+            headers.push((key, value));
         }
     }
 
     Ok(headers)
 }
 
-fn parse_headers_from_value(cx: &mut FunctionContext, value: Handle<JsValue>) -> NeonResult<IndexMap<String, String>> {
+fn parse_headers_from_value(cx: &mut FunctionContext, value: Handle<JsValue>) -> NeonResult<Vec<(String, String)>> {
     if value.is_a::<JsUndefined, _>(cx) || value.is_a::<JsNull, _>(cx) {
-        return Ok(IndexMap::new());
+        return Ok(Vec::new());
     }
 
     if value.is_a::<JsArray, _>(cx) {
@@ -102,226 +202,2834 @@ fn parse_headers_from_value(cx: &mut FunctionContext, value: Handle<JsValue>) ->
     cx.throw_type_error("headers must be an array or object")
 }
 
-// Convert JS object to RequestOptions
-fn js_object_to_request_options(
+fn parse_body_value(cx: &mut FunctionContext, value: Handle<JsValue>) -> NeonResult<Option<Vec<u8>>> {
+    if value.is_a::<JsUndefined, _>(cx) || value.is_a::<JsNull, _>(cx) {
+        return Ok(None);
+    }
+
+    if let Ok(text) = value.downcast::<JsString, _>(cx) {
+        return Ok(Some(text.value(cx).into_bytes()));
+    }
+
+    if let Ok(buffer) = value.downcast::<JsBuffer, _>(cx) {
+        return Ok(Some(buffer.as_slice(cx).to_vec()));
+    }
+
+    cx.throw_type_error("body must be a string or Buffer")
+}
+
+// Parse the client certificate and trust options shared by requests and sessions
+fn parse_tls_config(cx: &mut FunctionContext, input: &ConnectionInput) -> NeonResult<TlsConfig> {
+    let client_cert = parse_client_cert(cx, input.client_cert)?;
+
+    let mut ca = Vec::new();
+    if let Some(value) = input.ca {
+        ca.push(CaSource::Pem(pem_or_der_value(cx, value, "ca")?));
+    }
+    if let Some(path) = &input.ca_file {
+        ca.push(CaSource::File(path.clone()));
+    }
+
+    let danger_accept_invalid_certs = input.danger_accept_invalid_certs.unwrap_or(false);
+
+    let fingerprint = parse_tls_fingerprint(cx, input.tls_fingerprint)?;
+
+    Ok(TlsConfig {
+        client_cert,
+        ca,
+        danger_accept_invalid_certs,
+        fingerprint,
+    })
+}
+
+// `tlsFingerprint: { ja3? | ja4?, ciphers?, extensions?, curves?, sigalgs?, alpn?, grease? }`;
+// explicit lists replace the matching part of the JA3/JA4 string
+fn parse_tls_fingerprint(
     cx: &mut FunctionContext,
-    obj: Handle<JsObject>,
-) -> NeonResult<RequestOptions> {
-    // Get URL (required)
-    let url: Handle<JsString> = obj.get(cx, "url")?;
-    let url = url.value(cx);
+    fp_obj: Option<Handle<JsObject>>,
+) -> NeonResult<Option<TlsFingerprint>> {
+    let Some(fp_obj) = fp_obj else {
+        return Ok(None);
+    };
 
-    // Get browser (optional, defaults to chrome_142)
-    let browser_str = obj
-        .get_opt(cx, "browser")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or_else(|| "chrome_142".to_string());
+    let parsed = match (optional_string(cx, fp_obj, "ja3")?, optional_string(cx, fp_obj, "ja4")?) {
+        (Some(_), Some(_)) => return cx.throw_type_error("tlsFingerprint takes ja3 or ja4, not both"),
+        (Some(ja3), None) => TlsFingerprint::from_ja3(&ja3),
+        (None, Some(ja4)) => TlsFingerprint::from_ja4(&ja4),
+        (None, None) => Ok(TlsFingerprint::default()),
+    };
+    let mut fingerprint = parsed.or_else(|e| cx.throw_type_error(format!("{:#}", e)))?;
 
-    let emulation = parse_emulation(&browser_str);
+    let mut overrides = Vec::new();
+    if let Some(ids) = parse_code_points(cx, fp_obj, "ciphers", TlsFingerprint::cipher_id)? {
+        overrides.push(fingerprint.set_ciphers(&ids));
+    }
+    if let Some(ids) = parse_code_points(cx, fp_obj, "curves", TlsFingerprint::curve_id)? {
+        overrides.push(fingerprint.set_curves(&ids));
+    }
+    if let Some(ids) = parse_code_points(cx, fp_obj, "sigalgs", TlsFingerprint::sigalg_id)? {
+        overrides.push(fingerprint.set_sigalgs(&ids));
+    }
+    if let Some(ids) = parse_code_points(cx, fp_obj, "extensions", |name| {
+        anyhow::bail!("Extensions are given by number, not '{}'", name)
+    })? {
+        overrides.push(fingerprint.set_extensions(&ids));
+    }
+    if let Some(alpn) = fp_obj.get_opt::<JsArray, _, _>(cx, "alpn")? {
+        let protocols = alpn
+            .to_vec(cx)?
+            .into_iter()
+            .map(|value| value.downcast::<JsString, _>(cx).map(|v| v.value(cx)))
+            .collect::<Result<Vec<_>, _>>()
+            .or_else(|_| cx.throw_type_error("tlsFingerprint.alpn must be an array of strings"))?;
+        overrides.push(fingerprint.set_alpn(&protocols));
+    }
+    if let Err(e) = overrides.into_iter().collect::<anyhow::Result<()>>() {
+        return cx.throw_type_error(format!("{:#}", e));
+    }
+    if let Some(grease) = optional_bool(cx, fp_obj, "grease")? {
+        fingerprint.grease = Some(grease);
+    }
 
-    // Get method (optional, defaults to GET)
-    let method = obj
-        .get_opt(cx, "method")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or_else(|| "GET".to_string());
+    Ok(Some(fingerprint))
+}
 
-    // Get headers (optional)
-    let headers = if let Ok(Some(headers_val)) = obj.get_opt(cx, "headers") {
-        parse_headers_from_value(cx, headers_val)?
-    } else {
-        IndexMap::new()
+// `http2Fingerprint: { akamai?, headerTableSize?, enablePush?, maxConcurrentStreams?, initialWindowSize?,
+// maxFrameSize?, maxHeaderListSize?, settingsOrder?, windowUpdate?, pseudoHeaderOrder?, headersPriority? }`;
+// explicit fields replace the matching part of the Akamai string
+fn parse_http2_fingerprint(
+    cx: &mut FunctionContext,
+    h2_obj: Option<Handle<JsObject>>,
+) -> NeonResult<Option<Http2Fingerprint>> {
+    let Some(h2_obj) = h2_obj else {
+        return Ok(None);
     };
 
-    // Get body (optional)
-    let body = obj
-        .get_opt(cx, "body")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx));
+    let mut fingerprint = match optional_string(cx, h2_obj, "akamai")? {
+        Some(akamai) => Http2Fingerprint::from_akamai(&akamai).or_else(|e| cx.throw_type_error(format!("{:#}", e)))?,
+        None => Http2Fingerprint::default(),
+    };
 
-    // Get proxy (optional)
-    let proxy = obj
-        .get_opt(cx, "proxy")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx));
+    let u32_field = |cx: &mut FunctionContext, key: &str| -> NeonResult<Option<u32>> {
+        match optional_number(cx, h2_obj, key)? {
+            Some(n) if n.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&n) => {
+                cx.throw_type_error(format!("http2Fingerprint.{} must be a 32-bit unsigned integer", key))
+            }
+            value => Ok(value.map(|n| n as u32)),
+        }
+    };
 
-    // Get timeout (optional, defaults to 30000ms)
-    let timeout = obj
-        .get_opt(cx, "timeout")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
-        .map(|v| v.value(cx) as u64)
-        .unwrap_or(30000);
+    let mut overrides = Vec::new();
+    for (id, key) in [
+        (1, "headerTableSize"),
+        (3, "maxConcurrentStreams"),
+        (4, "initialWindowSize"),
+        (5, "maxFrameSize"),
+        (6, "maxHeaderListSize"),
+    ] {
+        if let Some(value) = u32_field(cx, key)? {
+            overrides.push(fingerprint.set_setting(id, value));
+        }
+    }
+    if let Some(enable_push) = optional_bool(cx, h2_obj, "enablePush")? {
+        overrides.push(fingerprint.set_setting(2, u32::from(enable_push)));
+    }
+    if let Some(array) = h2_obj.get_opt::<JsArray, _, _>(cx, "settingsOrder")? {
+        let mut ids = Vec::new();
+        for value in array.to_vec(cx)? {
+            let id = value.downcast_or_throw::<JsNumber, _>(cx)?.value(cx);
+            if id.fract() != 0.0 || !(0.0..=65535.0).contains(&id) {
+                return cx.throw_type_error("http2Fingerprint.settingsOrder must contain setting ids");
+            }
+            ids.push(id as u16);
+        }
+        overrides.push(fingerprint.set_settings_order(&ids));
+    }
+    if let Some(array) = h2_obj.get_opt::<JsArray, _, _>(cx, "pseudoHeaderOrder")? {
+        let mut names = Vec::new();
+        for value in array.to_vec(cx)? {
+            names.push(value.downcast_or_throw::<JsString, _>(cx)?.value(cx));
+        }
+        overrides.push(fingerprint.set_pseudo_order(&names));
+    }
+    if let Some(increment) = u32_field(cx, "windowUpdate")? {
+        fingerprint.window_update = Some(increment);
+    }
+    if let Some(priority_obj) = h2_obj.get_opt::<JsObject, _, _>(cx, "headersPriority")? {
+        let weight = optional_number(cx, priority_obj, "weight")?.unwrap_or(16.0);
+        let depends_on = optional_number(cx, priority_obj, "dependsOn")?.unwrap_or(0.0);
+        let exclusive = optional_bool(cx, priority_obj, "exclusive")?.unwrap_or(false);
+        if weight.fract() != 0.0 || depends_on.fract() != 0.0 || weight < 0.0 || depends_on < 0.0 {
+            return cx.throw_type_error("http2Fingerprint.headersPriority weight and dependsOn must be integers");
+        }
+        let depends_on = depends_on.min(u32::MAX as f64) as u32;
+        match StreamPriority::new(exclusive, depends_on, weight.min(65535.0) as u16) {
+            Ok(priority) => fingerprint.headers_priority = Some(priority),
+            Err(e) => overrides.push(Err(e)),
+        }
+    }
+    if let Err(e) = overrides.into_iter().collect::<anyhow::Result<()>>() {
+        return cx.throw_type_error(format!("{:#}", e));
+    }
 
-    // Get sessionId (optional)
-    let session_id = obj
-        .get_opt(cx, "sessionId")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .filter(|v| !v.trim().is_empty())
-        .unwrap_or_else(generate_session_id);
+    Ok(Some(fingerprint))
+}
 
-    let ephemeral = obj
-        .get_opt(cx, "ephemeral")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or(false);
+// A list of TLS code points, each given as a number or, where `by_name` knows it, a name
+fn parse_code_points(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+    key: &str,
+    by_name: fn(&str) -> anyhow::Result<u16>,
+) -> NeonResult<Option<Vec<u16>>> {
+    let Some(array) = obj.get_opt::<JsArray, _, _>(cx, key)? else {
+        return Ok(None);
+    };
 
-    let disable_default_headers = obj
-        .get_opt(cx, "disableDefaultHeaders")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or(false);
+    let mut ids = Vec::new();
+    for value in array.to_vec(cx)? {
+        if let Ok(number) = value.downcast::<JsNumber, _>(cx) {
+            let number = number.value(cx);
+            if number.fract() != 0.0 || !(0.0..=65535.0).contains(&number) {
+                return cx.throw_type_error(format!("tlsFingerprint.{} values must be 16-bit integers", key));
+            }
+            ids.push(number as u16);
+        } else if let Ok(name) = value.downcast::<JsString, _>(cx) {
+            let name = name.value(cx);
+            ids.push(by_name(&name).or_else(|e| cx.throw_type_error(format!("{:#}", e)))?);
+        } else {
+            return cx.throw_type_error(format!("tlsFingerprint.{} must contain numbers or names", key));
+        }
+    }
+    Ok(Some(ids))
+}
 
-    Ok(RequestOptions {
-        url,
-        emulation,
-        headers,
-        method,
-        body,
-        proxy,
-        timeout,
-        session_id,
-        ephemeral,
-        disable_default_headers,
+// Parse `pool: { maxIdlePerHost?, idleTimeout?, tcpKeepAlive?, tcpKeepAliveInterval?, ... }`, durations in ms
+fn parse_pool_config(cx: &mut FunctionContext, pool_obj: Option<Handle<JsObject>>) -> NeonResult<PoolConfig> {
+    let Some(pool_obj) = pool_obj else {
+        return Ok(PoolConfig::default());
+    };
+
+    let millis = |value: f64| Duration::from_millis(value.max(0.0) as u64);
+
+    // `happyEyeballs` is a delay in milliseconds, or false to turn racing off
+    let happy_eyeballs = match optional_bool(cx, pool_obj, "happyEyeballs")? {
+        Some(false) => Some(None),
+        _ => optional_number(cx, pool_obj, "happyEyeballs")?.map(|delay| Some(millis(delay))),
+    };
+
+    Ok(PoolConfig {
+        max_idle_per_host: optional_number(cx, pool_obj, "maxIdlePerHost")?.map(|max| max.max(0.0) as usize),
+        idle_timeout: optional_number(cx, pool_obj, "idleTimeout")?.map(millis),
+        tcp_keepalive: optional_number(cx, pool_obj, "tcpKeepAlive")?.map(millis),
+        tcp_keepalive_interval: optional_number(cx, pool_obj, "tcpKeepAliveInterval")?.map(millis),
+        tcp_keepalive_retries: optional_number(cx, pool_obj, "tcpKeepAliveRetries")?.map(|count| count.max(0.0) as u32),
+        tcp_nodelay: optional_bool(cx, pool_obj, "tcpNoDelay")?,
+        send_buffer_size: optional_number(cx, pool_obj, "sendBufferSize")?.map(|size| size.max(1.0) as usize),
+        recv_buffer_size: optional_number(cx, pool_obj, "receiveBufferSize")?.map(|size| size.max(1.0) as usize),
+        happy_eyeballs,
+        fallback_timeout: optional_number(cx, pool_obj, "fallbackTimeout")?.map(millis),
     })
 }
 
-// Convert Response to JS object
-fn response_to_js_object<'a, C: Context<'a>>(
-    cx: &mut C,
-    response: Response,
-) -> JsResult<'a, JsObject> {
-    let obj = cx.empty_object();
+// `auth` is `{ basic: { user, pass? } }`, `{ bearer }`, or `{ type, username, password?, domain? }`
+// for Digest and NTLM. The first two are sent up front, replacing any Authorization header;
+// the others wait for the server's challenge.
+fn parse_auth(
+    cx: &mut FunctionContext,
+    auth_obj: Option<Handle<JsObject>>,
+    headers: &mut Vec<(String, String)>,
+) -> NeonResult<Option<AuthConfig>> {
+    let Some(auth_obj) = auth_obj else {
+        return Ok(None);
+    };
 
-    // Status
-    let status = cx.number(response.status as f64);
-    obj.set(cx, "status", status)?;
+    let authorization = if let Some(basic) = auth_obj.get_opt::<JsObject, _, _>(cx, "basic")? {
+        let user: Handle<JsString> = basic.get(cx, "user")?;
+        let user = user.value(cx);
+        let pass = optional_string(cx, basic, "pass")?.unwrap_or_default();
+        // RFC 7617: the user-id ends at the first colon, so one inside it can't be sent
+        if user.contains(':') {
+            return cx.throw_type_error("Basic auth user cannot contain ':'");
+        }
+        auth::basic_authorization(&user, &pass)
+    } else if let Some(token) = optional_string(cx, auth_obj, "bearer")? {
+        if !auth::is_token68(&token) {
+            return cx.throw_type_error("Bearer token contains characters not allowed in a header");
+        }
+        format!("Bearer {}", token)
+    } else {
+        let kind: Handle<JsString> = auth_obj.get(cx, "type")?;
+        let kind = kind.value(cx);
+        let Some(scheme) = AuthScheme::parse(&kind) else {
+            return cx.throw_type_error(format!("Unknown auth type '{}'", kind));
+        };
+        let username: Handle<JsString> = auth_obj.get(cx, "username")?;
+        let password = optional_string(cx, auth_obj, "password")?;
+        let domain = optional_string(cx, auth_obj, "domain")?;
+        return Ok(Some(AuthConfig {
+            scheme,
+            username: username.value(cx),
+            password: password.unwrap_or_default(),
+            domain,
+        }));
+    };
 
-    // URL
-    let url = cx.string(&response.url);
-    obj.set(cx, "url", url)?;
+    headers.retain(|(key, _)| !key.eq_ignore_ascii_case("authorization"));
+    headers.push(("Authorization".to_string(), authorization));
+    Ok(None)
+}
 
-    // Headers
-    let headers_obj = cx.empty_object();
-    for (key, value) in response.headers {
-        let value_str = cx.string(&value);
-        headers_obj.set(cx, key.as_str(), value_str)?;
-    }
-    obj.set(cx, "headers", headers_obj)?;
+// Parse `resolve: { host: address | address[] }` into fixed resolver overrides,
+// `dns: { servers?, doh?, cacheSize?, ... }` into the built-in resolver's settings,
+// and `ipStrategy` into the address family filter
+fn parse_dns_config(cx: &mut FunctionContext, input: &ConnectionInput) -> NeonResult<DnsConfig> {
+    let ip_strategy = match &input.ip_strategy {
+        Some(value) => match IpStrategy::parse(value) {
+            Some(strategy) => Some(strategy),
+            None => return cx.throw_type_error(format!("Unknown ipStrategy '{}'", value)),
+        },
+        None => None,
+    };
+    let mut config = DnsConfig {
+        resolver: parse_resolver_settings(cx, input.dns)?,
+        ip_strategy,
+        ..DnsConfig::default()
+    };
+    let Some(resolve_obj) = input.resolve else {
+        return Ok(config);
+    };
 
-    // Cookies
-    let cookies_obj = cx.empty_object();
-    for (key, value) in response.cookies {
-        let value_str = cx.string(&value);
-        cookies_obj.set(cx, key.as_str(), value_str)?;
+    for key in resolve_obj.get_own_property_names(cx)?.to_vec(cx)? {
+        let host = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+        let value: Handle<JsValue> = resolve_obj.get(cx, host.as_str())?;
+        let values = match value.downcast::<JsArray, _>(cx) {
+            Ok(array) => array.to_vec(cx)?,
+            Err(_) => vec![value],
+        };
+
+        let mut addrs = Vec::with_capacity(values.len());
+        for value in values {
+            let text = value.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            match parse_override_addr(&text) {
+                Some(addr) => addrs.push(addr),
+                None => {
+                    return cx.throw_type_error(format!("Invalid address '{}' for host '{}' in resolve", text, host))
+                }
+            }
+        }
+        if addrs.is_empty() {
+            return cx.throw_type_error(format!("No addresses given for host '{}' in resolve", host));
+        }
+
+        config.overrides.insert(host.to_ascii_lowercase(), addrs);
     }
-    obj.set(cx, "cookies", cookies_obj)?;
 
-    // Body
-    let body = cx.string(&response.body);
-    obj.set(cx, "body", body)?;
+    Ok(config)
+}
 
-    Ok(obj)
+fn parse_resolver_settings(
+    cx: &mut FunctionContext,
+    dns_obj: Option<Handle<JsObject>>,
+) -> NeonResult<Option<ResolverSettings>> {
+    let Some(dns_obj) = dns_obj else {
+        return Ok(None);
+    };
+
+    let servers = match dns_obj.get_opt::<JsArray, _, _>(cx, "servers")? {
+        Some(array) => {
+            let mut servers = Vec::new();
+            for value in array.to_vec(cx)? {
+                let text = value.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+                match parse_nameserver(&text) {
+                    Some(addr) => servers.push(addr),
+                    None => return cx.throw_type_error(format!("Invalid nameserver '{}' in dns.servers", text)),
+                }
+            }
+            Some(servers)
+        }
+        None => None,
+    };
+    let doh = match dns_obj.get_opt::<JsString, _, _>(cx, "doh")? {
+        Some(value) => {
+            let name = value.value(cx);
+            match DohProvider::parse(&name) {
+                Some(provider) => Some(provider),
+                None => return cx.throw_type_error(format!("Unknown DNS-over-HTTPS provider '{}'", name)),
+            }
+        }
+        None => None,
+    };
+
+    let upstream = match (servers, doh) {
+        (Some(_), Some(_)) => return cx.throw_type_error("dns.servers and dns.doh cannot be combined"),
+        (Some(servers), None) if !servers.is_empty() => Upstream::Servers(servers),
+        (_, Some(provider)) => Upstream::Doh(provider),
+        _ => Upstream::System,
+    };
+
+    let millis = |value: f64| Duration::from_millis(value.max(0.0) as u64);
+
+    Ok(Some(ResolverSettings {
+        upstream,
+        cache_size: optional_number(cx, dns_obj, "cacheSize")?.map(|size| size.max(0.0) as u64),
+        min_ttl: optional_number(cx, dns_obj, "minTtl")?.map(millis),
+        max_ttl: optional_number(cx, dns_obj, "maxTtl")?.map(millis),
+        negative_ttl: optional_number(cx, dns_obj, "negativeTtl")?.map(millis),
+    }))
 }
 
-// Main request function exported to Node.js
-fn request(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    // Get the options object
-    let options_obj = cx.argument::<JsObject>(0)?;
+// Parse `{ cert, key?, passphrase? }`: a PEM chain with its key, or a PKCS#12 archive
+fn parse_client_cert(cx: &mut FunctionContext, cert_obj: Option<Handle<JsObject>>) -> NeonResult<Option<ClientCert>> {
+    let Some(cert_obj) = cert_obj else {
+        return Ok(None);
+    };
 
-    // Convert JS object to Rust struct
-    let options = js_object_to_request_options(&mut cx, options_obj)?;
+    let cert = match cert_obj.get_opt::<JsValue, _, _>(cx, "cert")? {
+        Some(value) => pem_or_der_value(cx, value, "clientCert.cert")?,
+        None => return cx.throw_type_error("clientCert.cert is required"),
+    };
+    let key = match cert_obj.get_opt::<JsValue, _, _>(cx, "key")? {
+        Some(value) => Some(pem_or_der_value(cx, value, "clientCert.key")?),
+        None => None,
+    };
+    let passphrase = optional_string(cx, cert_obj, "passphrase")?;
+
+    match key {
+        Some(key) => Ok(Some(ClientCert::Pem { cert, key })),
+        None if cert.starts_with(b"-----BEGIN") => cx.throw_type_error("clientCert.key is required for a PEM certificate"),
+        None => Ok(Some(ClientCert::Pkcs12 {
+            archive: cert,
+            passphrase: passphrase.unwrap_or_default(),
+        })),
+    }
+}
 
-    // Create a promise
-    let (deferred, promise) = cx.promise();
-    let settle_channel = cx.channel();
+fn pem_or_der_value(cx: &mut FunctionContext, value: Handle<JsValue>, name: &str) -> NeonResult<Vec<u8>> {
+    if let Ok(text) = value.downcast::<JsString, _>(cx) {
+        return Ok(text.value(cx).into_bytes());
+    }
 
-    HTTP_RUNTIME.spawn(async move {
-        let result = make_request(options).await;
+    if let Ok(buffer) = value.downcast::<JsBuffer, _>(cx) {
+        return Ok(buffer.as_slice(cx).to_vec());
+    }
 
-        // Send result back to JS
-        deferred.settle_with(&settle_channel, move |mut cx| match result {
-            Ok(response) => response_to_js_object(&mut cx, response),
-            Err(e) => {
-                // Format error with full chain for better debugging
-                let error_msg = format!("{:#}", e);
-                cx.throw_error(error_msg)
+    cx.throw_type_error(format!("{} must be a string or Buffer", name))
+}
+
+// Query values are strings or arrays of strings; the JS side stringifies numbers and booleans
+fn parse_query(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<Vec<(String, QueryValue)>> {
+    let mut params = Vec::new();
+
+    for key in obj.get_own_property_names(cx)?.to_vec(cx)? {
+        let name = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+        let value: Handle<JsValue> = obj.get(cx, name.as_str())?;
+        let value = match value.downcast::<JsArray, _>(cx) {
+            Ok(array) => {
+                let mut values = Vec::new();
+                for item in array.to_vec(cx)? {
+                    values.push(item.downcast_or_throw::<JsString, _>(cx)?.value(cx));
+                }
+                QueryValue::List(values)
             }
-        });
-    });
+            Err(_) => QueryValue::Single(value.downcast_or_throw::<JsString, _>(cx)?.value(cx)),
+        };
+        params.push((name, value));
+    }
 
-    Ok(promise)
+    Ok(params)
 }
 
-// Get list of available browser profiles
-fn get_profiles(mut cx: FunctionContext) -> JsResult<JsArray> {
-    let js_array = cx.empty_array();
-
-    for (i, profile) in generated_profiles::BROWSER_PROFILES.iter().enumerate() {
-        let js_string = cx.string(*profile);
-        js_array.set(&mut cx, i as u32, js_string)?;
+// Parse `headerPositions: { [name]: { before: anchor } | { after: anchor } }`
+fn parse_header_positions(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<Vec<(String, HeaderPosition)>> {
+    let mut positions = Vec::new();
+
+    for key in obj.get_own_property_names(cx)?.to_vec(cx)? {
+        let name = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+        let placement = obj.get::<JsObject, _, _>(cx, name.as_str())?;
+        let position = match (optional_string(cx, placement, "before")?, optional_string(cx, placement, "after")?) {
+            (Some(anchor), None) => HeaderPosition::Before(anchor),
+            (None, Some(anchor)) => HeaderPosition::After(anchor),
+            _ => {
+                let message = format!("Header position for '{}' needs exactly one of before or after", name);
+                return cx.throw_type_error(message);
+            }
+        };
+        positions.push((name, position));
     }
 
-    Ok(js_array)
+    Ok(positions)
 }
 
-fn create_session(mut cx: FunctionContext) -> JsResult<JsString> {
-    let options_value = cx.argument_opt(0);
+// Convert JS object to RequestOptions
+fn js_object_to_request_options(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<RequestOptions> {
+    let input = RequestInput::read(cx, obj)?;
+
+    // Get URL (required), with query parameters (optional) appended
+    let mut url = input.url;
+    if let Some(query_obj) = input.query {
+        let format = match input.query_array_format {
+            Some(value) => match ArrayFormat::parse(&value) {
+                Some(format) => format,
+                None => return cx.throw_type_error(format!("Unknown queryArrayFormat '{}'", value)),
+            },
+            None => ArrayFormat::default(),
+        };
+        let params = parse_query(cx, query_obj)?;
+        url = query::append_to_url(&url, &params, format);
+    }
 
-    let (session_id_opt, browser_opt, proxy_opt) = if let Some(value) = options_value {
-        if value.is_a::<JsUndefined, _>(&mut cx) || value.is_a::<JsNull, _>(&mut cx) {
-            (None, None, None)
-        } else {
-            let obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
-            let session_id = obj
-                .get_opt(&mut cx, "sessionId")?
-                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
-                .map(|v| v.value(&mut cx));
-            let browser = obj
-                .get_opt(&mut cx, "browser")?
-                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
-                .map(|v| v.value(&mut cx));
-            let proxy = obj
-                .get_opt(&mut cx, "proxy")?
-                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
-                .map(|v| v.value(&mut cx));
-            (session_id, browser, proxy)
+    // Get browser (optional, defaults to chrome_142)
+    let browser_str = input.browser.unwrap_or_else(|| "chrome_142".to_string());
+
+    let emulation = parse_emulation(cx, &browser_str)?;
+    let emulation_os = parse_emulation_os(cx, input.connection.emulation_os.clone())?;
+
+    // Get method (optional, defaults to GET)
+    let method = input.method.unwrap_or_else(|| "GET".to_string());
+
+    // Get headers (optional)
+    let mut headers = match input.headers {
+        Some(headers_val) => parse_headers_from_value(cx, headers_val)?,
+        None => Vec::new(),
+    };
+
+    // Get names of emulation headers to leave out (optional, lowercased)
+    let mut removed_headers = Vec::new();
+    if let Some(names) = input.remove_headers {
+        for name in names.to_vec(cx)? {
+            let name = name.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            removed_headers.push(name.to_ascii_lowercase());
         }
-    } else {
-        (None, None, None)
+    }
+
+    // Get header positions relative to the emulation's order (optional)
+    let header_positions = match input.header_positions {
+        Some(positions_obj) => parse_header_positions(cx, positions_obj)?,
+        None => Vec::new(),
     };
 
-    let session_id = session_id_opt.unwrap_or_else(generate_session_id);
-    let browser_str = browser_opt.unwrap_or_else(|| "chrome_142".to_string());
-    let emulation = parse_emulation(&browser_str);
+    // Get body (optional, string or Buffer)
+    let mut body = match input.body {
+        Some(value) => parse_body_value(cx, value)?,
+        None => None,
+    };
 
-    match create_managed_session(session_id.clone(), emulation, proxy_opt) {
-        Ok(id) => Ok(cx.string(id)),
-        Err(e) => {
-            let msg = format!("{:#}", e);
-            cx.throw_error(msg)
+    // Get form fields (optional), encoded into the body with a matching Content-Type
+    if let Some(form_obj) = input.form {
+        if body.is_some() {
+            return cx.throw_type_error("form cannot be combined with body");
+        }
+        let fields = parse_query(cx, form_obj)?;
+        body = Some(query::encode_form(&fields).into_bytes());
+        if !headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-type")) {
+            headers.push((
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            ));
         }
     }
-}
 
-fn clear_session(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+    // Get multipart fields (optional, array of { name, value } or file parts)
+    let multipart = match input.multipart {
+        Some(fields) => Some(parse_multipart_fields(cx, fields)?),
+        None => None,
+    };
 
-    if let Err(e) = clear_managed_session(&session_id) {
-        let msg = format!("{:#}", e);
-        return cx.throw_error(msg);
+    if body.is_some() && multipart.is_some() {
+        return cx.throw_type_error("body and multipart cannot be used together");
+    }
+
+    // Get JSON body (optional, serialized by the JS layer)
+    let json = match input.json {
+        Some(text) => match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(value) => Some(value),
+            Err(e) => return cx.throw_type_error(format!("Invalid json body: {}", e)),
+        },
+        None => None,
+    };
+
+    if json.is_some() && (body.is_some() || multipart.is_some()) {
+        return cx.throw_type_error("json cannot be combined with body or multipart");
+    }
+
+    // Get body file (optional, streamed from disk instead of buffered)
+    let body_file = input.body_file.map(PathBuf::from);
+
+    if body_file.is_some() && (body.is_some() || multipart.is_some() || json.is_some()) {
+        return cx.throw_type_error("bodyFile cannot be combined with body, multipart or json");
+    }
+
+    // Get request trailers (optional), sent after a chunked body file
+    let trailers = match input.trailers {
+        Some(trailers_val) => parse_headers_from_value(cx, trailers_val)?,
+        None => Vec::new(),
+    };
+
+    if !trailers.is_empty() && body_file.is_none() {
+        return cx.throw_type_error("trailers require bodyFile");
+    }
+
+    // Get the coding to compress the body with (optional)
+    let compress_body = match input.compress_body {
+        Some(value) => match BodyEncoding::parse(&value) {
+            Some(encoding) => Some(encoding),
+            None => {
+                return cx.throw_type_error(format!(
+                    "Unsupported compressBody '{}', expected gzip, deflate, br or zstd",
+                    value
+                ));
+            }
+        },
+        None => None,
+    };
+    if compress_body.is_some() {
+        if body_file.is_some() || multipart.is_some() {
+            return cx.throw_type_error("compressBody cannot be combined with bodyFile or multipart");
+        }
+        if headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-encoding")) {
+            return cx.throw_type_error("compressBody cannot be combined with a Content-Encoding header");
+        }
+    }
+
+    // Get proxy (optional)
+    let proxy = input.proxy;
+
+    // Get proxy credentials (optional, { username, password })
+    let proxy_auth = match input.proxy_auth {
+        Some(auth_obj) => {
+            let username: Handle<JsString> = auth_obj.get(cx, "username")?;
+            let password = optional_string(cx, auth_obj, "password")?;
+            Some(ProxyAuth {
+                username: username.value(cx),
+                password: password.unwrap_or_default(),
+            })
+        }
+        None => None,
+    };
+
+    // Get extra headers for the proxy (optional)
+    let proxy_headers = match input.proxy_headers {
+        Some(headers_val) => parse_headers_from_value(cx, headers_val)?,
+        None => Vec::new(),
+    };
+
+    // Get the proxy pool attempts draw from (optional), a handle carrying the pool's ID
+    let proxy_pool_id = match input.proxy_pool {
+        Some(handle) => Some(handle.get::<JsString, _, _>(cx, "id")?.value(cx)),
+        None => None,
+    };
+    let proxy_pool = match proxy_pool_id {
+        Some(id) => match proxy_pool::get(&id) {
+            Some(_) if proxy.is_some() => return cx.throw_type_error("proxy and proxyPool cannot be combined"),
+            Some(pool) => Some(pool),
+            None => return cx.throw_type_error(format!("Proxy pool '{}' does not exist or was closed", id)),
+        },
+        None => None,
+    };
+
+    // Get credentials (optional); Basic and Bearer become an Authorization header right away
+    let auth = parse_auth(cx, input.auth, &mut headers)?;
+
+    // Get AWS SigV4 signing settings (optional)
+    let aws_sigv4 = match input.aws_sig_v4 {
+        Some(aws_obj) => {
+            let region: Handle<JsString> = aws_obj.get(cx, "region")?;
+            let service: Handle<JsString> = aws_obj.get(cx, "service")?;
+            let access_key_id: Handle<JsString> = aws_obj.get(cx, "accessKeyId")?;
+            let secret_access_key: Handle<JsString> = aws_obj.get(cx, "secretAccessKey")?;
+            Some(SigV4Config {
+                region: region.value(cx),
+                service: service.value(cx),
+                access_key_id: access_key_id.value(cx),
+                secret_access_key: secret_access_key.value(cx),
+                session_token: optional_string(cx, aws_obj, "sessionToken")?,
+            })
+        }
+        None => None,
+    };
+
+    // Get source address and interface binding (optional)
+    let local_address = match input.local_address {
+        Some(text) => match text.trim().parse::<IpAddr>() {
+            Ok(address) => Some(address),
+            Err(_) => return cx.throw_type_error(format!("Invalid localAddress '{}'", text)),
+        },
+        None => None,
+    };
+    let interface = input.interface.filter(|name| !name.is_empty());
+    let unix_socket = input.unix_socket.filter(|path| !path.is_empty()).map(PathBuf::from);
+
+    // Get timeouts (optional); the 30000ms default per attempt is dropped when a read timeout
+    // or deadline is set, so a slow but steady body isn't cut off
+    let millis = |value: f64| Duration::from_millis(value.max(0.0) as u64);
+    let connect_timeout = input.connect_timeout.map(millis);
+    let read_timeout = input.read_timeout.map(millis);
+    let deadline = input.deadline.map(millis);
+    let timeout = match input.timeout {
+        Some(timeout) => Some(millis(timeout)),
+        None if read_timeout.is_some() || deadline.is_some() => None,
+        None => Some(Duration::from_millis(30000)),
+    };
+
+    // Get sessionId (optional)
+    let session_id = input
+        .session_id
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(generate_session_id);
+
+    let ephemeral = input.ephemeral.unwrap_or(false);
+
+    let disable_default_headers = input.disable_default_headers.unwrap_or(false);
+
+    // Get redirect policy (optional, defaults to following up to 10 redirects)
+    let redirect = match input.redirect {
+        Some(redirect_obj) => parse_redirect_policy(cx, redirect_obj)?,
+        None => RedirectPolicy::default(),
+    };
+
+    // Get forced HTTP version (optional, "1.1" or "2")
+    let http_version = match input.http_version.as_deref() {
+        None => None,
+        Some("1.1") => Some(wreq::Version::HTTP_11),
+        Some("2") => Some(wreq::Version::HTTP_2),
+        Some(other) => {
+            return cx.throw_type_error(format!("Unsupported httpVersion '{}', expected \"1.1\" or \"2\"", other))
+        }
+    };
+
+    // Get retry policy (optional, defaults to a single attempt)
+    let retry = match input.retry {
+        Some(retry_obj) => parse_retry_policy(cx, retry_obj)?,
+        None => RetryPolicy::default(),
+    };
+
+    // Get response body size limit (optional, unlimited by default)
+    let max_body_bytes = input.max_body_bytes.map(|limit| limit.max(0.0) as u64);
+
+    // Get decompression controls (optional, decompress by default)
+    let decompress = input.decompress.unwrap_or(true);
+    let accept_encoding = input.accept_encoding;
+
+    // Get the charset to decode the body text with (optional, detected by default)
+    let charset = match input.charset {
+        Some(label) => Some(parse_charset(cx, &label)?),
+        None => None,
+    };
+
+    // Get client certificate and extra trust roots (optional)
+    let tls = parse_tls_config(cx, &input.connection)?;
+
+    // Get HTTP/2 settings layered over the emulation's (optional)
+    let http2 = parse_http2_fingerprint(cx, input.connection.http2_fingerprint)?;
+
+    // Get connection pool settings (optional)
+    let pool = parse_pool_config(cx, input.connection.pool)?;
+
+    // Get hostname overrides, resolver and address family settings (optional)
+    let dns = parse_dns_config(cx, &input.connection)?;
+
+    // Get the session's cookie file (optional)
+    let cookie_store_path = input.connection.cookie_store_path.map(PathBuf::from);
+
+    // Get certificate pins (optional)
+    let mut pinned_sha256 = Vec::new();
+    if let Some(pins) = input.pinned_sha256 {
+        for pin in pins.to_vec(cx)? {
+            let pin = pin.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            match parse_pin(&pin) {
+                Some(hash) => pinned_sha256.push(hash),
+                None => return cx.throw_type_error(format!("Invalid SHA-256 pin: {}", pin)),
+            }
+        }
+    }
+
+    // Get HTTP cache mode (optional, only matters once a cache is configured)
+    let cache = match input.cache {
+        Some(value) => match CacheMode::parse(&value) {
+            Some(mode) => mode,
+            None => return cx.throw_type_error(format!("Unknown cache mode '{}'", value)),
+        },
+        None => CacheMode::Default,
+    };
+
+    // Revalidate with the URL's last ETag/Last-Modified (optional)
+    let conditional = input.conditional.unwrap_or(false);
+    let follow_refresh = input.follow_refresh.unwrap_or(false);
+
+    // Whether the session's jar is used (optional); "manual" sends `cookieMap` instead
+    let cookies = match input.cookies {
+        Some(value) => match CookiePolicy::parse(&value) {
+            Some(policy) => policy,
+            None => return cx.throw_type_error(format!("Unknown cookies policy '{}'", value)),
+        },
+        None => CookiePolicy::Include,
+    };
+    if let Some(map) = input.cookie_map {
+        let mut pairs = Vec::new();
+        for key in map.get_own_property_names(cx)?.to_vec(cx)? {
+            let name = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            let value = map.get::<JsString, _, _>(cx, name.as_str())?.value(cx);
+            pairs.push(format!("{}={}", name, value));
+        }
+        // Joined onto a Cookie header the caller set, since a request carries only one
+        let existing = headers.iter_mut().find(|(key, _)| key.eq_ignore_ascii_case("cookie"));
+        match existing {
+            Some((_, value)) if !pairs.is_empty() => *value = format!("{}; {}", value, pairs.join("; ")),
+            None if !pairs.is_empty() => headers.push(("Cookie".to_string(), pairs.join("; "))),
+            _ => {}
+        }
+    }
+
+    // Get trace/correlation ID (optional)
+    let trace_id = input.trace_id.filter(|id| !id.is_empty());
+    let request_id = input
+        .request_id
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(generate_request_id);
+
+    // Report the connection's fingerprint on the response (optional)
+    let inspect_fingerprint = input.inspect_fingerprint.unwrap_or(false);
+
+    // Share the response of an identical GET already in flight (optional)
+    let dedupe = input.dedupe.unwrap_or(false);
+
+    // Parse the body in Rust and return only the result (optional)
+    let parse = match input.parse {
+        Some(value) => Some(body_parser(cx, &value)?),
+        None => None,
+    };
+
+    // Get CSS selector extraction rules (optional, { name: selector | { selector, attr?, all? } })
+    let extract = match input.extract {
+        Some(rules) => parse_extract_rules(cx, rules)?,
+        None => Vec::new(),
+    };
+
+    // Get the algorithm to hash the body with while it is read (optional)
+    let hash_body = match input.hash_body {
+        Some(value) => match HashAlgorithm::parse(&value) {
+            Some(algorithm) => Some(algorithm),
+            None => {
+                return cx.throw_type_error(format!("Unsupported hashBody '{}', expected sha256, md5 or xxh3", value));
+            }
+        },
+        None => None,
+    };
+
+    // Get how far a streamed body may be read ahead of the consumer (optional, 64 KiB by default)
+    let high_water_mark = input.high_water_mark.map(|bytes| bytes.max(1.0) as u64);
+
+    // Get the function that runs `onSend` middleware before every send (optional)
+    let before_send = match input.before_send {
+        Some(callback) => {
+            let callback = callback.root(cx);
+            Some(SendHook::new(callback, cx.channel()))
+        }
+        None => None,
+    };
+
+    Ok(RequestOptions {
+        url,
+        emulation,
+        emulation_os,
+        headers,
+        removed_headers,
+        header_positions,
+        method,
+        body,
+        multipart,
+        json,
+        body_file,
+        trailers,
+        compress_body,
+        proxy,
+        proxy_auth,
+        proxy_headers,
+        proxy_pool,
+        direct: false,
+        auth,
+        aws_sigv4,
+        local_address,
+        interface,
+        unix_socket,
+        timeout,
+        connect_timeout,
+        read_timeout,
+        deadline,
+        session_id,
+        ephemeral,
+        keep_session: false,
+        disable_default_headers,
+        redirect,
+        retry,
+        http_version,
+        max_body_bytes,
+        decompress,
+        decode_after_read: false,
+        accept_encoding,
+        charset,
+        dedupe,
+        tls,
+        http2,
+        pool,
+        dns,
+        cookie_store_path,
+        pinned_sha256,
+        cache,
+        conditional,
+        follow_refresh,
+        cookies,
+        trace_id,
+        request_id,
+        inspect_fingerprint,
+        parse,
+        extract,
+        hash_body,
+        high_water_mark,
+        before_send,
+    })
+}
+
+fn parse_extract_rules(cx: &mut FunctionContext, rules: Handle<JsObject>) -> NeonResult<Vec<ExtractRule>> {
+    let mut parsed = Vec::new();
+    for key in rules.get_own_property_names(cx)?.to_vec(cx)? {
+        let name = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+        let value: Handle<JsValue> = rules.get(cx, name.as_str())?;
+        let (selector, attr, all) = match value.downcast::<JsString, _>(cx) {
+            Ok(selector) => (selector.value(cx), None, false),
+            Err(_) => {
+                let rule = value.downcast_or_throw::<JsObject, _>(cx)?;
+                let selector = rule.get::<JsString, _, _>(cx, "selector")?.value(cx);
+                let attr = optional_string(cx, rule, "attr")?;
+                let all = optional_bool(cx, rule, "all")?.unwrap_or(false);
+                (selector, attr, all)
+            }
+        };
+        match ExtractRule::new(name.clone(), &selector, attr, all) {
+            Ok(rule) => parsed.push(rule),
+            Err(e) => return cx.throw_type_error(format!("Invalid selector for extract.{}: {}", name, e)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn body_parser(cx: &mut FunctionContext, value: &str) -> NeonResult<BodyParser> {
+    match BodyParser::parse(value) {
+        Some(parser) => Ok(parser),
+        None => cx.throw_type_error(format!("Unknown parse mode '{}', expected json, html-meta or xml", value)),
+    }
+}
+
+fn parse_charset(cx: &mut FunctionContext, label: &str) -> NeonResult<&'static Encoding> {
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) => Ok(encoding),
+        None => cx.throw_range_error(format!("Unknown charset: {}", label)),
+    }
+}
+
+fn optional_string(cx: &mut FunctionContext, obj: Handle<JsObject>, key: &str) -> NeonResult<Option<String>> {
+    Ok(obj
+        .get_opt(cx, key)?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx)))
+}
+
+fn optional_number(cx: &mut FunctionContext, obj: Handle<JsObject>, key: &str) -> NeonResult<Option<f64>> {
+    Ok(obj
+        .get_opt(cx, key)?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx)))
+}
+
+fn optional_bool(cx: &mut FunctionContext, obj: Handle<JsObject>, key: &str) -> NeonResult<Option<bool>> {
+    Ok(obj
+        .get_opt(cx, key)?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx)))
+}
+
+fn parse_multipart_fields(cx: &mut FunctionContext, array: Handle<JsArray>) -> NeonResult<Vec<MultipartField>> {
+    let mut fields = Vec::new();
+    let len = array.len(cx);
+
+    for i in 0..len {
+        let element: Handle<JsValue> = array.get(cx, i)?;
+        let field = element.downcast::<JsObject, _>(cx).or_throw(cx)?;
+
+        let name: Handle<JsString> = field.get(cx, "name")?;
+        let name = name.value(cx);
+
+        let text = optional_string(cx, field, "value")?;
+        let path = optional_string(cx, field, "path")?;
+        let data = field
+            .get_opt(cx, "data")?
+            .and_then(|v: Handle<JsValue>| v.downcast::<JsBuffer, _>(cx).ok())
+            .map(|v| v.as_slice(cx).to_vec());
+
+        let source = match (text, path, data) {
+            (Some(text), None, None) => {
+                fields.push(MultipartField { name, value: MultipartValue::Text(text) });
+                continue;
+            }
+            (None, Some(path), None) => FileSource::Path(path),
+            (None, None, Some(data)) => FileSource::Bytes(data),
+            _ => {
+                return cx.throw_type_error(format!(
+                    "Multipart field '{}' must have exactly one of value, path or data",
+                    name
+                ))
+            }
+        };
+
+        let value = MultipartValue::File {
+            source,
+            filename: optional_string(cx, field, "filename")?,
+            content_type: optional_string(cx, field, "contentType")?,
+        };
+
+        fields.push(MultipartField { name, value });
+    }
+
+    Ok(fields)
+}
+
+fn parse_redirect_policy(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<RedirectPolicy> {
+    let defaults = RedirectPolicy::default();
+
+    let follow = obj
+        .get_opt(cx, "follow")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(defaults.follow);
+
+    let max = obj
+        .get_opt(cx, "max")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx) as u32)
+        .unwrap_or(defaults.max);
+
+    Ok(RedirectPolicy { follow, max })
+}
+
+fn parse_retry_policy(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<RetryPolicy> {
+    let defaults = RetryPolicy::default();
+
+    let max_attempts = optional_number(cx, obj, "maxAttempts")?
+        .map(|v| (v as u32).max(1))
+        .unwrap_or(defaults.max_attempts);
+    let backoff_base = optional_number(cx, obj, "backoffBase")?
+        .map(|v| v as u64)
+        .unwrap_or(defaults.backoff_base);
+    let backoff_cap = optional_number(cx, obj, "backoffCap")?
+        .map(|v| v as u64)
+        .unwrap_or(defaults.backoff_cap);
+
+    let jitter = obj
+        .get_opt(cx, "jitter")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(defaults.jitter);
+
+    let on_network_error = obj
+        .get_opt(cx, "retryOnNetworkError")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(defaults.on_network_error);
+
+    let statuses = match obj.get_opt::<JsArray, _, _>(cx, "retryStatuses")? {
+        Some(array) => {
+            let mut statuses = Vec::new();
+            for value in array.to_vec(cx)? {
+                let status = value.downcast::<JsNumber, _>(cx).or_throw(cx)?;
+                statuses.push(status.value(cx) as u16);
+            }
+            statuses
+        }
+        None => defaults.statuses,
+    };
+
+    Ok(RetryPolicy {
+        max_attempts,
+        backoff_base,
+        backoff_cap,
+        jitter,
+        statuses,
+        on_network_error,
+    })
+}
+
+fn response_cookie_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    cookie: &ResponseCookie,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let name = cx.string(&cookie.name);
+    obj.set(cx, "name", name)?;
+    let value = cx.string(&cookie.value);
+    obj.set(cx, "value", value)?;
+
+    if let Some(domain) = &cookie.domain {
+        let domain = cx.string(domain);
+        obj.set(cx, "domain", domain)?;
+    }
+    if let Some(path) = &cookie.path {
+        let path = cx.string(path);
+        obj.set(cx, "path", path)?;
+    }
+    if let Some(expires) = cookie.expires {
+        let expires = cx.number(expires);
+        obj.set(cx, "expires", expires)?;
+    }
+    if let Some(max_age) = cookie.max_age {
+        let max_age = cx.number(max_age as f64);
+        obj.set(cx, "maxAge", max_age)?;
+    }
+    if let Some(same_site) = cookie.same_site {
+        let same_site = cx.string(same_site);
+        obj.set(cx, "sameSite", same_site)?;
+    }
+
+    let secure = cx.boolean(cookie.secure);
+    obj.set(cx, "secure", secure)?;
+    let http_only = cx.boolean(cookie.http_only);
+    obj.set(cx, "httpOnly", http_only)?;
+
+    Ok(obj)
+}
+
+fn stored_cookie_to_js_object<'a, C: Context<'a>>(cx: &mut C, cookie: &StoredCookie) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let name = cx.string(&cookie.name);
+    obj.set(cx, "name", name)?;
+    let value = cx.string(&cookie.value);
+    obj.set(cx, "value", value)?;
+    let domain = cx.string(&cookie.domain);
+    obj.set(cx, "domain", domain)?;
+    let host_only = cx.boolean(cookie.host_only);
+    obj.set(cx, "hostOnly", host_only)?;
+    let path = cx.string(&cookie.path);
+    obj.set(cx, "path", path)?;
+
+    if let Some(expires) = cookie.expires {
+        let expires = cx.number(expires as f64);
+        obj.set(cx, "expires", expires)?;
+    }
+    if let Some(same_site) = &cookie.same_site {
+        let same_site = cx.string(same_site);
+        obj.set(cx, "sameSite", same_site)?;
+    }
+
+    let secure = cx.boolean(cookie.secure);
+    obj.set(cx, "secure", secure)?;
+    let http_only = cx.boolean(cookie.http_only);
+    obj.set(cx, "httpOnly", http_only)?;
+
+    Ok(obj)
+}
+
+fn peer_certificate_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    cert: &PeerCertificate,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let subject = cx.string(&cert.subject);
+    obj.set(cx, "subject", subject)?;
+    let issuer = cx.string(&cert.issuer);
+    obj.set(cx, "issuer", issuer)?;
+
+    let subject_alt_names = cx.empty_array();
+    for (i, name) in cert.subject_alt_names.iter().enumerate() {
+        let name = cx.string(name);
+        subject_alt_names.set(cx, i as u32, name)?;
+    }
+    obj.set(cx, "subjectAltNames", subject_alt_names)?;
+
+    let not_before = cx.number(cert.not_before);
+    obj.set(cx, "notBefore", not_before)?;
+    let not_after = cx.number(cert.not_after);
+    obj.set(cx, "notAfter", not_after)?;
+    let fingerprint256 = cx.string(&cert.fingerprint256);
+    obj.set(cx, "fingerprint256", fingerprint256)?;
+
+    Ok(obj)
+}
+
+fn fingerprint_report_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &fingerprint::Report,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let fields = [("ja3", &report.ja3), ("ja3Hash", &report.ja3_hash), ("ja4", &report.ja4)];
+    for (name, value) in fields {
+        let value: Handle<JsValue> = match value {
+            Some(value) => cx.string(value).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, name, value)?;
+    }
+    let alpn: Handle<JsValue> = match report.alpn {
+        Some(alpn) => cx.string(alpn).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "alpn", alpn)?;
+
+    let http2: Handle<JsValue> = match &report.http2 {
+        Some(http2) => {
+            let http2_obj = cx.empty_object();
+            let settings = cx.empty_array();
+            for (i, (id, value)) in http2.settings.iter().enumerate() {
+                let setting = cx.empty_array();
+                let id = cx.number(*id);
+                setting.set(cx, 0, id)?;
+                let value = cx.number(*value);
+                setting.set(cx, 1, value)?;
+                settings.set(cx, i as u32, setting)?;
+            }
+            http2_obj.set(cx, "settings", settings)?;
+            let window_update = cx.number(http2.window_update);
+            http2_obj.set(cx, "windowUpdate", window_update)?;
+            let pseudo_order = cx.empty_array();
+            for (i, pseudo) in http2.pseudo_order.iter().enumerate() {
+                let pseudo = cx.string(*pseudo);
+                pseudo_order.set(cx, i as u32, pseudo)?;
+            }
+            http2_obj.set(cx, "pseudoHeaderOrder", pseudo_order)?;
+            let akamai = cx.string(&http2.akamai);
+            http2_obj.set(cx, "akamai", akamai)?;
+            http2_obj.upcast()
+        }
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "http2", http2)?;
+
+    Ok(obj)
+}
+
+// A detected bot-protection challenge: { provider, signals }
+fn challenge_to_js_object<'a, C: Context<'a>>(cx: &mut C, info: &ChallengeInfo) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let provider = cx.string(info.provider);
+    obj.set(cx, "provider", provider)?;
+    let signals = cx.empty_array();
+    for (i, signal) in info.signals.iter().enumerate() {
+        let signal = cx.string(signal);
+        signals.set(cx, i as u32, signal)?;
+    }
+    obj.set(cx, "signals", signals)?;
+    Ok(obj)
+}
+
+fn header_tuples_to_js<'a, C: Context<'a>>(cx: &mut C, pairs: &[(String, String)]) -> JsResult<'a, JsArray> {
+    let array = cx.empty_array();
+    for (i, (key, value)) in pairs.iter().enumerate() {
+        let tuple = cx.empty_array();
+        let key = cx.string(key);
+        tuple.set(cx, 0, key)?;
+        let value = cx.string(value);
+        tuple.set(cx, 1, value)?;
+        array.set(cx, i as u32, tuple)?;
+    }
+    Ok(array)
+}
+
+// Convert Response to JS object
+fn response_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    response: Response,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    // Status
+    let status = cx.number(response.status as f64);
+    obj.set(cx, "status", status)?;
+
+    // Negotiated protocol version
+    let version = cx.string(response.version);
+    obj.set(cx, "version", version)?;
+
+    // URL
+    let url = cx.string(&response.url);
+    obj.set(cx, "url", url)?;
+
+    // ID the request's logs, events and HAR entry carry
+    let request_id = cx.string(&response.request_id);
+    obj.set(cx, "requestId", request_id)?;
+
+    // Headers as [name, value] tuples in wire order, repeated headers included
+    let headers = header_tuples_to_js(cx, &response.headers)?;
+    obj.set(cx, "headers", headers)?;
+
+    // Trailer fields sent after the body, in the same shape as headers
+    if !response.trailers.is_empty() {
+        let trailers = header_tuples_to_js(cx, &response.trailers)?;
+        obj.set(cx, "trailers", trailers)?;
+    }
+
+    // Bytes of the values that aren't valid UTF-8, as [index, Buffer] tuples
+    if !response.raw_header_values.is_empty() {
+        let raw_values = cx.empty_array();
+        for (i, (index, bytes)) in response.raw_header_values.iter().enumerate() {
+            let tuple = cx.empty_array();
+            let index = cx.number(*index as f64);
+            tuple.set(cx, 0, index)?;
+            let mut buffer = cx.buffer(bytes.len())?;
+            buffer.as_mut_slice(cx).copy_from_slice(bytes);
+            tuple.set(cx, 1, buffer)?;
+            raw_values.set(cx, i as u32, tuple)?;
+        }
+        obj.set(cx, "rawHeaderValues", raw_values)?;
+    }
+
+    // Cookies
+    let cookies_obj = cx.empty_object();
+    for (key, value) in response.cookies {
+        let value_str = cx.string(&value);
+        cookies_obj.set(cx, key.as_str(), value_str)?;
+    }
+    obj.set(cx, "cookies", cookies_obj)?;
+
+    // Structured cookies, one per set-cookie header
+    let set_cookies = cx.empty_array();
+    for (i, cookie) in response.set_cookies.iter().enumerate() {
+        let cookie_obj = response_cookie_to_js_object(cx, cookie)?;
+        set_cookies.set(cx, i as u32, cookie_obj)?;
+    }
+    obj.set(cx, "setCookies", set_cookies)?;
+
+    // Redirect chain
+    let redirects = cx.empty_array();
+    for (i, redirect_url) in response.redirects.iter().enumerate() {
+        let redirect_url = cx.string(redirect_url);
+        redirects.set(cx, i as u32, redirect_url)?;
+    }
+    obj.set(cx, "redirects", redirects)?;
+
+    // Status, location and cookies of each redirect
+    let redirect_hops = cx.empty_array();
+    for (i, hop) in response.redirect_hops.iter().enumerate() {
+        let hop_obj = cx.empty_object();
+        let url = cx.string(&hop.url);
+        hop_obj.set(cx, "url", url)?;
+        let status = cx.number(hop.status as f64);
+        hop_obj.set(cx, "status", status)?;
+        if let Some(location) = &hop.location {
+            let location = cx.string(location);
+            hop_obj.set(cx, "location", location)?;
+        }
+        let set_cookies = cx.empty_array();
+        for (j, cookie) in hop.set_cookies.iter().enumerate() {
+            let cookie_obj = response_cookie_to_js_object(cx, cookie)?;
+            set_cookies.set(cx, j as u32, cookie_obj)?;
+        }
+        hop_obj.set(cx, "setCookies", set_cookies)?;
+        redirect_hops.set(cx, i as u32, hop_obj)?;
+    }
+    obj.set(cx, "redirectHops", redirect_hops)?;
+
+    // Body
+    let body = cx.string(&response.body);
+    obj.set(cx, "body", body)?;
+
+    // Raw body bytes
+    let mut body_bytes = cx.buffer(response.body_bytes.len())?;
+    body_bytes
+        .as_mut_slice(cx)
+        .copy_from_slice(&response.body_bytes);
+    obj.set(cx, "bodyBytes", body_bytes)?;
+
+    if let Some(encoding) = response.charset {
+        let charset = cx.string(encoding.name());
+        obj.set(cx, "charset", charset)?;
+    }
+
+    let attempts = cx.number(response.attempts as f64);
+    obj.set(cx, "attempts", attempts)?;
+
+    if let Some(proxy) = &response.proxy {
+        let proxy = cx.string(proxy);
+        obj.set(cx, "proxy", proxy)?;
+    }
+
+    let from_cache = cx.boolean(response.from_cache);
+    obj.set(cx, "fromCache", from_cache)?;
+
+    // Phase timings in milliseconds; phases that didn't happen are left out
+    let timings = cx.empty_object();
+    let phases = [
+        ("dns", response.timings.dns),
+        ("connect", response.timings.connect),
+        ("ttfb", Some(response.timings.ttfb)),
+        ("download", response.timings.download),
+        ("total", response.timings.total),
+    ];
+    for (name, value) in phases {
+        if let Some(value) = value {
+            let value = cx.number(value);
+            timings.set(cx, name, value)?;
+        }
+    }
+    obj.set(cx, "timings", timings)?;
+
+    // Connection the response arrived on; absent for cached responses
+    if let Some(connection) = &response.connection {
+        let connection_obj = cx.empty_object();
+        let reused = cx.boolean(connection.reused);
+        connection_obj.set(cx, "reused", reused)?;
+        if let Some(addr) = connection.local_addr {
+            let addr = cx.string(addr.to_string());
+            connection_obj.set(cx, "localAddress", addr)?;
+        }
+        if let Some(addr) = connection.remote_addr {
+            let addr = cx.string(addr.to_string());
+            connection_obj.set(cx, "remoteAddress", addr)?;
+        }
+        let pool_key = cx.string(&connection.pool_key);
+        connection_obj.set(cx, "poolKey", pool_key)?;
+        obj.set(cx, "connection", connection_obj)?;
+    }
+
+    // Certificate the server presented; absent over plain HTTP
+    if let Some(cert) = &response.tls {
+        let tls = peer_certificate_to_js_object(cx, cert)?;
+        obj.set(cx, "tls", tls)?;
+    }
+
+    if let Some(report) = &response.fingerprint {
+        let fingerprint = fingerprint_report_to_js_object(cx, report)?;
+        obj.set(cx, "fingerprint", fingerprint)?;
+    }
+
+    // What `parse` extracted from the body, which was left empty
+    if let Some(parsed) = &response.parsed {
+        let parsed = json_to_js(cx, parsed)?;
+        obj.set(cx, "parsed", parsed)?;
+    }
+
+    // Matches of the `extract` selectors, by rule name
+    if let Some(extracted) = &response.extracted {
+        let extracted = json_to_js(cx, extracted)?;
+        obj.set(cx, "extracted", extracted)?;
+    }
+
+    if let Some(info) = &response.challenge {
+        let challenge = challenge_to_js_object(cx, info)?;
+        obj.set(cx, "challenge", challenge)?;
+    }
+
+    // Content codings taken off the body: [{ encoding, sniffed }], outermost first
+    if !response.decodings.is_empty() {
+        let decodings = cx.empty_array();
+        for (i, decoding) in response.decodings.iter().enumerate() {
+            let decoding_obj = cx.empty_object();
+            let encoding = cx.string(decoding.encoding.token());
+            decoding_obj.set(cx, "encoding", encoding)?;
+            let sniffed = cx.boolean(decoding.sniffed);
+            decoding_obj.set(cx, "sniffed", sniffed)?;
+            decodings.set(cx, i as u32, decoding_obj)?;
+        }
+        obj.set(cx, "decodings", decodings)?;
+    }
+
+    // Digest of the body, as lowercase hex
+    if let Some(hash) = &response.body_hash {
+        let hash = cx.string(&hash.hex);
+        obj.set(cx, "bodyHash", hash)?;
+    }
+
+    // Streaming handle, if the body was left unread
+    if let Some(stream_id) = response.stream_id {
+        let stream_id = cx.number(stream_id as f64);
+        obj.set(cx, "streamId", stream_id)?;
+    }
+
+    Ok(obj)
+}
+
+// Main request function exported to Node.js
+fn request(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    // Get the options object
+    let options_obj = cx.argument::<JsObject>(0)?;
+
+    // Convert JS object to Rust struct
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+
+    // Create a promise
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    let request_id = options.request_id.clone();
+    runtime::spawn(async move {
+        let result = runtime::cancellable(make_request(options)).await;
+
+        // Send result back to JS
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(response) => response_to_js_object(&mut cx, response),
+            Err(e) => throw_request_failure(&mut cx, &e, &request_id),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Streaming request: resolves once headers arrive, body is pulled with readBodyChunk
+fn request_stream(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let options_obj = cx.argument::<JsObject>(0)?;
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    let request_id = options.request_id.clone();
+    runtime::spawn(async move {
+        let result = runtime::cancellable(make_request_stream(options)).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(response) => response_to_js_object(&mut cx, response),
+            Err(e) => throw_request_failure(&mut cx, &e, &request_id),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Render request options as a curl command line without sending anything
+fn to_curl(mut cx: FunctionContext) -> JsResult<JsString> {
+    let options_obj = cx.argument::<JsObject>(0)?;
+    let mut options = js_object_to_request_options(&mut cx, options_obj)?;
+    if let Err(e) = compress::apply(&mut options) {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    Ok(cx.string(curl::to_curl(&options)))
+}
+
+// Build a request without sending it, returning what would go on the wire
+fn preview_request(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let options_obj = cx.argument::<JsObject>(0)?;
+    let mut options = js_object_to_request_options(&mut cx, options_obj)?;
+    if let Err(e) = compress::apply(&mut options) {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    let preview = preview::preview(&options);
+
+    let obj = cx.empty_object();
+    let method = cx.string(&preview.method);
+    obj.set(&mut cx, "method", method)?;
+    let url = cx.string(&preview.url);
+    obj.set(&mut cx, "url", url)?;
+    let target = cx.string(&preview.target);
+    obj.set(&mut cx, "target", target)?;
+    let headers = header_tuples_to_js(&mut cx, &preview.headers)?;
+    obj.set(&mut cx, "headers", headers)?;
+    if let Some(length) = preview.body_length {
+        let length = cx.number(length as f64);
+        obj.set(&mut cx, "bodyLength", length)?;
+    }
+    let raw = cx.string(preview.raw());
+    obj.set(&mut cx, "raw", raw)?;
+
+    Ok(obj)
+}
+
+fn download_progress_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    progress: DownloadProgress,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let received = cx.number(progress.received as f64);
+    obj.set(cx, "received", received)?;
+    if let Some(total) = progress.total {
+        let total = cx.number(total as f64);
+        obj.set(cx, "total", total)?;
+    }
+    let rate = cx.number(progress.rate);
+    obj.set(cx, "rate", rate)?;
+
+    Ok(obj)
+}
+
+// Download to a file on the runtime; the body never crosses into JS, only progress events do
+fn download(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let options_obj = cx.argument::<JsObject>(0)?;
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+    let dest = cx.argument::<JsString>(1)?.value(&mut cx);
+    let on_progress = match cx.argument_opt(2) {
+        Some(value) => value
+            .downcast::<JsFunction, _>(&mut cx)
+            .ok()
+            .map(|callback| Arc::new(callback.root(&mut cx))),
+        None => None,
+    };
+    // Range request options: { resume?, segments? }
+    let plan = match cx.argument_opt(3).and_then(|value| value.downcast::<JsObject, _>(&mut cx).ok()) {
+        Some(obj) => DownloadPlan {
+            resume: optional_bool(&mut cx, obj, "resume")?.unwrap_or(false),
+            segments: optional_number(&mut cx, obj, "segments")?.map_or(1, |segments| segments.max(1.0) as u32),
+        },
+        None => DownloadPlan::default(),
+    };
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+    let progress_channel = settle_channel.clone();
+
+    let request_id = options.request_id.clone();
+    runtime::spawn(async move {
+        let report = |progress: DownloadProgress| {
+            let Some(callback) = on_progress.clone() else {
+                return;
+            };
+            progress_channel.send(move |mut cx| {
+                let callback = callback.to_inner(&mut cx);
+                let this = cx.undefined();
+                let event = download_progress_to_js_object(&mut cx, progress)?;
+                callback.call(&mut cx, this, vec![event.upcast()])?;
+                Ok(())
+            });
+        };
+        let result = runtime::cancellable(make_download(options, Path::new(&dest), plan, report)).await;
+
+        // Queued after every progress event, so callers see the final one before the promise settles
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(downloaded) => {
+                let obj = response_to_js_object(&mut cx, downloaded.head)?;
+                let written = cx.number(downloaded.written as f64);
+                obj.set(&mut cx, "bytesWritten", written)?;
+                let resumed_from = cx.number(downloaded.resumed_from as f64);
+                obj.set(&mut cx, "resumedFrom", resumed_from)?;
+                Ok(obj)
+            }
+            Err(e) => throw_request_failure(&mut cx, &e, &request_id),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Read the next chunk of a streamed body; resolves to null at end of stream
+fn read_body_chunk(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    runtime::spawn(async move {
+        let result = read_chunk(id).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(Some(data)) => {
+                let mut buffer = cx.buffer(data.len())?;
+                buffer.as_mut_slice(&mut cx).copy_from_slice(&data);
+                Ok(buffer.upcast::<JsValue>())
+            }
+            Ok(None) => Ok(cx.null().upcast::<JsValue>()),
+            Err(e) => throw_classified(&mut cx, &e),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Parse the next complete NDJSON lines of a streamed body; resolves to null at end of stream
+fn read_body_json_lines(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    runtime::spawn(async move {
+        let result = read_json_lines(id).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(Some(values)) => json_values_to_js(&mut cx, &values).map(|array| array.upcast::<JsValue>()),
+            Ok(None) => Ok(cx.null().upcast::<JsValue>()),
+            Err(e) => throw_classified(&mut cx, &e),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Parse the next complete entries of a streamed sitemap; resolves to null at end of stream
+fn read_body_sitemap_entries(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    runtime::spawn(async move {
+        let result = read_sitemap_entries(id).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(Some(entries)) => sitemap_entries_to_js(&mut cx, &entries).map(|array| array.upcast::<JsValue>()),
+            Ok(None) => Ok(cx.null().upcast::<JsValue>()),
+            Err(e) => throw_classified(&mut cx, &e),
+        });
+    });
+
+    Ok(promise)
+}
+
+// Trailers of a stream read to the end, as [name, value] tuples
+fn take_stream_trailers(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+    let trailers = take_trailers(id);
+    header_tuples_to_js(&mut cx, &trailers)
+}
+
+// Digest of a stream read to the end, when its request set hashBody; null otherwise
+fn take_stream_hash(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+    match take_hash(id) {
+        Some(hash) => Ok(cx.string(hash.hex).upcast()),
+        None => Ok(cx.null().upcast()),
+    }
+}
+
+fn cancel_body_stream(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let id = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+    remove_stream(id);
+    Ok(cx.undefined())
+}
+
+// Parse a response body as JSON without round-tripping it through a JS string
+fn parse_json(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let bytes = cx.argument::<JsBuffer>(0)?;
+    let value = {
+        let data = bytes.as_slice(&cx);
+        // Match JSON.parse on decoded text, which never sees a UTF-8 BOM
+        let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+        serde_json::from_slice::<serde_json::Value>(data)
+    };
+
+    match value {
+        Ok(value) => json_to_js(&mut cx, &value),
+        Err(e) => throw_with_kind(&mut cx, ErrorKind::BodyDecodeError, format!("Invalid JSON body: {}", e)),
+    }
+}
+
+// Parse a buffered NDJSON body into an array of its lines' values
+fn parse_json_lines(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let bytes = cx.argument::<JsBuffer>(0)?;
+    let values = ndjson::parse_all(bytes.as_slice(&cx));
+
+    match values {
+        Ok(values) => json_values_to_js(&mut cx, &values),
+        Err(e) => throw_with_kind(&mut cx, ErrorKind::BodyDecodeError, e.to_string()),
+    }
+}
+
+fn json_values_to_js<'a, C: Context<'a>>(cx: &mut C, values: &[serde_json::Value]) -> JsResult<'a, JsArray> {
+    let array = cx.empty_array();
+    for (i, value) in values.iter().enumerate() {
+        let value = json_to_js(cx, value)?;
+        array.set(cx, i as u32, value)?;
+    }
+    Ok(array)
+}
+
+// Parse a buffered sitemap body, gzipped or not, into an array of its entries
+fn parse_sitemap(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let bytes = cx.argument::<JsBuffer>(0)?;
+    let entries = sitemap::parse_all(bytes.as_slice(&cx));
+
+    match entries {
+        Ok(entries) => sitemap_entries_to_js(&mut cx, &entries),
+        Err(e) => throw_with_kind(&mut cx, ErrorKind::BodyDecodeError, e.to_string()),
+    }
+}
+
+// Entries become { type, loc, lastmod?, changefreq?, priority? }
+fn sitemap_entries_to_js<'a, C: Context<'a>>(cx: &mut C, entries: &[SitemapEntry]) -> JsResult<'a, JsArray> {
+    let array = cx.empty_array();
+    for (i, entry) in entries.iter().enumerate() {
+        let obj = cx.empty_object();
+        let kind = cx.string(entry.kind.as_str());
+        obj.set(cx, "type", kind)?;
+        let loc = cx.string(&entry.loc);
+        obj.set(cx, "loc", loc)?;
+        if let Some(lastmod) = &entry.lastmod {
+            let lastmod = cx.string(lastmod);
+            obj.set(cx, "lastmod", lastmod)?;
+        }
+        if let Some(changefreq) = &entry.changefreq {
+            let changefreq = cx.string(changefreq);
+            obj.set(cx, "changefreq", changefreq)?;
+        }
+        if let Some(priority) = entry.priority {
+            let priority = cx.number(priority);
+            obj.set(cx, "priority", priority)?;
+        }
+        array.set(cx, i as u32, obj)?;
+    }
+    Ok(array)
+}
+
+// Run a `parse` mode over a body that didn't come from the network, e.g. a mock: (bytes, text, url, mode)
+fn parse_body(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let bytes = cx.argument::<JsBuffer>(0)?;
+    let text = cx.argument::<JsString>(1)?.value(&mut cx);
+    let url = cx.argument::<JsString>(2)?.value(&mut cx);
+    let mode = cx.argument::<JsString>(3)?.value(&mut cx);
+    let parser = body_parser(&mut cx, &mode)?;
+
+    match parser.run(bytes.as_slice(&cx), &text, &url) {
+        Ok(value) => json_to_js(&mut cx, &value),
+        Err(e) => throw_with_kind(&mut cx, ErrorKind::BodyDecodeError, e.to_string()),
+    }
+}
+
+// Frame protobuf-encoded messages into a gRPC-web request body
+fn grpc_web_encode(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+    let messages = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+    let mut buffers = Vec::with_capacity(messages.len());
+    for message in messages {
+        let buffer = message.downcast_or_throw::<JsBuffer, _>(&mut cx)?;
+        buffers.push(buffer.as_slice(&cx).to_vec());
+    }
+
+    let body = match grpc_web::encode(buffers.iter().map(Vec::as_slice)) {
+        Ok(body) => body,
+        Err(reason) => return cx.throw_range_error(format!("Cannot frame gRPC-web message: {}", reason)),
+    };
+    let mut buffer = cx.buffer(body.len())?;
+    buffer.as_mut_slice(&mut cx).copy_from_slice(&body);
+    Ok(buffer)
+}
+
+// Split a gRPC-web response body into { messages, trailers }
+fn grpc_web_decode(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let body = cx.argument::<JsBuffer>(0)?;
+    let decoded = match grpc_web::decode(body.as_slice(&cx)) {
+        Ok(decoded) => decoded,
+        Err(e) => return throw_with_kind(&mut cx, ErrorKind::BodyDecodeError, e.to_string()),
+    };
+
+    let obj = cx.empty_object();
+    let messages = cx.empty_array();
+    for (i, message) in decoded.messages.iter().enumerate() {
+        let mut buffer = cx.buffer(message.len())?;
+        buffer.as_mut_slice(&mut cx).copy_from_slice(message);
+        messages.set(&mut cx, i as u32, buffer)?;
+    }
+    obj.set(&mut cx, "messages", messages)?;
+    let trailers = header_tuples_to_js(&mut cx, &decoded.trailers)?;
+    obj.set(&mut cx, "trailers", trailers)?;
+    Ok(obj)
+}
+
+// Run `extract` rules over a body that didn't come from the network, e.g. a mock: (html, rules)
+fn extract_body(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let html = cx.argument::<JsString>(0)?.value(&mut cx);
+    let rules = cx.argument::<JsObject>(1)?;
+    let rules = parse_extract_rules(&mut cx, rules)?;
+    let extracted = extract::run(&rules, &html);
+    json_to_js(&mut cx, &extracted)
+}
+
+// Check a response that didn't come from the network, e.g. a mock, for challenge markers: (status, headers, body)
+fn detect_challenge(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let status = cx.argument::<JsNumber>(0)?.value(&mut cx) as u16;
+    let headers = cx.argument::<JsArray>(1)?;
+    let headers = parse_headers_from_array(&mut cx, headers)?;
+    let body = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    match challenge::detect(status, &headers, Some(&body)) {
+        Some(info) => Ok(challenge_to_js_object(&mut cx, &info)?.upcast()),
+        None => Ok(cx.null().upcast()),
+    }
+}
+
+// Decode a streamed body once it has been read, like buffered bodies are: (bytes, contentType, charset?)
+fn decode_text(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let bytes = cx.argument::<JsBuffer>(0)?;
+    let content_type = cx
+        .argument_opt(1)
+        .and_then(|v| v.downcast::<JsString, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx));
+    let forced = match cx.argument_opt(2).and_then(|v| v.downcast::<JsString, _>(&mut cx).ok()) {
+        Some(label) => {
+            let label = label.value(&mut cx);
+            Some(parse_charset(&mut cx, &label)?)
+        }
+        None => None,
+    };
+
+    let (text, encoding) = charset::decode(bytes.as_slice(&cx), content_type.as_deref(), forced);
+    let obj = cx.empty_object();
+    let text = cx.string(text);
+    obj.set(&mut cx, "text", text)?;
+    let charset = cx.string(encoding.name());
+    obj.set(&mut cx, "charset", charset)?;
+    Ok(obj)
+}
+
+fn json_to_js<'a, C: Context<'a>>(cx: &mut C, value: &serde_json::Value) -> JsResult<'a, JsValue> {
+    use serde_json::Value;
+
+    Ok(match value {
+        Value::Null => cx.null().upcast(),
+        Value::Bool(flag) => cx.boolean(*flag).upcast(),
+        Value::Number(number) => cx.number(number.as_f64().unwrap_or(f64::NAN)).upcast(),
+        Value::String(text) => cx.string(text).upcast(),
+        Value::Array(items) => {
+            let array = cx.empty_array();
+            for (i, item) in items.iter().enumerate() {
+                let item = json_to_js(cx, item)?;
+                array.set(cx, i as u32, item)?;
+            }
+            array.upcast()
+        }
+        Value::Object(entries) => {
+            let object = cx.empty_object();
+            for (key, item) in entries {
+                let item = json_to_js(cx, item)?;
+                object.set(cx, key.as_str(), item)?;
+            }
+            object.upcast()
+        }
+    })
+}
+
+// Get list of available browser profiles
+fn get_profiles(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let js_array = cx.empty_array();
+
+    for (i, profile) in generated_profiles::BROWSER_PROFILES.iter().enumerate() {
+        let js_string = cx.string(*profile);
+        js_array.set(&mut cx, i as u32, js_string)?;
+    }
+
+    Ok(js_array)
+}
+
+// Describe every browser profile: name, family, version and the OS it presents as
+fn list_emulations(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let js_array = cx.empty_array();
+
+    for (i, info) in emulation::list().into_iter().enumerate() {
+        let obj = cx.empty_object();
+        let name = cx.string(info.name);
+        obj.set(&mut cx, "name", name)?;
+        let family = cx.string(info.family);
+        obj.set(&mut cx, "family", family)?;
+        let version = cx.string(info.version);
+        obj.set(&mut cx, "version", version)?;
+        let os = cx.string(info.os);
+        obj.set(&mut cx, "os", os)?;
+        js_array.set(&mut cx, i as u32, obj)?;
+    }
+
+    Ok(js_array)
+}
+
+// The names of a browser profile's default headers, in the order it sends them
+fn get_header_order(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let name = cx.argument::<JsString>(0)?.value(&mut cx);
+    let emulation = parse_emulation(&mut cx, &name)?;
+    let js_array = cx.empty_array();
+
+    for (i, header) in header_order::template(&emulation).iter().enumerate() {
+        let js_string = cx.string(header);
+        js_array.set(&mut cx, i as u32, js_string)?;
+    }
+
+    Ok(js_array)
+}
+
+// Check a browser profile name, returning it unchanged or throwing with close matches
+fn validate_emulation(mut cx: FunctionContext) -> JsResult<JsString> {
+    let name = cx.argument::<JsString>(0)?.value(&mut cx);
+    parse_emulation(&mut cx, &name)?;
+    Ok(cx.string(name))
+}
+
+fn create_session(mut cx: FunctionContext) -> JsResult<JsString> {
+    let options_value = cx.argument_opt(0);
+
+    let (session_id_opt, browser_opt, emulation_os, proxy_opt, tls, http2, pool, dns, cookie_store_path) = if let Some(
+        value,
+    ) = options_value
+    {
+        if value.is_a::<JsUndefined, _>(&mut cx) || value.is_a::<JsNull, _>(&mut cx) {
+            (None, None, None, None, TlsConfig::default(), None, PoolConfig::default(), DnsConfig::default(), None)
+        } else {
+            let obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+            let session_id = obj
+                .get_opt(&mut cx, "sessionId")?
+                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
+                .map(|v| v.value(&mut cx));
+            let browser = obj
+                .get_opt(&mut cx, "browser")?
+                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
+                .map(|v| v.value(&mut cx));
+            let proxy = obj
+                .get_opt(&mut cx, "proxy")?
+                .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
+                .map(|v| v.value(&mut cx));
+            let input = ConnectionInput::read(&mut cx, obj)?;
+            let emulation_os = parse_emulation_os(&mut cx, input.emulation_os.clone())?;
+            let tls = parse_tls_config(&mut cx, &input)?;
+            let http2 = parse_http2_fingerprint(&mut cx, input.http2_fingerprint)?;
+            let pool = parse_pool_config(&mut cx, input.pool)?;
+            let dns = parse_dns_config(&mut cx, &input)?;
+            let cookie_store_path = input.cookie_store_path.map(PathBuf::from);
+            (session_id, browser, emulation_os, proxy, tls, http2, pool, dns, cookie_store_path)
+        }
+    } else {
+        (None, None, None, None, TlsConfig::default(), None, PoolConfig::default(), DnsConfig::default(), None)
+    };
+
+    let session_id = session_id_opt.unwrap_or_else(generate_session_id);
+    let browser_str = browser_opt.unwrap_or_else(|| "chrome_142".to_string());
+    let emulation = parse_emulation(&mut cx, &browser_str)?;
+
+    let pin_client = match options_value.and_then(|value| value.downcast::<JsObject, _>(&mut cx).ok()) {
+        Some(obj) => optional_bool(&mut cx, obj, "pinClient")?.unwrap_or(false),
+        None => false,
+    };
+
+    let config =
+        SessionConfig::new(emulation, emulation_os, proxy_opt, tls, pool, dns, cookie_store_path).with_http2(http2);
+    match create_managed_session(session_id.clone(), config, pin_client) {
+        Ok(id) => Ok(cx.string(id)),
+        Err(e) => {
+            let msg = format!("{:#}", e);
+            cx.throw_error(msg)
+        }
+    }
+}
+
+fn clear_session(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+
+    if let Err(e) = clear_managed_session(&session_id) {
+        let msg = format!("{:#}", e);
+        return cx.throw_error(msg);
+    }
+
+    Ok(cx.undefined())
+}
+
+// List a session's cookies, or only the ones a request to the given URL would carry
+fn get_session_cookies(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let url = cx
+        .argument_opt(1)
+        .and_then(|v| v.downcast::<JsString, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx));
+
+    let jar = match managed_cookie_jar(&session_id) {
+        Ok(jar) => jar,
+        Err(e) => return cx.throw_error(format!("{:#}", e)),
+    };
+
+    let cookies = match url {
+        Some(url) => match url.parse::<wreq::Uri>() {
+            Ok(uri) => jar.for_uri(&uri),
+            Err(_) => return cx.throw_type_error(format!("Invalid URL '{}'", url)),
+        },
+        None => jar.all(),
+    };
+
+    let array = cx.empty_array();
+    for (i, cookie) in cookies.iter().enumerate() {
+        let obj = stored_cookie_to_js_object(&mut cx, cookie)?;
+        array.set(&mut cx, i as u32, obj)?;
+    }
+    Ok(array)
+}
+
+// The Cookie header a request to the URL would carry, or null when no cookie applies
+fn get_cookie_header(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let url = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let jar = match managed_cookie_jar(&session_id) {
+        Ok(jar) => jar,
+        Err(e) => return cx.throw_error(format!("{:#}", e)),
+    };
+    let uri = match url.parse::<wreq::Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return cx.throw_type_error(format!("Invalid URL '{}'", url)),
+    };
+
+    match jar.header_for(&uri) {
+        Some(header) => Ok(cx.string(header).upcast()),
+        None => Ok(cx.null().upcast()),
+    }
+}
+
+fn set_session_cookie(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let obj = cx.argument::<JsObject>(1)?;
+
+    let name = obj.get::<JsString, _, _>(&mut cx, "name")?.value(&mut cx);
+    let value = obj.get::<JsString, _, _>(&mut cx, "value")?.value(&mut cx);
+    let domain = obj.get::<JsString, _, _>(&mut cx, "domain")?.value(&mut cx);
+    let path = optional_string(&mut cx, obj, "path")?.unwrap_or_else(|| "/".to_string());
+    // Epoch milliseconds; a time in the past deletes the cookie, like an expired Set-Cookie
+    let expires = optional_number(&mut cx, obj, "expires")?.map(|at| at.max(0.0) as u64);
+    let same_site = match optional_string(&mut cx, obj, "sameSite")? {
+        Some(value) if matches!(value.as_str(), "Strict" | "Lax" | "None") => Some(value),
+        Some(value) => return cx.throw_type_error(format!("Invalid sameSite '{}'", value)),
+        None => None,
+    };
+
+    let cookie = StoredCookie {
+        name,
+        value,
+        domain,
+        host_only: optional_bool(&mut cx, obj, "hostOnly")?.unwrap_or(false),
+        path,
+        expires,
+        secure: optional_bool(&mut cx, obj, "secure")?.unwrap_or(false),
+        http_only: optional_bool(&mut cx, obj, "httpOnly")?.unwrap_or(false),
+        same_site,
+    };
+
+    let result = managed_cookie_jar(&session_id).and_then(|jar| jar.set(cookie));
+    if let Err(e) = result {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    Ok(cx.undefined())
+}
+
+// Delete the cookies matching `{ name?, domain?, path? }`, returning how many were removed
+fn delete_session_cookies(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let filter = cx.argument::<JsObject>(1)?;
+
+    let name = optional_string(&mut cx, filter, "name")?;
+    let domain = optional_string(&mut cx, filter, "domain")?;
+    let path = optional_string(&mut cx, filter, "path")?;
+
+    let jar = match managed_cookie_jar(&session_id) {
+        Ok(jar) => jar,
+        Err(e) => return cx.throw_error(format!("{:#}", e)),
+    };
+    let removed = jar.remove(name.as_deref(), domain.as_deref(), path.as_deref());
+    Ok(cx.number(removed as f64))
+}
+
+fn drop_session(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
+
+    if let Err(e) = drop_managed_session(&session_id) {
+        let msg = format!("{:#}", e);
+        return cx.throw_error(msg);
     }
 
     Ok(cx.undefined())
 }
 
-fn drop_session(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-    let session_id = cx.argument::<JsString>(0)?.value(&mut cx);
-    drop_managed_session(&session_id);
+// Reconfigure the session client cache; omitted limits keep their current values
+fn configure_client_cache(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let obj = cx.argument::<JsObject>(0)?;
+    let mut config = SESSION_MANAGER.cache_config();
+
+    if let Some(max_entries) = optional_number(&mut cx, obj, "maxEntries")? {
+        if max_entries < 1.0 {
+            return cx.throw_range_error("maxEntries must be at least 1");
+        }
+        config.max_entries = max_entries as u64;
+    }
+
+    if let Some(idle_ttl) = optional_number(&mut cx, obj, "idleTtl")? {
+        if idle_ttl <= 0.0 {
+            return cx.throw_range_error("idleTtl must be positive");
+        }
+        config.idle_ttl = Duration::from_millis(idle_ttl as u64);
+    }
+
+    if let Some(sweep_interval) = optional_number(&mut cx, obj, "sweepInterval")? {
+        if sweep_interval < 1.0 {
+            return cx.throw_range_error("sweepInterval must be at least 1ms");
+        }
+        config.sweep_interval = Duration::from_millis(sweep_interval as u64);
+    }
+
+    configure_managed_cache(config);
+    Ok(cx.undefined())
+}
+
+fn set_concurrency(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let obj = cx.argument::<JsObject>(0)?;
+    let mut config = ConcurrencyConfig::default();
+
+    if let Some(global) = optional_number(&mut cx, obj, "global")? {
+        if global < 1.0 {
+            return cx.throw_range_error("global must be at least 1");
+        }
+        config.global = Some(global as usize);
+    }
+
+    if let Some(per_host) = optional_number(&mut cx, obj, "perHost")? {
+        if per_host < 1.0 {
+            return cx.throw_range_error("perHost must be at least 1");
+        }
+        config.per_host = Some(per_host as usize);
+    }
+
+    concurrency::configure(config);
+    Ok(cx.undefined())
+}
+
+fn get_concurrency(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let config = concurrency::current_config();
+    let obj = cx.empty_object();
+
+    if let Some(global) = config.global {
+        let global = cx.number(global as f64);
+        obj.set(&mut cx, "global", global)?;
+    }
+    if let Some(per_host) = config.per_host {
+        let per_host = cx.number(per_host as f64);
+        obj.set(&mut cx, "perHost", per_host)?;
+    }
+
+    Ok(obj)
+}
+
+fn set_rate_limit(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let obj = cx.argument::<JsObject>(0)?;
+
+    let Some(requests_per_second) = optional_number(&mut cx, obj, "requestsPerSecond")? else {
+        rate_limit::configure(None);
+        return Ok(cx.undefined());
+    };
+    if requests_per_second <= 0.0 || !requests_per_second.is_finite() {
+        return cx.throw_range_error("requestsPerSecond must be a positive number");
+    }
+
+    let burst = optional_number(&mut cx, obj, "burst")?.unwrap_or(1.0);
+    if burst < 1.0 {
+        return cx.throw_range_error("burst must be at least 1");
+    }
+
+    let scope = match optional_string(&mut cx, obj, "per")? {
+        Some(value) => match RateLimitScope::parse(&value) {
+            Some(scope) => scope,
+            None => return cx.throw_type_error(format!("Unknown rate limit scope '{}'", value)),
+        },
+        None => RateLimitScope::Global,
+    };
+
+    let fail_fast = obj
+        .get_opt(&mut cx, "failFast")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx))
+        .unwrap_or(false);
+
+    rate_limit::configure(Some(RateLimitConfig {
+        requests_per_second,
+        burst: burst.floor(),
+        scope,
+        fail_fast,
+    }));
+    Ok(cx.undefined())
+}
+
+fn get_rate_limit(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let obj = cx.empty_object();
+    let Some(config) = rate_limit::current_config() else {
+        return Ok(obj);
+    };
+
+    let requests_per_second = cx.number(config.requests_per_second);
+    obj.set(&mut cx, "requestsPerSecond", requests_per_second)?;
+    let burst = cx.number(config.burst);
+    obj.set(&mut cx, "burst", burst)?;
+    let per = cx.string(match config.scope {
+        RateLimitScope::Global => "global",
+        RateLimitScope::Host => "host",
+        RateLimitScope::Proxy => "proxy",
+    });
+    obj.set(&mut cx, "per", per)?;
+    let fail_fast = cx.boolean(config.fail_fast);
+    obj.set(&mut cx, "failFast", fail_fast)?;
+
+    Ok(obj)
+}
+
+/// Response statuses that count against a host's circuit unless others are given.
+const DEFAULT_CIRCUIT_STATUSES: [u16; 4] = [500, 502, 503, 504];
+
+// Set the per-host circuit breaker from `{ threshold?, cooldown?, statuses? }`, or remove it with null
+fn set_circuit_breaker(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let Some(obj) = cx.argument_opt(0).and_then(|v| v.downcast::<JsObject, _>(&mut cx).ok()) else {
+        circuit_breaker::configure(None);
+        return Ok(cx.undefined());
+    };
+
+    let threshold = optional_number(&mut cx, obj, "threshold")?.unwrap_or(5.0);
+    if threshold < 1.0 || !threshold.is_finite() {
+        return cx.throw_range_error("threshold must be at least 1");
+    }
+    let cooldown = optional_number(&mut cx, obj, "cooldown")?.unwrap_or(30_000.0);
+    if cooldown < 0.0 || !cooldown.is_finite() {
+        return cx.throw_range_error("cooldown must be a non-negative number of milliseconds");
+    }
+    let statuses = match obj.get_opt::<JsArray, _, _>(&mut cx, "statuses")? {
+        Some(array) => {
+            let mut statuses = Vec::new();
+            for value in array.to_vec(&mut cx)? {
+                statuses.push(value.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx) as u16);
+            }
+            statuses
+        }
+        None => DEFAULT_CIRCUIT_STATUSES.to_vec(),
+    };
+
+    circuit_breaker::configure(Some(CircuitBreakerConfig {
+        threshold: threshold as u32,
+        cooldown: Duration::from_millis(cooldown as u64),
+        statuses,
+    }));
+    Ok(cx.undefined())
+}
+
+fn circuit_breaker_stats(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let array = cx.empty_array();
+    for (i, stats) in circuit_breaker::stats().iter().enumerate() {
+        let obj = cx.empty_object();
+        let host = cx.string(&stats.host);
+        obj.set(&mut cx, "host", host)?;
+        let state = cx.string(stats.state);
+        obj.set(&mut cx, "state", state)?;
+        let consecutive = cx.number(stats.consecutive_failures);
+        obj.set(&mut cx, "consecutiveFailures", consecutive)?;
+        let open_until: Handle<JsValue> = match stats.open_until {
+            Some(until) => cx.number(until).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(&mut cx, "openUntil", open_until)?;
+        array.set(&mut cx, i as u32, obj)?;
+    }
+    Ok(array)
+}
+
+// Replace the routing table: `[{ hosts, proxy? | proxyPool? | direct? }]`, first match wins.
+// Hosts are glob strings or `{ source, ignoreCase }` from a RegExp.
+fn set_proxy_routes(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let array = cx.argument::<JsArray>(0)?;
+    let mut routes = Vec::new();
+
+    for value in array.to_vec(&mut cx)? {
+        let obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+
+        let mut patterns = Vec::new();
+        for host in obj.get::<JsArray, _, _>(&mut cx, "hosts")?.to_vec(&mut cx)? {
+            if let Ok(glob) = host.downcast::<JsString, _>(&mut cx) {
+                patterns.push(HostPattern::glob(&glob.value(&mut cx)));
+                continue;
+            }
+            let host = host.downcast_or_throw::<JsObject, _>(&mut cx)?;
+            let source = host.get::<JsString, _, _>(&mut cx, "source")?.value(&mut cx);
+            let ignore_case = optional_bool(&mut cx, host, "ignoreCase")?.unwrap_or(false);
+            match RegexBuilder::new(&source).case_insensitive(ignore_case).build() {
+                Ok(regex) => patterns.push(HostPattern::Regex(regex)),
+                Err(e) => return cx.throw_type_error(format!("Invalid host pattern /{}/: {}", source, e)),
+            }
+        }
+
+        let proxy = optional_string(&mut cx, obj, "proxy")?;
+        let pool_id = match obj.get_opt::<JsObject, _, _>(&mut cx, "proxyPool")? {
+            Some(handle) => Some(handle.get::<JsString, _, _>(&mut cx, "id")?.value(&mut cx)),
+            None => None,
+        };
+        let direct = optional_bool(&mut cx, obj, "direct")?.unwrap_or(false);
+
+        let target = match (proxy, pool_id, direct) {
+            (Some(proxy), None, false) => {
+                if let Err(e) = session::build_proxy(&proxy, None, &[]) {
+                    return cx.throw_type_error(format!("{:#}", e));
+                }
+                RouteTarget::Proxy(proxy)
+            }
+            (None, Some(id), false) => match proxy_pool::get(&id) {
+                Some(pool) => RouteTarget::Pool(pool),
+                None => return cx.throw_type_error(format!("Proxy pool '{}' does not exist or was closed", id)),
+            },
+            (None, None, true) => RouteTarget::Direct,
+            _ => return cx.throw_type_error("A proxy route needs exactly one of proxy, proxyPool or direct"),
+        };
+        routes.push(Route { patterns, target });
+    }
+
+    proxy_routes::configure(routes);
+    Ok(cx.undefined())
+}
+
+// Register a pool under an ID chosen in JS: `{ proxies, strategy?, maxFailures?, quarantine? }`
+fn create_proxy_pool(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let obj = cx.argument::<JsObject>(1)?;
+
+    let array = obj.get::<JsArray, _, _>(&mut cx, "proxies")?;
+    let mut proxies = Vec::new();
+    for value in array.to_vec(&mut cx)? {
+        let proxy = value.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx);
+        // Fail here rather than on whichever request draws the bad entry
+        if let Err(e) = session::build_proxy(&proxy, None, &[]) {
+            return cx.throw_type_error(format!("{:#}", e));
+        }
+        proxies.push(proxy);
+    }
+    if proxies.is_empty() {
+        return cx.throw_type_error("A proxy pool needs at least one proxy");
+    }
+
+    let strategy = match optional_string(&mut cx, obj, "strategy")? {
+        Some(value) => match ProxyStrategy::parse(&value) {
+            Some(strategy) => strategy,
+            None => return cx.throw_type_error(format!("Unknown proxy pool strategy '{}'", value)),
+        },
+        None => ProxyStrategy::RoundRobin,
+    };
+
+    let max_failures = optional_number(&mut cx, obj, "maxFailures")?.unwrap_or(3.0);
+    if max_failures < 1.0 {
+        return cx.throw_range_error("maxFailures must be at least 1");
+    }
+    let quarantine = optional_number(&mut cx, obj, "quarantine")?.unwrap_or(60_000.0);
+    if quarantine < 0.0 || !quarantine.is_finite() {
+        return cx.throw_range_error("quarantine must be a non-negative number of milliseconds");
+    }
+    let failover = optional_number(&mut cx, obj, "failover")?.unwrap_or(0.0);
+    if failover < 0.0 || !failover.is_finite() {
+        return cx.throw_range_error("failover must be a non-negative number of proxies");
+    }
+
+    proxy_pool::create(
+        id,
+        ProxyPoolConfig {
+            proxies,
+            strategy,
+            max_failures: max_failures as u32,
+            quarantine: Duration::from_millis(quarantine as u64),
+            failover: failover as u32,
+        },
+    );
+    Ok(cx.undefined())
+}
+
+fn proxy_pool_stats(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let id = cx.argument::<JsString>(0)?.value(&mut cx);
+    let Some(pool) = proxy_pool::get(&id) else {
+        return cx.throw_error(format!("Proxy pool '{}' does not exist or was closed", id));
+    };
+
+    let array = cx.empty_array();
+    for (i, stats) in pool.stats().iter().enumerate() {
+        let obj = cx.empty_object();
+        let url = cx.string(&stats.url);
+        obj.set(&mut cx, "proxy", url)?;
+        let successes = cx.number(stats.successes as f64);
+        obj.set(&mut cx, "successes", successes)?;
+        let failures = cx.number(stats.failures as f64);
+        obj.set(&mut cx, "failures", failures)?;
+        let consecutive = cx.number(stats.consecutive_failures);
+        obj.set(&mut cx, "consecutiveFailures", consecutive)?;
+        let quarantined_until: Handle<JsValue> = match stats.quarantined_until {
+            Some(until) => cx.number(until).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(&mut cx, "quarantinedUntil", quarantined_until)?;
+        array.set(&mut cx, i as u32, obj)?;
+    }
+    Ok(array)
+}
+
+/// Echo endpoint proxies are checked against unless another `testUrl` is given.
+const DEFAULT_PROXY_TEST_URL: &str = "https://httpbin.org/get";
+
+// Check proxies with `{ testUrl?, timeout?, concurrency?, browser? }`, resolving to one result each
+fn check_proxies(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let array = cx.argument::<JsArray>(0)?;
+    let mut proxies = Vec::new();
+    for value in array.to_vec(&mut cx)? {
+        proxies.push(value.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx));
+    }
+
+    let obj = cx.argument::<JsObject>(1)?;
+    let test_url = optional_string(&mut cx, obj, "testUrl")?.unwrap_or_else(|| DEFAULT_PROXY_TEST_URL.to_string());
+    let timeout = optional_number(&mut cx, obj, "timeout")?.unwrap_or(10_000.0);
+    if timeout <= 0.0 || !timeout.is_finite() {
+        return cx.throw_range_error("timeout must be a positive number of milliseconds");
+    }
+    let concurrency = optional_number(&mut cx, obj, "concurrency")?.unwrap_or(10.0);
+    if concurrency < 1.0 {
+        return cx.throw_range_error("concurrency must be at least 1");
+    }
+    let browser = optional_string(&mut cx, obj, "browser")?.unwrap_or_else(|| "chrome_142".to_string());
+    let emulation = parse_emulation(&mut cx, &browser)?;
+
+    let config = ProxyCheckConfig {
+        test_url,
+        timeout: Duration::from_millis(timeout as u64),
+        concurrency: concurrency as usize,
+        emulation,
+    };
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    runtime::spawn(async move {
+        let result = runtime::cancellable(proxy_check::check_proxies(proxies, config)).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(checks) => {
+                let array = cx.empty_array();
+                for (i, check) in checks.iter().enumerate() {
+                    let obj = proxy_check_to_js_object(&mut cx, check)?;
+                    array.set(&mut cx, i as u32, obj)?;
+                }
+                Ok(array)
+            }
+            Err(e) => throw_classified(&mut cx, &e),
+        });
+    });
+
+    Ok(promise)
+}
+
+fn proxy_check_to_js_object<'a, C: Context<'a>>(cx: &mut C, check: &ProxyCheck) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let proxy = cx.string(&check.proxy);
+    obj.set(cx, "proxy", proxy)?;
+    let ok = cx.boolean(check.ok());
+    obj.set(cx, "ok", ok)?;
+
+    let status: Handle<JsValue> = match check.status {
+        Some(status) => cx.number(status).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "status", status)?;
+    let latency: Handle<JsValue> = match check.latency {
+        Some(latency) => cx.number(latency.as_secs_f64() * 1000.0).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "latency", latency)?;
+    let exit_ip: Handle<JsValue> = match check.exit_ip {
+        Some(ip) => cx.string(ip.to_string()).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "exitIp", exit_ip)?;
+    let anonymity: Handle<JsValue> = match check.anonymity {
+        Some(anonymity) => cx.string(anonymity.as_str()).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "anonymity", anonymity)?;
+
+    let (error, code): (Handle<JsValue>, Handle<JsValue>) = match &check.error {
+        Some((kind, message)) => (cx.string(message).upcast(), cx.string(kind.code()).upcast()),
+        None => (cx.null().upcast(), cx.null().upcast()),
+    };
+    obj.set(cx, "error", error)?;
+    obj.set(cx, "code", code)?;
+
+    Ok(obj)
+}
+
+fn remove_proxy_pool(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let id = cx.argument::<JsString>(0)?.value(&mut cx);
+    proxy_pool::remove(&id);
+    Ok(cx.undefined())
+}
+
+// Configure the HTTP cache with `{ store, dir?, maxSize? }`, or turn it off with null
+fn set_http_cache(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let value = cx.argument::<JsValue>(0)?;
+    let store = if value.is_a::<JsNull, _>(&mut cx) || value.is_a::<JsUndefined, _>(&mut cx) {
+        None
+    } else {
+        let obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+        match optional_string(&mut cx, obj, "store")?.as_deref() {
+            None | Some("memory") => {
+                let max_size = optional_number(&mut cx, obj, "maxSize")?.unwrap_or(64.0 * 1024.0 * 1024.0);
+                if max_size < 1.0 {
+                    return cx.throw_range_error("maxSize must be at least 1 byte");
+                }
+                Some(CacheStore::Memory {
+                    max_bytes: max_size as u64,
+                })
+            }
+            Some("disk") => match optional_string(&mut cx, obj, "dir")? {
+                Some(dir) if !dir.is_empty() => Some(CacheStore::Disk(PathBuf::from(dir))),
+                _ => return cx.throw_type_error("dir is required for a disk cache"),
+            },
+            Some(other) => {
+                let message = format!("Unknown cache store '{}', expected \"memory\" or \"disk\"", other);
+                return cx.throw_type_error(message);
+            }
+        }
+    };
+
+    if let Err(e) = http_cache::configure(store) {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    Ok(cx.undefined())
+}
+
+fn clear_http_cache(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    conditional::clear();
+    if let Err(e) = http_cache::clear() {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    Ok(cx.undefined())
+}
+
+// Check a URL against its origin's robots.txt: (robots.txt request options, url, userAgent)
+fn check_robots(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let options_obj = cx.argument::<JsObject>(0)?;
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+    let url = cx.argument::<JsString>(1)?.value(&mut cx);
+    let user_agent = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    let request_id = options.request_id.clone();
+    runtime::spawn(async move {
+        let result = runtime::cancellable(robots::check(options, &url, &user_agent)).await;
+
+        deferred.settle_with(&settle_channel, move |mut cx| match result {
+            Ok(verdict) => {
+                let obj = cx.empty_object();
+                let allowed = cx.boolean(verdict.allowed);
+                obj.set(&mut cx, "allowed", allowed)?;
+                let crawl_delay: Handle<JsValue> = match verdict.crawl_delay {
+                    Some(delay) => cx.number(delay).upcast(),
+                    None => cx.null().upcast(),
+                };
+                obj.set(&mut cx, "crawlDelay", crawl_delay)?;
+                let sitemaps = cx.empty_array();
+                for (i, sitemap) in verdict.sitemaps.iter().enumerate() {
+                    let sitemap = cx.string(sitemap);
+                    sitemaps.set(&mut cx, i as u32, sitemap)?;
+                }
+                obj.set(&mut cx, "sitemaps", sitemaps)?;
+                let status = cx.number(verdict.status);
+                obj.set(&mut cx, "status", status)?;
+                Ok(obj)
+            }
+            Err(e) => throw_request_failure(&mut cx, &e, &request_id),
+        });
+    });
+
+    Ok(promise)
+}
+
+fn clear_robots_cache(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    robots::clear();
+    Ok(cx.undefined())
+}
+
+fn set_tracing(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let value = cx.argument::<JsValue>(0)?;
+    let config = if value.is_a::<JsNull, _>(&mut cx) || value.is_a::<JsUndefined, _>(&mut cx) {
+        None
+    } else {
+        let obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+        let level = match optional_string(&mut cx, obj, "level")? {
+            Some(value) => match telemetry::parse_level(&value) {
+                Some(level) => level,
+                None => return cx.throw_type_error(format!("Unknown tracing level '{}'", value)),
+            },
+            None => tracing::Level::INFO,
+        };
+        let json = optional_bool(&mut cx, obj, "json")?.unwrap_or(false);
+        Some(TracingConfig { level, json })
+    };
+
+    if let Err(e) = telemetry::configure(config) {
+        return cx.throw_error(format!("{:#}", e));
+    }
+    Ok(cx.undefined())
+}
+
+// Metrics as an object, or as Prometheus text when asked for "prometheus"
+fn get_metrics(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let format = match cx.argument_opt(0) {
+        Some(value) if !value.is_a::<JsUndefined, _>(&mut cx) => {
+            Some(value.downcast_or_throw::<JsString, _>(&mut cx)?.value(&mut cx))
+        }
+        _ => None,
+    };
+
+    match format.as_deref() {
+        None | Some("json") => {
+            let snapshot = serde_json::to_value(metrics::snapshot()).unwrap_or_default();
+            json_to_js(&mut cx, &snapshot)
+        }
+        Some("prometheus") => Ok(cx.string(metrics::prometheus()).upcast()),
+        Some(other) => {
+            let message = format!("Unknown metrics format '{}', expected \"json\" or \"prometheus\"", other);
+            cx.throw_type_error(message)
+        }
+    }
+}
+
+fn reset_metrics(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    metrics::reset();
+    Ok(cx.undefined())
+}
+
+fn set_event_listener(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let listener = match cx.argument_opt(0).map(|value| value.downcast::<JsFunction, _>(&mut cx)) {
+        Some(Ok(callback)) => {
+            // Events alone shouldn't keep the process running
+            let mut channel = cx.channel();
+            channel.unref(&mut cx);
+            Some((callback.root(&mut cx), channel))
+        }
+        _ => None,
+    };
+
+    events::set_listener(listener);
+    Ok(cx.undefined())
+}
+
+fn start_har_recording(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let mut config = HarConfig::default();
+
+    if let Some(obj) = cx.argument_opt(0).and_then(|value| value.downcast::<JsObject, _>(&mut cx).ok()) {
+        if let Some(max_body_size) = optional_number(&mut cx, obj, "maxBodySize")? {
+            if max_body_size < 0.0 {
+                return cx.throw_range_error("maxBodySize must not be negative");
+            }
+            config.max_body_bytes = max_body_size as usize;
+        }
+        if let Some(max_entries) = optional_number(&mut cx, obj, "maxEntries")? {
+            if max_entries < 1.0 {
+                return cx.throw_range_error("maxEntries must be at least 1");
+            }
+            config.max_entries = max_entries as usize;
+        }
+    }
+
+    har::start(config);
+    Ok(cx.undefined())
+}
+
+// The log goes to Node as a JSON string, or null when nothing is being recorded
+fn har_to_js(mut cx: FunctionContext, log: Option<String>) -> JsResult<JsValue> {
+    match log {
+        Some(log) => Ok(cx.string(log).upcast()),
+        None => Ok(cx.null().upcast()),
+    }
+}
+
+fn export_har(cx: FunctionContext) -> JsResult<JsValue> {
+    har_to_js(cx, har::export())
+}
+
+fn stop_har_recording(cx: FunctionContext) -> JsResult<JsValue> {
+    har_to_js(cx, har::stop())
+}
+
+fn clear_client_cache(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    clear_managed_cache();
+    Ok(cx.undefined())
+}
+
+// Settings for the next runtime start: `{ workerThreads?, maxBlockingThreads?, threadName?, threadStackSize? }`
+fn configure_runtime(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let obj = cx.argument::<JsObject>(0)?;
+    let mut config = RuntimeConfig::default();
+
+    for (key, slot, min) in [
+        ("workerThreads", &mut config.worker_threads, 1.0),
+        ("maxBlockingThreads", &mut config.max_blocking_threads, 1.0),
+        ("threadStackSize", &mut config.thread_stack_size, 65536.0),
+    ] {
+        if let Some(value) = optional_number(&mut cx, obj, key)? {
+            if value < min || !value.is_finite() {
+                return cx.throw_range_error(format!("{} must be at least {}", key, min));
+            }
+            *slot = Some(value as usize);
+        }
+    }
+    config.thread_name = optional_string(&mut cx, obj, "threadName")?.filter(|name| !name.is_empty());
+
+    if let Err(e) = runtime::configure(config) {
+        return throw_classified(&mut cx, &e);
+    }
     Ok(cx.undefined())
 }
 
+// Drain and stop the shared runtime off the JS thread, resolving to `{ cancelled }`
+fn shutdown(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let grace = cx.argument::<JsNumber>(0)?.value(&mut cx);
+    if grace < 0.0 || !grace.is_finite() {
+        return cx.throw_range_error("graceMs must be a non-negative number of milliseconds");
+    }
+
+    let (deferred, promise) = cx.promise();
+    let settle_channel = cx.channel();
+
+    std::thread::spawn(move || {
+        let cancelled = runtime::shutdown(Duration::from_millis(grace as u64));
+
+        deferred.settle_with(&settle_channel, move |mut cx| {
+            let obj = cx.empty_object();
+            let cancelled = cx.number(cancelled as f64);
+            obj.set(&mut cx, "cancelled", cancelled)?;
+            Ok(obj)
+        });
+    });
+
+    Ok(promise)
+}
+
+fn client_cache_stats(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let stats = managed_cache_stats();
+    let obj = cx.empty_object();
+
+    let hits = cx.number(stats.hits as f64);
+    obj.set(&mut cx, "hits", hits)?;
+    let misses = cx.number(stats.misses as f64);
+    obj.set(&mut cx, "misses", misses)?;
+    let evictions = cx.number(stats.evictions as f64);
+    obj.set(&mut cx, "evictions", evictions)?;
+    let size = cx.number(stats.size as f64);
+    obj.set(&mut cx, "size", size)?;
+
+    let entries = cx.empty_array();
+    for (i, entry) in stats.entries.iter().enumerate() {
+        let entry_obj = cx.empty_object();
+        let session_id = cx.string(&entry.session_id);
+        entry_obj.set(&mut cx, "sessionId", session_id)?;
+        let browser = cx.string(&entry.browser);
+        entry_obj.set(&mut cx, "browser", browser)?;
+        if let Some(proxy) = &entry.proxy {
+            let proxy = cx.string(proxy);
+            entry_obj.set(&mut cx, "proxy", proxy)?;
+        }
+        let last_used = cx.number(entry.last_used as f64);
+        entry_obj.set(&mut cx, "lastUsed", last_used)?;
+        let pinned = cx.boolean(entry.pinned);
+        entry_obj.set(&mut cx, "pinned", pinned)?;
+        entries.set(&mut cx, i as u32, entry_obj)?;
+    }
+    obj.set(&mut cx, "entries", entries)?;
+
+    Ok(obj)
+}
+
 // WebSocket connection function
 fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
     // Get the options object
@@ -338,13 +3046,15 @@ fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
         .map(|v| v.value(&mut cx))
         .unwrap_or_else(|| "chrome_142".to_string());
 
-    let emulation = parse_emulation(&browser_str);
+    let emulation = parse_emulation(&mut cx, &browser_str)?;
+    let emulation_os = optional_string(&mut cx, options_obj, "emulationOs")?;
+    let emulation_os = parse_emulation_os(&mut cx, emulation_os)?;
 
     // Get headers (optional)
     let headers = if let Ok(Some(headers_value)) = options_obj.get_opt(&mut cx, "headers") {
         parse_headers_from_value(&mut cx, headers_value)?
     } else {
-        IndexMap::new()
+        Vec::new()
     };
 
     // Get proxy (optional)
@@ -361,6 +3071,7 @@ fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let options = WebSocketOptions {
         url,
         emulation,
+        emulation_os,
         headers,
         proxy,
     };
@@ -630,10 +3341,63 @@ fn websocket_close(mut cx: FunctionContext) -> JsResult<JsPromise> {
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("request", request)?;
+    cx.export_function("requestStream", request_stream)?;
+    cx.export_function("readBodyChunk", read_body_chunk)?;
+    cx.export_function("readBodyJsonLines", read_body_json_lines)?;
+    cx.export_function("readBodySitemapEntries", read_body_sitemap_entries)?;
+    cx.export_function("takeStreamTrailers", take_stream_trailers)?;
+    cx.export_function("takeStreamHash", take_stream_hash)?;
+    cx.export_function("cancelBodyStream", cancel_body_stream)?;
+    cx.export_function("download", download)?;
+    cx.export_function("toCurl", to_curl)?;
+    cx.export_function("previewRequest", preview_request)?;
+    cx.export_function("parseJson", parse_json)?;
+    cx.export_function("parseJsonLines", parse_json_lines)?;
+    cx.export_function("parseSitemap", parse_sitemap)?;
+    cx.export_function("parseBody", parse_body)?;
+    cx.export_function("grpcWebEncode", grpc_web_encode)?;
+    cx.export_function("grpcWebDecode", grpc_web_decode)?;
+    cx.export_function("extractBody", extract_body)?;
+    cx.export_function("detectChallenge", detect_challenge)?;
+    cx.export_function("decodeText", decode_text)?;
     cx.export_function("getProfiles", get_profiles)?;
+    cx.export_function("getHeaderOrder", get_header_order)?;
+    cx.export_function("listEmulations", list_emulations)?;
+    cx.export_function("validateEmulation", validate_emulation)?;
     cx.export_function("createSession", create_session)?;
     cx.export_function("clearSession", clear_session)?;
+    cx.export_function("getSessionCookies", get_session_cookies)?;
+    cx.export_function("getCookieHeader", get_cookie_header)?;
+    cx.export_function("setSessionCookie", set_session_cookie)?;
+    cx.export_function("deleteSessionCookies", delete_session_cookies)?;
     cx.export_function("dropSession", drop_session)?;
+    cx.export_function("configureClientCache", configure_client_cache)?;
+    cx.export_function("clearClientCache", clear_client_cache)?;
+    cx.export_function("clientCacheStats", client_cache_stats)?;
+    cx.export_function("configureRuntime", configure_runtime)?;
+    cx.export_function("shutdown", shutdown)?;
+    cx.export_function("setConcurrency", set_concurrency)?;
+    cx.export_function("getConcurrency", get_concurrency)?;
+    cx.export_function("setRateLimit", set_rate_limit)?;
+    cx.export_function("getRateLimit", get_rate_limit)?;
+    cx.export_function("setCircuitBreaker", set_circuit_breaker)?;
+    cx.export_function("circuitBreakerStats", circuit_breaker_stats)?;
+    cx.export_function("createProxyPool", create_proxy_pool)?;
+    cx.export_function("proxyPoolStats", proxy_pool_stats)?;
+    cx.export_function("removeProxyPool", remove_proxy_pool)?;
+    cx.export_function("setProxyRoutes", set_proxy_routes)?;
+    cx.export_function("checkProxies", check_proxies)?;
+    cx.export_function("setHttpCache", set_http_cache)?;
+    cx.export_function("clearHttpCache", clear_http_cache)?;
+    cx.export_function("checkRobots", check_robots)?;
+    cx.export_function("clearRobotsCache", clear_robots_cache)?;
+    cx.export_function("setEventListener", set_event_listener)?;
+    cx.export_function("setTracing", set_tracing)?;
+    cx.export_function("getMetrics", get_metrics)?;
+    cx.export_function("resetMetrics", reset_metrics)?;
+    cx.export_function("startHarRecording", start_har_recording)?;
+    cx.export_function("exportHar", export_har)?;
+    cx.export_function("stopHarRecording", stop_har_recording)?;
     cx.export_function("websocketConnect", websocket_connect)?;
     cx.export_function("websocketSend", websocket_send)?;
     cx.export_function("websocketClose", websocket_close)?;
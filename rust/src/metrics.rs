@@ -0,0 +1,190 @@
+use crate::client::Response;
+use crate::error::ErrorKind;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in milliseconds.
+const LATENCY_BUCKETS: [f64; 12] = [
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10_000.0, 30_000.0,
+];
+
+/// Percentiles are taken over this many of the most recent requests.
+const LATENCY_WINDOW: usize = 4096;
+
+#[derive(Default)]
+struct Metrics {
+    /// Keyed by status class, "2xx" and so on
+    responses: BTreeMap<String, u64>,
+    /// Keyed by error code, e.g. "ERR_TIMEOUT"
+    errors: BTreeMap<&'static str, u64>,
+    /// Counts per bucket in `LATENCY_BUCKETS`, then the overflow
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum: f64,
+    latency_count: u64,
+    recent: VecDeque<f64>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    cache_hits: u64,
+    retries: u64,
+}
+
+static METRICS: Lazy<Mutex<Metrics>> = Lazy::new(|| Mutex::new(Metrics::default()));
+
+/// Count a finished request. `sent` and `received` are body sizes, when known.
+pub fn record(result: Result<&Response, &anyhow::Error>, elapsed: Duration, sent: Option<u64>, received: Option<u64>) {
+    let latency = elapsed.as_secs_f64() * 1000.0;
+    let mut metrics = METRICS.lock().unwrap();
+
+    match result {
+        Ok(response) => {
+            let class = format!("{}xx", response.status / 100);
+            *metrics.responses.entry(class).or_default() += 1;
+            metrics.retries += u64::from(response.attempts.saturating_sub(1));
+            if response.from_cache {
+                metrics.cache_hits += 1;
+            }
+        }
+        Err(e) => *metrics.errors.entry(ErrorKind::classify(e).code()).or_default() += 1,
+    }
+
+    let bucket = LATENCY_BUCKETS
+        .iter()
+        .position(|bound| latency <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len());
+    metrics.buckets[bucket] += 1;
+    metrics.latency_sum += latency;
+    metrics.latency_count += 1;
+    if metrics.recent.len() == LATENCY_WINDOW {
+        metrics.recent.pop_front();
+    }
+    metrics.recent.push_back(latency);
+
+    metrics.bytes_sent += sent.unwrap_or_default();
+    metrics.bytes_received += received.unwrap_or_default();
+}
+
+pub fn reset() {
+    *METRICS.lock().unwrap() = Metrics::default();
+}
+
+/// Point-in-time copy of the metrics, shaped for `getMetrics()`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    requests: u64,
+    responses: BTreeMap<String, u64>,
+    errors: BTreeMap<&'static str, u64>,
+    latency: LatencySnapshot,
+    bytes_sent: u64,
+    bytes_received: u64,
+    cache_hits: u64,
+    retries: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LatencySnapshot {
+    count: u64,
+    sum: f64,
+    /// Percentiles of the most recent requests; absent until one has finished
+    p50: Option<f64>,
+    p90: Option<f64>,
+    p99: Option<f64>,
+    /// Cumulative counts, Prometheus style
+    buckets: Vec<Bucket>,
+}
+
+#[derive(Debug, Serialize)]
+struct Bucket {
+    /// Upper bound in milliseconds; `None` for the catch-all bucket
+    le: Option<f64>,
+    count: u64,
+}
+
+pub fn snapshot() -> Snapshot {
+    let metrics = METRICS.lock().unwrap();
+
+    let mut recent = metrics.recent.iter().copied().collect::<Vec<_>>();
+    recent.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        let rank = ((p / 100.0) * recent.len() as f64).ceil() as usize;
+        recent.get(rank.saturating_sub(1)).copied()
+    };
+
+    let mut cumulative = 0;
+    let buckets = metrics
+        .buckets
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            cumulative += count;
+            Bucket {
+                le: LATENCY_BUCKETS.get(i).copied(),
+                count: cumulative,
+            }
+        })
+        .collect();
+
+    Snapshot {
+        requests: metrics.responses.values().sum::<u64>() + metrics.errors.values().sum::<u64>(),
+        responses: metrics.responses.clone(),
+        errors: metrics.errors.clone(),
+        latency: LatencySnapshot {
+            count: metrics.latency_count,
+            sum: metrics.latency_sum,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            buckets,
+        },
+        bytes_sent: metrics.bytes_sent,
+        bytes_received: metrics.bytes_received,
+        cache_hits: metrics.cache_hits,
+        retries: metrics.retries,
+    }
+}
+
+/// Render the metrics in the Prometheus text exposition format.
+pub fn prometheus() -> String {
+    let snapshot = snapshot();
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP wreq_requests_total Requests that received a response, by status class.");
+    let _ = writeln!(out, "# TYPE wreq_requests_total counter");
+    for (class, count) in &snapshot.responses {
+        let _ = writeln!(out, "wreq_requests_total{{status_class=\"{}\"}} {}", class, count);
+    }
+
+    let _ = writeln!(out, "# HELP wreq_request_errors_total Requests that failed, by error code.");
+    let _ = writeln!(out, "# TYPE wreq_request_errors_total counter");
+    for (code, count) in &snapshot.errors {
+        let _ = writeln!(out, "wreq_request_errors_total{{code=\"{}\"}} {}", code, count);
+    }
+
+    let _ = writeln!(out, "# HELP wreq_request_duration_seconds Time from sending a request to finishing it.");
+    let _ = writeln!(out, "# TYPE wreq_request_duration_seconds histogram");
+    for bucket in &snapshot.latency.buckets {
+        let le = bucket.le.map_or("+Inf".to_string(), |le| (le / 1000.0).to_string());
+        let _ = writeln!(out, "wreq_request_duration_seconds_bucket{{le=\"{}\"}} {}", le, bucket.count);
+    }
+    let _ = writeln!(out, "wreq_request_duration_seconds_sum {}", snapshot.latency.sum / 1000.0);
+    let _ = writeln!(out, "wreq_request_duration_seconds_count {}", snapshot.latency.count);
+
+    let counters = [
+        ("wreq_bytes_sent_total", "Request body bytes sent.", snapshot.bytes_sent),
+        ("wreq_bytes_received_total", "Response body bytes received.", snapshot.bytes_received),
+        ("wreq_cache_hits_total", "Responses served from the HTTP cache.", snapshot.cache_hits),
+        ("wreq_retries_total", "Attempts beyond the first.", snapshot.retries),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    out
+}
@@ -0,0 +1,60 @@
+use crate::error::BodyParseFailed;
+use serde_json::Value;
+
+/// Parses newline-delimited JSON (NDJSON, JSON Lines) however its lines fall across chunks.
+/// Blank lines are skipped and a trailing `\r` is dropped, so CRLF bodies parse too.
+#[derive(Debug, Default)]
+pub struct LineParser {
+    /// Bytes after the last newline seen, waiting for the rest of their line
+    pending: Vec<u8>,
+    /// Lines parsed so far, for error messages
+    line: u64,
+}
+
+impl LineParser {
+    /// Take a chunk and return the values of the lines it completes.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Value>, BodyParseFailed> {
+        self.pending.extend_from_slice(data);
+        let Some(end) = self.pending.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+
+        let rest = self.pending.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        let mut values = Vec::new();
+        for line in complete[..end].split(|byte| *byte == b'\n') {
+            values.extend(self.parse_line(line)?);
+        }
+        Ok(values)
+    }
+
+    /// The value of a last line that had no newline after it, once the body has ended.
+    pub fn finish(&mut self) -> Result<Option<Value>, BodyParseFailed> {
+        let line = std::mem::take(&mut self.pending);
+        self.parse_line(&line)
+    }
+
+    fn parse_line(&mut self, line: &[u8]) -> Result<Option<Value>, BodyParseFailed> {
+        self.line += 1;
+        let mut line = line.strip_suffix(b"\r").unwrap_or(line);
+        if self.line == 1 {
+            line = line.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(line);
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(None);
+        }
+
+        serde_json::from_slice(line).map(Some).map_err(|e| BodyParseFailed {
+            format: "JSON",
+            reason: format!("line {}: {}", self.line, e),
+        })
+    }
+}
+
+/// Parse a whole body that is already in memory.
+pub fn parse_all(body: &[u8]) -> Result<Vec<Value>, BodyParseFailed> {
+    let mut parser = LineParser::default();
+    let mut values = parser.feed(body)?;
+    values.extend(parser.finish()?);
+    Ok(values)
+}
@@ -0,0 +1,427 @@
+use crate::{optional_bool, optional_number, optional_string};
+use neon::prelude::*;
+
+// build.rs generates the TypeScript interfaces for these structs, as `NativeConnectionOptions` and
+// `NativeRequestOptions`. Keys are the fields in camelCase and the docs are copied as they stand, JSDoc
+// tags included. A trailing comment gives the TypeScript type where the Rust one doesn't say it.
+
+/// Options baked into a session's client, so a request inside a session can't change them.
+/// Sessions are created with these as well.
+pub struct ConnectionInput<'a> {
+    /// Operating system the browser profile presents as, instead of its own default
+    /// (macOS for desktop profiles). Changes the User-Agent and, for Chromium profiles,
+    /// `sec-ch-ua-platform` and `sec-ch-ua-mobile`; profiles tied to one platform, like
+    /// Safari on iOS or OkHttp, ignore it. Inside a session it is fixed when the session is created.
+    /// @example
+    /// ```typescript
+    /// // Chrome on Android
+    /// await fetch('https://example.com', { browser: 'chrome_142', emulationOs: 'android' });
+    /// ```
+    pub emulation_os: Option<String>, // EmulationOs
+    /// Client certificate for servers that require mutual TLS. Inside a session it
+    /// must match the certificate the session was created with, as must the other TLS options.
+    pub client_cert: Option<Handle<'a, JsObject>>, // ClientCertificate
+    /// Extra root certificates to trust on top of the system store, as PEM (one or
+    /// more concatenated certificates), e.g. a corporate proxy or staging CA.
+    pub ca: Option<Handle<'a, JsValue>>, // string | Buffer
+    /// Path to a PEM file of extra root certificates to trust.
+    pub ca_file: Option<String>,
+    /// Accept any server certificate, including self-signed, expired or mismatched ones.
+    /// This disables the protection TLS offers against interception; use it only for testing.
+    /// @default false
+    pub danger_accept_invalid_certs: Option<bool>,
+    /// Replace parts of the browser profile's TLS ClientHello, e.g. to match a JA3 or JA4
+    /// fingerprint captured from another client. Part of the session's client, like the
+    /// other TLS options.
+    ///
+    /// @example
+    /// ```typescript
+    /// { tlsFingerprint: { ja3: '771,4865-4866-4867,0-23-65281-10-11-35-16-5-13-18-51-45-43-27,29-23-24,0' } }
+    /// ```
+    pub tls_fingerprint: Option<Handle<'a, JsObject>>, // TlsFingerprintOptions
+    /// Override HTTP/2 settings, window size, priorities and pseudo-header order on top of
+    /// the browser profile, for targets whose checks differ from the built-in profiles. Part
+    /// of the session's client, like {@link tlsFingerprint}.
+    ///
+    /// @example
+    /// ```typescript
+    /// { http2Fingerprint: { initialWindowSize: 131072, pseudoHeaderOrder: ['m', 'p', 'a', 's'] } }
+    /// ```
+    pub http2_fingerprint: Option<Handle<'a, JsObject>>, // Http2FingerprintOptions
+    /// Connection reuse and TCP keepalive tuning. Like the TLS options, it is part of
+    /// the session's client and cannot differ between requests in one session.
+    pub pool: Option<Handle<'a, JsObject>>, // PoolOptions
+    /// Connect to fixed addresses for these hostnames instead of resolving them, like
+    /// curl's `--resolve`. TLS and the `Host` header still use the hostname. Addresses
+    /// may carry a port for familiarity, but the connection always uses the URL's port.
+    /// Part of the session's client, like {@link pool}.
+    ///
+    /// @example
+    /// ```typescript
+    /// { resolve: { 'api.example.com': '10.0.0.5', 'cdn.example.com': ['10.0.0.6', '::1'] } }
+    /// ```
+    pub resolve: Option<Handle<'a, JsObject>>, // Record<string, string | string[]>
+    /// Resolve hostnames with the built-in caching resolver instead of the system's,
+    /// optionally through chosen nameservers or DNS-over-HTTPS. Overrides in
+    /// {@link resolve} still take precedence. Part of the session's client, like {@link pool}.
+    ///
+    /// @example
+    /// ```typescript
+    /// { dns: { doh: 'cloudflare', minTtl: 60_000 } }
+    /// ```
+    pub dns: Option<Handle<'a, JsObject>>, // DnsOptions
+    /// Restrict connections to IPv4 or IPv6, or choose which family is tried first.
+    /// By default the resolver's order decides. Overridden hosts in {@link resolve}
+    /// are not filtered. Part of the session's client, like {@link pool}.
+    pub ip_strategy: Option<String>, // IpStrategy
+    /// File the session's cookies are loaded from when its client is created and saved to
+    /// whenever they change, so logged-in sessions survive restarts. Paths ending in `.txt`
+    /// use the Netscape `cookies.txt` format read by curl and wget; other paths are JSON.
+    /// Give each session its own file. Part of the session's client, like {@link pool}.
+    pub cookie_store_path: Option<String>,
+}
+
+impl<'a> ConnectionInput<'a> {
+    pub fn read(cx: &mut FunctionContext<'a>, obj: Handle<JsObject>) -> NeonResult<Self> {
+        Ok(Self {
+            emulation_os: optional_string(cx, obj, "emulationOs")?,
+            client_cert: obj.get_opt(cx, "clientCert")?,
+            ca: obj.get_opt(cx, "ca")?,
+            ca_file: optional_string(cx, obj, "caFile")?,
+            danger_accept_invalid_certs: optional_bool(cx, obj, "dangerAcceptInvalidCerts")?,
+            tls_fingerprint: obj.get_opt(cx, "tlsFingerprint")?,
+            http2_fingerprint: obj.get_opt(cx, "http2Fingerprint")?,
+            pool: obj.get_opt(cx, "pool")?,
+            resolve: obj.get_opt(cx, "resolve")?,
+            dns: obj.get_opt(cx, "dns")?,
+            ip_strategy: optional_string(cx, obj, "ipStrategy")?,
+            cookie_store_path: optional_string(cx, obj, "cookieStorePath")?,
+        })
+    }
+}
+
+/// A request as the JS layer hands it to `request`, `requestStream`, `download`, `toCurl`,
+/// `previewRequest` and `checkRobots`, validated and with its body serialized.
+pub struct RequestInput<'a> {
+    /// The URL to request, with any query string it already has.
+    pub url: String,
+    /// A string to set request's method.
+    /// @default 'GET'
+    pub method: Option<String>,
+    /// Headers in the order they were given. Browser-specific headers are added to them
+    /// based on the selected browser profile.
+    pub headers: Option<Handle<'a, JsValue>>, // Record<string, string> | HeaderTuple[]
+    /// Query parameters appended to the URL, after any it already has. Names and values
+    /// are percent-encoded, so they can hold any characters.
+    pub query: Option<Handle<'a, JsObject>>, // Record<string, QueryValue>
+    /// How array values in {@link query} are written: `"repeat"` gives `a=1&a=2`,
+    /// `"brackets"` gives `a[]=1&a[]=2`.
+    /// @default "repeat"
+    pub query_array_format: Option<String>, // 'repeat' | 'brackets'
+    /// Request body, sent byte-for-byte.
+    pub body: Option<Handle<'a, JsValue>>, // string | Buffer
+    /// Send a multipart/form-data body built from these fields. The boundary and
+    /// Content-Type header are generated automatically. Cannot be combined with {@link body}.
+    pub multipart: Option<Handle<'a, JsArray>>, // MultipartField[]
+    /// JSON body, already serialized. Sets `Content-Type: application/json` unless
+    /// a Content-Type header is given. Cannot be combined with {@link body} or {@link multipart}.
+    pub json: Option<String>,
+    /// Send these fields as an `application/x-www-form-urlencoded` body, the way a browser
+    /// submits a form. Values are encoded like {@link query} values, with spaces as `+`.
+    /// Sets the Content-Type unless one is given. Cannot be combined with {@link body},
+    /// {@link multipart} or {@link json}.
+    pub form: Option<Handle<'a, JsObject>>, // Record<string, QueryValue>
+    /// Path of a file to send as the body. It is streamed from disk rather than read
+    /// into memory, with `Content-Length` taken from the file size. Cannot be combined
+    /// with {@link body}, {@link multipart}, {@link json} or {@link form}.
+    pub body_file: Option<String>,
+    /// Trailer fields sent after {@link bodyFile}. The file then goes out chunked, without
+    /// `Content-Length`, and the names are announced in a `Trailer` header. Requires {@link bodyFile}.
+    pub trailers: Option<Handle<'a, JsValue>>, // Record<string, string> | HeaderTuple[]
+    /// Compress {@link body}, {@link json} or {@link form} before sending and set
+    /// `Content-Encoding`, for APIs that accept compressed uploads of large JSON or
+    /// NDJSON payloads. `"deflate"` is the zlib format HTTP means by it. Cannot be combined
+    /// with {@link multipart}, {@link bodyFile} or a `Content-Encoding` header.
+    pub compress_body: Option<String>, // BodyCompression
+    /// Whether and how far redirects are followed.
+    /// @default { follow: true, max: 10 }
+    pub redirect: Option<Handle<'a, JsObject>>, // RedirectPolicy
+    /// Also follow the redirects interstitial pages make without a 3xx: a `Refresh` header,
+    /// a `<meta http-equiv="refresh">` tag, or a short inline script that only assigns
+    /// `location` (`location.href = "..."`, `location.replace("...")`). The refresh delay
+    /// isn't waited out. Each hop is sent as a GET in the same session, so cookies the page
+    /// sets carry over, and is listed in `redirectHops` with the page's status. Hops count
+    /// towards {@link maxRedirects}. Only applies to buffered responses with {@link redirect}
+    /// set to `"follow"`.
+    /// @default false
+    pub follow_refresh: Option<bool>,
+    /// Force HTTP/1.1 or HTTP/2 instead of negotiating via ALPN. The protocol actually
+    /// used is reported in `response.version`.
+    pub http_version: Option<String>, // HttpVersion
+    /// Reject with a `ERR_BODY_TOO_LARGE` RequestError once the response body exceeds
+    /// this many bytes after decompression. The download is aborted as soon as the limit
+    /// is crossed, or before it starts when `Content-Length` already exceeds it.
+    pub max_body_bytes: Option<f64>,
+    /// Decode gzip, deflate, brotli and zstd bodies automatically. Set to `false` to
+    /// receive the raw encoded bytes with the original `Content-Encoding` header,
+    /// e.g. to relay a response untouched. Read such bodies with `arrayBuffer()` or `buffer()`.
+    /// @default true
+    pub decompress: Option<bool>,
+    /// Replace the browser profile's `Accept-Encoding` header, e.g. `"identity"` to
+    /// ask for an uncompressed body.
+    pub accept_encoding: Option<String>,
+    /// Decode the body text with this charset, e.g. `"shift_jis"` or `"windows-1251"`, instead of
+    /// detecting it from a byte order mark, the `Content-Type` header, or an HTML `<meta>` tag or
+    /// XML declaration, falling back to UTF-8. Any WHATWG encoding label is accepted; the raw bytes
+    /// are always available from `bytes()` or `buffer()`.
+    pub charset: Option<String>,
+    /// Retry failed attempts with exponential backoff. Responses report how many
+    /// attempts were made in `response.attempts`.
+    pub retry: Option<Handle<'a, JsObject>>, // RetryPolicy
+    /// Browser profile to impersonate for this request.
+    /// Automatically applies browser-specific headers, TLS fingerprints, and HTTP/2 settings.
+    /// @default 'chrome_142'
+    pub browser: Option<String>, // BrowserProfile
+    pub connection: ConnectionInput<'a>,
+    /// Proxy URL to route the request through (e.g., 'http://proxy.example.com:8080').
+    /// Supports `http://`, `https://`, `socks4://`, `socks4a://`, `socks5://` and
+    /// `socks5h://` (hostname resolved by the proxy). Inside a session this overrides
+    /// the session's default proxy for this request only; its client and cookies are reused.
+    pub proxy: Option<String>,
+    /// Credentials for {@link proxy}.
+    pub proxy_auth: Option<Handle<'a, JsObject>>, // ProxyAuth
+    /// SHA-256 pins for the server's certificate. The response is rejected with an
+    /// `ERR_PINNING` RequestError unless the leaf certificate or its public key hashes
+    /// to one of them. Accepts hex (colons optional, as in `response.tls.fingerprint256`)
+    /// or base64 with an optional `sha256/` prefix. The check runs when the response
+    /// head arrives, so a request body has already been sent by then.
+    pub pinned_sha256: Option<Handle<'a, JsArray>>, // string[]
+    /// Extra headers sent to {@link proxy}, e.g. provider-specific session or routing headers.
+    /// They go on the CONNECT request for HTTPS targets and on the request itself for HTTP targets.
+    pub proxy_headers: Option<Handle<'a, JsValue>>, // Record<string, string> | HeaderTuple[]
+    /// Pool to draw the proxy from, afresh for every attempt, by the ID {@link createProxyPool}
+    /// gave it. Cannot be combined with {@link proxy}.
+    pub proxy_pool: Option<Handle<'a, JsObject>>, // Pick<ProxyPoolHandle, 'id'>
+    /// Credentials: Basic or Bearer sent with the request, or Digest or NTLM used to answer
+    /// the server's challenge.
+    pub auth: Option<Handle<'a, JsObject>>, // AuthOptions
+    /// Sign the request with AWS Signature Version 4. Cannot be combined with {@link auth}.
+    pub aws_sig_v4: Option<Handle<'a, JsObject>>, // AwsSigV4Options
+    /// Local IP address to connect from, for machines with several egress addresses.
+    /// Connections are pooled per source address, so rotating it per request is cheap.
+    pub local_address: Option<String>,
+    /// Network interface to bind connections to (e.g. `'eth1'`). Supported on Linux,
+    /// Android, macOS and other Apple platforms, Solaris and illumos. On Linux this is
+    /// `SO_BINDTODEVICE`, set before connecting, so it also selects the device's routes.
+    pub interface: Option<String>,
+    /// Path of a Unix domain socket to send the request over, for local daemons such as
+    /// Docker. The URL still supplies the `Host` header and path, e.g.
+    /// `http://localhost/v1.43/containers/json`. Takes the place of any proxy; not
+    /// supported on Windows.
+    pub unix_socket: Option<String>,
+    /// How this request uses the HTTP cache enabled with {@link setHttpCache}. Only
+    /// buffered GET requests are served from the cache; streamed requests and downloads
+    /// always go to the network.
+    /// @default "default"
+    pub cache: Option<String>, // RequestCacheMode
+    /// Revalidate instead of refetching: remember the `ETag` and `Last-Modified` of the URL's
+    /// last `200`, send them as `If-None-Match`/`If-Modified-Since`, and answer a
+    /// `304 Not Modified` with the remembered body, as a `200` with `fromCache` set. Lighter
+    /// than {@link setHttpCache}: nothing is served without asking the server, and
+    /// `Cache-Control` only matters for `no-store`. Sessions keep their own validators. Only
+    /// buffered GET requests without conditional headers of their own take part.
+    /// @default false
+    ///
+    /// @example
+    /// ```typescript
+    /// const feed = await fetch('https://example.com/feed.xml', { conditional: true });
+    /// // Later: a 304 costs no body transfer, but the text is still there
+    /// const again = await fetch('https://example.com/feed.xml', { conditional: true });
+    /// console.log(again.fromCache, await again.text());
+    /// ```
+    pub conditional: Option<bool>,
+    /// Trace or correlation ID recorded on this request's log span (see {@link setTracing}).
+    /// A W3C trace ID (32 lowercase hex digits) is also sent as a `traceparent` header with
+    /// a fresh parent span ID, unless the request already has one.
+    pub trace_id: Option<String>,
+    /// ID for this request, e.g. one from your own logs. It tags the request's log span,
+    /// lifecycle events, HAR entry, {@link RequestError} and `response.requestId`. A UUID is
+    /// generated when none is given.
+    pub request_id: Option<String>,
+    /// Describe the fingerprint the connection presents in `response.fingerprint`: JA3,
+    /// JA4, the negotiated ALPN protocol and the HTTP/2 preface, for checking a profile
+    /// against services such as tls.peet.ws.
+    /// @default false
+    pub inspect_fingerprint: Option<bool>,
+    /// Parse the body in Rust and return the result as `response.parsed` instead of the
+    /// body, which is left empty. Worth it when only a page's metadata is needed, since
+    /// the markup never crosses into JavaScript. A body that doesn't parse rejects with
+    /// `ERR_BODY_DECODE`. Not available for streamed responses.
+    ///
+    /// @example
+    /// ```typescript
+    /// const response = await fetch('https://example.com', { parse: 'html-meta' });
+    /// const { title, meta } = response.parsed as HtmlMeta;
+    /// ```
+    pub parse: Option<String>, // BodyParser
+    /// Run CSS selectors over an HTML body in Rust and return their matches as
+    /// `response.extracted` instead of the body, which is left empty. Each field is a
+    /// selector, whose first match's text is returned, or an {@link ExtractRule} to read an
+    /// attribute or collect every match. Text has its whitespace collapsed, as a browser
+    /// renders it. The page is parsed once however many fields there are. Can be combined
+    /// with `parse`; not available for streamed responses.
+    ///
+    /// @example
+    /// ```typescript
+    /// const { extracted } = await fetch('https://news.example.com', {
+    ///   extract: {
+    ///     headline: 'h1',
+    ///     stories: { selector: 'article h2 > a', attr: 'href', all: true },
+    ///   },
+    /// });
+    /// ```
+    pub extract: Option<Handle<'a, JsObject>>, // Record<string, string | ExtractRule>
+    /// Hash the body in Rust as it arrives and return the digest as `response.bodyHash`, in
+    /// lowercase hex, so checking integrity or spotting duplicate content doesn't mean hashing
+    /// the bytes again in JavaScript. The digest covers the body after decompression, or the
+    /// raw bytes with `decompress: false`. Streamed responses have theirs once the body has
+    /// been read to the end; downloads hash what they write to disk.
+    ///
+    /// @example
+    /// ```typescript
+    /// const response = await fetch('https://example.com/feed.xml', { hashBody: 'xxh3' });
+    /// if (seen.has(response.bodyHash)) return;
+    /// ```
+    pub hash_body: Option<String>, // BodyHashAlgorithm
+    /// Share one network request among concurrent identical requests: a GET or HEAD
+    /// without a body, to the same URL with the same headers, session, proxy and
+    /// emulation as one already in flight waits for that one's response instead of
+    /// sending another. Each caller gets its own copy of the response. Streamed
+    /// requests are never shared.
+    /// @default false
+    pub dedupe: Option<bool>,
+    /// Request timeout in milliseconds. If the request takes longer than this value,
+    /// it will be aborted. Each retry gets its own. Defaults to 30000, unless
+    /// `readTimeout` or `deadline` is set, in which case there is none.
+    pub timeout: Option<f64>,
+    /// Milliseconds to open a new connection, proxy and TLS handshakes included.
+    /// Requests on a pooled connection don't wait on it.
+    pub connect_timeout: Option<f64>,
+    /// Milliseconds the server may go quiet: waiting for the response head, and then
+    /// between body chunks. A slow download that keeps receiving data isn't cut off,
+    /// while a stalled connection is.
+    pub read_timeout: Option<f64>,
+    /// Milliseconds for the whole request: every attempt, the backoff between them,
+    /// redirects and reading the body. A retry that could only start after the
+    /// deadline isn't made.
+    pub deadline: Option<f64>,
+    /// Whether this request uses the session's cookie jar, without needing a separate
+    /// session to keep cookies apart. Cookies a response sets are still reported on
+    /// `response.cookies` and `response.setCookies` when they aren't stored.
+    /// @default "include"
+    pub cookies: Option<String>, // CookiePolicy
+    /// Cookies to send with `cookies: "manual"`, by name. They go out in one `Cookie`
+    /// header, after any `Cookie` header in {@link headers}.
+    pub cookie_map: Option<Handle<'a, JsObject>>, // Record<string, string>
+    /// Identifier of an existing session created elsewhere (e.g., via {@link createSession}).
+    pub session_id: Option<String>,
+    /// Whether the session is discarded once the request finishes.
+    pub ephemeral: Option<bool>,
+    /// Lowercased names of headers set to `null`, left out of the browser profile's defaults.
+    pub remove_headers: Option<Handle<'a, JsArray>>, // string[]
+    /// Disable default headers from browser emulation. When enabled, only explicitly
+    /// provided headers will be sent with the request, preventing emulation headers
+    /// from being automatically added or appended.
+    /// @default false
+    pub disable_default_headers: Option<bool>,
+    /// Send these headers next to another header instead of after the browser profile's
+    /// defaults, so they sit where a real browser would put them. Anchors can be any header
+    /// the request sends; see {@link getHeaderOrder} for a profile's default order. A header
+    /// whose anchor isn't sent keeps its usual place. Positioned requests send header names
+    /// with the casing given here over HTTP/1.1.
+    ///
+    /// @example
+    /// ```typescript
+    /// { headers: { 'X-Api-Key': 'secret' }, headerPositions: { 'X-Api-Key': { after: 'accept' } } }
+    /// ```
+    pub header_positions: Option<Handle<'a, JsObject>>, // Record<string, HeaderPosition>
+    /// Bytes of a streamed body read ahead of the consumer. Once that much is waiting, reading
+    /// from the socket pauses until the consumer catches up, so a slow consumer slows the
+    /// transfer (through TCP flow control) instead of buffering the body in memory.
+    /// Only used with `stream`.
+    /// @default 65536
+    ///
+    /// @example
+    /// ```typescript
+    /// const response = await fetch('https://example.com/large.bin', { stream: true, highWaterMark: 16 * 1024 });
+    /// for await (const chunk of response) {
+    ///   await writeSlowly(chunk);
+    /// }
+    /// ```
+    pub high_water_mark: Option<f64>,
+    /// Runs the request's `onSend` middleware before every send.
+    pub before_send: Option<Handle<'a, JsFunction>>, // (request: MiddlewareSend) => Promise<MiddlewareSend | undefined>
+}
+
+impl<'a> RequestInput<'a> {
+    pub fn read(cx: &mut FunctionContext<'a>, obj: Handle<JsObject>) -> NeonResult<Self> {
+        let url: Handle<JsString> = obj.get(cx, "url")?;
+
+        Ok(Self {
+            url: url.value(cx),
+            method: optional_string(cx, obj, "method")?,
+            headers: obj.get_opt(cx, "headers")?,
+            query: obj.get_opt(cx, "query")?,
+            query_array_format: optional_string(cx, obj, "queryArrayFormat")?,
+            body: obj.get_opt(cx, "body")?,
+            multipart: obj.get_opt(cx, "multipart")?,
+            json: optional_string(cx, obj, "json")?,
+            form: obj.get_opt(cx, "form")?,
+            body_file: optional_string(cx, obj, "bodyFile")?,
+            trailers: obj.get_opt(cx, "trailers")?,
+            compress_body: optional_string(cx, obj, "compressBody")?,
+            redirect: obj.get_opt(cx, "redirect")?,
+            follow_refresh: optional_bool(cx, obj, "followRefresh")?,
+            http_version: optional_string(cx, obj, "httpVersion")?,
+            max_body_bytes: optional_number(cx, obj, "maxBodyBytes")?,
+            decompress: optional_bool(cx, obj, "decompress")?,
+            accept_encoding: optional_string(cx, obj, "acceptEncoding")?,
+            charset: optional_string(cx, obj, "charset")?,
+            retry: obj.get_opt(cx, "retry")?,
+            browser: optional_string(cx, obj, "browser")?,
+            connection: ConnectionInput::read(cx, obj)?,
+            proxy: optional_string(cx, obj, "proxy")?,
+            proxy_auth: obj.get_opt(cx, "proxyAuth")?,
+            pinned_sha256: obj.get_opt(cx, "pinnedSha256")?,
+            proxy_headers: obj.get_opt(cx, "proxyHeaders")?,
+            proxy_pool: obj.get_opt(cx, "proxyPool")?,
+            auth: obj.get_opt(cx, "auth")?,
+            aws_sig_v4: obj.get_opt(cx, "awsSigV4")?,
+            local_address: optional_string(cx, obj, "localAddress")?,
+            interface: optional_string(cx, obj, "interface")?,
+            unix_socket: optional_string(cx, obj, "unixSocket")?,
+            cache: optional_string(cx, obj, "cache")?,
+            conditional: optional_bool(cx, obj, "conditional")?,
+            trace_id: optional_string(cx, obj, "traceId")?,
+            request_id: optional_string(cx, obj, "requestId")?,
+            inspect_fingerprint: optional_bool(cx, obj, "inspectFingerprint")?,
+            parse: optional_string(cx, obj, "parse")?,
+            extract: obj.get_opt(cx, "extract")?,
+            hash_body: optional_string(cx, obj, "hashBody")?,
+            dedupe: optional_bool(cx, obj, "dedupe")?,
+            timeout: optional_number(cx, obj, "timeout")?,
+            connect_timeout: optional_number(cx, obj, "connectTimeout")?,
+            read_timeout: optional_number(cx, obj, "readTimeout")?,
+            deadline: optional_number(cx, obj, "deadline")?,
+            cookies: optional_string(cx, obj, "cookies")?,
+            cookie_map: obj.get_opt(cx, "cookieMap")?,
+            session_id: optional_string(cx, obj, "sessionId")?,
+            ephemeral: optional_bool(cx, obj, "ephemeral")?,
+            remove_headers: obj.get_opt(cx, "removeHeaders")?,
+            disable_default_headers: optional_bool(cx, obj, "disableDefaultHeaders")?,
+            header_positions: obj.get_opt(cx, "headerPositions")?,
+            high_water_mark: optional_number(cx, obj, "highWaterMark")?,
+            before_send: obj.get_opt(cx, "beforeSend")?,
+        })
+    }
+}
@@ -0,0 +1,186 @@
+use crate::error::BodyParseFailed;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{json, Map, Value};
+use url::Url;
+
+/// What a response body is reduced to in Rust, so only the result crosses into JS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyParser {
+    /// The parsed JSON value
+    Json,
+    /// Title, language, canonical URL, `<meta>` fields and links of an HTML page
+    HtmlMeta,
+    /// The document as a tree of `{ name, attributes, children }` elements
+    Xml,
+}
+
+impl BodyParser {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(BodyParser::Json),
+            "html-meta" => Some(BodyParser::HtmlMeta),
+            "xml" => Some(BodyParser::Xml),
+            _ => None,
+        }
+    }
+
+    fn format(self) -> &'static str {
+        match self {
+            BodyParser::Json => "JSON",
+            BodyParser::HtmlMeta => "HTML",
+            BodyParser::Xml => "XML",
+        }
+    }
+
+    /// Parse a body: raw bytes for JSON, the decoded text for markup.
+    pub fn run(self, bytes: &[u8], text: &str, url: &str) -> Result<Value, BodyParseFailed> {
+        let parsed = match self {
+            BodyParser::Json => {
+                // Match JSON.parse on decoded text, which never sees a UTF-8 BOM
+                let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+                serde_json::from_slice(bytes).map_err(|e| e.to_string())
+            }
+            BodyParser::HtmlMeta => Ok(html_meta(text, url)),
+            BodyParser::Xml => xml_tree(text),
+        };
+        parsed.map_err(|reason| BodyParseFailed {
+            format: self.format(),
+            reason,
+        })
+    }
+}
+
+/// An element's text with runs of whitespace collapsed to single spaces, as a browser renders it.
+pub fn element_text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn html_meta(text: &str, url: &str) -> Value {
+    let document = Html::parse_document(text);
+    let select = |selector: &str| Selector::parse(selector).expect("static selector");
+    let first_attr = |selector: &str, attr: &str| {
+        document
+            .select(&select(selector))
+            .find_map(|element| element.value().attr(attr))
+            .map(|value| value.trim().to_string())
+    };
+
+    // Relative links resolve against <base href>, itself relative to the response URL
+    let page = Url::parse(url).ok();
+    let base = match (first_attr("base[href]", "href"), &page) {
+        (Some(href), Some(page)) => page.join(&href).ok(),
+        _ => page.clone(),
+    };
+    let resolve = |href: &str| match &base {
+        Some(base) => base.join(href).ok().map(String::from),
+        None => Url::parse(href).ok().map(String::from),
+    };
+
+    let title = document.select(&select("title")).next().map(element_text);
+
+    // The first value for each name wins, as it does for browsers and crawlers
+    let mut meta = Map::new();
+    for element in document.select(&select("meta[content]")) {
+        let attrs = element.value();
+        let name = ["name", "property", "http-equiv", "itemprop"]
+            .iter()
+            .find_map(|key| attrs.attr(key))
+            .map(|name| name.trim().to_ascii_lowercase());
+        if let (Some(name), Some(content)) = (name, attrs.attr("content")) {
+            if !name.is_empty() && !meta.contains_key(&name) {
+                meta.insert(name, Value::String(content.trim().to_string()));
+            }
+        }
+    }
+
+    let mut links = Vec::new();
+    for href in document.select(&select("a[href]")).filter_map(|element| element.value().attr("href")) {
+        if let Some(link) = resolve(href.trim()).filter(|link| link.starts_with("http")) {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+
+    json!({
+        "title": title,
+        "lang": first_attr("html[lang]", "lang"),
+        "canonical": first_attr("link[rel~=canonical][href]", "href").and_then(|href| resolve(&href)),
+        "meta": meta,
+        "links": links,
+    })
+}
+
+// Elements become { name, attributes, children }; text and CDATA become strings, and
+// whitespace between elements, comments and processing instructions are dropped
+fn xml_tree(text: &str) -> Result<Value, String> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+
+    let mut open: Vec<Map<String, Value>> = Vec::new();
+    let mut root = None;
+    let mut attach = |open: &mut Vec<Map<String, Value>>, node: Value| match open.last_mut() {
+        Some(parent) => {
+            if let Some(Value::Array(children)) = parent.get_mut("children") {
+                children.push(node);
+            }
+        }
+        None => {
+            if root.is_none() && node.is_object() {
+                root = Some(node);
+            }
+        }
+    };
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("{} at byte {}", e, reader.error_position()))?;
+        let is_empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(start) | Event::Empty(start) => {
+                let mut attributes = Map::new();
+                for attribute in start.attributes() {
+                    let attribute = attribute.map_err(|e| e.to_string())?;
+                    let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+                    let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+                    attributes.insert(key, Value::String(value.into_owned()));
+                }
+                let mut element = Map::new();
+                element.insert(
+                    "name".to_string(),
+                    Value::String(String::from_utf8_lossy(start.name().as_ref()).into_owned()),
+                );
+                element.insert("attributes".to_string(), Value::Object(attributes));
+                element.insert("children".to_string(), Value::Array(Vec::new()));
+                if is_empty {
+                    attach(&mut open, Value::Object(element));
+                } else {
+                    open.push(element);
+                }
+            }
+            Event::End(_) => {
+                if let Some(element) = open.pop() {
+                    attach(&mut open, Value::Object(element));
+                }
+            }
+            Event::Text(content) => {
+                let content = content.unescape().map_err(|e| e.to_string())?;
+                attach(&mut open, Value::String(content.into_owned()));
+            }
+            Event::CData(content) => {
+                let content = String::from_utf8_lossy(&content.into_inner()).into_owned();
+                attach(&mut open, Value::String(content));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !open.is_empty() {
+        return Err("unexpected end of document".to_string());
+    }
+    root.ok_or_else(|| "no root element".to_string())
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+use wreq::ClientBuilder;
+
+/// Connection reuse and TCP keepalive settings for a session's client.
+/// Unset fields keep wreq's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Idle connections kept per host; 0 turns connection reuse off
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection stays in the pool before it is closed
+    pub idle_timeout: Option<Duration>,
+    /// Idle time before the first TCP keepalive probe is sent
+    pub tcp_keepalive: Option<Duration>,
+    /// Interval between TCP keepalive probes
+    pub tcp_keepalive_interval: Option<Duration>,
+    /// Unanswered keepalive probes before the connection is dropped
+    pub tcp_keepalive_retries: Option<u32>,
+    /// `TCP_NODELAY`; wreq turns it on, so small writes go out without waiting for an ACK
+    pub tcp_nodelay: Option<bool>,
+    /// `SO_SNDBUF` and `SO_RCVBUF` in bytes; the kernel may round or double them
+    pub send_buffer_size: Option<usize>,
+    pub recv_buffer_size: Option<usize>,
+    /// How long the first address family gets before the other one is raced against it
+    /// (RFC 6555); `Some(None)` tries one family at a time
+    pub happy_eyeballs: Option<Option<Duration>>,
+    /// Connect time each address family gets, split evenly among its addresses
+    pub fallback_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(max) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if let Some(timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+
+        if let Some(interval) = self.tcp_keepalive_interval {
+            builder = builder.tcp_keepalive_interval(interval);
+        }
+
+        if let Some(retries) = self.tcp_keepalive_retries {
+            builder = builder.tcp_keepalive_retries(retries);
+        }
+
+        if let Some(nodelay) = self.tcp_nodelay {
+            builder = builder.tcp_nodelay(nodelay);
+        }
+
+        // Left unset, the kernel autotunes the buffers, which setting them turns off
+        if let Some(size) = self.send_buffer_size {
+            builder = builder.tcp_send_buffer_size(size);
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            builder = builder.tcp_recv_buffer_size(size);
+        }
+
+        if let Some(delay) = self.happy_eyeballs {
+            builder = builder.tcp_happy_eyeballs_timeout(delay);
+        }
+
+        // wreq divides this among the addresses it tries, so one that never answers
+        // leaves time for the next
+        if let Some(timeout) = self.fallback_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        builder
+    }
+}
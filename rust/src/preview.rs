@@ -0,0 +1,86 @@
+use crate::client::{outgoing_headers, RequestOptions};
+use wreq::header::{CONTENT_LENGTH, CONTENT_TYPE, HOST, TRAILER, TRANSFER_ENCODING};
+use wreq::Uri;
+
+/// What a request would put on the wire, built without sending it.
+pub struct RequestPreview {
+    pub method: String,
+    pub url: String,
+    /// Path and query, as written in the HTTP/1.1 request line
+    pub target: String,
+    /// Headers in the order they go out, emulation defaults and session cookies included
+    pub headers: Vec<(String, String)>,
+    /// Body size in bytes; `None` when it isn't known before sending (multipart, chunked uploads)
+    pub body_length: Option<u64>,
+}
+
+impl RequestPreview {
+    /// The request head as HTTP/1.1 text. Over HTTP/2 the same headers follow the pseudo-headers, lowercased.
+    pub fn raw(&self) -> String {
+        let mut raw = format!("{} {} HTTP/1.1\r\n", self.method, self.target);
+        for (name, value) in &self.headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str("\r\n");
+        raw
+    }
+}
+
+/// Build the request `send_request` would send for `options`, up to the point where it
+/// needs the network. Challenge auth and SigV4 signatures are added when sending, so
+/// they don't appear here.
+pub fn preview(options: &RequestOptions) -> RequestPreview {
+    let method = if options.method.is_empty() {
+        "GET".to_string()
+    } else {
+        options.method.clone()
+    };
+    let uri = options.url.parse::<Uri>().ok();
+    let target = uri
+        .as_ref()
+        .and_then(|uri| uri.path_and_query())
+        .map(|target| target.as_str().to_string())
+        .unwrap_or_else(|| "/".to_string());
+
+    let body_length = if let Some(json) = &options.json {
+        Some(json.to_string().len() as u64)
+    } else if let Some(body) = &options.body {
+        Some(body.len() as u64)
+    } else if let Some(path) = options.body_file.as_ref().filter(|_| options.trailers.is_empty()) {
+        std::fs::metadata(path).ok().map(|metadata| metadata.len())
+    } else {
+        None
+    };
+
+    let mut headers = outgoing_headers(options);
+    let has = |headers: &[(String, String)], name: &str| headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(name));
+
+    // wreq adds these once the body is attached, after the caller's and the emulation's headers.
+    // A multipart boundary is only picked when the form is built for sending
+    if options.multipart.is_some() && !has(&headers, CONTENT_TYPE.as_str()) {
+        headers.push((CONTENT_TYPE.to_string(), "multipart/form-data".to_string()));
+    }
+    if !options.trailers.is_empty() {
+        let names: Vec<&str> = options.trailers.iter().map(|(name, _)| name.as_str()).collect();
+        headers.push((TRAILER.to_string(), names.join(", ")));
+        headers.push((TRANSFER_ENCODING.to_string(), "chunked".to_string()));
+    }
+    if let Some(length) = body_length.filter(|_| !has(&headers, CONTENT_LENGTH.as_str())) {
+        headers.push((CONTENT_LENGTH.to_string(), length.to_string()));
+    }
+
+    // HTTP/1.1 leads with Host, as browsers do; HTTP/2 carries it as :authority instead
+    if !has(&headers, HOST.as_str()) {
+        if let Some(authority) = uri.as_ref().and_then(|uri| uri.authority()) {
+            headers.insert(0, (HOST.to_string(), authority.as_str().to_string()));
+        }
+    }
+
+    RequestPreview {
+        method,
+        url: options.url.clone(),
+        target,
+        headers,
+        body_length,
+    }
+}
@@ -0,0 +1,202 @@
+use crate::client::read_body;
+use crate::error::ErrorKind;
+use crate::session::build_proxy;
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use wreq::Client as HttpClient;
+use wreq_util::Emulation;
+
+/// Settings for a `checkProxies` run.
+#[derive(Debug, Clone)]
+pub struct ProxyCheckConfig {
+    /// Echo endpoint answering like httpbin's `/get`, or with a bare IP address
+    pub test_url: String,
+    pub timeout: Duration,
+    /// Proxies checked at once
+    pub concurrency: usize,
+    pub emulation: Emulation,
+}
+
+/// How much of the client a proxy gives away, judged from what the test URL saw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anonymity {
+    /// The client's own address reached the server
+    Transparent,
+    /// The address is hidden, but headers such as `Via` or `X-Forwarded-For` show a proxy
+    Anonymous,
+    /// Nothing marks the request as proxied
+    Elite,
+}
+
+impl Anonymity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Anonymity::Transparent => "transparent",
+            Anonymity::Anonymous => "anonymous",
+            Anonymity::Elite => "elite",
+        }
+    }
+}
+
+/// Outcome for one proxy, in the order the proxies were given.
+#[derive(Debug, Clone)]
+pub struct ProxyCheck {
+    pub proxy: String,
+    /// Status of the test URL's response, when one arrived
+    pub status: Option<u16>,
+    /// Time until the response head arrived, connecting through the proxy included
+    pub latency: Option<Duration>,
+    pub exit_ip: Option<IpAddr>,
+    pub anonymity: Option<Anonymity>,
+    pub error: Option<(ErrorKind, String)>,
+}
+
+impl ProxyCheck {
+    pub fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Most of a test URL's body that is read; echo endpoints answer with a few hundred bytes.
+const BODY_LIMIT: u64 = 64 * 1024;
+
+/// Headers proxies add that give the request away as proxied.
+const PROXY_HEADERS: &[&str] = &[
+    "via",
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-real-ip",
+    "x-proxy-id",
+    "proxy-connection",
+    "client-ip",
+];
+
+/// Check every proxy against the test URL, `concurrency` at a time. The test URL is also
+/// requested directly once, to learn the address a transparent proxy would reveal.
+pub async fn check_proxies(proxies: Vec<String>, config: ProxyCheckConfig) -> Result<Vec<ProxyCheck>> {
+    // Fresh and proxy-free, so system proxy settings and pooled connections stay out of it
+    let client = HttpClient::builder()
+        .emulation(config.emulation.clone())
+        .no_proxy()
+        .timeout(config.timeout)
+        .build()
+        .context("Failed to build HTTP client")?;
+
+    let own_ip = match fetch_echo(&client, &config.test_url, None).await {
+        Ok((_, _, echo)) => echo.ip,
+        Err(e) => {
+            tracing::debug!(error = %format!("{:#}", e), "direct request to the proxy test URL failed");
+            None
+        }
+    };
+
+    let checks = futures_util::stream::iter(proxies)
+        .map(|proxy| {
+            let client = &client;
+            let test_url = config.test_url.as_str();
+            async move { check_proxy(client, test_url, proxy, own_ip).await }
+        })
+        .buffered(config.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    Ok(checks)
+}
+
+async fn check_proxy(client: &HttpClient, test_url: &str, proxy: String, own_ip: Option<IpAddr>) -> ProxyCheck {
+    let mut check = ProxyCheck {
+        proxy,
+        status: None,
+        latency: None,
+        exit_ip: None,
+        anonymity: None,
+        error: None,
+    };
+
+    match fetch_echo(client, test_url, Some(&check.proxy)).await {
+        Ok((status, latency, echo)) => {
+            check.status = Some(status);
+            check.latency = Some(latency);
+            if status == 407 {
+                check.error = Some((ErrorKind::ProxyError, "Proxy requires authentication (407)".to_string()));
+            } else if !(200..300).contains(&status) {
+                check.error = Some((ErrorKind::RequestError, format!("Test URL answered HTTP {}", status)));
+            } else {
+                check.exit_ip = echo.ip;
+                check.anonymity = echo.anonymity(own_ip);
+            }
+        }
+        Err(e) => check.error = Some((ErrorKind::classify(&e), format!("{:#}", e))),
+    }
+    check
+}
+
+// What an echo endpoint reported about the request it received
+struct Echo {
+    ip: Option<IpAddr>,
+    /// Header names and values, when the endpoint echoes them
+    headers: Option<Vec<(String, String)>>,
+}
+
+impl Echo {
+    fn parse(body: &[u8]) -> Self {
+        let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) else {
+            // ipify and friends answer with the bare address
+            let ip = std::str::from_utf8(body).ok().and_then(|text| text.trim().parse().ok());
+            return Echo { ip, headers: None };
+        };
+
+        // httpbin reports `origin`, ipify's JSON format `ip`; a chain of proxies lists several
+        let ip = ["origin", "ip"]
+            .iter()
+            .filter_map(|key| value.get(key)?.as_str())
+            .find_map(|addresses| addresses.split(',').next()?.trim().parse().ok());
+        let headers = value.get("headers").and_then(|headers| headers.as_object()).map(|headers| {
+            headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+                    (name.to_ascii_lowercase(), value)
+                })
+                .collect()
+        });
+        Echo { ip, headers }
+    }
+
+    fn anonymity(&self, own_ip: Option<IpAddr>) -> Option<Anonymity> {
+        // Without the client's own address there is nothing to compare against
+        let own_ip = own_ip?;
+        if self.ip == Some(own_ip) {
+            return Some(Anonymity::Transparent);
+        }
+
+        let headers = self.headers.as_ref()?;
+        let own = own_ip.to_string();
+        if headers
+            .iter()
+            .any(|(_, value)| value.split([',', ' ', ';', '=']).any(|part| part == own))
+        {
+            Some(Anonymity::Transparent)
+        } else if headers.iter().any(|(name, _)| PROXY_HEADERS.contains(&name.as_str())) {
+            Some(Anonymity::Anonymous)
+        } else {
+            Some(Anonymity::Elite)
+        }
+    }
+}
+
+async fn fetch_echo(client: &HttpClient, test_url: &str, proxy: Option<&str>) -> Result<(u16, Duration, Echo)> {
+    let mut request = client.get(test_url);
+    if let Some(proxy) = proxy {
+        request = request.proxy(build_proxy(proxy, None, &[])?);
+    }
+
+    let started = Instant::now();
+    let response = request.send().await?;
+    let latency = started.elapsed();
+    let status = response.status().as_u16();
+    let body = read_body(response, Some(BODY_LIMIT)).await?;
+    Ok((status, latency, Echo::parse(&body)))
+}
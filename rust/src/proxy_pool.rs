@@ -0,0 +1,263 @@
+use crate::error::ErrorKind;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How a pool picks the proxy for each attempt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProxyStrategy {
+    /// Each proxy in turn
+    #[default]
+    RoundRobin,
+    /// Any proxy, uniformly
+    Random,
+    /// The same proxy for every request to a hostname, until it is quarantined
+    StickyPerHost,
+    /// The proxy with the fewest failures so far, taking turns on ties
+    LeastErrors,
+}
+
+impl ProxyStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "round-robin" => Some(Self::RoundRobin),
+            "random" => Some(Self::Random),
+            "sticky-per-host" => Some(Self::StickyPerHost),
+            "least-errors" => Some(Self::LeastErrors),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyPoolConfig {
+    pub proxies: Vec<String>,
+    pub strategy: ProxyStrategy,
+    /// Failures in a row that put a proxy in quarantine
+    pub max_failures: u32,
+    pub quarantine: Duration,
+    /// Other proxies a request is sent through, straight away, after a proxy failure
+    pub failover: u32,
+}
+
+/// One proxy's record, as reported by `proxyPoolStats`.
+#[derive(Debug, Clone)]
+pub struct ProxyStats {
+    pub url: String,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    /// Milliseconds since the Unix epoch at which the proxy is used again
+    pub quarantined_until: Option<f64>,
+}
+
+/// Hostnames remembered by a sticky pool before the assignments start over.
+const MAX_STICKY_HOSTS: usize = 4096;
+
+struct Entry {
+    url: String,
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    quarantined_until: Option<Instant>,
+}
+
+impl Entry {
+    fn available(&self, now: Instant) -> bool {
+        self.quarantined_until.is_none_or(|until| until <= now)
+    }
+}
+
+struct State {
+    entries: Vec<Entry>,
+    next: usize,
+    sticky: HashMap<String, usize>,
+}
+
+pub struct ProxyPool {
+    config: ProxyPoolConfig,
+    state: Mutex<State>,
+}
+
+impl fmt::Debug for ProxyPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyPool").field("strategy", &self.config.strategy).finish_non_exhaustive()
+    }
+}
+
+impl ProxyPool {
+    fn new(config: ProxyPoolConfig) -> Self {
+        let entries = config
+            .proxies
+            .iter()
+            .map(|url| Entry {
+                url: url.clone(),
+                successes: 0,
+                failures: 0,
+                consecutive_failures: 0,
+                quarantined_until: None,
+            })
+            .collect();
+        Self {
+            config,
+            state: Mutex::new(State {
+                entries,
+                next: 0,
+                sticky: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn failover(&self) -> u32 {
+        self.config.failover
+    }
+
+    pub fn size(&self) -> usize {
+        self.config.proxies.len()
+    }
+
+    /// Proxy for an attempt at a request to `host`. Quarantined proxies, and those in
+    /// `failed` while others remain, are skipped; when every proxy is quarantined, the one
+    /// released soonest is used anyway rather than failing the request.
+    pub fn pick(&self, host: Option<&str>, failed: &[String]) -> String {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let mut candidates: Vec<usize> = (0..state.entries.len())
+            .filter(|&index| !failed.contains(&state.entries[index].url))
+            .collect();
+        if candidates.is_empty() {
+            candidates = (0..state.entries.len()).collect();
+        }
+        let available: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&index| state.entries[index].available(now))
+            .collect();
+
+        let index = if available.is_empty() {
+            candidates
+                .iter()
+                .copied()
+                .min_by_key(|&index| state.entries[index].quarantined_until)
+                .unwrap_or_default()
+        } else {
+            match self.config.strategy {
+                ProxyStrategy::RoundRobin => next_in_turn(&mut state, &available),
+                ProxyStrategy::Random => available[fastrand::usize(..available.len())],
+                ProxyStrategy::StickyPerHost => {
+                    let host = host.unwrap_or_default();
+                    match state.sticky.get(host) {
+                        Some(&index) if available.contains(&index) => index,
+                        _ => {
+                            let index = next_in_turn(&mut state, &available);
+                            if state.sticky.len() >= MAX_STICKY_HOSTS {
+                                state.sticky.clear();
+                            }
+                            state.sticky.insert(host.to_string(), index);
+                            index
+                        }
+                    }
+                }
+                ProxyStrategy::LeastErrors => {
+                    let fewest = available.iter().map(|&index| state.entries[index].failures).min();
+                    let tied: Vec<usize> = available
+                        .iter()
+                        .copied()
+                        .filter(|&index| Some(state.entries[index].failures) == fewest)
+                        .collect();
+                    next_in_turn(&mut state, &tied)
+                }
+            }
+        };
+
+        state.entries[index].url.clone()
+    }
+
+    /// Record how an attempt through `proxy` went. A proxy that has failed `max_failures`
+    /// times in a row is quarantined; once released, a single further failure sends it
+    /// back, while a success clears its streak.
+    pub fn report(&self, proxy: &str, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        let Some(entry) = state.entries.iter_mut().find(|entry| entry.url == proxy) else {
+            return;
+        };
+
+        if failed {
+            entry.failures += 1;
+            entry.consecutive_failures += 1;
+            if entry.consecutive_failures >= self.config.max_failures {
+                entry.quarantined_until = Some(Instant::now() + self.config.quarantine);
+                tracing::warn!(failures = entry.consecutive_failures, "proxy quarantined");
+            }
+        } else {
+            entry.successes += 1;
+            entry.consecutive_failures = 0;
+            entry.quarantined_until = None;
+        }
+    }
+
+    pub fn stats(&self) -> Vec<ProxyStats> {
+        let now = Instant::now();
+        let wall = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .iter()
+            .map(|entry| ProxyStats {
+                url: entry.url.clone(),
+                successes: entry.successes,
+                failures: entry.failures,
+                consecutive_failures: entry.consecutive_failures,
+                quarantined_until: entry
+                    .quarantined_until
+                    .filter(|until| *until > now)
+                    .map(|until| (wall + (until - now)).as_secs_f64() * 1000.0),
+            })
+            .collect()
+    }
+}
+
+// The first of `candidates` at or after the cursor, which then moves past it
+fn next_in_turn(state: &mut State, candidates: &[usize]) -> usize {
+    let cursor = state.next;
+    let index = candidates
+        .iter()
+        .copied()
+        .find(|&index| index >= cursor)
+        .unwrap_or(candidates[0]);
+    state.next = index + 1;
+    index
+}
+
+/// Whether an attempt's outcome counts against the proxy it went through: failures to
+/// reach or tunnel through it, and `407 Proxy Authentication Required`. Errors from the
+/// origin server, or from the request itself, don't.
+pub fn is_proxy_failure(result: Result<u16, &anyhow::Error>) -> bool {
+    match result {
+        Ok(status) => status == 407,
+        Err(error) => matches!(
+            ErrorKind::classify(error),
+            ErrorKind::ProxyError | ErrorKind::ConnectError | ErrorKind::Timeout | ErrorKind::DnsFailure
+        ),
+    }
+}
+
+static POOLS: Lazy<RwLock<HashMap<String, Arc<ProxyPool>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Register a pool under `id`, replacing any pool already there.
+pub fn create(id: String, config: ProxyPoolConfig) {
+    POOLS.write().unwrap().insert(id, Arc::new(ProxyPool::new(config)));
+}
+
+pub fn get(id: &str) -> Option<Arc<ProxyPool>> {
+    POOLS.read().unwrap().get(id).cloned()
+}
+
+/// Remove a pool. Requests already holding it finish with it.
+pub fn remove(id: &str) -> bool {
+    POOLS.write().unwrap().remove(id).is_some()
+}
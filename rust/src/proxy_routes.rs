@@ -0,0 +1,91 @@
+use crate::concurrency::request_host;
+use crate::proxy_pool::ProxyPool;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::sync::{Arc, RwLock};
+
+/// A hostname pattern a route applies to.
+#[derive(Debug, Clone)]
+pub enum HostPattern {
+    /// `*` matches any run of characters; a pattern without one also covers subdomains,
+    /// like `NO_PROXY` entries
+    Glob(String),
+    Regex(Regex),
+}
+
+impl HostPattern {
+    pub fn glob(pattern: &str) -> Self {
+        HostPattern::Glob(pattern.trim_start_matches('.').to_ascii_lowercase())
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Glob(pattern) if !pattern.contains('*') => {
+                host == pattern || host.strip_suffix(pattern.as_str()).is_some_and(|rest| rest.ends_with('.'))
+            }
+            HostPattern::Glob(pattern) => glob_matches(pattern.as_bytes(), host.as_bytes()),
+            HostPattern::Regex(regex) => regex.is_match(host),
+        }
+    }
+}
+
+// Match `*` wildcards against the whole of `text`, backtracking to the last star on a mismatch
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Where requests to a matching host go.
+#[derive(Debug, Clone)]
+pub enum RouteTarget {
+    /// Straight to the server, skipping the request's and the session's proxy
+    Direct,
+    Proxy(String),
+    Pool(Arc<ProxyPool>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub patterns: Vec<HostPattern>,
+    pub target: RouteTarget,
+}
+
+static ROUTES: Lazy<RwLock<Arc<Vec<Route>>>> = Lazy::new(|| RwLock::new(Arc::new(Vec::new())));
+
+/// Replace the routing table; an empty one leaves every request's proxy as it is.
+pub fn configure(routes: Vec<Route>) {
+    *ROUTES.write().unwrap() = Arc::new(routes);
+}
+
+/// The first route whose patterns match the hostname of `url`.
+pub fn route(url: &str) -> Option<RouteTarget> {
+    let routes = ROUTES.read().unwrap().clone();
+    if routes.is_empty() {
+        return None;
+    }
+
+    let host = request_host(url)?;
+    // IPv6 literals are matched without their brackets
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    routes
+        .iter()
+        .find(|route| route.patterns.iter().any(|pattern| pattern.matches(host)))
+        .map(|route| route.target.clone())
+}
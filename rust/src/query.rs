@@ -0,0 +1,111 @@
+/// One query parameter's value: a single value, or several sent under the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryValue {
+    Single(String),
+    List(Vec<String>),
+}
+
+/// How a parameter with several values is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayFormat {
+    /// `a=1&a=2`
+    #[default]
+    Repeat,
+    /// `a[]=1&a[]=2`, as PHP and Rails expect
+    Brackets,
+}
+
+impl ArrayFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "repeat" => Some(ArrayFormat::Repeat),
+            "brackets" => Some(ArrayFormat::Brackets),
+            _ => None,
+        }
+    }
+}
+
+/// Percent-encode a query name or value, leaving only RFC 3986 unreserved characters as
+/// they are; spaces become `%20`.
+pub fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for &byte in value.as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+// The `application/x-www-form-urlencoded` serializer browsers submit forms with: spaces
+// become `+`, and `*` is left alone where `~` is escaped
+fn encode_form_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for &byte in value.as_bytes() {
+        match byte {
+            b' ' => encoded.push('+'),
+            b'*' | b'-' | b'.' | b'_' => encoded.push(byte as char),
+            _ if byte.is_ascii_alphanumeric() => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Encode form fields as a request body, in the order given. Fields with several values
+/// are repeated, as a form with several inputs of the same name would send them.
+pub fn encode_form(fields: &[(String, QueryValue)]) -> String {
+    let mut pairs = Vec::new();
+    for (name, value) in fields {
+        let values = match value {
+            QueryValue::Single(value) => std::slice::from_ref(value),
+            QueryValue::List(values) => values.as_slice(),
+        };
+        for value in values {
+            pairs.push(format!("{}={}", encode_form_component(name), encode_form_component(value)));
+        }
+    }
+    pairs.join("&")
+}
+
+/// Encode parameters as `name=value` pairs joined with `&`, in the order given.
+pub fn encode_pairs(params: &[(String, QueryValue)], format: ArrayFormat) -> String {
+    let mut pairs = Vec::new();
+    for (name, value) in params {
+        let name = encode_component(name);
+        match value {
+            QueryValue::Single(value) => pairs.push(format!("{}={}", name, encode_component(value))),
+            QueryValue::List(values) => {
+                let name = match format {
+                    ArrayFormat::Repeat => name,
+                    ArrayFormat::Brackets => format!("{}%5B%5D", name),
+                };
+                for value in values {
+                    pairs.push(format!("{}={}", name, encode_component(value)));
+                }
+            }
+        }
+    }
+    pairs.join("&")
+}
+
+/// Append parameters to `url`, after any query it already has and before its fragment.
+pub fn append_to_url(url: &str, params: &[(String, QueryValue)], format: ArrayFormat) -> String {
+    let encoded = encode_pairs(params, format);
+    if encoded.is_empty() {
+        return url.to_string();
+    }
+
+    let (base, fragment) = match url.find('#') {
+        Some(index) => url.split_at(index),
+        None => (url, ""),
+    };
+    let separator = match base.find('?') {
+        None => "?",
+        Some(index) if index + 1 == base.len() || base.ends_with('&') => "",
+        Some(_) => "&",
+    };
+    format!("{}{}{}{}", base, separator, encoded, fragment)
+}
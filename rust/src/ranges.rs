@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use wreq::header::{HeaderMap, HeaderName, CONTENT_LENGTH, CONTENT_RANGE, ETAG, LAST_MODIFIED};
+
+/// A `Content-Range` header: `bytes start-end/total` on a `206`, `bytes */total` on a `416`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// First and last byte served, inclusive
+    pub range: Option<(u64, u64)>,
+    /// Full length of the body, unless the server sent `*`
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let rest = value.trim().strip_prefix("bytes")?;
+        let (range, total) = rest.trim_start().split_once('/')?;
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse().ok()?),
+        };
+        let range = match range.trim() {
+            "*" => None,
+            range => {
+                let (first, last) = range.split_once('-')?;
+                let (first, last): (u64, u64) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+                if last < first {
+                    return None;
+                }
+                Some((first, last))
+            }
+        };
+        Some(Self { range, total })
+    }
+}
+
+/// What identifies the body a partial download holds, kept next to it in `<dest>.resume`
+/// so a later download can ask for the rest of that same body with `If-Range`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeState {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    total: Option<u64>,
+}
+
+impl ResumeState {
+    /// The state of a response's body, if it has a validator `If-Range` accepts: a strong
+    /// ETag or a `Last-Modified` date.
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Option<Self> {
+        let header = |name: HeaderName| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let etag = header(ETAG).filter(|etag| !etag.starts_with("W/"));
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+
+        let total = match ContentRange::from_headers(headers) {
+            Some(range) => range.total,
+            None => header(CONTENT_LENGTH).and_then(|length| length.parse().ok()),
+        };
+        Some(Self {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            total,
+        })
+    }
+
+    /// Value for `If-Range`; the ETag when there is one, since it catches changes within a second.
+    pub fn validator(&self) -> &str {
+        self.etag.as_deref().or(self.last_modified.as_deref()).unwrap_or_default()
+    }
+
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Whether a response carries the same validators, i.e. serves the same body.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        match Self::from_headers(&self.url, headers) {
+            Some(other) if self.etag.is_some() && other.etag.is_some() => self.etag == other.etag,
+            Some(other) if self.last_modified.is_some() && other.last_modified.is_some() => {
+                self.last_modified == other.last_modified
+            }
+            // A 206 need not repeat the validators; If-Range already made the server check them
+            _ => true,
+        }
+    }
+}
+
+fn state_path(dest: &Path) -> PathBuf {
+    let mut path = dest.as_os_str().to_owned();
+    path.push(".resume");
+    PathBuf::from(path)
+}
+
+/// Where a download of `url` to `dest` can pick up: the size of the partial file and the
+/// state saved with it. `None` when there is nothing to resume, or the saved state is
+/// missing, for another URL, or doesn't fit the file.
+pub async fn load(dest: &Path, url: &str) -> Option<(u64, ResumeState)> {
+    let len = tokio::fs::metadata(dest).await.ok()?.len();
+    let saved = tokio::fs::read(state_path(dest)).await.ok()?;
+    let state: ResumeState = serde_json::from_slice(&saved).ok()?;
+    if len == 0 || state.url != url || state.total.is_some_and(|total| len > total) {
+        return None;
+    }
+    Some((len, state))
+}
+
+pub async fn save(dest: &Path, state: &ResumeState) -> Result<()> {
+    let path = state_path(dest);
+    let json = serde_json::to_vec(state).context("Failed to serialize the resume state")?;
+    tokio::fs::write(&path, json)
+        .await
+        .with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+pub async fn remove(dest: &Path) {
+    let _ = tokio::fs::remove_file(state_path(dest)).await;
+}
@@ -0,0 +1,144 @@
+use crate::concurrency::request_host;
+use crate::error::RateLimited;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// What a token bucket is shared by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitScope {
+    /// One bucket for every request
+    #[default]
+    Global,
+    /// One bucket per hostname
+    Host,
+    /// One bucket per proxy URL, with direct requests sharing another
+    Proxy,
+}
+
+impl RateLimitScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "global" => Some(Self::Global),
+            "host" => Some(Self::Host),
+            "proxy" => Some(Self::Proxy),
+            _ => None,
+        }
+    }
+}
+
+/// Request rate limit, set from Node with `setRateLimit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    pub requests_per_second: f64,
+    /// Requests that may go out back to back after an idle period
+    pub burst: f64,
+    pub scope: RateLimitScope,
+    /// Reject with `ERR_RATE_LIMITED` instead of waiting for a token
+    pub fail_fast: bool,
+}
+
+/// Buckets are pruned once this many have piled up, dropping the full (idle) ones.
+const MAX_BUCKETS: usize = 1024;
+
+struct Bucket {
+    /// Goes negative while requests are waiting on tokens they have reserved
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &RateLimitConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.requests_per_second).min(config.burst);
+        self.updated = now;
+    }
+}
+
+struct Limiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Limiter {
+    /// Take a token, returning how long to wait before using it. With `fail_fast`
+    /// nothing is reserved and `None` means the bucket is empty.
+    fn reserve(&self, key: String) -> Option<Duration> {
+        let config = &self.config;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| {
+                bucket.refill(config, now);
+                bucket.tokens < config.burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: config.burst,
+            updated: now,
+        });
+        bucket.refill(config, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Some(Duration::ZERO);
+        }
+        if config.fail_fast {
+            return None;
+        }
+
+        // Reserve the next token, queueing behind earlier reservations
+        bucket.tokens -= 1.0;
+        Some(Duration::from_secs_f64(-bucket.tokens / config.requests_per_second))
+    }
+}
+
+static LIMITER: Lazy<RwLock<Option<Arc<Limiter>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Replace the rate limit, or remove it with `None`. Requests already waiting keep
+/// the reservation they made.
+pub fn configure(config: Option<RateLimitConfig>) {
+    let limiter = config.map(|config| {
+        Arc::new(Limiter {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    });
+    *LIMITER.write().unwrap() = limiter;
+}
+
+pub fn current_config() -> Option<RateLimitConfig> {
+    LIMITER.read().unwrap().as_ref().map(|limiter| limiter.config)
+}
+
+/// Wait for a token before sending a request to `url` (through `proxy`, if any).
+pub async fn throttle(url: &str, proxy: Option<&str>) -> Result<(), RateLimited> {
+    let Some(limiter) = LIMITER.read().unwrap().clone() else {
+        return Ok(());
+    };
+
+    let key = match limiter.config.scope {
+        RateLimitScope::Global => String::new(),
+        RateLimitScope::Host => request_host(url).unwrap_or_default(),
+        RateLimitScope::Proxy => proxy.unwrap_or_default().to_string(),
+    };
+
+    // Proxy URLs can carry credentials, so only hostnames make it into the error
+    let host = (limiter.config.scope == RateLimitScope::Host).then(|| key.clone());
+
+    match limiter.reserve(key) {
+        Some(wait) => {
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            Ok(())
+        }
+        None => Err(RateLimited {
+            requests_per_second: limiter.config.requests_per_second,
+            host,
+        }),
+    }
+}
@@ -0,0 +1,97 @@
+use crate::client::Response;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Longest inline script still taken for a plain redirect; anything longer is an app that
+/// may assign `location` only on some path.
+const MAX_SCRIPT_LEN: usize = 1024;
+
+// `location = "..."`, `window.location.href = '...'`, `location.replace("...")` and the like
+static LOCATION_SCRIPT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+        (?:\b(?:window|document|self|top)\s*\.\s*)?\blocation
+        (?:
+            (?:\s*\.\s*href)?\s*=\s*
+            | \s*\.\s*(?:replace|assign)\s*\(\s*
+        )
+        (?:"([^"\\]*)"|'([^'\\]*)')"#,
+    )
+    .expect("static regex")
+});
+
+/// Where a page sends the browser without an HTTP redirect: a `Refresh` header, a
+/// `<meta http-equiv="refresh">` tag, or a short inline script that assigns `location`.
+/// The target is resolved against the page; refreshes of the page itself don't count.
+pub fn target(response: &Response) -> Option<String> {
+    if !(200..300).contains(&response.status) {
+        return None;
+    }
+    let header = |name: &str| {
+        response
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let target = match header("refresh").and_then(refresh_url) {
+        Some(url) => url,
+        None if header("content-type").is_none_or(|value| value.to_ascii_lowercase().contains("html")) => {
+            html_target(&response.body)?
+        }
+        None => return None,
+    };
+
+    let page = Url::parse(&response.url).ok()?;
+    let target = page.join(&target).ok()?;
+    let same_page = target.as_str().split('#').next() == page.as_str().split('#').next();
+    (matches!(target.scheme(), "http" | "https") && !same_page).then(|| target.to_string())
+}
+
+fn html_target(body: &str) -> Option<String> {
+    let document = Html::parse_document(body);
+    let select = |selector: &str| Selector::parse(selector).expect("static selector");
+
+    let meta = document
+        .select(&select("meta[http-equiv][content]"))
+        .map(|element| element.value())
+        .filter(|meta| meta.attr("http-equiv").is_some_and(|value| value.eq_ignore_ascii_case("refresh")))
+        .find_map(|meta| meta.attr("content").and_then(refresh_url));
+    if meta.is_some() {
+        return meta;
+    }
+
+    document
+        .select(&select("script:not([src])"))
+        .map(|script| script.text().collect::<String>())
+        .filter(|text| text.trim().len() <= MAX_SCRIPT_LEN)
+        .find_map(|text| {
+            let captures = LOCATION_SCRIPT.captures(&text)?;
+            let url = captures.get(1).or_else(|| captures.get(2))?;
+            Some(url.as_str().to_string())
+        })
+}
+
+// The URL of a refresh like `0; url=/next` or `5;URL='/next'`; one without a URL just
+// reloads the page. Follows the HTML spec's parsing loosely
+fn refresh_url(content: &str) -> Option<String> {
+    let rest = content.trim_start().trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let rest = rest.trim_start().strip_prefix([';', ','])?.trim_start();
+    let rest = match rest.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => match rest[3..].trim_start().strip_prefix('=') {
+            Some(rest) => rest.trim_start(),
+            None => rest,
+        },
+        _ => rest,
+    };
+
+    let url = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or_default(),
+        _ => rest,
+    };
+    let url = url.trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
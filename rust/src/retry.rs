@@ -0,0 +1,87 @@
+use serde::Deserialize;
+use std::time::{Duration, SystemTime};
+use wreq::header::RETRY_AFTER;
+
+/// Retry behaviour for a single request. Delays double on every retry, up to the cap,
+/// unless the server sends `Retry-After`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, the first one included, so the default never retries
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled on every attempt
+    pub backoff_base: u64,
+    /// Upper bound for any single delay in milliseconds, `Retry-After` included
+    pub backoff_cap: u64,
+    /// Randomise up to half of each delay to avoid synchronized retries
+    pub jitter: bool,
+    /// Response statuses that trigger a retry
+    #[serde(rename = "retryStatuses")]
+    pub statuses: Vec<u16>,
+    /// Retry connection failures, resets and timeouts
+    #[serde(rename = "retryOnNetworkError")]
+    pub on_network_error: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff_base: 100,
+            backoff_cap: 10_000,
+            jitter: true,
+            statuses: vec![429, 502, 503, 504],
+            on_network_error: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn should_retry_status(&self, status: u16) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// Only failures that happen on the wire are worth retrying; builder and
+    /// redirect errors would fail the same way again.
+    pub fn should_retry_error(&self, error: &anyhow::Error) -> bool {
+        self.on_network_error
+            && error.chain().any(|cause| {
+                cause.downcast_ref::<wreq::Error>().is_some_and(|e| {
+                    e.is_connect() || e.is_timeout() || e.is_connection_reset() || e.is_request()
+                })
+            })
+    }
+
+    /// Exponential backoff for the retry following `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .backoff_base
+            .saturating_mul(1u64 << exponent)
+            .min(self.backoff_cap);
+
+        // Equal jitter: keep half the delay, randomise the rest
+        let delay = if self.jitter && delay > 1 {
+            delay / 2 + fastrand::u64(0..=delay / 2)
+        } else {
+            delay
+        };
+
+        Duration::from_millis(delay)
+    }
+
+    /// Delay requested by the server via `Retry-After`, capped at `backoff_cap`.
+    pub fn retry_after(&self, response: &wreq::Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        let delay = match value.parse::<u64>() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => httpdate::parse_http_date(value)
+                .ok()?
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+        };
+
+        Some(delay.min(Duration::from_millis(self.backoff_cap)))
+    }
+}
@@ -0,0 +1,253 @@
+use crate::client::{make_request, RequestOptions};
+use anyhow::{Context, Result};
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// How much of a robots.txt is parsed; RFC 9309 §2.5 asks for at least 500 KiB.
+const MAX_PARSED_BYTES: usize = 512 * 1024;
+
+/// How long a fetched robots.txt is used before it is fetched again (RFC 9309 §2.4).
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A server error keeps the site closed for a shorter while, so crawling resumes once it recovers.
+const UNREACHABLE_TTL: Duration = Duration::from_secs(60);
+
+/// Most origins whose rules are kept before the least recently used are dropped.
+const MAX_ENTRIES: u64 = 10_000;
+
+/// A parsed robots.txt, or what its absence means.
+#[derive(Debug, Default)]
+pub struct Robots {
+    groups: Vec<Group>,
+    /// `Sitemap` lines, which apply to every crawler
+    pub sitemaps: Vec<String>,
+    /// The server failed to answer: nothing may be crawled (RFC 9309 §2.3.1.4)
+    unreachable: bool,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    /// Lowercased product tokens from the group's `User-agent` lines
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    /// Percent-encoded path pattern, with `*` wildcards and an optional `$` end anchor
+    pattern: Vec<u8>,
+}
+
+/// What a robots.txt says about one URL for one crawler.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    pub allowed: bool,
+    /// Seconds to wait between requests, from the matching group's `Crawl-delay`
+    pub crawl_delay: Option<f64>,
+    pub sitemaps: Vec<String>,
+    /// Status the robots.txt was fetched with
+    pub status: u16,
+}
+
+impl Robots {
+    /// Parse a robots.txt body. Lines that aren't `name: value` pairs are skipped, as are
+    /// rules before the first `User-agent` line.
+    pub fn parse(text: &str) -> Self {
+        let mut robots = Robots::default();
+        // Consecutive User-agent lines share one group; a rule ends the run
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        robots.groups.push(Group::default());
+                        in_agents = true;
+                    }
+                    if let Some(group) = robots.groups.last_mut() {
+                        group.agents.push(product_token(value));
+                    }
+                }
+                name @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty Disallow allows everything, which is what no rule does too
+                    let Some(group) = robots.groups.last_mut().filter(|_| !value.is_empty()) else {
+                        continue;
+                    };
+                    group.rules.push(Rule {
+                        allow: name == "allow",
+                        pattern: normalize(value.as_bytes()),
+                    });
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    let delay = value.parse::<f64>().ok().filter(|delay| delay.is_finite() && *delay >= 0.0);
+                    if let (Some(group), Some(delay)) = (robots.groups.last_mut(), delay) {
+                        group.crawl_delay.get_or_insert(delay);
+                    }
+                }
+                "sitemap" if !value.is_empty() => robots.sitemaps.push(value.to_string()),
+                _ => {}
+            }
+        }
+        robots
+    }
+
+    /// Whether `path` (with its query) may be crawled by `user_agent`, and how fast.
+    pub fn check(&self, path: &str, user_agent: &str, status: u16) -> Verdict {
+        let token = product_token(user_agent);
+        let named: Vec<&Group> = self.groups.iter().filter(|group| group.agents.contains(&token)).collect();
+        // Groups naming the crawler replace the `*` ones rather than adding to them
+        let groups = if named.is_empty() {
+            self.groups.iter().filter(|group| group.agents.iter().any(|agent| agent == "*")).collect()
+        } else {
+            named
+        };
+
+        let path = normalize(path.as_bytes());
+        // The longest matching pattern wins, Allow breaking ties (RFC 9309 §2.2.2)
+        let allowed = path == b"/robots.txt"
+            || (!self.unreachable
+                && groups
+                    .iter()
+                    .flat_map(|group| &group.rules)
+                    .filter(|rule| matches(&rule.pattern, &path))
+                    .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+                    .is_none_or(|rule| rule.allow));
+        Verdict {
+            allowed,
+            crawl_delay: groups.iter().find_map(|group| group.crawl_delay),
+            sitemaps: self.sitemaps.clone(),
+            status,
+        }
+    }
+}
+
+// The name a crawler matches groups by: `Googlebot/2.1 (+http://...)` is `googlebot`
+fn product_token(user_agent: &str) -> String {
+    let token = user_agent.trim().split(['/', ' ', '\t']).next().unwrap_or_default();
+    token.to_ascii_lowercase()
+}
+
+// Percent-encode what isn't ASCII and uppercase existing escapes, so `/ä`, `/%c3%a4` and
+// `/%C3%A4` compare equal
+fn normalize(path: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let byte = path[i];
+        let escape = path.get(i + 1..i + 3).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        if let (b'%', Some(hex)) = (byte, escape) {
+            normalized.push(b'%');
+            normalized.extend(hex.iter().map(u8::to_ascii_uppercase));
+            i += 3;
+            continue;
+        }
+        if byte.is_ascii() && !byte.is_ascii_control() && byte != b' ' {
+            normalized.push(byte);
+        } else {
+            normalized.extend_from_slice(format!("%{:02X}", byte).as_bytes());
+        }
+        i += 1;
+    }
+    normalized
+}
+
+// Match a pattern against the start of a path: `*` stands for any run of bytes and a
+// trailing `$` for the end of the path
+fn matches(pattern: &[u8], path: &[u8]) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix(b"$") {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    // Every offset into the path the pattern so far can end at, in ascending order
+    let mut ends = vec![0];
+    for &byte in pattern {
+        if byte == b'*' {
+            ends = (ends[0]..=path.len()).collect();
+        } else {
+            ends = ends
+                .into_iter()
+                .filter(|&end| path.get(end) == Some(&byte))
+                .map(|end| end + 1)
+                .collect();
+        }
+        if ends.is_empty() {
+            return false;
+        }
+    }
+    !anchored || ends.last() == Some(&path.len())
+}
+
+struct Entry {
+    robots: OnceCell<(Arc<Robots>, u16, Instant)>,
+}
+
+// Keyed like conditional requests: sessions keep their own, and the rest share one per origin
+static CACHE: Lazy<Cache<String, Arc<Entry>>> = Lazy::new(|| Cache::builder().max_capacity(MAX_ENTRIES).build());
+
+/// Check `url` against its origin's robots.txt, fetched with `options` (whose URL is the
+/// robots.txt one) the first time and cached for a day. Requests that find the site
+/// unreachable fail and aren't cached; a missing or forbidden file allows everything.
+pub async fn check(options: RequestOptions, url: &str, user_agent: &str) -> Result<Verdict> {
+    let target = url::Url::parse(url).with_context(|| format!("Invalid URL '{}'", url))?;
+    let path = match target.query() {
+        Some(query) => format!("{}?{}", target.path(), query),
+        None => target.path().to_string(),
+    };
+
+    let session = if options.ephemeral { "" } else { options.session_id.as_str() };
+    let key = format!("{}\0{}", session, options.url);
+    loop {
+        // Concurrent checks of a new origin wait on the one fetch
+        let entry = CACHE.get_with(key.clone(), || {
+            Arc::new(Entry {
+                robots: OnceCell::new(),
+            })
+        });
+        let (robots, status, expires) = entry.robots.get_or_try_init(|| fetch(options.clone())).await?;
+        if *expires > Instant::now() {
+            return Ok(robots.check(&path, user_agent, *status));
+        }
+        // Unless another caller has already put a fresh one in its place
+        if CACHE.get(&key).is_some_and(|current| Arc::ptr_eq(&current, &entry)) {
+            CACHE.invalidate(&key);
+        }
+    }
+}
+
+async fn fetch(mut options: RequestOptions) -> Result<(Arc<Robots>, u16, Instant)> {
+    options.method = "GET".to_string();
+    let response = make_request(options).await?;
+    let status = response.status;
+    let robots = match status {
+        200..=299 => {
+            let body = &response.body_bytes[..response.body_bytes.len().min(MAX_PARSED_BYTES)];
+            Robots::parse(&String::from_utf8_lossy(body))
+        }
+        // Too many requests is the server's trouble, not a missing file
+        429 | 500.. => Robots {
+            unreachable: true,
+            ..Robots::default()
+        },
+        // Missing, forbidden, or redirected somewhere that isn't a robots.txt: nothing is off limits
+        _ => Robots::default(),
+    };
+    let ttl = if robots.unreachable { UNREACHABLE_TTL } else { TTL };
+    Ok((Arc::new(robots), status, Instant::now() + ttl))
+}
+
+/// Forget every fetched robots.txt.
+pub fn clear() {
+    CACHE.invalidate_all();
+}
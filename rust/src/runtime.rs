@@ -0,0 +1,203 @@
+use crate::error::ShuttingDown;
+use crate::session::clear_all_managed_sessions;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// How long requests cut off at the end of a shutdown get to reject before the runtime goes.
+const CANCEL_GRACE: Duration = Duration::from_millis(250);
+
+/// Settings for the shared runtime, from `configureRuntime`; `None` keeps tokio's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// Defaults to one per CPU core
+    pub worker_threads: Option<usize>,
+    pub max_blocking_threads: Option<usize>,
+    /// Name given to every runtime thread, to tell them apart in profilers and `top`
+    pub thread_name: Option<String>,
+    pub thread_stack_size: Option<usize>,
+}
+
+impl RuntimeConfig {
+    fn build(&self) -> Runtime {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(threads) = self.worker_threads {
+            builder.worker_threads(threads);
+        }
+        if let Some(threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(threads);
+        }
+        if let Some(name) = &self.thread_name {
+            builder.thread_name(name);
+        }
+        if let Some(size) = self.thread_stack_size {
+            builder.thread_stack_size(size);
+        }
+        builder.build().expect("Failed to create shared HTTP runtime")
+    }
+}
+
+struct State {
+    config: RuntimeConfig,
+    /// Started on first use, and again on the first use after a shutdown
+    runtime: Option<Runtime>,
+    /// Set from the start of a shutdown until the runtime is gone
+    draining: bool,
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| {
+    Mutex::new(State {
+        config: RuntimeConfig::default(),
+        runtime: None,
+        draining: false,
+    })
+});
+
+/// Tasks started for JS calls that haven't finished, which a shutdown waits for.
+static IN_FLIGHT: Lazy<(Mutex<usize>, Condvar)> = Lazy::new(|| (Mutex::new(0), Condvar::new()));
+
+/// Flipped when a shutdown's grace period runs out, cutting off the requests still running.
+static CANCEL: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+fn handle() -> Handle {
+    let mut state = STATE.lock().unwrap();
+    let State { config, runtime, .. } = &mut *state;
+    runtime.get_or_insert_with(|| config.build()).handle().clone()
+}
+
+/// Settings the runtime starts with. Only a runtime that isn't running can be configured:
+/// before the first request, or after a shutdown.
+pub fn configure(config: RuntimeConfig) -> Result<()> {
+    let mut state = STATE.lock().unwrap();
+    if state.runtime.is_some() {
+        anyhow::bail!("The HTTP runtime is already running; call shutdown() before configuring it");
+    }
+    state.config = config;
+    Ok(())
+}
+
+// Counts a task as in flight until dropped, however the task ends
+struct InFlight;
+
+impl InFlight {
+    fn enter() -> Self {
+        *IN_FLIGHT.0.lock().unwrap() += 1;
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let (count, idle) = &*IN_FLIGHT;
+        let mut count = count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            idle.notify_all();
+        }
+    }
+}
+
+/// Run a task for a JS call on the shared runtime, starting the runtime if it isn't
+/// running. A shutdown waits for these tasks.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let in_flight = InFlight::enter();
+    handle().spawn(async move {
+        let output = future.await;
+        drop(in_flight);
+        output
+    })
+}
+
+/// Blocking work, such as writing a cookie file, that a shutdown also waits for.
+pub fn spawn_blocking<F, R>(work: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let in_flight = InFlight::enter();
+    handle().spawn_blocking(move || {
+        let output = work();
+        drop(in_flight);
+        output
+    })
+}
+
+/// Background work a shutdown doesn't wait for and simply stops, like the client cache sweeper.
+pub fn spawn_detached<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    handle().spawn(future)
+}
+
+/// Run a request unless a shutdown is under way, giving up on it if the shutdown's
+/// grace period runs out first.
+pub async fn cancellable<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    if STATE.lock().unwrap().draining {
+        return Err(ShuttingDown.into());
+    }
+
+    let mut cancel = CANCEL.subscribe();
+    tokio::select! {
+        result = future => result,
+        _ = cancel.wait_for(|cancelled| *cancelled) => Err(ShuttingDown.into()),
+    }
+}
+
+// Wait until no tasks are in flight or `timeout` passes; returns how many are left
+fn wait_idle(timeout: Duration) -> usize {
+    let (count, idle) = &*IN_FLIGHT;
+    let deadline = Instant::now() + timeout;
+    let mut count = count.lock().unwrap();
+    while *count > 0 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        count = idle.wait_timeout(count, remaining).unwrap().0;
+    }
+    *count
+}
+
+/// Stop taking requests, wait up to `grace` for the ones in flight, cut off the rest,
+/// drop the cached clients and stop the runtime's threads. Blocks, so it runs off the
+/// JS thread. The next request starts a fresh runtime. Returns how many tasks were cut off.
+pub fn shutdown(grace: Duration) -> usize {
+    {
+        let mut state = STATE.lock().unwrap();
+        if state.runtime.is_none() {
+            return 0;
+        }
+        state.draining = true;
+    }
+
+    let cut_off = wait_idle(grace);
+    if cut_off > 0 {
+        CANCEL.send_replace(true);
+        wait_idle(CANCEL_GRACE);
+    }
+
+    // Clients hold pooled connections driven by the runtime, so they go first, pinned ones too
+    clear_all_managed_sessions();
+    CANCEL.send_replace(false);
+    let runtime = {
+        let mut state = STATE.lock().unwrap();
+        state.draining = false;
+        state.runtime.take()
+    };
+    if let Some(runtime) = runtime {
+        runtime.shutdown_background();
+    }
+    cut_off
+}
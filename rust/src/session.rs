@@ -0,0 +1,517 @@
+use crate::auth::basic_authorization;
+use crate::client::{ProxyAuth, RequestOptions};
+use crate::cookie_jar::CookieJar;
+use crate::dns::DnsConfig;
+use crate::emulation;
+use crate::fingerprint::{self, Http2Fingerprint};
+use crate::pool::PoolConfig;
+use crate::runtime;
+use crate::timings::ConnectTimingLayer;
+use crate::tls::TlsConfig;
+use anyhow::{Context, Result};
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use wreq::header::{HeaderMap, HeaderName, HeaderValue};
+use wreq::{Client as HttpClient, Proxy};
+use wreq_util::{Emulation, EmulationOS};
+
+pub static SESSION_MANAGER: Lazy<SessionManager> = Lazy::new(SessionManager::new);
+
+#[derive(Clone)]
+pub struct SessionConfig {
+    emulation: Emulation,
+    emulation_os: Option<EmulationOS>,
+    label: String,
+    proxy: Option<String>,
+    tls: TlsConfig,
+    http2: Option<Http2Fingerprint>,
+    pool: PoolConfig,
+    dns: DnsConfig,
+    cookie_store_path: Option<PathBuf>,
+}
+
+impl SessionConfig {
+    pub fn from_request(options: &RequestOptions) -> Self {
+        Self {
+            emulation: options.emulation.clone(),
+            emulation_os: options.emulation_os,
+            label: emulation::profile_label(&options.emulation, options.emulation_os),
+            proxy: options.proxy.clone(),
+            tls: options.tls.clone(),
+            http2: options.http2.clone(),
+            pool: options.pool.clone(),
+            dns: options.dns.clone(),
+            cookie_store_path: options.cookie_store_path.clone(),
+        }
+    }
+
+    pub fn new(
+        emulation: Emulation,
+        emulation_os: Option<EmulationOS>,
+        proxy: Option<String>,
+        tls: TlsConfig,
+        pool: PoolConfig,
+        dns: DnsConfig,
+        cookie_store_path: Option<PathBuf>,
+    ) -> Self {
+        let label = emulation::profile_label(&emulation, emulation_os);
+        Self {
+            emulation,
+            emulation_os,
+            label,
+            proxy,
+            tls,
+            http2: None,
+            pool,
+            dns,
+            cookie_store_path,
+        }
+    }
+
+    /// Layer custom HTTP/2 settings over the emulation's.
+    pub fn with_http2(mut self, http2: Option<Http2Fingerprint>) -> Self {
+        self.http2 = http2;
+        self
+    }
+
+    // The proxy is applied per request, so only the emulation (OS included), the client settings
+    // (TLS, HTTP/2, pool, DNS) and the cookie file pin a session's client; sessions never share a
+    // client across identities or trust stores
+    fn matches(&self, other: &SessionConfig) -> bool {
+        self.label == other.label
+            && self.tls == other.tls
+            && self.http2 == other.http2
+            && self.pool == other.pool
+            && self.dns == other.dns
+            && self.cookie_store_path == other.cookie_store_path
+    }
+}
+
+/// A session owns exactly one client and the cookie jar that client writes to,
+/// so cookies never leak between sessions.
+pub struct SessionEntry {
+    pub client: Arc<HttpClient>,
+    pub cookie_jar: Arc<CookieJar>,
+    config: SessionConfig,
+    /// Milliseconds since the Unix epoch of the last request through this session
+    last_used: AtomicU64,
+}
+
+impl SessionEntry {
+    /// Proxy the session was created with, used when a request doesn't name its own.
+    pub fn default_proxy(&self) -> Option<&str> {
+        self.config.proxy.as_deref()
+    }
+
+    fn touch(&self) {
+        self.last_used.store(now_millis(), Ordering::Relaxed);
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Limits for the session cache. Every entry owns a client with its own connection
+/// pool and TLS session cache, so idle entries are evicted instead of living forever.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheConfig {
+    pub max_entries: u64,
+    pub idle_ttl: Duration,
+    /// How often expired entries are swept; the cache otherwise only evicts on access
+    pub sweep_interval: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1024,
+            idle_ttl: Duration::from_secs(300),
+            sweep_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+type SessionCache = Cache<String, Arc<SessionEntry>>;
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Snapshot of the session cache, for spotting configurations that thrash it.
+#[derive(Debug, Clone)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped for being idle too long or to stay under `max_entries`
+    pub evictions: u64,
+    pub size: u64,
+    pub entries: Vec<CacheEntryStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheEntryStats {
+    pub session_id: String,
+    pub browser: String,
+    pub proxy: Option<String>,
+    pub last_used: u64,
+    /// Held outside the cache until the session is closed
+    pub pinned: bool,
+}
+
+pub struct SessionManager {
+    cache: RwLock<SessionCache>,
+    /// Sessions created with `pinClient`, which idle and size limits never evict
+    pinned: RwLock<HashMap<String, Arc<SessionEntry>>>,
+    config: Mutex<CacheConfig>,
+    sweeper: Mutex<Option<JoinHandle<()>>>,
+    counters: Arc<CacheCounters>,
+}
+
+impl SessionManager {
+    fn new() -> Self {
+        let config = CacheConfig::default();
+        let counters = Arc::new(CacheCounters::default());
+        let cache = build_cache(&config, counters.clone());
+        let sweeper = spawn_sweeper(cache.clone(), config.sweep_interval);
+
+        Self {
+            cache: RwLock::new(cache),
+            pinned: RwLock::new(HashMap::new()),
+            config: Mutex::new(config),
+            sweeper: Mutex::new(Some(sweeper)),
+            counters,
+        }
+    }
+
+    fn cache(&self) -> SessionCache {
+        self.cache.read().unwrap().clone()
+    }
+
+    fn lookup(&self, session_id: &str) -> Option<Arc<SessionEntry>> {
+        let pinned = self.pinned.read().unwrap().get(session_id).cloned();
+        pinned.or_else(|| self.cache().get(session_id))
+    }
+
+    pub fn cache_config(&self) -> CacheConfig {
+        *self.config.lock().unwrap()
+    }
+
+    /// Rebuild the cache with new limits, carrying existing sessions over.
+    pub fn configure(&self, config: CacheConfig) {
+        let cache = build_cache(&config, self.counters.clone());
+
+        {
+            let mut current = self.cache.write().unwrap();
+            for (session_id, entry) in current.iter() {
+                cache.insert(session_id.as_ref().clone(), entry);
+            }
+            *current = cache.clone();
+        }
+
+        let mut sweeper = self.sweeper.lock().unwrap();
+        if let Some(previous) = sweeper.take() {
+            previous.abort();
+        }
+        *sweeper = Some(spawn_sweeper(cache, config.sweep_interval));
+        *self.config.lock().unwrap() = config;
+    }
+
+    // A runtime shutdown stops the sweeper; the next client built on the fresh runtime restarts it
+    fn ensure_sweeper(&self) {
+        let mut sweeper = self.sweeper.lock().unwrap();
+        if sweeper.as_ref().is_none_or(JoinHandle::is_finished) {
+            *sweeper = Some(spawn_sweeper(self.cache(), self.cache_config().sweep_interval));
+        }
+    }
+
+    /// Drop every cached session along with its client and cookies, pinned ones too when
+    /// `include_pinned` is set.
+    pub fn clear(&self, include_pinned: bool) {
+        self.cache().invalidate_all();
+        if include_pinned {
+            self.pinned.write().unwrap().clear();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let cache = self.cache();
+        // Apply pending evictions so the snapshot doesn't list expired entries
+        cache.run_pending_tasks();
+
+        let entry_stats = |session_id: &str, entry: &SessionEntry, pinned: bool| CacheEntryStats {
+            session_id: session_id.to_string(),
+            browser: entry.config.label.clone(),
+            proxy: entry.config.proxy.clone(),
+            last_used: entry.last_used.load(Ordering::Relaxed),
+            pinned,
+        };
+        let mut entries = cache
+            .iter()
+            .map(|(session_id, entry)| entry_stats(&session_id, &entry, false))
+            .collect::<Vec<_>>();
+        for (session_id, entry) in self.pinned.read().unwrap().iter() {
+            entries.push(entry_stats(session_id, entry, true));
+        }
+        entries.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            size: entries.len() as u64,
+            entries,
+        }
+    }
+
+    pub fn session_for(&self, session_id: &str, config: SessionConfig) -> Result<Arc<SessionEntry>> {
+        if let Some(entry) = self.lookup(session_id) {
+            if entry.config.matches(&config) {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                entry.touch();
+                return Ok(entry);
+            } else {
+                anyhow::bail!(
+                    "Session '{}' was created with a different browser or client configuration",
+                    session_id
+                );
+            }
+        }
+
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        let entry = self.build_entry(config)?;
+        self.cache().insert(session_id.to_string(), entry.clone());
+        Ok(entry)
+    }
+
+    fn build_entry(&self, config: SessionConfig) -> Result<Arc<SessionEntry>> {
+        self.ensure_sweeper();
+
+        // Fail fast on a malformed default proxy rather than on the first request
+        if let Some(proxy_url) = config.proxy.as_deref() {
+            build_proxy(proxy_url, None, &[])?;
+        }
+
+        // A persisted jar starts with the cookies saved by an earlier run
+        let cookie_jar = Arc::new(match config.cookie_store_path.as_deref() {
+            Some(path) => CookieJar::open(path)?,
+            None => CookieJar::default(),
+        });
+        let client = Arc::new(build_client(&config, cookie_jar.clone())?);
+        Ok(Arc::new(SessionEntry {
+            client,
+            cookie_jar,
+            config,
+            last_used: AtomicU64::new(now_millis()),
+        }))
+    }
+
+    fn create_session(&self, session_id: String, config: SessionConfig, pinned: bool) -> Result<String> {
+        let entry = self.build_entry(config)?;
+        let cache = self.cache();
+        let mut pinned_sessions = self.pinned.write().unwrap();
+        if pinned {
+            cache.invalidate(&session_id);
+            pinned_sessions.insert(session_id.clone(), entry);
+        } else {
+            pinned_sessions.remove(&session_id);
+            cache.insert(session_id.clone(), entry);
+        }
+        Ok(session_id)
+    }
+
+    fn clear_session(&self, session_id: &str) -> Result<()> {
+        self.cookie_jar(session_id)?.clear();
+        Ok(())
+    }
+
+    fn cookie_jar(&self, session_id: &str) -> Result<Arc<CookieJar>> {
+        let existing = self
+            .lookup(session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+        Ok(existing.cookie_jar.clone())
+    }
+
+    pub fn drop_session(&self, session_id: &str) {
+        self.cache().invalidate(session_id);
+    }
+
+    /// Drop a session for good, writing out its cookies first if they are persisted.
+    fn close_session(&self, session_id: &str) -> Result<()> {
+        let cache = self.cache();
+        let pinned = self.pinned.write().unwrap().remove(session_id);
+        let Some(entry) = pinned.or_else(|| cache.get(session_id)) else {
+            return Ok(());
+        };
+        cache.invalidate(session_id);
+        entry.cookie_jar.persist()
+    }
+}
+
+fn build_cache(config: &CacheConfig, counters: Arc<CacheCounters>) -> SessionCache {
+    Cache::builder()
+        .max_capacity(config.max_entries)
+        .time_to_idle(config.idle_ttl)
+        .eviction_listener(move |_, _, cause| {
+            if cause.was_evicted() {
+                counters.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .build()
+}
+
+// Runs pending evictions so idle clients release their sockets even when no requests arrive
+fn spawn_sweeper(cache: SessionCache, interval: Duration) -> JoinHandle<()> {
+    runtime::spawn_detached(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            cache.run_pending_tasks();
+        }
+    })
+}
+
+// Proxies are not baked into the client: they are attached per request so a single
+// client (and its TLS context) can be rotated through any number of proxies.
+fn build_client(config: &SessionConfig, cookie_jar: Arc<CookieJar>) -> Result<HttpClient> {
+    let builder = HttpClient::builder()
+        .emulation(fingerprint::emulation_with(
+            &config.emulation,
+            config.emulation_os,
+            config.tls.fingerprint.as_ref(),
+            config.http2.as_ref(),
+        ))
+        .cookie_provider(cookie_jar)
+        .history(true)
+        .tls_info(true)
+        .connector_layer(ConnectTimingLayer);
+
+    let builder = config.pool.apply(builder);
+    // Installs the resolver, which also records DNS timings
+    let builder = config.dns.apply(builder);
+
+    config.tls.apply(builder)?.build().context("Failed to build HTTP client")
+}
+
+/// Proxy schemes wreq can connect through. `socks5h` and `socks4a` resolve the target
+/// hostname on the proxy, `socks5` and `socks4` resolve it locally.
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+pub fn build_proxy(proxy_url: &str, auth: Option<&ProxyAuth>, headers: &[(String, String)]) -> Result<Proxy> {
+    // A URL without a scheme is treated as an HTTP proxy by wreq
+    if let Some((scheme, _)) = proxy_url.split_once("://") {
+        let scheme = scheme.to_ascii_lowercase();
+        if !PROXY_SCHEMES.contains(&scheme.as_str()) {
+            anyhow::bail!(
+                "Unsupported proxy scheme '{}' (expected one of: {})",
+                scheme,
+                PROXY_SCHEMES.join(", ")
+            );
+        }
+        if scheme.starts_with("socks") && !headers.is_empty() {
+            anyhow::bail!("Proxy headers are not supported for SOCKS proxies");
+        }
+    }
+
+    let mut proxy = Proxy::all(proxy_url).context("Failed to create proxy")?;
+
+    if let Some(auth) = auth {
+        proxy = proxy.basic_auth(&auth.username, &auth.password);
+    }
+
+    if !headers.is_empty() {
+        proxy = proxy.custom_http_headers(proxy_header_map(headers)?);
+    }
+
+    Ok(proxy)
+}
+
+pub fn proxy_header_map(headers: &[(String, String)]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid proxy header name '{}'", name))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for proxy header '{}'", name))?;
+        map.append(name, value);
+    }
+
+    Ok(map)
+}
+
+/// Credentials for a proxy, either passed explicitly or embedded in its URL.
+pub fn proxy_credentials(proxy_url: &str, auth: Option<&ProxyAuth>) -> Option<ProxyAuth> {
+    if let Some(auth) = auth {
+        return Some(auth.clone());
+    }
+
+    let uri: wreq::Uri = proxy_url.parse().ok()?;
+    let (userinfo, _) = uri.authority()?.as_str().rsplit_once('@')?;
+    let (username, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+    Some(ProxyAuth {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+pub fn basic_auth_header(auth: &ProxyAuth) -> String {
+    basic_authorization(&auth.username, &auth.password)
+}
+
+/// Create a session. A pinned one keeps its client, and with it its connections and
+/// cookies, until it is closed, however long it idles or however full the cache gets.
+pub fn create_managed_session(session_id: String, config: SessionConfig, pinned: bool) -> Result<String> {
+    SESSION_MANAGER.create_session(session_id, config, pinned)
+}
+
+pub fn clear_managed_session(session_id: &str) -> Result<()> {
+    SESSION_MANAGER.clear_session(session_id)
+}
+
+pub fn managed_cookie_jar(session_id: &str) -> Result<Arc<CookieJar>> {
+    SESSION_MANAGER.cookie_jar(session_id)
+}
+
+/// Proxy an existing session was created with, if it has one.
+pub fn managed_session_proxy(session_id: &str) -> Option<String> {
+    SESSION_MANAGER.lookup(session_id)?.default_proxy().map(str::to_string)
+}
+
+pub fn configure_managed_cache(config: CacheConfig) {
+    SESSION_MANAGER.configure(config);
+}
+
+pub fn clear_managed_cache() {
+    SESSION_MANAGER.clear(false);
+}
+
+/// Drop every session's client, pinned ones included, as a runtime shutdown must.
+pub fn clear_all_managed_sessions() {
+    SESSION_MANAGER.clear(true);
+}
+
+pub fn managed_cache_stats() -> CacheStats {
+    SESSION_MANAGER.stats()
+}
+
+pub fn drop_managed_session(session_id: &str) -> Result<()> {
+    SESSION_MANAGER.close_session(session_id)
+}
+
+pub fn generate_session_id() -> String {
+    Uuid::new_v4().to_string()
+}
@@ -0,0 +1,235 @@
+use crate::client::{outgoing_headers, RequestOptions};
+use crate::har::iso8601;
+use anyhow::{Context, Result};
+use boring2::hash::{hash, hmac_sha256, Hasher, MessageDigest};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use wreq::Uri;
+
+/// Credentials and scope for AWS Signature Version 4.
+#[derive(Debug, Clone)]
+pub struct SigV4Config {
+    pub region: String,
+    /// Signing name of the service, e.g. "s3" or "execute-api"
+    pub service: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// For temporary credentials, sent as `X-Amz-Security-Token`
+    pub session_token: Option<String>,
+}
+
+/// Payload hash for bodies that can't be hashed before they are sent; S3 accepts it,
+/// most other services don't.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+// Left out of the signature: hop-by-hop headers a proxy may rewrite, the signature itself,
+// and cookies, which the cookie store may update between signing and sending
+const UNSIGNED_HEADERS: &[&str] = &[
+    "authorization",
+    "connection",
+    "cookie",
+    "expect",
+    "keep-alive",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "x-amzn-trace-id",
+];
+
+/// Sign the request in place, adding `X-Amz-Date`, `X-Amz-Content-Sha256`, the session
+/// token and `Authorization`. The signature covers the headers the request goes out with,
+/// the emulation's defaults included, and the date, so every attempt is signed afresh.
+/// A caller's own `X-Amz-Content-Sha256` (e.g. `UNSIGNED-PAYLOAD`) is kept as the payload hash.
+pub async fn sign(options: &mut RequestOptions, config: &SigV4Config) -> Result<()> {
+    let uri = options.url.parse::<Uri>().context("Invalid URL")?;
+    let payload_hash = payload_hash(options).await?;
+    // 20240102T030405Z, from the ISO 8601 form
+    let stamp = iso8601(SystemTime::now());
+    let amz_date = format!("{}Z", stamp[..19].replace(['-', ':'], ""));
+    let date = &amz_date[..8];
+
+    options.headers.retain(|(key, _)| {
+        !["authorization", "x-amz-date", "x-amz-content-sha256", "x-amz-security-token"]
+            .iter()
+            .any(|name| key.eq_ignore_ascii_case(name))
+    });
+    options.headers.push(("X-Amz-Date".to_string(), amz_date.clone()));
+    options.headers.push(("X-Amz-Content-Sha256".to_string(), payload_hash.clone()));
+    if let Some(token) = &config.session_token {
+        options.headers.push(("X-Amz-Security-Token".to_string(), token.clone()));
+    }
+
+    // Lowercase names in order, repeated headers joined with commas, runs of spaces collapsed
+    let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, value) in outgoing_headers(options) {
+        let name = name.to_ascii_lowercase();
+        if !UNSIGNED_HEADERS.contains(&name.as_str()) {
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            headers.entry(name).or_default().push(value);
+        }
+    }
+    headers.entry("host".to_string()).or_insert_with(|| vec![host(&uri)]);
+
+    let signed_headers = headers.keys().map(String::as_str).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, values)| format!("{}:{}\n", name, values.join(",")))
+        .collect();
+    let method = if options.method.is_empty() { "GET" } else { &options.method };
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_path(&uri, &config.service),
+        canonical_query(&uri),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, config.region, config.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&hash(MessageDigest::sha256(), canonical_request.as_bytes())?)
+    );
+
+    let mut key = hmac_sha256(format!("AWS4{}", config.secret_access_key).as_bytes(), date.as_bytes())?;
+    for part in [config.region.as_str(), config.service.as_str(), "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes())?;
+    }
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes())?);
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key_id, scope, signed_headers, signature
+    );
+    options.headers.push(("Authorization".to_string(), authorization));
+    Ok(())
+}
+
+// SHA-256 of the body as it will be sent. Multipart boundaries are only chosen when the
+// request is built, so those bodies go unsigned.
+async fn payload_hash(options: &RequestOptions) -> Result<String> {
+    let declared = options
+        .headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("x-amz-content-sha256"));
+    if let Some((_, value)) = declared {
+        return Ok(value.clone());
+    }
+
+    let digest = if let Some(body) = &options.body {
+        hash(MessageDigest::sha256(), body)?
+    } else if let Some(json) = &options.json {
+        hash(MessageDigest::sha256(), &serde_json::to_vec(json)?)?
+    } else if let Some(path) = &options.body_file {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open body file '{}'", path.display()))?;
+        let mut hasher = Hasher::new(MessageDigest::sha256())?;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .await
+                .with_context(|| format!("Failed to read body file '{}'", path.display()))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read])?;
+        }
+        hasher.finish()?
+    } else if options.multipart.is_some() {
+        return Ok(UNSIGNED_PAYLOAD.to_string());
+    } else {
+        hash(MessageDigest::sha256(), b"")?
+    };
+    Ok(hex(&digest))
+}
+
+// The Host header as sent: the port only appears when it isn't the scheme's default
+fn host(uri: &Uri) -> String {
+    let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+    let default_port = if uri.scheme_str() == Some("http") { 80 } else { 443 };
+    match uri.port_u16() {
+        Some(port) if port != default_port => format!("{}:{}", host, port),
+        _ => host,
+    }
+}
+
+// Each path segment decoded and encoded again with only unreserved characters left as they
+// are. Services other than S3 expect the segments encoded a second time.
+fn canonical_path(uri: &Uri, service: &str) -> String {
+    let path = if uri.path().is_empty() { "/" } else { uri.path() };
+    path.split('/')
+        .map(|segment| {
+            let once = encode(&percent_decode(segment));
+            if service == "s3" { once } else { encode(once.as_bytes()) }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Query parameters encoded like path segments and sorted by name, then value
+fn canonical_query(uri: &Uri) -> String {
+    let mut params = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (encode(&percent_decode(name)), encode(&percent_decode(value)))
+        })
+        .collect::<Vec<_>>();
+    params.sort();
+    params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+// RFC 3986 encoding with everything but unreserved characters escaped, as SigV4 requires
+fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,68 @@
+use crate::client::{outgoing_headers, RequestOptions, Response};
+use crate::emulation;
+use crate::error::{ErrorKind, SharedFailure};
+use anyhow::Result;
+use boring2::hash::{hash, MessageDigest};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+type Outcome = Result<Response, SharedFailure>;
+
+/// Requests in flight that identical ones can wait on, by `key`.
+static IN_FLIGHT: Lazy<Mutex<HashMap<Vec<u8>, Shared<BoxFuture<'static, Outcome>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Only bodiless reads can be shared, since sending anything else twice is the point.
+pub fn eligible(options: &RequestOptions) -> bool {
+    let method = options.method.to_ascii_uppercase();
+    let bodiless = options.body.is_none() && options.multipart.is_none() && options.json.is_none();
+    matches!(method.as_str(), "" | "GET" | "HEAD") && bodiless && options.body_file.is_none()
+}
+
+/// SHA-256 of everything that tells two requests apart: method, URL, browser profile,
+/// session, proxy, whether the jar is used and the headers they go out with, cookies included.
+/// Requests outside a session start with an empty jar, so they share one session part.
+pub fn key(options: &RequestOptions) -> Vec<u8> {
+    let session = if options.ephemeral { "" } else { options.session_id.as_str() };
+    let mut material = vec![
+        options.method.to_ascii_uppercase(),
+        options.url.clone(),
+        emulation::profile_label(&options.emulation, options.emulation_os),
+        session.to_string(),
+        options.proxy.clone().unwrap_or_default(),
+        options.cookies.as_str().to_string(),
+    ];
+    for (name, value) in outgoing_headers(options) {
+        material.push(format!("{}: {}", name.to_ascii_lowercase(), value));
+    }
+    let material = material.join("\0");
+    hash(MessageDigest::sha256(), material.as_bytes()).map_or_else(|_| material.into_bytes(), |digest| digest.to_vec())
+}
+
+/// Send a request, or wait for the identical one already in flight and take a copy of
+/// its response. Failures are shared too, with their message and class.
+pub async fn run(key: Vec<u8>, send: impl Future<Output = Result<Response>> + Send + 'static) -> Result<Response> {
+    let shared = IN_FLIGHT
+        .lock()
+        .unwrap()
+        .entry(key.clone())
+        .or_insert_with(|| {
+            async move {
+                let outcome = send.await.map_err(|error| SharedFailure {
+                    kind: ErrorKind::classify(&error),
+                    message: format!("{:#}", error),
+                });
+                // Requests made from now on go out again
+                IN_FLIGHT.lock().unwrap().remove(&key);
+                outcome
+            }
+            .boxed()
+            .shared()
+        })
+        .clone();
+
+    Ok(shared.await?)
+}
@@ -0,0 +1,260 @@
+use crate::error::BodyParseFailed;
+use flate2::write::MultiGzDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::io::Write;
+
+/// Most bytes held waiting for the end of a tag; a sitemap's tags are a few hundred bytes.
+const MAX_PENDING: usize = 1024 * 1024;
+
+/// Which list an entry came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A page, from a `<urlset>`
+    Url,
+    /// Another sitemap, from a `<sitemapindex>`
+    Sitemap,
+}
+
+impl EntryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Url => "url",
+            EntryKind::Sitemap => "sitemap",
+        }
+    }
+}
+
+/// One `<url>` or `<sitemap>` element. Values are kept as written, trimmed.
+#[derive(Debug, Clone)]
+pub struct SitemapEntry {
+    pub kind: EntryKind,
+    pub loc: String,
+    pub lastmod: Option<String>,
+    pub changefreq: Option<String>,
+    pub priority: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Loc,
+    Lastmod,
+    Changefreq,
+    Priority,
+}
+
+/// How the body is encoded, once its first bytes have been seen.
+enum Input {
+    /// Fewer bytes than the gzip magic so far
+    Unknown(Vec<u8>),
+    Xml,
+    /// A `.xml.gz` file served as is, rather than with `Content-Encoding: gzip`
+    Gzip(Box<MultiGzDecoder<Vec<u8>>>),
+}
+
+/// Parses a sitemap (sitemaps.org protocol) or sitemap index however its bytes fall across
+/// chunks, gzip-compressed or not. Elements are matched by local name, so namespace prefixes
+/// don't matter, and extensions such as `<image:image>` are skipped.
+pub struct SitemapParser {
+    input: Input,
+    /// Decoded XML after the last complete tag, waiting for the rest
+    pending: Vec<u8>,
+    /// Whether a `<urlset>` or `<sitemapindex>` has been seen
+    root: bool,
+    entry: Option<SitemapEntry>,
+    /// Elements open inside the current entry
+    depth: usize,
+    field: Option<Field>,
+    text: String,
+}
+
+impl Default for SitemapParser {
+    fn default() -> Self {
+        SitemapParser {
+            input: Input::Unknown(Vec::new()),
+            pending: Vec::new(),
+            root: false,
+            entry: None,
+            depth: 0,
+            field: None,
+            text: String::new(),
+        }
+    }
+}
+
+fn fail(reason: impl Into<String>) -> BodyParseFailed {
+    BodyParseFailed {
+        format: "sitemap",
+        reason: reason.into(),
+    }
+}
+
+impl SitemapParser {
+    /// Take a chunk and return the entries it completes.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<SitemapEntry>, BodyParseFailed> {
+        let xml = self.decode(data)?;
+        self.pending.extend_from_slice(&xml);
+        let Some(end) = self.pending.iter().rposition(|byte| *byte == b'>') else {
+            if self.pending.len() > MAX_PENDING {
+                return Err(fail(format!("no tag ends within {} bytes", MAX_PENDING)));
+            }
+            return Ok(Vec::new());
+        };
+
+        let rest = self.pending.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        let (entries, consumed) = self.parse(&complete, false)?;
+        // A `>` inside a comment or CDATA section can end the chunk mid-markup
+        if consumed < complete.len() {
+            let mut pending = complete[consumed..].to_vec();
+            pending.append(&mut self.pending);
+            if pending.len() > MAX_PENDING {
+                return Err(fail(format!("no tag ends within {} bytes", MAX_PENDING)));
+            }
+            self.pending = pending;
+        }
+        Ok(entries)
+    }
+
+    /// The entries left once the body has ended; fails if it held no sitemap.
+    pub fn finish(&mut self) -> Result<Vec<SitemapEntry>, BodyParseFailed> {
+        let mut xml = match std::mem::replace(&mut self.input, Input::Xml) {
+            Input::Unknown(bytes) => bytes,
+            Input::Xml => Vec::new(),
+            Input::Gzip(mut decoder) => {
+                decoder.try_finish().map_err(|e| fail(format!("gzip: {}", e)))?;
+                std::mem::take(decoder.get_mut())
+            }
+        };
+        let mut complete = std::mem::take(&mut self.pending);
+        complete.append(&mut xml);
+        let (entries, _) = self.parse(&complete, true)?;
+        if !self.root {
+            return Err(fail("no <urlset> or <sitemapindex> element"));
+        }
+        Ok(entries)
+    }
+
+    // Turn a chunk of the body into XML, sniffing for gzip on the first bytes
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, BodyParseFailed> {
+        if let Input::Unknown(head) = &mut self.input {
+            head.extend_from_slice(data);
+            if head.len() < 2 {
+                return Ok(Vec::new());
+            }
+            let head = std::mem::take(head);
+            self.input = if head.starts_with(&[0x1f, 0x8b]) {
+                Input::Gzip(Box::new(MultiGzDecoder::new(Vec::new())))
+            } else {
+                Input::Xml
+            };
+            return self.decode(&head);
+        }
+
+        match &mut self.input {
+            Input::Gzip(decoder) => {
+                decoder.write_all(data).map_err(|e| fail(format!("gzip: {}", e)))?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            _ => Ok(data.to_vec()),
+        }
+    }
+
+    // Parse complete markup, carrying the open entry over from earlier chunks. Unless this
+    // is the last of the body, a syntax error means the markup was cut short: what precedes
+    // it is parsed and its offset returned, so the rest can wait for more bytes
+    fn parse(&mut self, xml: &[u8], last: bool) -> Result<(Vec<SitemapEntry>, usize), BodyParseFailed> {
+        let mut reader = Reader::from_reader(xml);
+        // Elements opened in earlier chunks end in this one
+        reader.config_mut().check_end_names = false;
+
+        let mut entries = Vec::new();
+        loop {
+            let position = reader.buffer_position() as usize;
+            let event = match reader.read_event() {
+                Ok(event) => event,
+                Err(quick_xml::Error::Syntax(_)) if !last => return Ok((entries, position)),
+                Err(e) => return Err(fail(format!("{} at byte {}", e, reader.error_position()))),
+            };
+            match event {
+                Event::Start(start) => self.open(start.local_name().as_ref()),
+                Event::End(_) => entries.extend(self.close()),
+                Event::Text(content) if self.field.is_some() => {
+                    let content = content.unescape().map_err(|e| fail(e.to_string()))?;
+                    self.text.push_str(&content);
+                }
+                Event::CData(content) if self.field.is_some() => {
+                    self.text.push_str(&String::from_utf8_lossy(&content.into_inner()));
+                }
+                Event::Eof => return Ok((entries, xml.len())),
+                _ => {}
+            }
+        }
+    }
+
+    fn open(&mut self, name: &[u8]) {
+        if self.entry.is_some() {
+            self.depth += 1;
+            // Only the entry's own children count; `<image:loc>` is nested deeper
+            if self.depth == 1 {
+                self.field = match name {
+                    b"loc" => Some(Field::Loc),
+                    b"lastmod" => Some(Field::Lastmod),
+                    b"changefreq" => Some(Field::Changefreq),
+                    b"priority" => Some(Field::Priority),
+                    _ => None,
+                };
+                self.text.clear();
+            }
+            return;
+        }
+
+        let kind = match name {
+            b"urlset" | b"sitemapindex" => {
+                self.root = true;
+                return;
+            }
+            b"url" => EntryKind::Url,
+            b"sitemap" => EntryKind::Sitemap,
+            _ => return,
+        };
+        self.entry = Some(SitemapEntry {
+            kind,
+            loc: String::new(),
+            lastmod: None,
+            changefreq: None,
+            priority: None,
+        });
+    }
+
+    // Close an element, returning the entry it ends if it had a location
+    fn close(&mut self) -> Option<SitemapEntry> {
+        let entry = self.entry.as_mut()?;
+        if self.depth == 0 {
+            return self.entry.take().filter(|entry| !entry.loc.is_empty());
+        }
+
+        if self.depth == 1 {
+            let value = self.text.trim();
+            match self.field.take() {
+                Some(Field::Loc) => entry.loc = value.to_string(),
+                Some(Field::Lastmod) => entry.lastmod = Some(value.to_string()).filter(|value| !value.is_empty()),
+                Some(Field::Changefreq) => {
+                    entry.changefreq = Some(value.to_ascii_lowercase()).filter(|value| !value.is_empty());
+                }
+                Some(Field::Priority) => entry.priority = value.parse().ok(),
+                None => {}
+            }
+        }
+        self.depth -= 1;
+        None
+    }
+}
+
+/// Parse a whole body that is already in memory.
+pub fn parse_all(body: &[u8]) -> Result<Vec<SitemapEntry>, BodyParseFailed> {
+    let mut parser = SitemapParser::default();
+    let mut entries = parser.feed(body)?;
+    entries.extend(parser.finish()?);
+    Ok(entries)
+}
@@ -0,0 +1,305 @@
+use crate::body_hash::{BodyHash, BodyHasher, HashAlgorithm};
+use crate::concurrency::Permit;
+use crate::error::{BodyParseFailed, BodyTooLarge};
+use crate::ndjson::LineParser;
+use crate::runtime;
+use crate::sitemap::{SitemapEntry, SitemapParser};
+use crate::trailers;
+use anyhow::Result;
+use http_body_util::BodyExt;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{mpsc, Mutex, Notify, Semaphore};
+use tokio::task::AbortHandle;
+
+// Global storage for response bodies that are being streamed to Node.js
+static BODY_STREAMS: Lazy<StdMutex<HashMap<u64, Arc<BodyStream>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+// Trailers of streams that reached their end, until JS takes them
+static STREAM_TRAILERS: Lazy<StdMutex<HashMap<u64, Vec<(String, String)>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+// Body digests of streams that reached their end, until JS takes them
+static STREAM_HASHES: Lazy<StdMutex<HashMap<u64, BodyHash>>> = Lazy::new(|| StdMutex::new(HashMap::new()));
+
+/// Bytes read ahead of the consumer when the request doesn't set `highWaterMark`
+pub const DEFAULT_HIGH_WATER_MARK: u64 = 64 * 1024;
+
+/// What the reader task hands to `read_chunk`, in body order.
+enum Message {
+    Data(Vec<u8>),
+    /// The body ended, with its trailers and the digest of everything before them
+    End(Vec<(String, String)>, Option<BodyHash>),
+    Failed(anyhow::Error),
+}
+
+struct BodyStream {
+    messages: Mutex<mpsc::UnboundedReceiver<Message>>,
+    /// Room left under the high-water mark, in bytes. The reader takes it before queueing a
+    /// chunk and `read_chunk` gives it back, so a slow consumer stops reads from the socket
+    room: Arc<Semaphore>,
+    high_water_mark: u32,
+    reader: AbortHandle,
+    /// Lines split off the body so far, when it is read as NDJSON
+    lines: StdMutex<LineParser>,
+    /// Entries parsed so far, when it is read as a sitemap
+    sitemap: StdMutex<SitemapParser>,
+    // Wakes a pending read when the stream is cancelled, so idle bodies
+    // (e.g. event streams) don't keep a read parked forever
+    cancelled: Notify,
+    /// Concurrency slot of the request, released along with the stream
+    _permit: Permit,
+}
+
+impl BodyStream {
+    fn weight(&self, len: usize) -> u32 {
+        weight(len, self.high_water_mark)
+    }
+}
+
+static NEXT_STREAM_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+/// Store a response whose body will be read chunk by chunk and return its ID. Up to
+/// `high_water_mark` bytes are read ahead of the consumer before reads from the socket pause.
+pub fn store_stream(
+    response: wreq::Response,
+    limit: Option<u64>,
+    hash: Option<HashAlgorithm>,
+    high_water_mark: u64,
+    permit: Permit,
+) -> Result<u64> {
+    let hasher = hash.map(BodyHasher::new).transpose()?;
+    let high_water_mark = high_water_mark.clamp(1, u64::from(u32::MAX)) as u32;
+    let room = Arc::new(Semaphore::new(high_water_mark as usize));
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let body = wreq::Body::from(response);
+    let reader = runtime::spawn_detached(pump(body, limit, hasher, room.clone(), high_water_mark, sender));
+
+    let mut id_lock = NEXT_STREAM_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut streams = BODY_STREAMS.lock().unwrap();
+    streams.insert(
+        id,
+        Arc::new(BodyStream {
+            messages: Mutex::new(receiver),
+            room,
+            high_water_mark,
+            reader: reader.abort_handle(),
+            lines: StdMutex::new(LineParser::default()),
+            sitemap: StdMutex::new(SitemapParser::default()),
+            cancelled: Notify::new(),
+            _permit: permit,
+        }),
+    );
+    Ok(id)
+}
+
+// Read the body into the stream's queue for as long as it has room. Stops at the end of
+// the body, on the first error, or when the stream is dropped
+async fn pump(
+    mut body: wreq::Body,
+    limit: Option<u64>,
+    mut hasher: Option<BodyHasher>,
+    room: Arc<Semaphore>,
+    high_water_mark: u32,
+    sender: mpsc::UnboundedSender<Message>,
+) {
+    let mut received = 0u64;
+    let mut trailer_pairs = Vec::new();
+
+    while let Some(frame) = body.frame().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                let _ = sender.send(Message::Failed(
+                    anyhow::Error::new(e).context("Failed to read response body chunk"),
+                ));
+                return;
+            }
+        };
+
+        let bytes = match frame.into_data() {
+            Ok(bytes) => bytes,
+            Err(frame) => {
+                // Trailers come after the last chunk
+                if let Ok(fields) = frame.into_trailers() {
+                    trailer_pairs = trailers::pairs(&fields);
+                }
+                continue;
+            }
+        };
+
+        received += bytes.len() as u64;
+        if let Some(limit) = limit.filter(|limit| received > *limit) {
+            let _ = sender.send(Message::Failed(BodyTooLarge { limit }.into()));
+            return;
+        }
+        if let Some(Err(e)) = hasher.as_mut().map(|hasher| hasher.update(&bytes)) {
+            let _ = sender.send(Message::Failed(e));
+            return;
+        }
+
+        match room.acquire_many(weight(bytes.len(), high_water_mark)).await {
+            Ok(permit) => permit.forget(),
+            Err(_) => return,
+        }
+        if sender.send(Message::Data(bytes.to_vec())).is_err() {
+            return;
+        }
+    }
+
+    let message = match hasher.map(BodyHasher::finish).transpose() {
+        Ok(hash) => Message::End(trailer_pairs, hash),
+        Err(e) => Message::Failed(e),
+    };
+    let _ = sender.send(message);
+}
+
+// A chunk bigger than the whole buffer takes all of it rather than waiting forever
+fn weight(len: usize, high_water_mark: u32) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX).min(high_water_mark)
+}
+
+/// What the next read of a stream turned up.
+enum Chunk {
+    Data(Vec<u8>),
+    End,
+    Cancelled,
+}
+
+fn find_stream(id: u64) -> Option<Arc<BodyStream>> {
+    BODY_STREAMS.lock().unwrap().get(&id).cloned()
+}
+
+/// Read the next body chunk. Returns `None` once the body is exhausted, at which
+/// point the stream is released.
+pub async fn read_chunk(id: u64) -> Result<Option<Vec<u8>>> {
+    let Some(stream) = find_stream(id) else {
+        return Ok(None);
+    };
+
+    match next_chunk(id, &stream).await? {
+        Chunk::Data(data) => Ok(Some(data)),
+        Chunk::End | Chunk::Cancelled => Ok(None),
+    }
+}
+
+/// Read body chunks until they complete at least one NDJSON line, and return the lines'
+/// values. Returns `None` once the body is exhausted, like `read_chunk`.
+pub async fn read_json_lines(id: u64) -> Result<Option<Vec<Value>>> {
+    read_parsed(id, |stream, chunk| {
+        let mut lines = stream.lines.lock().unwrap();
+        match chunk {
+            Some(data) => lines.feed(data),
+            // The last line may end without a newline
+            None => lines.finish().map(|value| value.into_iter().collect()),
+        }
+    })
+    .await
+}
+
+/// Read body chunks until they complete at least one sitemap entry, and return the
+/// entries. Returns `None` once the body is exhausted, like `read_chunk`.
+pub async fn read_sitemap_entries(id: u64) -> Result<Option<Vec<SitemapEntry>>> {
+    read_parsed(id, |stream, chunk| {
+        let mut sitemap = stream.sitemap.lock().unwrap();
+        match chunk {
+            Some(data) => sitemap.feed(data),
+            None => sitemap.finish(),
+        }
+    })
+    .await
+}
+
+// Feed chunks to `parse` until it yields something, then with `None` once the body has
+// ended. A parse error drops the stream
+async fn read_parsed<T>(
+    id: u64,
+    parse: impl Fn(&BodyStream, Option<&[u8]>) -> Result<Vec<T>, BodyParseFailed>,
+) -> Result<Option<Vec<T>>> {
+    let Some(stream) = find_stream(id) else {
+        return Ok(None);
+    };
+
+    loop {
+        let chunk = next_chunk(id, &stream).await?;
+        let parsed = match &chunk {
+            Chunk::Data(data) => parse(&stream, Some(data)),
+            Chunk::End => parse(&stream, None),
+            Chunk::Cancelled => return Ok(None),
+        };
+        let values = match parsed {
+            Ok(values) => values,
+            Err(e) => {
+                remove_stream(id);
+                return Err(e.into());
+            }
+        };
+        if !values.is_empty() {
+            return Ok(Some(values));
+        }
+        if matches!(chunk, Chunk::End) {
+            return Ok(None);
+        }
+    }
+}
+
+async fn next_chunk(id: u64, stream: &BodyStream) -> Result<Chunk> {
+    let message = {
+        let mut messages = stream.messages.lock().await;
+        tokio::select! {
+            message = messages.recv() => message,
+            _ = stream.cancelled.notified() => return Ok(Chunk::Cancelled),
+        }
+    };
+
+    match message {
+        Some(Message::Data(data)) => {
+            stream.room.add_permits(stream.weight(data.len()) as usize);
+            Ok(Chunk::Data(data))
+        }
+        Some(Message::End(trailer_pairs, hash)) => {
+            remove_stream(id);
+            if !trailer_pairs.is_empty() {
+                STREAM_TRAILERS.lock().unwrap().insert(id, trailer_pairs);
+            }
+            if let Some(hash) = hash {
+                STREAM_HASHES.lock().unwrap().insert(id, hash);
+            }
+            Ok(Chunk::End)
+        }
+        Some(Message::Failed(e)) => {
+            remove_stream(id);
+            Err(e)
+        }
+        // The reader stopped without a word, which only a cancel does
+        None => Ok(Chunk::Cancelled),
+    }
+}
+
+/// Take the trailers of a stream that has been read to the end; empty when it had none.
+pub fn take_trailers(id: u64) -> Vec<(String, String)> {
+    STREAM_TRAILERS.lock().unwrap().remove(&id).unwrap_or_default()
+}
+
+/// Take the body digest of a stream that has been read to the end, if its request asked for one.
+pub fn take_hash(id: u64) -> Option<BodyHash> {
+    STREAM_HASHES.lock().unwrap().remove(&id)
+}
+
+/// Drop a stream, closing the underlying connection if the body was not fully read
+pub fn remove_stream(id: u64) {
+    let mut streams = BODY_STREAMS.lock().unwrap();
+    if let Some(stream) = streams.remove(&id) {
+        // Dropping the reader drops the body with it
+        stream.reader.abort();
+        // notify_one keeps a permit if no read is waiting yet
+        stream.cancelled.notify_one();
+    }
+}
@@ -0,0 +1,67 @@
+use once_cell::sync::OnceCell;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Layer, Registry};
+
+/// Log output settings, set from Node with `setTracing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TracingConfig {
+    pub level: Level,
+    /// One JSON object per line instead of human-readable text
+    pub json: bool,
+}
+
+pub fn parse_level(value: &str) -> Option<Level> {
+    match value {
+        "error" => Some(Level::ERROR),
+        "warn" => Some(Level::WARN),
+        "info" => Some(Level::INFO),
+        "debug" => Some(Level::DEBUG),
+        "trace" => Some(Level::TRACE),
+        _ => None,
+    }
+}
+
+type OutputLayer = Option<Box<dyn Layer<Registry> + Send + Sync>>;
+
+// A global subscriber can only be installed once, so it holds a layer that is swapped out
+static OUTPUT: OnceCell<reload::Handle<OutputLayer, Registry>> = OnceCell::new();
+
+/// Start writing spans and events to stderr, or stop with `None`.
+pub fn configure(config: Option<TracingConfig>) -> anyhow::Result<()> {
+    let layer: OutputLayer = config.map(|config| {
+        let filter = LevelFilter::from_level(config.level);
+        let output = fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_span_events(fmt::format::FmtSpan::CLOSE);
+        if config.json {
+            output.json().with_current_span(true).with_span_list(false).with_filter(filter).boxed()
+        } else {
+            output.with_filter(filter).boxed()
+        }
+    });
+
+    let handle = OUTPUT.get_or_try_init(|| {
+        let (layer, handle) = reload::Layer::new(None);
+        tracing_subscriber::registry().with(layer).try_init()?;
+        Ok::<_, anyhow::Error>(handle)
+    })?;
+    handle.reload(layer)?;
+    Ok(())
+}
+
+/// Whether `value` is a W3C trace ID: 32 lowercase hex digits, not all zero.
+pub fn is_w3c_trace_id(value: &str) -> bool {
+    value.len() == 32
+        && value.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        && value.bytes().any(|byte| byte != b'0')
+}
+
+/// A `traceparent` header continuing `trace_id` with a fresh, random parent span ID.
+/// Returns the header value and the span ID.
+pub fn traceparent(trace_id: &str) -> (String, String) {
+    let span_id = format!("{:016x}", fastrand::u64(1..));
+    (format!("00-{}-{}-01", trace_id, span_id), span_id)
+}
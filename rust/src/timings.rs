@@ -0,0 +1,174 @@
+use crate::error::ConnectTimedOut;
+use crate::events;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+use tracing::Instrument;
+use wreq::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Phase durations for the final attempt of a request, in milliseconds.
+/// `dns` and `connect` are `None` when a pooled connection was reused (or, for
+/// `dns`, when the host was an IP literal); `download` and `total` are `None`
+/// for streamed responses whose body hasn't been read yet.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    pub dns: Option<f64>,
+    /// TCP connect plus TLS and proxy handshakes, which wreq performs as one step
+    pub connect: Option<f64>,
+    pub ttfb: f64,
+    pub download: Option<f64>,
+    pub total: Option<f64>,
+}
+
+impl Timings {
+    pub fn head(connection: ConnectTimings, ttfb: Duration) -> Self {
+        let dns = connection.dns;
+        // The connector's span includes the lookup, so take it back out
+        let connect = connection
+            .connect
+            .map(|connect| connect.saturating_sub(dns.unwrap_or_default()));
+
+        Self {
+            dns: dns.map(as_millis),
+            connect: connect.map(as_millis),
+            ttfb: as_millis(ttfb),
+            download: None,
+            total: None,
+        }
+    }
+
+    pub fn finish(&mut self, download: Duration) {
+        let download = as_millis(download);
+        self.download = Some(download);
+        self.total = Some(self.ttfb + download);
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Raw connection phase durations collected while a request is in flight.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectTimings {
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+}
+
+tokio::task_local! {
+    static CONNECT_TIMINGS: Arc<StdMutex<ConnectTimings>>;
+    static CONNECT_TIMEOUT: Option<Duration>;
+}
+
+/// Run `future` while collecting the DNS and connect durations of any new
+/// connection it opens, failing any that takes longer than `connect_timeout`.
+/// The pool polls new connections inline on the request's task, so the resolver
+/// and connector layer can reach the task-local slots.
+pub async fn measure_connect<F: Future>(connect_timeout: Option<Duration>, future: F) -> (F::Output, ConnectTimings) {
+    let slot = Arc::new(StdMutex::new(ConnectTimings::default()));
+    let output = CONNECT_TIMINGS
+        .scope(slot.clone(), CONNECT_TIMEOUT.scope(connect_timeout, future))
+        .await;
+    let timings = *slot.lock().unwrap();
+    (output, timings)
+}
+
+impl ConnectTimings {
+    /// Whether a connection was opened rather than taken from the pool.
+    pub fn opened(&self) -> bool {
+        self.connect.is_some()
+    }
+}
+
+fn record(update: impl FnOnce(&mut ConnectTimings)) {
+    let _ = CONNECT_TIMINGS.try_with(|slot| {
+        if let Ok(mut timings) = slot.lock() {
+            update(&mut timings);
+        }
+    });
+}
+
+/// Report a lookup's duration for the connection being opened, from any resolver.
+pub fn record_dns(elapsed: Duration) {
+    record(|timings| timings.dns = Some(elapsed));
+    events::dns_resolved(elapsed);
+    tracing::debug!(elapsed_ms = elapsed.as_secs_f64() * 1000.0, "dns resolved");
+}
+
+/// System resolver that reports how long each lookup took.
+pub struct TimingResolver;
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let started = Instant::now();
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            record_dns(started.elapsed());
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Connector layer that reports how long establishing a new connection took, and
+/// enforces the request's connect timeout.
+#[derive(Clone)]
+pub struct ConnectTimingLayer;
+
+impl<S> Layer<S> for ConnectTimingLayer {
+    type Service = ConnectTiming<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTiming { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConnectTiming<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for ConnectTiming<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: From<ConnectTimedOut> + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let started = Instant::now();
+        // Covers the TCP, proxy and TLS handshakes, which wreq performs as one step
+        let connecting = self.inner.call(request).instrument(tracing::debug_span!("connect"));
+        let limit = CONNECT_TIMEOUT.try_with(|limit| *limit).ok().flatten();
+
+        Box::pin(async move {
+            let result = match limit {
+                Some(limit) => match tokio::time::timeout(limit, connecting).await {
+                    Ok(result) => result,
+                    Err(_) => Err(ConnectTimedOut { limit }.into()),
+                },
+                None => connecting.await,
+            };
+            if result.is_ok() {
+                let elapsed = started.elapsed();
+                let mut dns = None;
+                record(|timings| {
+                    timings.connect = Some(elapsed);
+                    dns = timings.dns;
+                });
+                events::connected(elapsed.saturating_sub(dns.unwrap_or_default()));
+            }
+            result
+        })
+    }
+}
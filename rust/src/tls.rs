@@ -0,0 +1,200 @@
+use crate::error::PinningViolation;
+use crate::fingerprint::TlsFingerprint;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use boring2::asn1::{Asn1Time, Asn1TimeRef};
+use boring2::hash::{hash, MessageDigest};
+use boring2::x509::{X509NameRef, X509};
+use std::net::IpAddr;
+use wreq::tls::{CertStore, Identity};
+use wreq::ClientBuilder;
+
+/// TLS settings baked into a client when it is built. Sessions compare them so a
+/// request never reuses a client with a different identity or trust configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub client_cert: Option<ClientCert>,
+    /// Root certificates trusted in addition to the system store
+    pub ca: Vec<CaSource>,
+    /// Skip certificate and hostname verification entirely
+    pub danger_accept_invalid_certs: bool,
+    /// ClientHello overrides layered over the emulation's TLS options
+    pub fingerprint: Option<TlsFingerprint>,
+}
+
+impl TlsConfig {
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if let Some(client_cert) = &self.client_cert {
+            builder = builder.identity(client_cert.identity()?);
+        }
+
+        if !self.ca.is_empty() {
+            builder = builder.cert_store(self.cert_store()?);
+        }
+
+        if self.danger_accept_invalid_certs {
+            builder = builder.cert_verification(false).verify_hostname(false);
+        }
+
+        Ok(builder)
+    }
+
+    // Extra roots are layered over the system store rather than replacing it
+    fn cert_store(&self) -> Result<CertStore> {
+        let mut store = CertStore::builder().set_default_paths();
+
+        for source in &self.ca {
+            store = match source {
+                CaSource::Pem(pem) => store.add_stack_pem_certs(pem),
+                CaSource::File(path) => {
+                    let pem = std::fs::read(path).with_context(|| format!("Failed to read CA file '{}'", path))?;
+                    store.add_stack_pem_certs(pem)
+                }
+            };
+        }
+
+        store.build().context("Invalid CA certificate bundle")
+    }
+}
+
+/// Where to load a PEM bundle of extra root certificates from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaSource {
+    Pem(Vec<u8>),
+    /// Read when the client is built, so a session picks up the file as it was at creation
+    File(String),
+}
+
+/// Client certificate presented for mutual TLS. Kept as the raw bytes it was given in,
+/// so sessions can tell identities apart without holding parsed key material.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientCert {
+    /// PEM certificate chain, leaf first, and its PKCS#8 PEM private key
+    Pem { cert: Vec<u8>, key: Vec<u8> },
+    /// DER-encoded PKCS#12 archive and the passphrase protecting it
+    Pkcs12 { archive: Vec<u8>, passphrase: String },
+}
+
+impl ClientCert {
+    pub fn identity(&self) -> Result<Identity> {
+        match self {
+            ClientCert::Pem { cert, key } => {
+                Identity::from_pkcs8_pem(cert, key).context("Invalid client certificate or PKCS#8 private key")
+            }
+            ClientCert::Pkcs12 { archive, passphrase } => {
+                Identity::from_pkcs12_der(archive, passphrase).context("Invalid PKCS#12 client certificate or passphrase")
+            }
+        }
+    }
+}
+
+/// The leaf certificate a server presented, for pinning and auditing in JS.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    /// Distinguished name as `KEY=value` pairs, e.g. `CN=example.com, O=Example`
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names and IP addresses from the subjectAltName extension
+    pub subject_alt_names: Vec<String>,
+    /// Validity bounds as milliseconds since the Unix epoch
+    pub not_before: f64,
+    pub not_after: f64,
+    /// SHA-256 of the DER encoding as colon-separated uppercase hex, like Node's `fingerprint256`
+    pub fingerprint256: String,
+}
+
+impl PeerCertificate {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let cert = X509::from_der(der).context("Invalid peer certificate")?;
+
+        let subject_alt_names = cert
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| {
+                        name.dnsname().map(str::to_string).or_else(|| {
+                            let ip = match name.ipaddress()? {
+                                [a, b, c, d] => IpAddr::from([*a, *b, *c, *d]),
+                                bytes => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+                            };
+                            Some(ip.to_string())
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let fingerprint256 = colon_hex(&cert.digest(MessageDigest::sha256())?);
+
+        Ok(Self {
+            subject: name_to_string(cert.subject_name()),
+            issuer: name_to_string(cert.issuer_name()),
+            subject_alt_names,
+            not_before: epoch_millis(cert.not_before())?,
+            not_after: epoch_millis(cert.not_after())?,
+            fingerprint256,
+        })
+    }
+}
+
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .filter_map(|entry| {
+            let key = entry.object().nid().short_name().ok()?;
+            let value = entry.data().as_utf8().ok()?;
+            Some(format!("{}={}", key, value))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn epoch_millis(time: &Asn1TimeRef) -> Result<f64> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    Ok((diff.days as f64 * 86_400.0 + diff.secs as f64) * 1000.0)
+}
+
+/// Decode a SHA-256 pin: hex (colons optional, as in `fingerprint256`) or base64,
+/// optionally prefixed with `sha256/` as in HPKP-style public key pins.
+pub fn parse_pin(pin: &str) -> Option<[u8; 32]> {
+    let hex = pin.replace(':', "");
+    let bytes = if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<_>>>()?
+    } else {
+        STANDARD.decode(pin.strip_prefix("sha256/").unwrap_or(pin)).ok()?
+    };
+    bytes.try_into().ok()
+}
+
+/// Accept the connection if either the leaf certificate or its SubjectPublicKeyInfo
+/// hashes to one of `pins`. Public key pins survive certificate renewals with the same key.
+pub fn verify_pins(der: Option<&[u8]>, pins: &[[u8; 32]]) -> Result<()> {
+    let Some(der) = der else {
+        return Err(PinningViolation { fingerprint256: None }.into());
+    };
+
+    let cert = X509::from_der(der).context("Invalid peer certificate")?;
+    let leaf = cert.digest(MessageDigest::sha256())?;
+    let spki = hash(MessageDigest::sha256(), &cert.public_key()?.public_key_to_der()?)?;
+
+    if pins.iter().any(|pin| pin[..] == leaf[..] || pin[..] == spki[..]) {
+        return Ok(());
+    }
+
+    Err(PinningViolation {
+        fingerprint256: Some(colon_hex(&leaf)),
+    }
+    .into())
+}
+
+fn colon_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,55 @@
+use http_body::{Body, Frame, SizeHint};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wreq::header::HeaderMap;
+
+/// A request body followed by trailer fields, sent once the last chunk has gone out:
+/// as a trailer section of a chunked HTTP/1.1 body, or a closing HEADERS frame on HTTP/2.
+pub struct WithTrailers {
+    inner: wreq::Body,
+    trailers: Option<HeaderMap>,
+}
+
+impl WithTrailers {
+    pub fn new(inner: wreq::Body, trailers: HeaderMap) -> Self {
+        Self {
+            inner,
+            trailers: Some(trailers),
+        }
+    }
+}
+
+impl Body for WithTrailers {
+    type Data = <wreq::Body as Body>::Data;
+    type Error = wreq::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(None) => Poll::Ready(self.trailers.take().map(|trailers| Ok(Frame::trailers(trailers)))),
+            polled => polled,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers.is_none() && self.inner.is_end_stream()
+    }
+
+    // Never report an exact size: a known length would be sent as Content-Length, and HTTP/1.1
+    // can only carry trailers on a chunked body.
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = SizeHint::new();
+        hint.set_lower(self.inner.size_hint().lower());
+        hint
+    }
+}
+
+/// Trailer fields as name/value pairs; values that aren't valid UTF-8 are left out.
+pub fn pairs(trailers: &HeaderMap) -> Vec<(String, String)> {
+    trailers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
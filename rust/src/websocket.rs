@@ -1,6 +1,7 @@
+use crate::emulation;
+use crate::session::build_proxy;
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
-use indexmap::IndexMap;
 use neon::prelude::*;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
@@ -8,7 +9,7 @@ use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::Mutex;
 use wreq::ws::message::Message;
 use wreq::ws::WebSocket;
-use wreq_util::Emulation;
+use wreq_util::{Emulation, EmulationOS};
 
 // Global storage for WebSocket connections
 static WS_CONNECTIONS: Lazy<StdMutex<HashMap<u64, Arc<WsConnection>>>> =
@@ -28,7 +29,8 @@ pub static WS_RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
 pub struct WebSocketOptions {
     pub url: String,
     pub emulation: Emulation,
-    pub headers: IndexMap<String, String>,
+    pub emulation_os: Option<EmulationOS>,
+    pub headers: Vec<(String, String)>,
     pub proxy: Option<String>,
 }
 
@@ -107,11 +109,12 @@ pub async fn connect_websocket(
     options: WebSocketOptions,
 ) -> Result<(WsConnection, futures_util::stream::SplitStream<WebSocket>)> {
     // Build client with emulation and proxy
-    let mut client_builder = wreq::Client::builder().emulation(options.emulation);
+    let mut client_builder =
+        wreq::Client::builder().emulation(emulation::build(&options.emulation, options.emulation_os));
 
     // Apply proxy if present
     if let Some(proxy_url) = &options.proxy {
-        let proxy = wreq::Proxy::all(proxy_url).context("Failed to create proxy")?;
+        let proxy = build_proxy(proxy_url, None, &[])?;
         client_builder = client_builder.proxy(proxy);
     }
 
@@ -125,7 +128,7 @@ pub async fn connect_websocket(
 
     // Apply custom headers
     for (key, value) in options.headers.iter() {
-        request = request.header(key, value);
+        request = request.header_append(key, value);
     }
 
     // Send upgrade request
@@ -3,6 +3,34 @@
  * DO NOT EDIT MANUALLY
  */
 
+import type {
+  AuthOptions,
+  AwsSigV4Options,
+  BodyDecoding,
+  ChallengeInfo,
+  ClientCertificate,
+  ConnectionFingerprint,
+  DnsOptions,
+  ExtractRule,
+  ExtractedValues,
+  HeaderPosition,
+  HeaderTuple,
+  Http2FingerprintOptions,
+  HttpVersion,
+  MiddlewareSend,
+  MultipartField,
+  NativeConnectionInfo,
+  NativeCookie,
+  NativeRedirectHop,
+  NativeTlsInfo,
+  PoolOptions,
+  ProxyAuth,
+  ProxyPoolHandle,
+  QueryValue,
+  Timings,
+  TlsFingerprintOptions,
+} from './types';
+
 /**
  * Browser profile names supported
  */
@@ -92,3 +120,803 @@ export type BrowserProfile =
   | 'okhttp_4.10'
   | 'okhttp_4.12'
   | 'okhttp_5';
+
+/**
+ * Operating system a browser profile presents as, in its User-Agent, client hints and platform
+ * headers
+ */
+export type EmulationOs =
+  | 'windows'
+  | 'macos'
+  | 'linux'
+  | 'android'
+  | 'ios';
+
+/**
+ * Content coding applied to an outgoing body.
+ *
+ * - `'deflate'` - zlib-wrapped, which is what `Content-Encoding: deflate` means
+ */
+export type BodyCompression =
+  | 'gzip'
+  | 'deflate'
+  | 'br'
+  | 'zstd';
+
+/**
+ * Digest computed over a response body as it is read.
+ *
+ * - `'xxh3'` - 64-bit XXH3: not cryptographic, but fast enough to hash anything the network delivers
+ */
+export type BodyHashAlgorithm =
+  | 'sha256'
+  | 'md5'
+  | 'xxh3';
+
+/**
+ * What a response body is reduced to in Rust, so only the result crosses into JS.
+ *
+ * - `'json'` - the parsed JSON value
+ * - `'html-meta'` - title, language, canonical URL, `<meta>` fields and links of an HTML page
+ * - `'xml'` - the document as a tree of `{ name, attributes, children }` elements
+ */
+export type BodyParser =
+  | 'json'
+  | 'html-meta'
+  | 'xml';
+
+/**
+ * Whether a request uses its session's jar, from the request's `cookies` option.
+ *
+ * - `'include'` - send the jar's cookies and store the ones responses set
+ * - `'omit'` - leave the jar alone: none of its cookies are sent and `Set-Cookie` isn't stored
+ * - `'manual'` - leave the jar alone, sending the caller's own cookies instead
+ */
+export type CookiePolicy =
+  | 'include'
+  | 'omit'
+  | 'manual';
+
+/**
+ * Which resolved addresses to connect to. The connector tries the family of the first address,
+ * falling back to the other one if that is slow or fails.
+ *
+ * - `'ipv4-only'` - IPv4 addresses only
+ * - `'ipv6-only'` - IPv6 addresses only
+ * - `'prefer-ipv4'` - IPv4 first, IPv6 when it is slow or fails
+ * - `'prefer-ipv6'` - IPv6 first, IPv4 when it is slow or fails
+ */
+export type IpStrategy =
+  | 'ipv4-only'
+  | 'ipv6-only'
+  | 'prefer-ipv4'
+  | 'prefer-ipv6';
+
+/**
+ * How a pool picks the proxy for each attempt.
+ *
+ * - `'round-robin'` - each proxy in turn
+ * - `'random'` - any proxy, uniformly
+ * - `'sticky-per-host'` - the same proxy for every request to a hostname, until it is quarantined
+ * - `'least-errors'` - the proxy with the fewest failures so far, taking turns on ties
+ */
+export type ProxyPoolStrategy =
+  | 'round-robin'
+  | 'random'
+  | 'sticky-per-host'
+  | 'least-errors';
+
+/**
+ * Per-request cache behaviour, mirroring `RequestInit.cache` from the Fetch standard.
+ *
+ * - `'default'` - serve fresh responses, revalidate stale ones
+ * - `'no-store'` - neither read nor update the cache
+ * - `'reload'` - skip the lookup, but store the response
+ * - `'no-cache'` - revalidate a stored response even when it is fresh
+ * - `'force-cache'` - serve any stored response, however stale, before going to the network
+ */
+export type RequestCacheMode =
+  | 'default'
+  | 'no-store'
+  | 'reload'
+  | 'no-cache'
+  | 'force-cache';
+
+/**
+ * How redirects are handled for a single request.
+ */
+export interface RedirectPolicy {
+  /**
+   * Whether 3xx responses with a Location header are followed
+   * @default true
+   */
+  follow?: boolean;
+  /**
+   * Maximum number of hops to follow before failing
+   * @default 10
+   */
+  max?: number;
+}
+
+/**
+ * Retry behaviour for a single request. Delays double on every retry, up to the cap, unless the
+ * server sends `Retry-After`.
+ */
+export interface RetryPolicy {
+  /**
+   * Total number of attempts, the first one included, so the default never retries
+   * @default 1
+   */
+  maxAttempts?: number;
+  /**
+   * Delay before the first retry in milliseconds, doubled on every attempt
+   * @default 100
+   */
+  backoffBase?: number;
+  /**
+   * Upper bound for any single delay in milliseconds, `Retry-After` included
+   * @default 10000
+   */
+  backoffCap?: number;
+  /**
+   * Randomise up to half of each delay to avoid synchronized retries
+   * @default true
+   */
+  jitter?: boolean;
+  /**
+   * Response statuses that trigger a retry
+   * @default [429, 502, 503, 504]
+   */
+  retryStatuses?: number[];
+  /**
+   * Retry connection failures, resets and timeouts
+   * @default true
+   */
+  retryOnNetworkError?: boolean;
+}
+
+/**
+ * Options baked into a session's client, so a request inside a session can't change them.
+ * Sessions are created with these as well.
+ *
+ * @internal
+ */
+export interface NativeConnectionOptions {
+  /**
+   * Operating system the browser profile presents as, instead of its own default
+   * (macOS for desktop profiles). Changes the User-Agent and, for Chromium profiles,
+   * `sec-ch-ua-platform` and `sec-ch-ua-mobile`; profiles tied to one platform, like
+   * Safari on iOS or OkHttp, ignore it. Inside a session it is fixed when the session is created.
+   * @example
+   * ```typescript
+   * // Chrome on Android
+   * await fetch('https://example.com', { browser: 'chrome_142', emulationOs: 'android' });
+   * ```
+   */
+  emulationOs?: EmulationOs;
+  /**
+   * Client certificate for servers that require mutual TLS. Inside a session it
+   * must match the certificate the session was created with, as must the other TLS options.
+   */
+  clientCert?: ClientCertificate;
+  /**
+   * Extra root certificates to trust on top of the system store, as PEM (one or
+   * more concatenated certificates), e.g. a corporate proxy or staging CA.
+   */
+  ca?: string | Buffer;
+  /**
+   * Path to a PEM file of extra root certificates to trust.
+   */
+  caFile?: string;
+  /**
+   * Accept any server certificate, including self-signed, expired or mismatched ones.
+   * This disables the protection TLS offers against interception; use it only for testing.
+   * @default false
+   */
+  dangerAcceptInvalidCerts?: boolean;
+  /**
+   * Replace parts of the browser profile's TLS ClientHello, e.g. to match a JA3 or JA4
+   * fingerprint captured from another client. Part of the session's client, like the
+   * other TLS options.
+   *
+   * @example
+   * ```typescript
+   * { tlsFingerprint: { ja3: '771,4865-4866-4867,0-23-65281-10-11-35-16-5-13-18-51-45-43-27,29-23-24,0' } }
+   * ```
+   */
+  tlsFingerprint?: TlsFingerprintOptions;
+  /**
+   * Override HTTP/2 settings, window size, priorities and pseudo-header order on top of
+   * the browser profile, for targets whose checks differ from the built-in profiles. Part
+   * of the session's client, like {@link tlsFingerprint}.
+   *
+   * @example
+   * ```typescript
+   * { http2Fingerprint: { initialWindowSize: 131072, pseudoHeaderOrder: ['m', 'p', 'a', 's'] } }
+   * ```
+   */
+  http2Fingerprint?: Http2FingerprintOptions;
+  /**
+   * Connection reuse and TCP keepalive tuning. Like the TLS options, it is part of
+   * the session's client and cannot differ between requests in one session.
+   */
+  pool?: PoolOptions;
+  /**
+   * Connect to fixed addresses for these hostnames instead of resolving them, like
+   * curl's `--resolve`. TLS and the `Host` header still use the hostname. Addresses
+   * may carry a port for familiarity, but the connection always uses the URL's port.
+   * Part of the session's client, like {@link pool}.
+   *
+   * @example
+   * ```typescript
+   * { resolve: { 'api.example.com': '10.0.0.5', 'cdn.example.com': ['10.0.0.6', '::1'] } }
+   * ```
+   */
+  resolve?: Record<string, string | string[]>;
+  /**
+   * Resolve hostnames with the built-in caching resolver instead of the system's,
+   * optionally through chosen nameservers or DNS-over-HTTPS. Overrides in
+   * {@link resolve} still take precedence. Part of the session's client, like {@link pool}.
+   *
+   * @example
+   * ```typescript
+   * { dns: { doh: 'cloudflare', minTtl: 60_000 } }
+   * ```
+   */
+  dns?: DnsOptions;
+  /**
+   * Restrict connections to IPv4 or IPv6, or choose which family is tried first.
+   * By default the resolver's order decides. Overridden hosts in {@link resolve}
+   * are not filtered. Part of the session's client, like {@link pool}.
+   */
+  ipStrategy?: IpStrategy;
+  /**
+   * File the session's cookies are loaded from when its client is created and saved to
+   * whenever they change, so logged-in sessions survive restarts. Paths ending in `.txt`
+   * use the Netscape `cookies.txt` format read by curl and wget; other paths are JSON.
+   * Give each session its own file. Part of the session's client, like {@link pool}.
+   */
+  cookieStorePath?: string;
+}
+
+/**
+ * A request as the JS layer hands it to `request`, `requestStream`, `download`, `toCurl`,
+ * `previewRequest` and `checkRobots`, validated and with its body serialized.
+ *
+ * @internal
+ */
+export interface NativeRequestOptions extends NativeConnectionOptions {
+  /**
+   * The URL to request, with any query string it already has.
+   */
+  url: string;
+  /**
+   * A string to set request's method.
+   * @default 'GET'
+   */
+  method?: string;
+  /**
+   * Headers in the order they were given. Browser-specific headers are added to them
+   * based on the selected browser profile.
+   */
+  headers?: Record<string, string> | HeaderTuple[];
+  /**
+   * Query parameters appended to the URL, after any it already has. Names and values
+   * are percent-encoded, so they can hold any characters.
+   */
+  query?: Record<string, QueryValue>;
+  /**
+   * How array values in {@link query} are written: `"repeat"` gives `a=1&a=2`,
+   * `"brackets"` gives `a[]=1&a[]=2`.
+   * @default "repeat"
+   */
+  queryArrayFormat?: 'repeat' | 'brackets';
+  /**
+   * Request body, sent byte-for-byte.
+   */
+  body?: string | Buffer;
+  /**
+   * Send a multipart/form-data body built from these fields. The boundary and
+   * Content-Type header are generated automatically. Cannot be combined with {@link body}.
+   */
+  multipart?: MultipartField[];
+  /**
+   * JSON body, already serialized. Sets `Content-Type: application/json` unless
+   * a Content-Type header is given. Cannot be combined with {@link body} or {@link multipart}.
+   */
+  json?: string;
+  /**
+   * Send these fields as an `application/x-www-form-urlencoded` body, the way a browser
+   * submits a form. Values are encoded like {@link query} values, with spaces as `+`.
+   * Sets the Content-Type unless one is given. Cannot be combined with {@link body},
+   * {@link multipart} or {@link json}.
+   */
+  form?: Record<string, QueryValue>;
+  /**
+   * Path of a file to send as the body. It is streamed from disk rather than read
+   * into memory, with `Content-Length` taken from the file size. Cannot be combined
+   * with {@link body}, {@link multipart}, {@link json} or {@link form}.
+   */
+  bodyFile?: string;
+  /**
+   * Trailer fields sent after {@link bodyFile}. The file then goes out chunked, without
+   * `Content-Length`, and the names are announced in a `Trailer` header. Requires {@link bodyFile}.
+   */
+  trailers?: Record<string, string> | HeaderTuple[];
+  /**
+   * Compress {@link body}, {@link json} or {@link form} before sending and set
+   * `Content-Encoding`, for APIs that accept compressed uploads of large JSON or
+   * NDJSON payloads. `"deflate"` is the zlib format HTTP means by it. Cannot be combined
+   * with {@link multipart}, {@link bodyFile} or a `Content-Encoding` header.
+   */
+  compressBody?: BodyCompression;
+  /**
+   * Whether and how far redirects are followed.
+   * @default { follow: true, max: 10 }
+   */
+  redirect?: RedirectPolicy;
+  /**
+   * Also follow the redirects interstitial pages make without a 3xx: a `Refresh` header,
+   * a `<meta http-equiv="refresh">` tag, or a short inline script that only assigns
+   * `location` (`location.href = "..."`, `location.replace("...")`). The refresh delay
+   * isn't waited out. Each hop is sent as a GET in the same session, so cookies the page
+   * sets carry over, and is listed in `redirectHops` with the page's status. Hops count
+   * towards {@link maxRedirects}. Only applies to buffered responses with {@link redirect}
+   * set to `"follow"`.
+   * @default false
+   */
+  followRefresh?: boolean;
+  /**
+   * Force HTTP/1.1 or HTTP/2 instead of negotiating via ALPN. The protocol actually
+   * used is reported in `response.version`.
+   */
+  httpVersion?: HttpVersion;
+  /**
+   * Reject with a `ERR_BODY_TOO_LARGE` RequestError once the response body exceeds
+   * this many bytes after decompression. The download is aborted as soon as the limit
+   * is crossed, or before it starts when `Content-Length` already exceeds it.
+   */
+  maxBodyBytes?: number;
+  /**
+   * Decode gzip, deflate, brotli and zstd bodies automatically. Set to `false` to
+   * receive the raw encoded bytes with the original `Content-Encoding` header,
+   * e.g. to relay a response untouched. Read such bodies with `arrayBuffer()` or `buffer()`.
+   * @default true
+   */
+  decompress?: boolean;
+  /**
+   * Replace the browser profile's `Accept-Encoding` header, e.g. `"identity"` to
+   * ask for an uncompressed body.
+   */
+  acceptEncoding?: string;
+  /**
+   * Decode the body text with this charset, e.g. `"shift_jis"` or `"windows-1251"`, instead of
+   * detecting it from a byte order mark, the `Content-Type` header, or an HTML `<meta>` tag or
+   * XML declaration, falling back to UTF-8. Any WHATWG encoding label is accepted; the raw bytes
+   * are always available from `bytes()` or `buffer()`.
+   */
+  charset?: string;
+  /**
+   * Retry failed attempts with exponential backoff. Responses report how many
+   * attempts were made in `response.attempts`.
+   */
+  retry?: RetryPolicy;
+  /**
+   * Browser profile to impersonate for this request.
+   * Automatically applies browser-specific headers, TLS fingerprints, and HTTP/2 settings.
+   * @default 'chrome_142'
+   */
+  browser?: BrowserProfile;
+  /**
+   * Proxy URL to route the request through (e.g., 'http://proxy.example.com:8080').
+   * Supports `http://`, `https://`, `socks4://`, `socks4a://`, `socks5://` and
+   * `socks5h://` (hostname resolved by the proxy). Inside a session this overrides
+   * the session's default proxy for this request only; its client and cookies are reused.
+   */
+  proxy?: string;
+  /**
+   * Credentials for {@link proxy}.
+   */
+  proxyAuth?: ProxyAuth;
+  /**
+   * SHA-256 pins for the server's certificate. The response is rejected with an
+   * `ERR_PINNING` RequestError unless the leaf certificate or its public key hashes
+   * to one of them. Accepts hex (colons optional, as in `response.tls.fingerprint256`)
+   * or base64 with an optional `sha256/` prefix. The check runs when the response
+   * head arrives, so a request body has already been sent by then.
+   */
+  pinnedSha256?: string[];
+  /**
+   * Extra headers sent to {@link proxy}, e.g. provider-specific session or routing headers.
+   * They go on the CONNECT request for HTTPS targets and on the request itself for HTTP targets.
+   */
+  proxyHeaders?: Record<string, string> | HeaderTuple[];
+  /**
+   * Pool to draw the proxy from, afresh for every attempt, by the ID {@link createProxyPool}
+   * gave it. Cannot be combined with {@link proxy}.
+   */
+  proxyPool?: Pick<ProxyPoolHandle, 'id'>;
+  /**
+   * Credentials: Basic or Bearer sent with the request, or Digest or NTLM used to answer
+   * the server's challenge.
+   */
+  auth?: AuthOptions;
+  /**
+   * Sign the request with AWS Signature Version 4. Cannot be combined with {@link auth}.
+   */
+  awsSigV4?: AwsSigV4Options;
+  /**
+   * Local IP address to connect from, for machines with several egress addresses.
+   * Connections are pooled per source address, so rotating it per request is cheap.
+   */
+  localAddress?: string;
+  /**
+   * Network interface to bind connections to (e.g. `'eth1'`). Supported on Linux,
+   * Android, macOS and other Apple platforms, Solaris and illumos. On Linux this is
+   * `SO_BINDTODEVICE`, set before connecting, so it also selects the device's routes.
+   */
+  interface?: string;
+  /**
+   * Path of a Unix domain socket to send the request over, for local daemons such as
+   * Docker. The URL still supplies the `Host` header and path, e.g.
+   * `http://localhost/v1.43/containers/json`. Takes the place of any proxy; not
+   * supported on Windows.
+   */
+  unixSocket?: string;
+  /**
+   * How this request uses the HTTP cache enabled with {@link setHttpCache}. Only
+   * buffered GET requests are served from the cache; streamed requests and downloads
+   * always go to the network.
+   * @default "default"
+   */
+  cache?: RequestCacheMode;
+  /**
+   * Revalidate instead of refetching: remember the `ETag` and `Last-Modified` of the URL's
+   * last `200`, send them as `If-None-Match`/`If-Modified-Since`, and answer a
+   * `304 Not Modified` with the remembered body, as a `200` with `fromCache` set. Lighter
+   * than {@link setHttpCache}: nothing is served without asking the server, and
+   * `Cache-Control` only matters for `no-store`. Sessions keep their own validators. Only
+   * buffered GET requests without conditional headers of their own take part.
+   * @default false
+   *
+   * @example
+   * ```typescript
+   * const feed = await fetch('https://example.com/feed.xml', { conditional: true });
+   * // Later: a 304 costs no body transfer, but the text is still there
+   * const again = await fetch('https://example.com/feed.xml', { conditional: true });
+   * console.log(again.fromCache, await again.text());
+   * ```
+   */
+  conditional?: boolean;
+  /**
+   * Trace or correlation ID recorded on this request's log span (see {@link setTracing}).
+   * A W3C trace ID (32 lowercase hex digits) is also sent as a `traceparent` header with
+   * a fresh parent span ID, unless the request already has one.
+   */
+  traceId?: string;
+  /**
+   * ID for this request, e.g. one from your own logs. It tags the request's log span,
+   * lifecycle events, HAR entry, {@link RequestError} and `response.requestId`. A UUID is
+   * generated when none is given.
+   */
+  requestId?: string;
+  /**
+   * Describe the fingerprint the connection presents in `response.fingerprint`: JA3,
+   * JA4, the negotiated ALPN protocol and the HTTP/2 preface, for checking a profile
+   * against services such as tls.peet.ws.
+   * @default false
+   */
+  inspectFingerprint?: boolean;
+  /**
+   * Parse the body in Rust and return the result as `response.parsed` instead of the
+   * body, which is left empty. Worth it when only a page's metadata is needed, since
+   * the markup never crosses into JavaScript. A body that doesn't parse rejects with
+   * `ERR_BODY_DECODE`. Not available for streamed responses.
+   *
+   * @example
+   * ```typescript
+   * const response = await fetch('https://example.com', { parse: 'html-meta' });
+   * const { title, meta } = response.parsed as HtmlMeta;
+   * ```
+   */
+  parse?: BodyParser;
+  /**
+   * Run CSS selectors over an HTML body in Rust and return their matches as
+   * `response.extracted` instead of the body, which is left empty. Each field is a
+   * selector, whose first match's text is returned, or an {@link ExtractRule} to read an
+   * attribute or collect every match. Text has its whitespace collapsed, as a browser
+   * renders it. The page is parsed once however many fields there are. Can be combined
+   * with `parse`; not available for streamed responses.
+   *
+   * @example
+   * ```typescript
+   * const { extracted } = await fetch('https://news.example.com', {
+   *   extract: {
+   *     headline: 'h1',
+   *     stories: { selector: 'article h2 > a', attr: 'href', all: true },
+   *   },
+   * });
+   * ```
+   */
+  extract?: Record<string, string | ExtractRule>;
+  /**
+   * Hash the body in Rust as it arrives and return the digest as `response.bodyHash`, in
+   * lowercase hex, so checking integrity or spotting duplicate content doesn't mean hashing
+   * the bytes again in JavaScript. The digest covers the body after decompression, or the
+   * raw bytes with `decompress: false`. Streamed responses have theirs once the body has
+   * been read to the end; downloads hash what they write to disk.
+   *
+   * @example
+   * ```typescript
+   * const response = await fetch('https://example.com/feed.xml', { hashBody: 'xxh3' });
+   * if (seen.has(response.bodyHash)) return;
+   * ```
+   */
+  hashBody?: BodyHashAlgorithm;
+  /**
+   * Share one network request among concurrent identical requests: a GET or HEAD
+   * without a body, to the same URL with the same headers, session, proxy and
+   * emulation as one already in flight waits for that one's response instead of
+   * sending another. Each caller gets its own copy of the response. Streamed
+   * requests are never shared.
+   * @default false
+   */
+  dedupe?: boolean;
+  /**
+   * Request timeout in milliseconds. If the request takes longer than this value,
+   * it will be aborted. Each retry gets its own. Defaults to 30000, unless
+   * `readTimeout` or `deadline` is set, in which case there is none.
+   */
+  timeout?: number;
+  /**
+   * Milliseconds to open a new connection, proxy and TLS handshakes included.
+   * Requests on a pooled connection don't wait on it.
+   */
+  connectTimeout?: number;
+  /**
+   * Milliseconds the server may go quiet: waiting for the response head, and then
+   * between body chunks. A slow download that keeps receiving data isn't cut off,
+   * while a stalled connection is.
+   */
+  readTimeout?: number;
+  /**
+   * Milliseconds for the whole request: every attempt, the backoff between them,
+   * redirects and reading the body. A retry that could only start after the
+   * deadline isn't made.
+   */
+  deadline?: number;
+  /**
+   * Whether this request uses the session's cookie jar, without needing a separate
+   * session to keep cookies apart. Cookies a response sets are still reported on
+   * `response.cookies` and `response.setCookies` when they aren't stored.
+   * @default "include"
+   */
+  cookies?: CookiePolicy;
+  /**
+   * Cookies to send with `cookies: "manual"`, by name. They go out in one `Cookie`
+   * header, after any `Cookie` header in {@link headers}.
+   */
+  cookieMap?: Record<string, string>;
+  /**
+   * Identifier of an existing session created elsewhere (e.g., via {@link createSession}).
+   */
+  sessionId?: string;
+  /**
+   * Whether the session is discarded once the request finishes.
+   */
+  ephemeral?: boolean;
+  /**
+   * Lowercased names of headers set to `null`, left out of the browser profile's defaults.
+   */
+  removeHeaders?: string[];
+  /**
+   * Disable default headers from browser emulation. When enabled, only explicitly
+   * provided headers will be sent with the request, preventing emulation headers
+   * from being automatically added or appended.
+   * @default false
+   */
+  disableDefaultHeaders?: boolean;
+  /**
+   * Send these headers next to another header instead of after the browser profile's
+   * defaults, so they sit where a real browser would put them. Anchors can be any header
+   * the request sends; see {@link getHeaderOrder} for a profile's default order. A header
+   * whose anchor isn't sent keeps its usual place. Positioned requests send header names
+   * with the casing given here over HTTP/1.1.
+   *
+   * @example
+   * ```typescript
+   * { headers: { 'X-Api-Key': 'secret' }, headerPositions: { 'X-Api-Key': { after: 'accept' } } }
+   * ```
+   */
+  headerPositions?: Record<string, HeaderPosition>;
+  /**
+   * Bytes of a streamed body read ahead of the consumer. Once that much is waiting, reading
+   * from the socket pauses until the consumer catches up, so a slow consumer slows the
+   * transfer (through TCP flow control) instead of buffering the body in memory.
+   * Only used with `stream`.
+   * @default 65536
+   *
+   * @example
+   * ```typescript
+   * const response = await fetch('https://example.com/large.bin', { stream: true, highWaterMark: 16 * 1024 });
+   * for await (const chunk of response) {
+   *   await writeSlowly(chunk);
+   * }
+   * ```
+   */
+  highWaterMark?: number;
+  /**
+   * Runs the request's `onSend` middleware before every send.
+   */
+  beforeSend?: (request: MiddlewareSend) => Promise<MiddlewareSend | undefined>;
+}
+
+/**
+ * A finished request, as JS gets it
+ *
+ * @internal
+ */
+export interface NativeResponse {
+  /**
+   * HTTP status code, e.g. 200 or 404
+   */
+  status: number;
+  /**
+   * Protocol the response arrived over, e.g. "HTTP/2"
+   */
+  version: string;
+  /**
+   * Response headers in wire order, repeated names included
+   */
+  headers: HeaderTuple[];
+  /**
+   * Values that aren't valid UTF-8, by index into `headers`, which holds them decoded as Latin-1
+   */
+  rawHeaderValues?: [number, Buffer][];
+  /**
+   * Trailer fields that followed the body, e.g. `grpc-status`
+   */
+  trailers?: HeaderTuple[];
+  /**
+   * ID of the request this answered, as in its logs, events and HAR entry
+   */
+  requestId: string;
+  /**
+   * Body decoded as text, with the charset in `charset`
+   */
+  body: string;
+  /**
+   * Body as it arrived, after any content codings were removed
+   */
+  bodyBytes: Buffer;
+  /**
+   * Encoding `body` was decoded with; for a streamed body, only one the request forced
+   */
+  charset?: string;
+  /**
+   * Cookies the response set, by name
+   */
+  cookies: Record<string, string>;
+  /**
+   * Every cookie the response set, with its attributes, in header order
+   */
+  setCookies: NativeCookie[];
+  /**
+   * Final URL, after any redirects
+   */
+  url: string;
+  /**
+   * URLs that answered with a redirect, in the order they were visited
+   */
+  redirects: string[];
+  /**
+   * The same redirects with their status, `Location` and cookies
+   */
+  redirectHops: NativeRedirectHop[];
+  /**
+   * Handle to pull the body with, when it was left unread for streaming
+   */
+  streamId?: number;
+  /**
+   * Number of times the request was sent, retries and proxy failovers included
+   */
+  attempts: number;
+  /**
+   * Phase durations of the final attempt
+   */
+  timings: Timings;
+  /**
+   * Proxy the final attempt went through, when the request or its proxy pool named one
+   */
+  proxy?: string;
+  /**
+   * Connection the final attempt went out on; cached responses have none
+   */
+  connection?: NativeConnectionInfo;
+  /**
+   * Leaf certificate of the connection the response arrived on
+   */
+  tls?: NativeTlsInfo;
+  /**
+   * Body served from the HTTP cache, either fresh or after a `304` revalidation
+   */
+  fromCache: boolean;
+  /**
+   * JA3/JA4, ALPN and HTTP/2 preface of the connection, when asked for
+   */
+  fingerprint?: ConnectionFingerprint;
+  /**
+   * What `parse` made of the body, which is then left empty
+   */
+  parsed?: unknown;
+  /**
+   * Matches of the `extract` rules by name; the body is left empty as with `parsed`
+   */
+  extracted?: ExtractedValues;
+  /**
+   * Signs that this is a bot-protection challenge rather than the page asked for
+   */
+  challenge?: ChallengeInfo;
+  /**
+   * Digest of the body, when the request asked for one; streamed bodies have theirs once read
+   */
+  bodyHash?: string;
+  /**
+   * Content codings removed from a buffered body, outermost first
+   */
+  decodings?: BodyDecoding[];
+}
+
+/**
+ * Failure class of a {@link RequestError}.
+ *
+ * - `ERR_TIMEOUT` - the request exceeded its `timeout`
+ * - `ERR_DNS` - the hostname could not be resolved
+ * - `ERR_CONNECT` - the connection was refused or reset
+ * - `ERR_TLS` - the TLS handshake or certificate verification failed
+ * - `ERR_PROXY` - the proxy rejected the connection or could not be reached
+ * - `ERR_TOO_MANY_REDIRECTS` - `maxRedirects` was exceeded
+ * - `ERR_BODY_DECODE` - the response body could not be read, decompressed or parsed
+ * - `ERR_BODY_TOO_LARGE` - the response body exceeded `maxBodyBytes`
+ * - `ERR_PINNING` - the server certificate matched none of `pinnedSha256`
+ * - `ERR_ABORTED` - the connection was torn down while the request was in flight
+ * - `ERR_RATE_LIMITED` - the rate limit was reached and set to `failFast`
+ * - `ERR_CIRCUIT_OPEN` - the host's circuit breaker is open after repeated failures
+ * - `ERR_SHUTDOWN` - `shutdown()` was under way, or its grace period ran out
+ * - `ERR_REQUEST` - anything else, such as invalid options
+ */
+export type ErrorCode =
+  | 'ERR_TIMEOUT'
+  | 'ERR_DNS'
+  | 'ERR_CONNECT'
+  | 'ERR_TLS'
+  | 'ERR_PROXY'
+  | 'ERR_TOO_MANY_REDIRECTS'
+  | 'ERR_BODY_DECODE'
+  | 'ERR_BODY_TOO_LARGE'
+  | 'ERR_PINNING'
+  | 'ERR_ABORTED'
+  | 'ERR_RATE_LIMITED'
+  | 'ERR_CIRCUIT_OPEN'
+  | 'ERR_SHUTDOWN'
+  | 'ERR_REQUEST';
+
+/**
+ * Every {@link ErrorCode}, in declaration order
+ */
+export const ERROR_CODES: readonly ErrorCode[] = [
+  'ERR_TIMEOUT',
+  'ERR_DNS',
+  'ERR_CONNECT',
+  'ERR_TLS',
+  'ERR_PROXY',
+  'ERR_TOO_MANY_REDIRECTS',
+  'ERR_BODY_DECODE',
+  'ERR_BODY_TOO_LARGE',
+  'ERR_PINNING',
+  'ERR_ABORTED',
+  'ERR_RATE_LIMITED',
+  'ERR_CIRCUIT_OPEN',
+  'ERR_SHUTDOWN',
+  'ERR_REQUEST',
+];
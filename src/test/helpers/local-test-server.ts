@@ -2,6 +2,7 @@ import { createHash } from "node:crypto";
 import { createServer, type IncomingMessage, type ServerResponse } from "node:http";
 import type { AddressInfo, Socket } from "node:net";
 import { setTimeout as delay } from "node:timers/promises";
+import { brotliCompressSync, gzipSync } from "node:zlib";
 
 const WS_MAGIC_STRING = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
@@ -14,6 +15,13 @@ export interface LocalTestServer {
 export async function startLocalTestServer(): Promise<LocalTestServer> {
   let baseUrl = "http://127.0.0.1";
   const sockets = new Set<Socket>();
+  const retryHits = new Map<string, number>();
+  const sseConnections = new Map<string, number>();
+  const cacheHits = new Map<string, number>();
+  const tokenHits = new Map<string, number>();
+  const countHits = new Map<string, number>();
+  const rangeRequests = new Map<string, string[]>();
+  let robotsFetches = 0;
 
   const server = createServer(async (req, res) => {
     try {
@@ -81,6 +89,11 @@ export async function startLocalTestServer(): Promise<LocalTestServer> {
     const url = new URL(req.url ?? "/", resolvedBase);
     const path = url.pathname;
 
+    // Absolute-form request targets mean the server is being used as a forward proxy
+    if (req.url?.startsWith("http://")) {
+      res.setHeader("X-Proxied", "1");
+    }
+
     if (path === "/get") {
       return json(res, createEchoPayload(req, url));
     }
@@ -108,6 +121,77 @@ export async function startLocalTestServer(): Promise<LocalTestServer> {
       return json(res, { cookies: parseCookies(req.headers.cookie) });
     }
 
+    if (path === "/cookies/attributes") {
+      res.setHeader("Set-Cookie", [
+        "first=1; Path=/; HttpOnly",
+        "second=2; Path=/account; Max-Age=3600; Secure; SameSite=Lax",
+      ]);
+      return json(res, { ok: true });
+    }
+
+    // "Привет" in windows-1251, declared only by a <meta> tag
+    if (path === "/windows-1251") {
+      const greeting = Buffer.from([0xcf, 0xf0, 0xe8, 0xe2, 0xe5, 0xf2]);
+      res.setHeader("Content-Type", "text/html");
+      res.end(
+        Buffer.concat([
+          Buffer.from('<html><head><meta charset="windows-1251"></head><body>'),
+          greeting,
+          Buffer.from("</body></html>"),
+        ]),
+      );
+      return;
+    }
+
+    // Page with metadata and links for the body parsers
+    if (path === "/html") {
+      res.setHeader("Content-Type", "text/html; charset=utf-8");
+      res.end(
+        [
+          '<!doctype html><html lang="en"><head>',
+          "<title> Local\n  test page </title>",
+          '<meta name="description" content="A page for parser tests">',
+          '<meta property="og:image" content="https://example.com/og.png">',
+          '<link rel="canonical" href="/html?canonical=1">',
+          '</head><body><h1 class="title">Hello</h1><ul class="links">',
+          '<li><a href="/get">Get</a></li>',
+          '<li><a href="https://example.com/" rel="external">Example</a></li>',
+          '<li><a href="/get">Again</a></li>',
+          '<li><a href="mailto:team@example.com">Mail</a></li>',
+          "</ul></body></html>",
+        ].join(""),
+      );
+      return;
+    }
+
+    // Cloudflare-style managed challenge
+    if (path === "/challenge") {
+      res.statusCode = 403;
+      res.setHeader("Content-Type", "text/html");
+      res.setHeader("cf-mitigated", "challenge");
+      res.end(
+        "<!DOCTYPE html><html><head><title>Just a moment...</title></head><body>" +
+          '<script src="/cdn-cgi/challenge-platform/h/g/orchestrate/chl_page/v1"></script></body></html>',
+      );
+      return;
+    }
+
+    if (path === "/xml") {
+      res.setHeader("Content-Type", "application/xml");
+      res.end(
+        '<?xml version="1.0"?>\n<feed>\n  <entry id="1"><title>First &amp; best</title></entry>\n' +
+          '  <entry id="2"><title><![CDATA[<Second>]]></title></entry>\n</feed>\n',
+      );
+      return;
+    }
+
+    // Repeated Vary headers and a Latin-1 value that isn't valid UTF-8
+    if (path === "/raw-headers") {
+      res.setHeader("Vary", ["Accept", "Accept-Encoding"]);
+      res.setHeader("X-Latin1", "caf\u00e9");
+      return json(res, { ok: true });
+    }
+
     if (path.startsWith("/cookies/set")) {
       const cookiesToSet = Array.from(url.searchParams.entries()).map(([key, value]) => `${key}=${value}; Path=/`);
       const existingCookies = parseCookies(req.headers.cookie);
@@ -119,6 +203,411 @@ export async function startLocalTestServer(): Promise<LocalTestServer> {
       return json(res, { cookies: { ...existingCookies, ...newCookies } });
     }
 
+    if (path === "/post") {
+      const body = await readBody(req);
+      return json(res, {
+        ...createEchoPayload(req, url),
+        data: body.toString("utf8"),
+        dataBase64: body.toString("base64"),
+      });
+    }
+
+    // Chunked response ending in trailers; echoes back any trailers the request sent
+    if (path === "/trailers") {
+      const body = await readBody(req);
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Trailer", "grpc-status, x-checksum");
+      res.write(JSON.stringify({ length: body.length, trailers: req.trailers }));
+      res.addTrailers({ "grpc-status": "0", "x-checksum": "sha256-abc" });
+      res.end();
+      return;
+    }
+
+    if (path === "/grpc-web/echo") {
+      // Echo each framed message back, then a trailer frame with the status and request metadata
+      const body = await readBody(req);
+      const frames: Buffer[] = [];
+      for (let offset = 0; offset + 5 <= body.length; ) {
+        const length = body.readUInt32BE(offset + 1);
+        frames.push(body.subarray(offset, offset + 5 + length));
+        offset += 5 + length;
+      }
+      const trailer = Buffer.from(
+        `grpc-status: 0\r\ngrpc-message: echoed%20${frames.length}\r\nx-grpc-web: ${req.headers["x-grpc-web"]}\r\n`,
+      );
+      const header = Buffer.alloc(5);
+      header[0] = 0x80;
+      header.writeUInt32BE(trailer.length, 1);
+      res.setHeader("Content-Type", req.headers["content-type"] ?? "application/octet-stream");
+      res.end(Buffer.concat([...frames, header, trailer]));
+      return;
+    }
+
+    if (path === "/grpc-web/unimplemented") {
+      res.setHeader("Content-Type", "application/grpc-web+proto");
+      res.setHeader("grpc-status", "12");
+      res.setHeader("grpc-message", "unknown%20method");
+      res.end();
+      return;
+    }
+
+    if (path === "/gzip") {
+      const payload = gzipSync(JSON.stringify({ gzipped: true, headers: canonicalizeHeaders(req) }));
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Content-Encoding", "gzip");
+      res.end(payload);
+      return;
+    }
+
+    if (path === "/encoding/stacked") {
+      // Gzipped, then brotli-compressed, listed in the order applied
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Content-Encoding", "gzip, br");
+      res.end(brotliCompressSync(gzipSync(JSON.stringify({ stacked: true }))));
+      return;
+    }
+
+    if (path === "/encoding/mislabeled") {
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Content-Encoding", "br");
+      res.end(gzipSync(JSON.stringify({ mislabeled: true })));
+      return;
+    }
+
+    if (path === "/encoding/truncated") {
+      // The first half of a brotli stream, which has no signature to give it away
+      const rows = Array.from({ length: 2000 }, (_, i) => ({ i, square: i * i }));
+      const payload = brotliCompressSync(JSON.stringify(rows));
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Content-Encoding", "br");
+      res.end(payload.subarray(0, Math.floor(payload.length / 2)));
+      return;
+    }
+
+    if (path === "/encoding/plain") {
+      res.setHeader("Content-Type", "application/json");
+      res.setHeader("Content-Encoding", "gzip");
+      res.end(JSON.stringify({ plain: true }));
+      return;
+    }
+
+    const bytesMatch = path.match(/^\/bytes\/(\d+)/);
+    if (bytesMatch) {
+      const size = Number(bytesMatch[1]);
+      const payload = Buffer.alloc(size);
+      for (let i = 0; i < size; i++) {
+        payload[i] = i % 256;
+      }
+      res.setHeader("Content-Type", "application/octet-stream");
+      res.end(payload);
+      return;
+    }
+
+    // Fails the first N hits for a key with 503 + Retry-After: 0, then succeeds
+    const retryMatch = path.match(/^\/retry\/([^/]+)\/(\d+)/);
+    if (retryMatch) {
+      const key = retryMatch[1] ?? "";
+      const failures = Number(retryMatch[2]);
+      const hits = (retryHits.get(key) ?? 0) + 1;
+      retryHits.set(key, hits);
+
+      if (hits <= failures) {
+        res.statusCode = 503;
+        res.setHeader("Retry-After", "0");
+        return json(res, { hits });
+      }
+      return json(res, { hits });
+    }
+
+    // Counts hits per key, answering after a short delay so concurrent requests overlap
+    const countMatch = path.match(/^\/count\/([^/]+)/);
+    if (countMatch) {
+      const key = countMatch[1] ?? "";
+      const hits = (countHits.get(key) ?? 0) + 1;
+      countHits.set(key, hits);
+      await delay(200);
+      return json(res, { hits });
+    }
+
+    // Cacheable response with an ETag; ?cc= sets Cache-Control and a matching If-None-Match gets a 304
+    const cacheMatch = path.match(/^\/cache\/([^/]+)/);
+    if (cacheMatch) {
+      const key = cacheMatch[1] ?? "";
+      const hits = (cacheHits.get(key) ?? 0) + 1;
+      cacheHits.set(key, hits);
+
+      res.setHeader("Cache-Control", url.searchParams.get("cc") ?? "max-age=60");
+      res.setHeader("ETag", `"${key}"`);
+      if (req.headers["if-none-match"] === `"${key}"`) {
+        res.statusCode = 304;
+        res.end();
+        return;
+      }
+      return json(res, { hits });
+    }
+
+    // OAuth2 token endpoint; tokens are numbered per key and scope echoes the grant type
+    const tokenMatch = path.match(/^\/oauth\/token\/([^/]+)/);
+    if (tokenMatch) {
+      const key = tokenMatch[1] ?? "";
+      const form = new URLSearchParams((await readBody(req)).toString("utf8"));
+      if (form.get("client_id") === "rejected") {
+        res.statusCode = 401;
+        return json(res, { error: "invalid_client", error_description: "Unknown client" });
+      }
+
+      const hits = (tokenHits.get(key) ?? 0) + 1;
+      tokenHits.set(key, hits);
+      await delay(20);
+      return json(res, {
+        access_token: `${key}-${hits}`,
+        token_type: "Bearer",
+        expires_in: Number(url.searchParams.get("expires_in") ?? 3600),
+        refresh_token: `refresh-${hits}`,
+        scope: form.get("grant_type"),
+      });
+    }
+
+    // Digest challenge (qop=auth) that accepts the given credentials; ?algorithm= picks the hash
+    const digestMatch = path.match(/^\/digest-auth\/([^/]+)\/([^/]+)/);
+    if (digestMatch) {
+      const [, user = "", pass = ""] = digestMatch;
+      const algorithm = url.searchParams.get("algorithm") ?? "MD5";
+      const hashName = algorithm === "SHA-256" ? "sha256" : "md5";
+      const h = (value: string) => createHash(hashName).update(value).digest("hex");
+      const realm = "wreq-js";
+      const nonce = "dcd98b7102dd2f0e8b11d0f600bfb0c093";
+
+      const params = parseDigestHeader(req.headers.authorization);
+      if (params) {
+        const ha1 = h(`${user}:${realm}:${pass}`);
+        const ha2 = h(`${req.method}:${params.uri}`);
+        const expected = h(`${ha1}:${nonce}:${params.nc}:${params.cnonce}:${params.qop}:${ha2}`);
+        if (params.username === user && params.nonce === nonce && params.response === expected) {
+          return json(res, { authenticated: true, user, algorithm: params.algorithm });
+        }
+      }
+
+      res.statusCode = 401;
+      res.setHeader(
+        "WWW-Authenticate",
+        `Digest realm="${realm}", qop="auth,auth-int", nonce="${nonce}", opaque="5ccc069c", algorithm=${algorithm}`,
+      );
+      return json(res, { authenticated: false });
+    }
+
+    // Event stream that ends after two events, then resumes from Last-Event-ID on reconnect
+    const sseMatch = path.match(/^\/sse\/([^/]+)/);
+    if (sseMatch) {
+      const key = sseMatch[1] ?? "";
+      const connection = (sseConnections.get(key) ?? 0) + 1;
+      sseConnections.set(key, connection);
+
+      res.writeHead(200, { "Content-Type": "text/event-stream", "Cache-Control": "no-cache" });
+
+      if (connection === 1) {
+        res.write("retry: 10\n: keep-alive comment\n\n");
+        res.write("id: 1\ndata: hello\n\n");
+        // Split a frame across writes to exercise incremental parsing
+        res.write("event: update\r\ndata: multi\r");
+        await delay(10);
+        res.write("\ndata: line\r\n\r\n");
+        res.end();
+        return;
+      }
+
+      const lastEventId = req.headers["last-event-id"] ?? "";
+      res.write(`id: 2\ndata: resumed after ${lastEventId}\n\n`);
+      // Keep the stream open until the client goes away
+      return;
+    }
+
+    // Interstitials that redirect without a 3xx: meta refresh, then a script, then a Refresh header
+    if (path === "/refresh/meta") {
+      res.setHeader("Content-Type", "text/html");
+      res.setHeader("Set-Cookie", "gate=passed; Path=/");
+      res.end(`<html><head><meta http-equiv="Refresh" content="0; URL='/refresh/script'"></head></html>`);
+      return;
+    }
+
+    if (path === "/refresh/script") {
+      res.setHeader("Content-Type", "text/html; charset=utf-8");
+      res.end('<html><body>Redirecting<script>window.location.href = "/refresh/header";</script></body></html>');
+      return;
+    }
+
+    if (path === "/refresh/header") {
+      res.setHeader("Content-Type", "text/html");
+      res.setHeader("Refresh", "0;url=/cookies");
+      res.end("<p>Redirecting</p>");
+      return;
+    }
+
+    if (path === "/refresh/reload") {
+      res.setHeader("Content-Type", "text/html");
+      res.end('<html><head><meta http-equiv="refresh" content="30"></head></html>');
+      return;
+    }
+
+    const refreshLoopMatch = path.match(/^\/refresh\/loop\/(\d+)$/);
+    if (refreshLoopMatch) {
+      res.setHeader("Content-Type", "text/html");
+      res.end(`<meta http-equiv="refresh" content="0;url=/refresh/loop/${Number(refreshLoopMatch[1]) + 1}">`);
+      return;
+    }
+
+    const redirectMatch = path.match(/^\/redirect\/(\d+)/);
+    if (redirectMatch) {
+      const remaining = Number(redirectMatch[1]);
+      res.statusCode = 302;
+      res.setHeader("Location", remaining > 1 ? `/redirect/${remaining - 1}` : "/get");
+      // ?cookie=<name> sets a cookie on this hop, like a login form's redirect
+      const cookie = url.searchParams.get("cookie");
+      if (cookie) {
+        res.setHeader("Set-Cookie", `${cookie}=hop-${remaining}; Path=/; HttpOnly`);
+      }
+      res.end();
+      return;
+    }
+
+    if (path === "/robots.txt") {
+      robotsFetches++;
+      res.setHeader("Content-Type", "text/plain");
+      res.end(
+        [
+          "# Test rules",
+          "User-agent: *",
+          "Disallow: /private",
+          "Allow: /private/open",
+          "Disallow: /*.pdf$",
+          "Crawl-delay: 2",
+          "",
+          "User-agent: StrictBot",
+          "User-agent: otherbot",
+          "Disallow: /",
+          "",
+          "Sitemap: https://example.com/sitemap.xml",
+        ].join("\n"),
+      );
+      return;
+    }
+
+    if (path === "/robots-log") {
+      return json(res, { fetches: robotsFetches });
+    }
+
+    if (path === "/sitemaps/index.xml") {
+      // Lists itself too, which must not be fetched again
+      const base = `http://${req.headers.host}/sitemaps`;
+      res.setHeader("Content-Type", "application/xml");
+      res.end(
+        `<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>${base}/pages.xml</loc><lastmod>2024-05-01</lastmod></sitemap>
+  <sitemap><loc>${base}/more.xml.gz</loc></sitemap>
+  <sitemap><loc>${base}/index.xml</loc></sitemap>
+</sitemapindex>`,
+      );
+      return;
+    }
+
+    if (path === "/sitemaps/pages.xml") {
+      // Written in pieces that split tags, with an image extension whose <loc> isn't a page
+      res.setHeader("Content-Type", "application/xml");
+      res.write('<?xml version="1.0"?>\n<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"');
+      res.write(' xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">\n<url><lo');
+      await delay(10);
+      res.write("c>https://example.com/a?x=1&amp;y=2</loc><lastmod>2024-05-01</lastmod>");
+      res.write("<changefreq>Daily</changefreq><priority>0.8</priority>");
+      res.write("<image:image><image:loc>https://example.com/a.png</image:loc></image:image></url>\n<!-- > -->");
+      await delay(10);
+      res.end("<url><loc><![CDATA[https://example.com/b]]></loc></url>\n</urlset>");
+      return;
+    }
+
+    if (path === "/sitemaps/more.xml.gz") {
+      res.setHeader("Content-Type", "application/gzip");
+      const xml = '<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">';
+      res.end(gzipSync(`${xml}<url><loc>https://example.com/c</loc></url></urlset>`));
+      return;
+    }
+
+    if (path === "/ndjson/split") {
+      // A line split across writes, CRLF endings, a blank line and no newline at the end
+      res.setHeader("Content-Type", "application/x-ndjson");
+      res.write('{"a":');
+      await delay(10);
+      res.write('1}\r\n\n{"b":');
+      await delay(10);
+      res.end("2}");
+      return;
+    }
+
+    if (path === "/ndjson/invalid") {
+      res.setHeader("Content-Type", "application/x-ndjson");
+      res.end('{"ok":true}\nnot json\n');
+      return;
+    }
+
+    // Serves `size` bytes (i % 256) with a strong ETag and byte ranges. Without a Range header,
+    // `fail` cuts the connection after that many bytes
+    const rangedMatch = path.match(/^\/ranged\/([^/]+)$/);
+    if (rangedMatch) {
+      const key = rangedMatch[1];
+      const size = Number(url.searchParams.get("size") ?? "1024");
+      const body = Buffer.from(Array.from({ length: size }, (_, i) => i % 256));
+      const etag = `"${key}-v1"`;
+      const range = req.headers.range;
+      rangeRequests.set(key, [...(rangeRequests.get(key) ?? []), range ?? "none"]);
+      res.setHeader("Accept-Ranges", "bytes");
+      res.setHeader("ETag", etag);
+
+      const ifRange = req.headers["if-range"];
+      const match = range?.match(/^bytes=(\d+)-(\d*)$/);
+      if (match && (ifRange === undefined || ifRange === etag)) {
+        const first = Number(match[1]);
+        if (first >= size) {
+          res.statusCode = 416;
+          res.setHeader("Content-Range", `bytes */${size}`);
+          res.end();
+          return;
+        }
+        const last = Math.min(match[2] ? Number(match[2]) : size - 1, size - 1);
+        res.statusCode = 206;
+        res.setHeader("Content-Range", `bytes ${first}-${last}/${size}`);
+        res.end(body.subarray(first, last + 1));
+        return;
+      }
+
+      res.setHeader("Content-Length", size);
+      const fail = url.searchParams.get("fail");
+      if (fail !== null && range === undefined) {
+        res.write(body.subarray(0, Number(fail)));
+        await delay(50);
+        res.destroy();
+        return;
+      }
+      res.end(body);
+      return;
+    }
+
+    const rangeLogMatch = path.match(/^\/ranged-log\/([^/]+)$/);
+    if (rangeLogMatch) {
+      return json(res, { ranges: rangeRequests.get(rangeLogMatch[1]) ?? [] });
+    }
+
+    const streamMatch = path.match(/^\/stream\/(\d+)/);
+    if (streamMatch) {
+      const count = Number(streamMatch[1]);
+      res.setHeader("Content-Type", "application/json");
+      for (let id = 0; id < count; id++) {
+        res.write(`${JSON.stringify({ id, url: url.toString() })}\n`);
+        await delay(10);
+      }
+      res.end();
+      return;
+    }
+
     const delayMatch = path.match(/^\/delay\/(\d+)/);
     if (delayMatch) {
       const seconds = Number(delayMatch[1]);
@@ -130,6 +619,14 @@ export async function startLocalTestServer(): Promise<LocalTestServer> {
     json(res, { error: "not found", path });
   }
 
+  async function readBody(req: IncomingMessage): Promise<Buffer> {
+    const chunks: Buffer[] = [];
+    for await (const chunk of req) {
+      chunks.push(chunk as Buffer);
+    }
+    return Buffer.concat(chunks);
+  }
+
   function createEchoPayload(req: IncomingMessage, url: URL) {
     const args = Object.fromEntries(url.searchParams.entries()) as Record<string, string>;
 
@@ -172,6 +669,19 @@ export async function startLocalTestServer(): Promise<LocalTestServer> {
     }, {});
   }
 
+  function parseDigestHeader(header: string | undefined): Record<string, string> | undefined {
+    if (!header?.startsWith("Digest ")) {
+      return undefined;
+    }
+
+    const params: Record<string, string> = {};
+    for (const match of header.slice(7).matchAll(/(\w+)=(?:"((?:[^"\\]|\\.)*)"|([^\s,]+))/g)) {
+      const [, key = "", quoted, token] = match;
+      params[key] = quoted?.replace(/\\(.)/g, "$1") ?? token ?? "";
+    }
+    return params;
+  }
+
   function json(res: ServerResponse, body: unknown) {
     if (!res.hasHeader("Content-Type")) {
       res.setHeader("Content-Type", "application/json");
@@ -0,0 +1,160 @@
+import { type AddressInfo, connect, createServer, type Socket } from "node:net";
+
+export interface SocksConnectRecord {
+  host: string;
+  port: number;
+  addressType: "ipv4" | "domain" | "ipv6";
+  username?: string;
+}
+
+export interface Socks5Proxy {
+  /** Address of the proxy without a scheme, e.g. `127.0.0.1:1080` */
+  address: string;
+  /** Every CONNECT request the proxy has seen, in order */
+  connections: SocksConnectRecord[];
+  close(): Promise<void>;
+}
+
+/**
+ * Minimal SOCKS5 proxy (RFC 1928) supporting CONNECT with either no auth or
+ * username/password auth (RFC 1929). Records how each target was addressed so
+ * tests can tell local DNS resolution (socks5) from remote resolution (socks5h).
+ */
+export async function startSocks5Proxy(): Promise<Socks5Proxy> {
+  const connections: SocksConnectRecord[] = [];
+  const sockets = new Set<Socket>();
+
+  const server = createServer((client) => {
+    sockets.add(client);
+    client.on("close", () => sockets.delete(client));
+    client.on("error", () => client.destroy());
+
+    handleClient(client, connections, sockets).catch(() => client.destroy());
+  });
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(0, "127.0.0.1", () => {
+      server.off("error", reject);
+      resolve();
+    });
+  });
+
+  const { port } = server.address() as AddressInfo;
+
+  return {
+    address: `127.0.0.1:${port}`,
+    connections,
+    close: async () => {
+      for (const socket of sockets) {
+        socket.destroy();
+      }
+      await new Promise<void>((resolve, reject) => server.close((error) => (error ? reject(error) : resolve())));
+    },
+  };
+}
+
+async function handleClient(client: Socket, connections: SocksConnectRecord[], sockets: Set<Socket>) {
+  const reader = createReader(client);
+
+  const greeting = await reader.read(2);
+  if (greeting.readUInt8(0) !== 5) {
+    throw new Error("Unsupported SOCKS version");
+  }
+  const methods = await reader.read(greeting.readUInt8(1));
+
+  let username: string | undefined;
+  if (methods.includes(0x02)) {
+    client.write(Buffer.from([5, 2]));
+    const userHeader = await reader.read(2);
+    username = (await reader.read(userHeader.readUInt8(1))).toString("utf8");
+    const passwordLength = (await reader.read(1)).readUInt8(0);
+    await reader.read(passwordLength);
+    client.write(Buffer.from([1, 0]));
+  } else {
+    client.write(Buffer.from([5, 0]));
+  }
+
+  const request = await reader.read(4);
+  const command = request.readUInt8(1);
+  const atyp = request.readUInt8(3);
+
+  let host: string;
+  let addressType: SocksConnectRecord["addressType"];
+  if (atyp === 1) {
+    host = Array.from(await reader.read(4)).join(".");
+    addressType = "ipv4";
+  } else if (atyp === 3) {
+    const length = (await reader.read(1)).readUInt8(0);
+    host = (await reader.read(length)).toString("utf8");
+    addressType = "domain";
+  } else if (atyp === 4) {
+    const raw = await reader.read(16);
+    const groups: string[] = [];
+    for (let i = 0; i < 16; i += 2) {
+      groups.push(raw.readUInt16BE(i).toString(16));
+    }
+    host = groups.join(":");
+    addressType = "ipv6";
+  } else {
+    throw new Error(`Unsupported address type ${atyp}`);
+  }
+  const port = (await reader.read(2)).readUInt16BE(0);
+
+  // Only CONNECT is needed for HTTP traffic
+  if (command !== 1) {
+    client.end(Buffer.from([5, 7, 0, 1, 0, 0, 0, 0, 0, 0]));
+    return;
+  }
+
+  connections.push({ host, port, addressType, ...(username !== undefined && { username }) });
+
+  // The test server only listens on IPv4 loopback
+  const upstream = connect(port, host === "localhost" ? "127.0.0.1" : host, () => {
+    client.write(Buffer.from([5, 0, 0, 1, 0, 0, 0, 0, 0, 0]));
+    const pending = reader.release();
+    if (pending.length > 0) {
+      upstream.write(pending);
+    }
+    client.pipe(upstream);
+    upstream.pipe(client);
+  });
+  sockets.add(upstream);
+  upstream.on("close", () => sockets.delete(upstream));
+  upstream.on("error", () => client.destroy());
+}
+
+function createReader(socket: Socket) {
+  let buffered = Buffer.alloc(0);
+  let waiting: { size: number; resolve: (chunk: Buffer) => void } | undefined;
+
+  const flush = () => {
+    if (waiting && buffered.length >= waiting.size) {
+      const { size, resolve } = waiting;
+      waiting = undefined;
+      const chunk = buffered.subarray(0, size);
+      buffered = buffered.subarray(size);
+      resolve(chunk);
+    }
+  };
+
+  const onData = (data: Buffer) => {
+    buffered = Buffer.concat([buffered, data]);
+    flush();
+  };
+  socket.on("data", onData);
+
+  return {
+    read(size: number): Promise<Buffer> {
+      return new Promise((resolve) => {
+        waiting = { size, resolve };
+        flush();
+      });
+    },
+    // Stop buffering and hand back anything received past the handshake
+    release(): Buffer {
+      socket.off("data", onData);
+      return buffered;
+    },
+  };
+}
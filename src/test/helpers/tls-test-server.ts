@@ -0,0 +1,63 @@
+import { createServer } from "node:https";
+import type { AddressInfo, Socket } from "node:net";
+import type { TLSSocket } from "node:tls";
+import { CA_CERT, SERVER_CERT, SERVER_KEY } from "./tls-fixtures";
+
+export interface TlsTestServer {
+  /** Base URL of the server, e.g. `https://localhost:12345` */
+  url: string;
+  close(): Promise<void>;
+}
+
+/**
+ * HTTPS server presenting a certificate issued by the test CA. It asks for a
+ * client certificate without requiring one, and answers every request with JSON
+ * describing the certificate the client presented.
+ */
+export async function startTlsTestServer(): Promise<TlsTestServer> {
+  const sockets = new Set<Socket>();
+
+  const server = createServer(
+    { cert: SERVER_CERT, key: SERVER_KEY, ca: CA_CERT, requestCert: true, rejectUnauthorized: false },
+    (req, res) => {
+      const socket = req.socket as TLSSocket;
+      const peer = socket.getPeerCertificate();
+      const subject = peer.subject?.CN;
+
+      res.setHeader("Content-Type", "application/json");
+      res.end(
+        JSON.stringify({
+          authorized: socket.authorized,
+          ...(subject !== undefined && { clientSubject: subject }),
+        }),
+      );
+    },
+  );
+
+  server.on("connection", (socket: Socket) => {
+    sockets.add(socket);
+    socket.on("close", () => sockets.delete(socket));
+  });
+  // Handshakes rejected by the client surface here; they are expected in tests
+  server.on("tlsClientError", () => {});
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(0, "127.0.0.1", () => {
+      server.off("error", reject);
+      resolve();
+    });
+  });
+
+  const { port } = server.address() as AddressInfo;
+
+  return {
+    url: `https://localhost:${port}`,
+    close: async () => {
+      for (const socket of sockets) {
+        socket.destroy();
+      }
+      await new Promise<void>((resolve, reject) => server.close((error) => (error ? reject(error) : resolve())));
+    },
+  };
+}
@@ -1,7 +1,69 @@
 import assert from "node:assert";
+import { createHash, createHmac, X509Certificate } from "node:crypto";
+import { access, mkdtemp, readFile, rm, writeFile } from "node:fs/promises";
+import { createServer as createHttpServer } from "node:http";
+import { type AddressInfo, createServer } from "node:net";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
 import { before, describe, test } from "node:test";
-import type { BrowserProfile, Session } from "../wreq-js";
-import { createSession, getProfiles, Headers, RequestError, withSession, fetch as wreqFetch } from "../wreq-js";
+import { brotliDecompressSync, gunzipSync, inflateSync } from "node:zlib";
+import type { BrowserProfile, DownloadProgress, RequestEvent, Response, Session, XmlElement } from "../wreq-js";
+import {
+  clearClientCache,
+  checkProxies,
+  checkRobots,
+  clearRobotsCache,
+  circuitBreakerStats,
+  clientCacheStats,
+  configureClientCache,
+  configureRuntime,
+  createProxyPool,
+  createSession,
+  createTokenManager,
+  download,
+  eventSource,
+  exportHar,
+  getConcurrency,
+  getHeaderOrder,
+  getMetrics,
+  getProfiles,
+  getRateLimit,
+  grpcWeb,
+  Headers,
+  isAllowedByRobots,
+  listEmulations,
+  parseBrowserProfile,
+  prewarm,
+  RequestError,
+  resetMetrics,
+  setCircuitBreaker,
+  setConcurrency,
+  setDefaults,
+  setEventListener,
+  setHttpCache,
+  setMocks,
+  setProxyRouting,
+  setRateLimit,
+  setTracing,
+  shutdown,
+  sitemap,
+  startHarRecording,
+  stopHarRecording,
+  toCurl,
+  use,
+  withSession,
+  fetch as wreqFetch,
+} from "../wreq-js";
+import { startSocks5Proxy } from "./helpers/socks5-proxy";
+import {
+  CA_CERT,
+  CLIENT_CERT,
+  CLIENT_KEY,
+  CLIENT_PKCS12,
+  CLIENT_PKCS12_PASSPHRASE,
+  SERVER_CERT,
+} from "./helpers/tls-fixtures";
+import { startTlsTestServer } from "./helpers/tls-test-server";
 
 const HTTP_TEST_BASE_URL = process.env.HTTP_TEST_BASE_URL ?? "https://httpbingo.org";
 const httpUrl = (path: string) => new URL(path, HTTP_TEST_BASE_URL).toString();
@@ -10,6 +72,15 @@ function headerIndex(rawHeaders: string[], name: string) {
   return rawHeaders.findIndex((value, index) => index % 2 === 0 && value.toLowerCase() === name.toLowerCase());
 }
 
+// A proxy URL on a port that was just released, so connecting to it is refused
+async function deadProxyUrl() {
+  const server = createServer();
+  await new Promise<void>((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const { port } = server.address() as AddressInfo;
+  await new Promise<void>((resolve) => server.close(() => resolve()));
+  return `http://127.0.0.1:${port}`;
+}
+
 describe("HTTP", () => {
   before(() => {
     console.log("🔌 HTTP Test Suite\n");
@@ -68,6 +139,22 @@ describe("HTTP", () => {
     }
   });
 
+  test("should present the browser profile as the chosen OS", async () => {
+    const userAgent = async (init: Parameters<typeof wreqFetch>[1]) => {
+      const response = await wreqFetch(httpUrl("/user-agent"), { browser: "chrome_142", ...init });
+      return (await response.json<{ "user-agent": string }>())["user-agent"];
+    };
+
+    assert.match(await userAgent({}), /Macintosh/);
+    assert.match(await userAgent({ emulationOs: "android" }), /Android/);
+    assert.match(await userAgent({ emulationOs: "windows" }), /Windows NT/);
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/user-agent"), { emulationOs: "beos" as never }),
+      /Unknown emulationOs 'beos'/,
+    );
+  });
+
   test("should handle timeout errors", async () => {
     await assert.rejects(
       async () => {
@@ -159,6 +246,81 @@ describe("HTTP", () => {
     assert.ok(secondIndex < thirdIndex, "X-Second should appear before X-Third");
   });
 
+  test("should send and expose repeated headers", async () => {
+    const response = await wreqFetch(httpUrl("/headers"), {
+      browser: "chrome_142",
+      headers: [
+        ["X-Repeat", "one"],
+        ["X-Other", "middle"],
+        ["X-Repeat", "two"],
+      ],
+      disableDefaultHeaders: true,
+      timeout: 10000,
+    });
+
+    const body = await response.json<{ rawHeaders: string[] }>();
+    const repeated = body.rawHeaders.filter(
+      (_, index) => index % 2 === 1 && body.rawHeaders[index - 1]?.toLowerCase() === "x-repeat",
+    );
+    assert.deepStrictEqual(repeated, ["one", "two"], "Both values should be sent as separate headers");
+
+    const cookies = await wreqFetch(httpUrl("/cookies/attributes"), { browser: "chrome_142", timeout: 10000 });
+    assert.strictEqual(cookies.headers.getSetCookie().length, 2, "Each Set-Cookie header should be kept");
+    assert.deepStrictEqual(
+      cookies.headers
+        .toTuples()
+        .filter(([name]) => name === "set-cookie")
+        .map(([, value]) => value.split(";")[0]),
+      ["first=1", "second=2"],
+    );
+  });
+
+  test("should expose every response header with the bytes of non-UTF-8 values", async () => {
+    const response = await wreqFetch(httpUrl("/raw-headers"), { browser: "chrome_142", timeout: 10000 });
+
+    assert.deepStrictEqual(response.headers.getAll("VARY"), ["Accept", "Accept-Encoding"]);
+    assert.deepStrictEqual(
+      response.rawHeaders.filter(({ name }) => name === "vary").map(({ value }) => value),
+      ["Accept", "Accept-Encoding"],
+    );
+
+    const latin1 = response.rawHeaders.find(({ name }) => name === "x-latin1");
+    assert.ok(latin1, "The non-UTF-8 header should be kept");
+    assert.strictEqual(latin1.value, "caf\u00e9");
+    assert.deepStrictEqual(latin1.bytes, Buffer.from([0x63, 0x61, 0x66, 0xe9]));
+    assert.strictEqual(
+      response.rawHeaders.find(({ name }) => name === "vary")?.bytes,
+      undefined,
+      "UTF-8 values should not carry bytes",
+    );
+  });
+
+  test("should decode bodies with the charset their markup declares", async () => {
+    const detected = await wreqFetch(httpUrl("/windows-1251"), { browser: "chrome_142", timeout: 10000 });
+    assert.ok((await detected.text()).includes("Привет"));
+    assert.strictEqual(detected.charset, "windows-1251");
+
+    const forced = await wreqFetch(httpUrl("/windows-1251"), {
+      browser: "chrome_142",
+      charset: "latin1",
+      timeout: 10000,
+    });
+    assert.strictEqual(forced.charset, "windows-1252");
+    assert.ok(!(await forced.clone().text()).includes("Привет"));
+    const greeting = new Uint8Array([0xcf, 0xf0, 0xe8, 0xe2, 0xe5, 0xf2]);
+    assert.deepStrictEqual((await forced.bytes()).subarray(54, 60), greeting, "bytes() should be undecoded");
+
+    const streamed = await wreqFetch(httpUrl("/windows-1251"), { browser: "chrome_142", stream: true, timeout: 10000 });
+    assert.strictEqual(streamed.charset, null);
+    assert.ok((await streamed.text()).includes("Привет"));
+    assert.strictEqual(streamed.charset, "windows-1251");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { browser: "chrome_142", charset: "" }),
+      (error: unknown) => error instanceof RequestError,
+    );
+  });
+
   test("should keep object header order intact", async () => {
     const response = await wreqFetch(httpUrl("/headers"), {
       browser: "chrome_142",
@@ -186,6 +348,34 @@ describe("HTTP", () => {
     assert.ok(middleIndex < endIndex, "X-Middle should precede X-End");
   });
 
+  test("should place headers relative to the emulation's order", async () => {
+    const template = getHeaderOrder("chrome_142");
+    assert.ok(template.includes("user-agent") && template.includes("accept"), "Template should list default headers");
+    assert.ok(template.every((name) => name === name.toLowerCase()), "Template names should be lowercase");
+
+    const response = await wreqFetch(httpUrl("/headers"), {
+      browser: "chrome_142",
+      headers: { "X-Api-Key": "secret", "X-Early": "1", "X-Anywhere": "2" },
+      headerPositions: {
+        "X-Api-Key": { after: "user-agent" },
+        "X-Early": { before: "accept" },
+        "X-Anywhere": { after: "x-not-sent" },
+      },
+      timeout: 10000,
+    });
+
+    const body = await response.json<{ rawHeaders: string[] }>();
+    const index = (name: string) => headerIndex(body.rawHeaders, name);
+    assert.strictEqual(index("X-Api-Key"), index("user-agent") + 2, "X-Api-Key should follow User-Agent");
+    assert.strictEqual(index("X-Early"), index("accept") - 2, "X-Early should precede Accept");
+    assert.ok(index("X-Anywhere") !== -1, "Headers with a missing anchor should still be sent");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { headerPositions: { "X-Api-Key": { before: "accept", after: "user-agent" } } }),
+      (error: unknown) => error instanceof RequestError && /exactly one of before or after/.test(error.message),
+    );
+  });
+
   test("should provide functional clone and text helpers", async () => {
     const response = await wreqFetch(httpUrl("/json"), {
       browser: "chrome_142",
@@ -202,123 +392,2844 @@ describe("HTTP", () => {
     assert.ok(clone.bodyUsed, "clone body should be consumed");
   });
 
-  test("should reject aborted requests with AbortError", async () => {
-    const controller = new AbortController();
-    controller.abort();
+  test("should hash response bodies as they are read", async () => {
+    const buffered = await wreqFetch(httpUrl("/bytes/4096"), { hashBody: "sha256", timeout: 10000 });
+    const bytes = Buffer.from(await buffered.arrayBuffer());
+    assert.strictEqual(buffered.bodyHash, createHash("sha256").update(bytes).digest("hex"));
+
+    const streamed = await wreqFetch(httpUrl("/bytes/4096"), { hashBody: "md5", stream: true, timeout: 10000 });
+    assert.strictEqual(streamed.bodyHash, null, "A streamed body has no digest until it has been read");
+    const streamedBytes = await streamed.buffer();
+    assert.strictEqual(streamed.bodyHash, createHash("md5").update(streamedBytes).digest("hex"));
+
+    const fast = await wreqFetch(httpUrl("/bytes/4096"), { hashBody: "xxh3", timeout: 10000 });
+    assert.match(fast.bodyHash ?? "", /^[0-9a-f]{16}$/);
+
+    const plain = await wreqFetch(httpUrl("/bytes/16"), { timeout: 10000 });
+    assert.strictEqual(plain.bodyHash, null);
 
     await assert.rejects(
-      async () => {
-        await wreqFetch(httpUrl("/get"), {
-          browser: "chrome_142",
-          signal: controller.signal,
-          timeout: 1000,
-        });
-      },
-      (error: unknown) => error instanceof Error && error.name === "AbortError",
-      "Should reject with AbortError",
+      wreqFetch(httpUrl("/bytes/16"), { hashBody: "sha1" as unknown as "sha256" }),
+      (error: unknown) => error instanceof RequestError && /hashBody/.test(error.message),
     );
   });
 
-  test("should work with custom Headers helper", () => {
-    const headers = new Headers({
-      "X-Test": "alpha",
+  test("should return binary response bodies intact", async () => {
+    const response = await wreqFetch(httpUrl("/bytes/256"), {
+      browser: "chrome_142",
+      timeout: 10000,
     });
 
-    headers.append("x-test", "beta");
-    headers.set("X-Another", "value");
+    const bytes = Buffer.from(await response.arrayBuffer());
 
-    const collected = Array.from(headers.entries());
+    assert.strictEqual(bytes.length, 256, "Should receive every byte");
+    for (let i = 0; i < bytes.length; i++) {
+      assert.strictEqual(bytes[i], i, `Byte ${i} should be preserved`);
+    }
+  });
 
-    assert.strictEqual(headers.get("X-Test"), "alpha, beta", "append should concatenate values");
-    assert.strictEqual(headers.get("x-another"), "value", "set should overwrite values");
-    assert.ok(collected.length >= 2, "entries should iterate all headers");
+  test("should send binary request bodies intact", async () => {
+    const payload = Buffer.from([0x00, 0xff, 0x80, 0x7f, 0xc3, 0x28]);
+    const response = await wreqFetch(httpUrl("/post"), {
+      browser: "chrome_142",
+      method: "POST",
+      body: payload,
+      timeout: 10000,
+    });
+
+    const body = await response.json<{ dataBase64: string }>();
+
+    assert.strictEqual(body.dataBase64, payload.toString("base64"), "Server should receive the exact bytes");
   });
 
-  test("should validate browser profiles in fetch", async () => {
+  test("should upload multipart form data", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "notes.txt");
+    await writeFile(filePath, "file from disk");
+
+    try {
+      const response = await wreqFetch(httpUrl("/post"), {
+        browser: "chrome_142",
+        method: "POST",
+        multipart: [
+          { name: "title", value: "hello" },
+          { name: "upload", path: filePath },
+          { name: "blob", data: Buffer.from([0xde, 0xad]), filename: "blob.bin", contentType: "image/png" },
+        ],
+        timeout: 10000,
+      });
+
+      const body = await response.json<{ headers: Record<string, string>; data: string }>();
+      const contentType = body.headers["Content-Type"] ?? "";
+
+      assert.ok(contentType.startsWith("multipart/form-data; boundary="), "Should set multipart content type");
+      assert.ok(body.data.includes('name="title"\r\n\r\nhello'), "Should include text field");
+      assert.ok(body.data.includes('name="upload"; filename="notes.txt"'), "Should name file parts after the path");
+      assert.ok(body.data.includes("file from disk"), "Should include file contents");
+      assert.ok(body.data.includes('filename="blob.bin"\r\nContent-Type: image/png'), "Should honour filename and type");
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("should compress request bodies", async () => {
+    const payload = { rows: Array.from({ length: 200 }, (_, id) => ({ id, name: "row" })) };
+    const expected = JSON.stringify(payload);
+    const decoders = { gzip: gunzipSync, deflate: inflateSync, br: brotliDecompressSync };
+
+    for (const [encoding, decode] of Object.entries(decoders)) {
+      const response = await wreqFetch(httpUrl("/post"), {
+        browser: "chrome_142",
+        method: "POST",
+        json: payload,
+        compressBody: encoding as "gzip" | "deflate" | "br",
+        timeout: 10000,
+      });
+      const body = await response.json<{ headers: Record<string, string>; dataBase64: string }>();
+      const sent = Buffer.from(body.dataBase64, "base64");
+      assert.strictEqual(body.headers["Content-Encoding"], encoding);
+      assert.strictEqual(body.headers["Content-Type"], "application/json");
+      assert.ok(sent.length < expected.length, `${encoding} should shrink the body`);
+      assert.strictEqual(decode(sent).toString("utf8"), expected);
+    }
+
+    const zstd = await wreqFetch(httpUrl("/post"), {
+      method: "POST",
+      body: "x".repeat(1000),
+      compressBody: "zstd",
+      timeout: 10000,
+    });
+    const zstdBody = await zstd.json<{ dataBase64: string }>();
+    assert.deepStrictEqual([...Buffer.from(zstdBody.dataBase64, "base64").subarray(0, 4)], [0x28, 0xb5, 0x2f, 0xfd]);
+
     await assert.rejects(
-      async () => {
-        await wreqFetch(httpUrl("/get"), {
-          browser: "nonexistent_browser" as BrowserProfile,
-          timeout: 1000,
-        });
-      },
-      (error: unknown) => error instanceof RequestError,
-      "Should reject invalid browser profiles",
+      wreqFetch(httpUrl("/post"), {
+        method: "POST",
+        body: "x",
+        headers: { "Content-Encoding": "gzip" },
+        compressBody: "gzip",
+      }),
+      (error: unknown) => error instanceof RequestError && /Content-Encoding header/.test(error.message),
     );
   });
 
-  test("should isolate cookies for default fetch calls", async () => {
-    await wreqFetch(httpUrl("/cookies/set?ephemeral=on"), {
+  test("should expose response trailers and send request trailers", async () => {
+    const buffered = await wreqFetch(httpUrl("/trailers"), { browser: "chrome_142", timeout: 10000 });
+    assert.strictEqual(await buffered.text(), JSON.stringify({ length: 0, trailers: {} }));
+    const trailers = await buffered.trailers;
+    assert.strictEqual(trailers.get("grpc-status"), "0", "Should report the grpc-status trailer");
+    assert.strictEqual(trailers.get("x-checksum"), "sha256-abc");
+    assert.strictEqual(buffered.headers.get("grpc-status"), null, "Trailers should stay out of headers");
+
+    const streamed = await wreqFetch(httpUrl("/trailers"), { browser: "chrome_142", stream: true, timeout: 10000 });
+    await streamed.text();
+    assert.strictEqual((await streamed.trailers).get("grpc-status"), "0", "Streamed bodies should report trailers");
+
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "upload.bin");
+    await writeFile(filePath, Buffer.alloc(1024, "wreq"));
+
+    try {
+      const response = await wreqFetch(httpUrl("/trailers"), {
+        browser: "chrome_142",
+        method: "POST",
+        bodyFile: filePath,
+        trailers: { "x-upload-checksum": "crc32-1234" },
+        timeout: 10000,
+      });
+      const body = await response.json<{ length: number; trailers: Record<string, string> }>();
+      assert.strictEqual(body.length, 1024, "Server should receive the whole file");
+      assert.strictEqual(body.trailers["x-upload-checksum"], "crc32-1234", "Server should receive the trailers");
+
+      await assert.rejects(
+        wreqFetch(httpUrl("/post"), { method: "POST", body: "inline", trailers: { "x-checksum": "1" } }),
+        (error: unknown) => error instanceof RequestError && /trailers require bodyFile/.test(error.message),
+      );
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("should stream request bodies from a file", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "upload.bin");
+    const payload = Buffer.alloc(256 * 1024, "wreq");
+    await writeFile(filePath, payload);
+
+    try {
+      const response = await wreqFetch(httpUrl("/post"), {
+        browser: "chrome_142",
+        method: "POST",
+        bodyFile: filePath,
+        timeout: 10000,
+      });
+
+      const body = await response.json<{ headers: Record<string, string>; dataBase64: string }>();
+      assert.strictEqual(body.headers["Content-Length"], String(payload.length), "Should declare the file size");
+      assert.strictEqual(body.dataBase64, payload.toString("base64"), "Server should receive the whole file");
+
+      await assert.rejects(
+        wreqFetch(httpUrl("/post"), { method: "POST", bodyFile: filePath, body: "inline" }),
+        (error: unknown) => error instanceof RequestError && /bodyFile cannot be combined/.test(error.message),
+      );
+      await assert.rejects(
+        wreqFetch(httpUrl("/post"), { method: "POST", bodyFile: join(dir, "missing.bin"), timeout: 10000 }),
+        (error: unknown) => error instanceof RequestError && /Failed to open body file/.test(error.message),
+      );
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("should send and parse JSON bodies", async () => {
+    const payload = { name: "wreq", tags: ["a", "b"], nested: { count: 2, empty: null } };
+    const response = await wreqFetch(httpUrl("/post"), {
       browser: "chrome_142",
-      timeout: 5000,
+      method: "POST",
+      json: payload,
+      timeout: 10000,
     });
 
-    const response = await wreqFetch(httpUrl("/cookies"), {
+    const echo = await response.json<{ data: string; headers: Record<string, string> }>();
+
+    assert.ok(echo.headers["Content-Type"]?.startsWith("application/json"), "Content-Type should be set");
+    assert.deepStrictEqual(JSON.parse(echo.data), payload, "Body should be the serialized value");
+    assert.deepStrictEqual(Object.keys(JSON.parse(echo.data)), Object.keys(payload), "Key order should be kept");
+
+    const binary = await wreqFetch(httpUrl("/bytes/16"), { browser: "chrome_142", timeout: 10000 });
+    await assert.rejects(
+      binary.json(),
+      (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_DECODE",
+    );
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/post"), { method: "POST", body: "plain", json: {} }),
+      (error: unknown) => error instanceof RequestError,
+    );
+  });
+
+  test("should encode and append query parameters", async () => {
+    const response = await wreqFetch(httpUrl("/get?x=1"), {
+      query: { q: "café & bar", page: 2, tag: ["a", "b"], skipped: undefined, ok: true },
+      timeout: 10000,
+    });
+    const body = await response.json<{ args: Record<string, string>; url: string }>();
+    assert.ok(body.url.endsWith("/get?x=1&q=caf%C3%A9%20%26%20bar&page=2&tag=a&tag=b&ok=true"), body.url);
+    assert.strictEqual(body.args.q, "café & bar");
+
+    const brackets = await wreqFetch(httpUrl("/get"), {
+      query: { ids: [1, 2] },
+      queryArrayFormat: "brackets",
+      timeout: 10000,
+    });
+    const bracketsBody = await brackets.json<{ url: string }>();
+    assert.ok(bracketsBody.url.endsWith("/get?ids%5B%5D=1&ids%5B%5D=2"), bracketsBody.url);
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { query: { nested: {} as unknown as string } }),
+      /query\.nested must be/,
+    );
+  });
+
+  test("should send form fields as a URL-encoded body", async () => {
+    const response = await wreqFetch(httpUrl("/post"), {
+      method: "POST",
+      form: { user: "a b", note: "x&y=z*~", id: 7, tag: ["1", "2"], skipped: null },
+      timeout: 10000,
+    });
+    const body = await response.json<{ data: string; headers: Record<string, string> }>();
+    assert.strictEqual(body.data, "user=a+b&note=x%26y%3Dz*%7E&id=7&tag=1&tag=2");
+    assert.strictEqual(body.headers["Content-Type"], "application/x-www-form-urlencoded");
+    assert.strictEqual(new URLSearchParams(body.data).get("note"), "x&y=z*~");
+
+    const custom = await wreqFetch(httpUrl("/post"), {
+      method: "POST",
+      headers: { "Content-Type": "application/x-www-form-urlencoded; charset=utf-8" },
+      form: { a: "1" },
+      timeout: 10000,
+    });
+    const customBody = await custom.json<{ headers: Record<string, string> }>();
+    assert.strictEqual(customBody.headers["Content-Type"], "application/x-www-form-urlencoded; charset=utf-8");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/post"), { method: "POST", body: "x", form: { a: "1" } }),
+      /form cannot be combined/,
+    );
+  });
+
+  test("should send extension and lowercase standard methods", async () => {
+    const propfind = await wreqFetch(httpUrl("/post"), {
       browser: "chrome_142",
-      timeout: 5000,
+      method: "PROPFIND",
+      body: "<propfind/>",
+      timeout: 10000,
     });
+    const propfindBody = await propfind.json<{ method: string; data: string }>();
+    assert.strictEqual(propfindBody.method, "PROPFIND");
+    assert.strictEqual(propfindBody.data, "<propfind/>");
 
-    const body = await response.json<{ cookies: Record<string, string> }>();
+    const options = await wreqFetch(httpUrl("/post"), { browser: "chrome_142", method: "options", timeout: 10000 });
+    assert.strictEqual((await options.json<{ method: string }>()).method, "OPTIONS");
 
-    assert.ok(!body.cookies.ephemeral, "Ephemeral cookies should not persist across requests");
+    await assert.rejects(
+      wreqFetch(httpUrl("/post"), { method: "BAD METHOD" }),
+      (error: unknown) => error instanceof RequestError && /Invalid HTTP method/.test(error.message),
+    );
   });
 
-  test("should isolate cookies between sessions", async () => {
-    const sessionA = await createSession({ browser: "chrome_142" });
-    const sessionB = await createSession({ browser: "chrome_142" });
+  test("should abort bodies over maxBodyBytes", async () => {
+    const isTooLarge = (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_TOO_LARGE";
 
-    try {
-      await sessionA.fetch(httpUrl("/cookies/set?flavor=alpha"), { timeout: 10000 });
-      await sessionB.fetch(httpUrl("/cookies/set?flavor=beta"), { timeout: 10000 });
+    const small = await wreqFetch(httpUrl("/bytes/64"), { browser: "chrome_142", maxBodyBytes: 64, timeout: 10000 });
+    assert.strictEqual((await small.buffer()).length, 64, "Bodies at the limit should be accepted");
 
-      const cookiesA = await sessionA.fetch(httpUrl("/cookies"), { timeout: 10000 });
-      const cookiesB = await sessionB.fetch(httpUrl("/cookies"), { timeout: 10000 });
+    await assert.rejects(
+      wreqFetch(httpUrl("/bytes/1024"), { browser: "chrome_142", maxBodyBytes: 100, timeout: 10000 }),
+      isTooLarge,
+      "A Content-Length over the limit should be rejected",
+    );
 
-      const bodyA = await cookiesA.json<{ cookies: Record<string, string> }>();
-      const bodyB = await cookiesB.json<{ cookies: Record<string, string> }>();
+    const streamed = await wreqFetch(httpUrl("/stream/20"), {
+      browser: "chrome_142",
+      maxBodyBytes: 50,
+      stream: true,
+      timeout: 10000,
+    });
+    await assert.rejects(streamed.buffer(), isTooLarge, "Streamed bodies should be cut off once over the limit");
+  });
 
-      assert.strictEqual(bodyA.cookies.flavor, "alpha", "Session A should keep its own cookies");
-      assert.strictEqual(bodyB.cookies.flavor, "beta", "Session B should keep its own cookies");
+  test("should download bodies to a file", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "bytes.bin");
+    const events: DownloadProgress[] = [];
+
+    try {
+      const { response, path, bytesWritten } = await download(httpUrl("/bytes/4096"), filePath, {
+        browser: "chrome_142",
+        timeout: 10000,
+        onProgress: (progress) => events.push(progress),
+      });
+
+      assert.strictEqual(response.status, 200);
+      assert.strictEqual(response.bodyUsed, true, "The body went to disk, not into the response");
+      assert.strictEqual(path, filePath);
+      assert.strictEqual(bytesWritten, 4096);
+
+      const contents = await readFile(filePath);
+      assert.strictEqual(contents.length, 4096);
+      assert.strictEqual(contents[300], 300 % 256);
+
+      const last = events.at(-1);
+      assert.ok(last, "A final progress event should be reported");
+      assert.strictEqual(last.received, 4096);
+      assert.strictEqual(last.total, 4096);
+
+      const tooLarge = join(dir, "too-large.bin");
+      await assert.rejects(
+        download(httpUrl("/stream/20"), tooLarge, { browser: "chrome_142", maxBodyBytes: 50, timeout: 10000 }),
+        (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_TOO_LARGE",
+      );
+      await assert.rejects(access(tooLarge), "Partial downloads should be removed");
     } finally {
-      await sessionA.close();
-      await sessionB.close();
+      await rm(dir, { recursive: true, force: true });
     }
   });
 
-  test("should clear session cookies on demand", async () => {
-    const session = await createSession({ browser: "chrome_142" });
+  test("should resume partial downloads", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "resume.bin");
+    const expected = Buffer.from(Array.from({ length: 100_000 }, (_, i) => i % 256));
 
     try {
-      await session.fetch(httpUrl("/cookies/set?token=123"), { timeout: 10000 });
-      await session.clearCookies();
+      await assert.rejects(
+        download(httpUrl("/ranged/resume?size=100000&fail=30000"), filePath, { resume: true, timeout: 10000 }),
+        RequestError,
+      );
+      const partial = (await readFile(filePath)).length;
+      assert.ok(partial > 0 && partial <= 30_000, "The partial file should be kept");
+      await access(`${filePath}.resume`);
+
+      const resumed = await download(httpUrl("/ranged/resume?size=100000&fail=30000"), filePath, {
+        resume: true,
+        hashBody: "sha256",
+        timeout: 10000,
+      });
+      assert.strictEqual(resumed.response.status, 206);
+      assert.strictEqual(resumed.resumedFrom, partial);
+      assert.strictEqual(resumed.bytesWritten, 100_000 - partial);
+      assert.deepStrictEqual(await readFile(filePath), expected);
+      assert.strictEqual(resumed.response.bodyHash, createHash("sha256").update(expected).digest("hex"));
+      await assert.rejects(access(`${filePath}.resume`), "The resume state should go once the file is complete");
+
+      const log = await wreqFetch(httpUrl("/ranged-log/resume"), { timeout: 10000 });
+      const { ranges } = await log.json<{ ranges: string[] }>();
+      assert.deepStrictEqual(ranges, ["none", `bytes=${partial}-`]);
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
 
-      const response = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
-      const body = await response.json<{ cookies: Record<string, string> }>();
+  test("should download large files in parallel segments", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const filePath = join(dir, "segments.bin");
+    const expected = Buffer.from(Array.from({ length: 100_000 }, (_, i) => i % 256));
 
-      assert.deepStrictEqual(body.cookies, {}, "Clearing the session should drop stored cookies");
+    try {
+      const result = await download(httpUrl("/ranged/segments?size=100000"), filePath, {
+        segments: 4,
+        hashBody: "md5",
+        timeout: 10000,
+      });
+      assert.strictEqual(result.response.status, 200, "The response should describe the whole file");
+      assert.strictEqual(result.response.headers.get("content-length"), "100000");
+      assert.strictEqual(result.bytesWritten, 100_000);
+      assert.deepStrictEqual(await readFile(filePath), expected);
+      assert.strictEqual(result.response.bodyHash, createHash("md5").update(expected).digest("hex"));
+
+      const log = await wreqFetch(httpUrl("/ranged-log/segments"), { timeout: 10000 });
+      const { ranges } = await log.json<{ ranges: string[] }>();
+      assert.deepStrictEqual(ranges.sort(), [
+        "bytes=0-0",
+        "bytes=0-24999",
+        "bytes=25000-49999",
+        "bytes=50000-74999",
+        "bytes=75000-99999",
+      ]);
+
+      await assert.rejects(
+        download(httpUrl("/bytes/16"), filePath, { segments: 0 }),
+        /segments must be a positive integer/,
+      );
     } finally {
-      await session.close();
+      await rm(dir, { recursive: true, force: true });
     }
   });
 
-  test("withSession helper should dispose sessions automatically", async () => {
-    let capturedSession: Session | undefined;
+  test("should check URLs against robots.txt", async () => {
+    clearRobotsCache();
+    const fetches = async () => {
+      const log = await wreqFetch(httpUrl("/robots-log"), { timeout: 10000 });
+      return (await log.json<{ fetches: number }>()).fetches;
+    };
+    const before = await fetches();
+
+    const blocked = await checkRobots(httpUrl("/private/page"), "MyBot/1.0", { browser: "chrome_142" });
+    assert.strictEqual(blocked.allowed, false);
+    assert.strictEqual(blocked.crawlDelay, 2);
+    assert.deepStrictEqual(blocked.sitemaps, ["https://example.com/sitemap.xml"]);
+    assert.strictEqual(blocked.status, 200);
+
+    assert.strictEqual(await isAllowedByRobots(httpUrl("/private/open/page"), "MyBot/1.0"), true, "Longer Allow wins");
+    assert.strictEqual(await isAllowedByRobots(httpUrl("/docs/file.pdf"), "MyBot/1.0"), false);
+    assert.strictEqual(await isAllowedByRobots(httpUrl("/docs/file.pdf?x=1"), "MyBot/1.0"), true, "$ anchors the end");
+    assert.strictEqual(await isAllowedByRobots(httpUrl("/public"), "MyBot/1.0"), true);
+
+    const strict = await checkRobots(httpUrl("/public"), "strictbot/2.0 (+https://example.com)");
+    assert.strictEqual(strict.allowed, false, "A named group replaces the * group");
+    assert.strictEqual(strict.crawlDelay, null);
+    assert.strictEqual(await isAllowedByRobots(httpUrl("/robots.txt"), "StrictBot"), true);
+
+    assert.strictEqual(await fetches(), before + 1, "robots.txt should be fetched once per origin");
+
+    clearRobotsCache();
+    await checkRobots(httpUrl("/public"), "MyBot");
+    assert.strictEqual(await fetches(), before + 2);
+
+    await assert.rejects(checkRobots(httpUrl("/public"), ""), /userAgent must be a non-empty string/);
+  });
 
-    await withSession(async (session) => {
-      capturedSession = session;
-      const response = await session.fetch(httpUrl("/get"), { timeout: 5000 });
-      assert.strictEqual(response.status, 200);
+  test("should fetch and parse sitemaps", async () => {
+    const entries = [];
+    for await (const entry of sitemap(httpUrl("/sitemaps/index.xml"), { browser: "chrome_142", timeout: 10000 })) {
+      entries.push(entry);
+    }
+    assert.deepStrictEqual(entries, [
+      {
+        type: "url",
+        loc: "https://example.com/a?x=1&y=2",
+        lastmod: "2024-05-01",
+        changefreq: "daily",
+        priority: 0.8,
+      },
+      { type: "url", loc: "https://example.com/b" },
+      { type: "url", loc: "https://example.com/c" },
+    ]);
+
+    const index = [];
+    for await (const entry of sitemap(httpUrl("/sitemaps/index.xml"), { followIndex: false })) {
+      index.push(entry);
+    }
+    assert.strictEqual(index.length, 3);
+    assert.deepStrictEqual(index[0], { type: "sitemap", loc: httpUrl("/sitemaps/pages.xml"), lastmod: "2024-05-01" });
+
+    const buffered = await wreqFetch(httpUrl("/sitemaps/more.xml.gz"), { timeout: 10000 });
+    const gzipped = [];
+    for await (const entry of buffered.sitemap()) {
+      gzipped.push(entry.loc);
+    }
+    assert.deepStrictEqual(gzipped, ["https://example.com/c"], "Buffered .xml.gz bodies parse too");
+
+    const notSitemap = await wreqFetch(httpUrl("/json"), { stream: true, timeout: 10000 });
+    await assert.rejects(
+      (async () => {
+        for await (const _ of notSitemap.sitemap()) {
+          // drain
+        }
+      })(),
+      (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_DECODE",
+    );
+
+    await assert.rejects(async () => {
+      for await (const _ of sitemap(httpUrl("/sitemaps/missing.xml"))) {
+        // drain
+      }
+    }, /answered HTTP 404/);
+  });
+
+  test("should control response decompression", async () => {
+    const decoded = await wreqFetch(httpUrl("/gzip"), { browser: "chrome_142", timeout: 10000 });
+    assert.strictEqual((await decoded.json<{ gzipped: boolean }>()).gzipped, true);
+
+    const raw = await wreqFetch(httpUrl("/gzip"), {
+      browser: "chrome_142",
+      decompress: false,
+      acceptEncoding: "gzip",
+      timeout: 10000,
     });
+    assert.strictEqual(raw.headers.get("content-encoding"), "gzip", "Content-Encoding should be kept");
 
-    const session = capturedSession;
-    assert.ok(session, "withSession should provide a session instance");
+    const body = JSON.parse(gunzipSync(await raw.buffer()).toString("utf8")) as {
+      headers: Record<string, string>;
+    };
+    assert.strictEqual(body.headers["Accept-Encoding"], "gzip", "acceptEncoding should replace the profile's value");
+  });
+
+  test("should decode stacked and mislabelled content codings", async () => {
+    const stacked = await wreqFetch(httpUrl("/encoding/stacked"), { browser: "chrome_142", timeout: 10000 });
+    assert.deepStrictEqual(await stacked.json(), { stacked: true });
+    assert.deepStrictEqual(stacked.decodings, [
+      { encoding: "br", sniffed: false },
+      { encoding: "gzip", sniffed: false },
+    ]);
+    assert.strictEqual(stacked.headers.get("content-encoding"), null, "Removed codings should leave the headers");
+
+    const mislabeled = await wreqFetch(httpUrl("/encoding/mislabeled"), { browser: "chrome_142", timeout: 10000 });
+    assert.deepStrictEqual(await mislabeled.json(), { mislabeled: true });
+    assert.deepStrictEqual(mislabeled.decodings, [{ encoding: "gzip", sniffed: true }]);
+
+    const plain = await wreqFetch(httpUrl("/encoding/plain"), { browser: "chrome_142", timeout: 10000 });
+    assert.deepStrictEqual(await plain.json(), { plain: true });
+    assert.deepStrictEqual(plain.decodings, [], "A label on an unencoded body should be dropped");
+    assert.strictEqual(plain.headers.get("content-encoding"), null);
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/encoding/truncated"), { browser: "chrome_142", timeout: 10000 }),
+      (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_DECODE",
+      "A cut-short brotli body should fail rather than come back compressed",
+    );
+  });
 
+  test("should reject multipart combined with body", async () => {
     await assert.rejects(
       async () => {
-        await session.fetch(httpUrl("/get"), { timeout: 5000 });
+        await wreqFetch(httpUrl("/post"), {
+          method: "POST",
+          body: "plain",
+          multipart: [{ name: "field", value: "x" }],
+        });
       },
       (error: unknown) => error instanceof RequestError,
-      "Using a closed session should fail",
     );
   });
+
+  test("should stream response bodies chunk by chunk", async () => {
+    const response = await wreqFetch(httpUrl("/stream/5"), {
+      browser: "chrome_142",
+      stream: true,
+      timeout: 10000,
+    });
+
+    assert.ok(response.streaming, "Response should be in streaming mode");
+    assert.strictEqual(response.body, "", "Streamed responses should not buffer the body");
+
+    const parts: Buffer[] = [];
+    for await (const chunk of response) {
+      parts.push(chunk);
+    }
+
+    const lines = Buffer.concat(parts).toString("utf8").trim().split("\n");
+
+    assert.strictEqual(lines.length, 5, "Should receive every streamed line");
+    assert.ok(response.bodyUsed, "Iterating should consume the body");
+  });
+
+  test("should parse NDJSON bodies line by line", async () => {
+    const collect = async (response: Response) => {
+      const values: unknown[] = [];
+      for await (const value of response.ndjson()) {
+        values.push(value);
+      }
+      return values;
+    };
+
+    const streamed = await wreqFetch(httpUrl("/stream/5"), { stream: true, timeout: 10000 });
+    const rows = (await collect(streamed)) as Array<{ id: number }>;
+    assert.deepStrictEqual(rows.map((row) => row.id), [0, 1, 2, 3, 4]);
+    assert.ok(streamed.bodyUsed);
+
+    const split = await wreqFetch(httpUrl("/ndjson/split"), { stream: true, timeout: 10000 });
+    assert.deepStrictEqual(await collect(split), [{ a: 1 }, { b: 2 }]);
+
+    const buffered = await wreqFetch(httpUrl("/ndjson/split"), { timeout: 10000 });
+    assert.deepStrictEqual(await collect(buffered), [{ a: 1 }, { b: 2 }], "Buffered bodies parse the same way");
+
+    const invalid = await wreqFetch(httpUrl("/ndjson/invalid"), { stream: true, timeout: 10000 });
+    await assert.rejects(
+      collect(invalid),
+      (error: unknown) =>
+        error instanceof RequestError && error.code === "ERR_BODY_DECODE" && /line 2/.test(error.message),
+    );
+  });
+
+  test("should stream bodies through a small read-ahead buffer", async () => {
+    const response = await wreqFetch(httpUrl("/bytes/65536"), {
+      browser: "chrome_142",
+      stream: true,
+      highWaterMark: 1,
+      hashBody: "sha256",
+      timeout: 10000,
+    });
+
+    const body = await response.buffer();
+    assert.strictEqual(body.length, 65536, "Every byte should arrive with the reader paused between chunks");
+    assert.strictEqual(body[1000], 1000 % 256);
+    assert.strictEqual(response.bodyHash, createHash("sha256").update(body).digest("hex"));
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/bytes/16"), { stream: true, highWaterMark: 0 }),
+      /highWaterMark must be a positive integer/,
+    );
+  });
+
+  test("should make gRPC-web calls", async () => {
+    const call = await grpcWeb(httpUrl("/grpc-web/echo"), [Buffer.from("first"), new Uint8Array([1, 2, 3])], {
+      browser: "chrome_142",
+      timeout: 10000,
+    });
+    assert.deepStrictEqual(
+      call.messages.map((message) => [...message]),
+      [[...Buffer.from("first")], [1, 2, 3]],
+    );
+    assert.strictEqual(call.status, 0);
+    assert.strictEqual(call.statusMessage, "echoed 2");
+    assert.strictEqual(call.trailers.get("x-grpc-web"), "1");
+    assert.strictEqual(call.response.headers.get("content-type"), "application/grpc-web+proto");
+
+    const unimplemented = await grpcWeb(httpUrl("/grpc-web/unimplemented"), new Uint8Array(), { timeout: 10000 });
+    assert.deepStrictEqual(unimplemented.messages, []);
+    assert.strictEqual(unimplemented.status, 12, "Trailers-only responses carry the status in headers");
+    assert.strictEqual(unimplemented.statusMessage, "unknown method");
+
+    const missing = await grpcWeb(httpUrl("/grpc-web/missing"), new Uint8Array(), { timeout: 10000 });
+    assert.strictEqual(missing.status, 12, "HTTP errors map to a gRPC status");
+  });
+
+  test("should report the negotiated HTTP version", async () => {
+    const response = await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+
+    // The local test server only speaks HTTP/1.1 over plain TCP
+    assert.strictEqual(response.version, "HTTP/1.1");
+  });
+
+  test("should force the requested HTTP version", async () => {
+    const response = await wreqFetch(httpUrl("/get"), {
+      browser: "chrome_142",
+      httpVersion: "1.1",
+      timeout: 10000,
+    });
+    assert.strictEqual(response.version, "HTTP/1.1");
+
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), { httpVersion: "3" as never });
+      },
+      (error: unknown) => error instanceof RequestError,
+    );
+  });
+
+  test("should follow redirects and report the chain", async () => {
+    const response = await wreqFetch(httpUrl("/redirect/3"), {
+      browser: "chrome_142",
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, "Should land on the final resource");
+    assert.ok(response.redirected, "Response should be marked as redirected");
+    assert.deepStrictEqual(
+      response.redirects,
+      [httpUrl("/redirect/3"), httpUrl("/redirect/2"), httpUrl("/redirect/1")],
+      "Should list every hop in order",
+    );
+    assert.strictEqual(response.url, httpUrl("/get"));
+  });
+
+  test("should report each redirect hop's status, location and cookies", async () => {
+    const response = await wreqFetch(httpUrl("/redirect/2?cookie=session"), {
+      browser: "chrome_142",
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.redirectHops.length, 2);
+    const [login, next] = response.redirectHops;
+    assert.strictEqual(login?.url, httpUrl("/redirect/2?cookie=session"));
+    assert.strictEqual(login?.status, 302);
+    assert.strictEqual(login?.location, "/redirect/1");
+    assert.deepStrictEqual(
+      login?.setCookies.map(({ name, value, httpOnly }) => ({ name, value, httpOnly })),
+      [{ name: "session", value: "hop-2", httpOnly: true }],
+      "The cookie set mid-redirect should be reported",
+    );
+    assert.strictEqual(next?.location, "/get");
+    assert.deepStrictEqual(next?.setCookies, []);
+  });
+
+  test("should follow meta refreshes and script redirects with followRefresh", async () => {
+    const response = await wreqFetch(httpUrl("/refresh/meta"), {
+      browser: "chrome_142",
+      followRefresh: true,
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.url, httpUrl("/cookies"));
+    assert.deepStrictEqual((await response.json<{ cookies: Record<string, string> }>()).cookies, { gate: "passed" });
+    assert.deepStrictEqual(
+      response.redirectHops.map(({ url, status, location }) => ({ url, status, location })),
+      [
+        { url: httpUrl("/refresh/meta"), status: 200, location: httpUrl("/refresh/script") },
+        { url: httpUrl("/refresh/script"), status: 200, location: httpUrl("/refresh/header") },
+        { url: httpUrl("/refresh/header"), status: 200, location: httpUrl("/cookies") },
+      ],
+    );
+    assert.strictEqual(response.redirectHops[0]?.setCookies[0]?.name, "gate");
+
+    const untouched = await wreqFetch(httpUrl("/refresh/meta"), { timeout: 10000 });
+    assert.strictEqual(untouched.url, httpUrl("/refresh/meta"), "Refreshes are only followed when asked to");
+
+    const reload = await wreqFetch(httpUrl("/refresh/reload"), { followRefresh: true, timeout: 10000 });
+    assert.strictEqual(reload.url, httpUrl("/refresh/reload"), "A refresh without a URL reloads, so it isn't followed");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/refresh/loop/0"), { followRefresh: true, maxRedirects: 3, timeout: 10000 }),
+      (error: unknown) => error instanceof RequestError && error.code === "ERR_TOO_MANY_REDIRECTS",
+    );
+  });
+
+  test("should return 3xx responses untouched with redirect: 'manual'", async () => {
+    const response = await wreqFetch(httpUrl("/redirect/1?cookie=state"), {
+      browser: "chrome_142",
+      redirect: "manual",
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 302);
+    assert.strictEqual(response.headers.get("location"), "/get");
+    assert.strictEqual(response.url, httpUrl("/redirect/1?cookie=state"));
+    assert.strictEqual(response.redirected, false);
+    assert.deepStrictEqual(response.redirects, []);
+    assert.deepStrictEqual(
+      response.setCookies.map(({ name, value }) => ({ name, value })),
+      [{ name: "state", value: "hop-1" }],
+    );
+  });
+
+  test("should enforce redirect limits", async () => {
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/redirect/3"), {
+          browser: "chrome_142",
+          maxRedirects: 2,
+          timeout: 10000,
+        });
+      },
+      (error: unknown) => error instanceof RequestError,
+      "Should fail once the redirect budget is exhausted",
+    );
+
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/redirect/1"), {
+          browser: "chrome_142",
+          redirect: "error",
+          timeout: 10000,
+        });
+      },
+      (error: unknown) => error instanceof RequestError,
+      "redirect: 'error' should reject on the first redirect",
+    );
+  });
+
+  test("should classify failures with error codes", async () => {
+    const hasCode = (code: string) => (error: unknown) => error instanceof RequestError && error.code === code;
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/redirect/3"), { browser: "chrome_142", maxRedirects: 1, timeout: 10000 }),
+      hasCode("ERR_TOO_MANY_REDIRECTS"),
+    );
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/delay/2"), { browser: "chrome_142", timeout: 200 }),
+      hasCode("ERR_TIMEOUT"),
+    );
+
+    // Grab a free port, then release it so nothing is listening there
+    const server = createServer();
+    await new Promise<void>((resolve) => server.listen(0, "127.0.0.1", resolve));
+    const { port } = server.address() as AddressInfo;
+    await new Promise<void>((resolve) => server.close(() => resolve()));
+
+    await assert.rejects(
+      wreqFetch(`http://127.0.0.1:${port}/`, { browser: "chrome_142", timeout: 5000 }),
+      hasCode("ERR_CONNECT"),
+    );
+  });
+
+  test("should retry retryable statuses until success", async () => {
+    const response = await wreqFetch(httpUrl("/retry/until-success/2"), {
+      browser: "chrome_142",
+      retry: { maxAttempts: 3, backoffBase: 1 },
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, "Third attempt should succeed");
+    assert.strictEqual(response.attempts, 3, "Should report every attempt");
+    assert.deepStrictEqual(await response.json(), { hits: 3 });
+  });
+
+  test("should return the last response once retries are exhausted", async () => {
+    const response = await wreqFetch(httpUrl("/retry/exhausted/5"), {
+      browser: "chrome_142",
+      retry: { maxAttempts: 2, backoffBase: 1 },
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 503, "Should surface the final failing response");
+    assert.strictEqual(response.attempts, 2);
+
+    const single = await wreqFetch(httpUrl("/retry/no-policy/5"), { timeout: 10000 });
+    assert.strictEqual(single.attempts, 1, "Requests are not retried by default");
+  });
+
+  test("should apply read timeouts and an overall deadline", async () => {
+    const hasCode = (code: string) => (error: unknown) => error instanceof RequestError && error.code === code;
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/delay/2"), { readTimeout: 200, connectTimeout: 5000 }),
+      hasCode("ERR_TIMEOUT"),
+    );
+
+    const steady = await wreqFetch(httpUrl("/get"), { readTimeout: 5000, connectTimeout: 5000 });
+    assert.strictEqual(steady.status, 200);
+
+    const started = Date.now();
+    const response = await wreqFetch(httpUrl("/retry/deadline/5"), {
+      retry: { maxAttempts: 3, backoffBase: 2000 },
+      deadline: 800,
+    });
+    assert.strictEqual(response.status, 503, "A retry past the deadline should not be made");
+    assert.strictEqual(response.attempts, 1);
+    assert.ok(Date.now() - started < 1500, "Should not wait out the backoff");
+
+    await assert.rejects(wreqFetch(httpUrl("/delay/2"), { deadline: 300 }), hasCode("ERR_TIMEOUT"));
+    await assert.rejects(wreqFetch(httpUrl("/get"), { connectTimeout: 0 }), /connectTimeout must be a positive number/);
+  });
+
+  test("should report request timings", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      const first = await session.get(`http://localhost:${port}/get`, { timeout: 10000 });
+      const { dns, connect, ttfb, download, total } = first.timings;
+
+      assert.strictEqual(typeof dns, "number", "Fresh connection to a hostname should report DNS time");
+      assert.strictEqual(typeof connect, "number", "Fresh connection should report connect time");
+      assert.ok(ttfb >= (connect ?? 0), "TTFB includes connection setup");
+      assert.ok(download !== undefined && total !== undefined && total >= ttfb, "Total should cover TTFB");
+
+      const reused = await session.get(`http://localhost:${port}/get`, { timeout: 10000 });
+      assert.strictEqual(reused.timings.connect, undefined, "Pooled connection should skip connect");
+      assert.strictEqual(reused.timings.dns, undefined, "Pooled connection should skip DNS");
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should report whether the connection was reused and its pool key", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      const first = await session.get(`http://localhost:${port}/get`, { timeout: 10000 });
+      const reused = await session.get(`http://localhost:${port}/get`, { timeout: 10000 });
+      assert.ok(first.connection && reused.connection);
+
+      assert.strictEqual(first.connection.reused, false, "The first request should open a connection");
+      assert.strictEqual(reused.connection.reused, true, "The second request should take it from the pool");
+      assert.strictEqual(reused.connection.localAddress, first.connection.localAddress, "Same socket");
+      assert.ok(first.connection.remoteAddress?.endsWith(`:${port}`));
+      assert.strictEqual(first.connection.poolKey, `${session.id} http://localhost:${port}`);
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should receive server-sent events and resume with Last-Event-ID", async () => {
+    const events = eventSource(httpUrl("/sse/resume"), { browser: "chrome_142", timeout: 10000 });
+    const received: { type: string; data: string; lastEventId: string }[] = [];
+
+    for await (const event of events) {
+      received.push(event);
+      if (received.length === 3) {
+        break;
+      }
+    }
+
+    assert.ok(events.closed, "Breaking out of the loop should close the stream");
+    assert.deepStrictEqual(received, [
+      { type: "message", data: "hello", lastEventId: "1" },
+      { type: "update", data: "multi\nline", lastEventId: "1" },
+      { type: "message", data: "resumed after 1", lastEventId: "2" },
+    ]);
+  });
+
+  test("should reject aborted requests with AbortError", async () => {
+    const controller = new AbortController();
+    controller.abort();
+
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), {
+          browser: "chrome_142",
+          signal: controller.signal,
+          timeout: 1000,
+        });
+      },
+      (error: unknown) => error instanceof Error && error.name === "AbortError",
+      "Should reject with AbortError",
+    );
+  });
+
+  test("should work with custom Headers helper", () => {
+    const headers = new Headers({
+      "X-Test": "alpha",
+    });
+
+    headers.append("x-test", "beta");
+    headers.set("X-Another", "value");
+
+    const collected = Array.from(headers.entries());
+
+    assert.strictEqual(headers.get("X-Test"), "alpha, beta", "append should concatenate values");
+    assert.strictEqual(headers.get("x-another"), "value", "set should overwrite values");
+    assert.ok(collected.length >= 2, "entries should iterate all headers");
+    assert.deepStrictEqual(
+      headers.toTuples(),
+      [
+        ["X-Test", "alpha"],
+        ["x-test", "beta"],
+        ["X-Another", "value"],
+      ],
+      "toTuples should keep every entry in order",
+    );
+  });
+
+  test("should validate browser profiles in fetch", async () => {
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), {
+          browser: "nonexistent_browser" as BrowserProfile,
+          timeout: 1000,
+        });
+      },
+      (error: unknown) => error instanceof RequestError,
+      "Should reject invalid browser profiles",
+    );
+  });
+
+  test("should describe and parse browser profiles", async () => {
+    const emulations = listEmulations();
+    assert.strictEqual(emulations.length, getProfiles().length);
+    const byName = (name: string) => emulations.find((info) => info.name === name);
+    assert.deepStrictEqual(byName("chrome_142"), { name: "chrome_142", family: "chrome", version: "142", os: "macos" });
+    assert.deepStrictEqual(byName("safari_ios_17.2"), {
+      name: "safari_ios_17.2",
+      family: "safari",
+      version: "17.2",
+      os: "ios",
+    });
+    assert.strictEqual(byName("firefox_android_135")?.os, "android");
+
+    assert.strictEqual(parseBrowserProfile("firefox_139"), "firefox_139");
+    assert.throws(
+      () => parseBrowserProfile("Chrome-142"),
+      (error: unknown) => error instanceof RequestError && /did you mean chrome_142/.test(error.message),
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { browser: "firefox" as BrowserProfile }),
+      /Unknown browser profile 'firefox'; did you mean firefox_143/,
+    );
+  });
+
+  test("should pick browser profiles from weights", async () => {
+    const response = await wreqFetch(httpUrl("/user-agent"), {
+      browsers: { chrome_142: 0, firefox_139: 1 },
+      timeout: 10000,
+    });
+    assert.strictEqual(response.browser, "firefox_139");
+    assert.match(JSON.parse(response.body)["user-agent"], /Firefox/);
+    assert.strictEqual(response.clone().browser, "firefox_139");
+
+    const picked = new Set<string>();
+    for (let i = 0; i < 20; i++) {
+      const res = await wreqFetch(httpUrl("/get"), { browsers: { chrome_142: 1, safari_18: 1 }, timeout: 10000 });
+      picked.add(res.browser);
+    }
+    assert.deepStrictEqual([...picked].sort(), ["chrome_142", "safari_18"]);
+
+    const session = await createSession({ browsers: { safari_18: 5 } });
+    try {
+      assert.strictEqual(session.browser, "safari_18");
+      const res = await session.fetch(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(res.browser, "safari_18");
+      await assert.rejects(session.fetch(httpUrl("/get"), { browsers: { chrome_142: 1 } }), /cannot be changed/);
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(wreqFetch(httpUrl("/get"), { browsers: { chrome_142: 0 } }), /positive weight/);
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { browser: "chrome_142", browsers: { chrome_142: 1 } }),
+      /cannot be combined/,
+    );
+  });
+
+  test("should parse every set-cookie header with attributes", async () => {
+    const response = await wreqFetch(httpUrl("/cookies/attributes"), {
+      browser: "chrome_142",
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.setCookies.length, 2, "Should parse both cookies");
+
+    const [first, second] = response.setCookies;
+
+    assert.strictEqual(first?.name, "first");
+    assert.strictEqual(first?.httpOnly, true, "HttpOnly should be reported");
+    assert.strictEqual(second?.path, "/account", "Path should be reported");
+    assert.strictEqual(second?.maxAge, 3600, "Max-Age should be reported");
+    assert.strictEqual(second?.secure, true, "Secure should be reported");
+    assert.strictEqual(second?.sameSite, "Lax", "SameSite should be reported");
+    assert.deepStrictEqual(response.cookies, { first: "1", second: "2" });
+  });
+
+  test("should omit or replace the session's cookies per request", async () => {
+    const session = await createSession({ browser: "chrome_142" });
+    const cookiesOf = async (response: Response) =>
+      (await response.json<{ cookies: Record<string, string> }>()).cookies;
+
+    try {
+      await session.fetch(httpUrl("/cookies/set?sid=jar"), { timeout: 10000 });
+
+      const omitted = await session.fetch(httpUrl("/cookies/set?ignored=yes"), { cookies: "omit", timeout: 10000 });
+      assert.deepStrictEqual(await cookiesOf(omitted), { ignored: "yes" }, "The jar's cookies should stay out");
+      assert.strictEqual(omitted.cookies.ignored, "yes", "Set-Cookie should still be reported");
+
+      const manual = await session.fetch(httpUrl("/cookies"), {
+        cookies: "manual",
+        cookieMap: { sid: "mine", theme: "dark" },
+        timeout: 10000,
+      });
+      assert.deepStrictEqual(await cookiesOf(manual), { sid: "mine", theme: "dark" });
+
+      const included = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      assert.deepStrictEqual(await cookiesOf(included), { sid: "jar" }, "Omitted Set-Cookie should not be stored");
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/cookies"), { cookieMap: { sid: "x" } }),
+      /cookieMap requires cookies: "manual"/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/cookies"), { cookies: "same-origin" as unknown as "omit" }),
+      /Unknown cookies policy 'same-origin'/,
+    );
+  });
+
+  test("should configure and clear the client cache", async () => {
+    assert.throws(() => configureClientCache({ maxEntries: 0 }), RequestError);
+    assert.throws(() => configureClientCache({ idleTtl: -1 }), RequestError);
+
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      await session.fetch(httpUrl("/cookies/set?cached=yes"), { timeout: 10000 });
+      configureClientCache({ maxEntries: 64, idleTtl: 60_000, sweepInterval: 1000 });
+
+      const before = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      const beforeBody = await before.json<{ cookies: Record<string, string> }>();
+      assert.strictEqual(beforeBody.cookies.cached, "yes", "Reconfiguring should keep live sessions");
+
+      clearClientCache();
+
+      const after = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      const afterBody = await after.json<{ cookies: Record<string, string> }>();
+      assert.ok(!afterBody.cookies.cached, "Clearing the cache should drop the session's client and cookies");
+    } finally {
+      await session.close();
+      configureClientCache({ maxEntries: 1024, idleTtl: 300_000, sweepInterval: 60_000 });
+    }
+  });
+
+  test("should keep a pinned session's client through cache clears", async () => {
+    const session = await createSession({ browser: "chrome_142", pinClient: true });
+
+    try {
+      await session.fetch(httpUrl("/cookies/set?pinned=yes"), { timeout: 10000 });
+      clearClientCache();
+
+      const response = await session.fetch(httpUrl("/cookies"), { timeout: 2000 });
+      const body = await response.json<{ cookies: Record<string, string> }>();
+      assert.strictEqual(body.cookies.pinned, "yes", "A pinned session should keep its cookies");
+
+      const entry = clientCacheStats().entries.find((candidate) => candidate.sessionId === session.id);
+      assert.strictEqual(entry?.pinned, true);
+    } finally {
+      await session.close();
+    }
+
+    assert.ok(!clientCacheStats().entries.some((entry) => entry.sessionId === session.id));
+    await assert.rejects(createSession({ pinClient: "yes" as unknown as boolean }), RequestError);
+  });
+
+  test("should report client cache statistics", async () => {
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      const initial = clientCacheStats();
+
+      await session.fetch(httpUrl("/get"), { timeout: 10000 });
+      await session.fetch(httpUrl("/get"), { timeout: 10000 });
+      await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+
+      const stats = clientCacheStats();
+      assert.ok(stats.hits >= initial.hits + 2, "Session requests should reuse the cached client");
+      assert.ok(stats.misses >= initial.misses + 1, "Ephemeral requests should build a new client");
+
+      const entry = stats.entries.find((candidate) => candidate.sessionId === session.id);
+      assert.ok(entry, "The session should be listed");
+      assert.strictEqual(entry.browser, "chrome_142");
+      assert.ok(entry.lastUsed >= Date.now() - 60_000, "lastUsed should be a recent timestamp");
+      assert.strictEqual(stats.size, stats.entries.length);
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should drain requests on shutdown and start afresh afterwards", async () => {
+    const finishing = wreqFetch(httpUrl("/delay/1"), { timeout: 10000 });
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    const drained = await shutdown({ graceMs: 5000 });
+    assert.strictEqual(drained.cancelled, 0);
+    assert.strictEqual((await finishing).status, 200, "In-flight request should finish within the grace period");
+    assert.strictEqual(clientCacheStats().size, 0, "Cached clients should be dropped");
+
+    const stalled = wreqFetch(httpUrl("/delay/5"), { timeout: 10000 });
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    const cut = await shutdown({ graceMs: 100 });
+    assert.ok(cut.cancelled >= 1);
+    await assert.rejects(stalled, (error: unknown) => error instanceof RequestError && error.code === "ERR_SHUTDOWN");
+
+    const after = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+    assert.strictEqual(after.status, 200, "A request after shutdown should start a fresh runtime");
+    await assert.rejects(shutdown({ graceMs: -1 }), /graceMs must be a non-negative number/);
+  });
+
+  test("should configure the runtime while it is stopped", async () => {
+    await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+    assert.throws(() => configureRuntime({ workerThreads: 2 }), /already running/);
+
+    try {
+      await shutdown({ graceMs: 5000 });
+      configureRuntime({ workerThreads: 1, maxBlockingThreads: 4, threadName: "wreq-test" });
+      const response = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(response.status, 200, "The runtime should restart with the new settings");
+    } finally {
+      await shutdown({ graceMs: 5000 });
+      configureRuntime({});
+    }
+
+    assert.throws(() => configureRuntime({ workerThreads: 0 }), /workerThreads must be a positive integer/);
+  });
+
+  test("should isolate cookies for default fetch calls", async () => {
+    await wreqFetch(httpUrl("/cookies/set?ephemeral=on"), {
+      browser: "chrome_142",
+      timeout: 5000,
+    });
+
+    const response = await wreqFetch(httpUrl("/cookies"), {
+      browser: "chrome_142",
+      timeout: 5000,
+    });
+
+    const body = await response.json<{ cookies: Record<string, string> }>();
+
+    assert.ok(!body.cookies.ephemeral, "Ephemeral cookies should not persist across requests");
+  });
+
+  test("should isolate cookies between sessions", async () => {
+    const sessionA = await createSession({ browser: "chrome_142" });
+    const sessionB = await createSession({ browser: "chrome_142" });
+
+    try {
+      await sessionA.fetch(httpUrl("/cookies/set?flavor=alpha"), { timeout: 10000 });
+      await sessionB.fetch(httpUrl("/cookies/set?flavor=beta"), { timeout: 10000 });
+
+      const cookiesA = await sessionA.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      const cookiesB = await sessionB.fetch(httpUrl("/cookies"), { timeout: 10000 });
+
+      const bodyA = await cookiesA.json<{ cookies: Record<string, string> }>();
+      const bodyB = await cookiesB.json<{ cookies: Record<string, string> }>();
+
+      assert.strictEqual(bodyA.cookies.flavor, "alpha", "Session A should keep its own cookies");
+      assert.strictEqual(bodyB.cookies.flavor, "beta", "Session B should keep its own cookies");
+    } finally {
+      await sessionA.close();
+      await sessionB.close();
+    }
+  });
+
+  test("should clear session cookies on demand", async () => {
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      await session.fetch(httpUrl("/cookies/set?token=123"), { timeout: 10000 });
+      await session.clearCookies();
+
+      const response = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      const body = await response.json<{ cookies: Record<string, string> }>();
+
+      assert.deepStrictEqual(body.cookies, {}, "Clearing the session should drop stored cookies");
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should list, set and delete session cookies", async () => {
+    const session = await createSession();
+    const { hostname } = new URL(HTTP_TEST_BASE_URL);
+
+    try {
+      await session.fetch(httpUrl("/cookies/set?visited=yes"), { timeout: 10000 });
+      await session.setCookie({ name: "manual", value: "1", domain: hostname, path: "/cookies" });
+      await session.setCookie({ name: "elsewhere", value: "1", domain: "example.com" });
+
+      const all = await session.getCookies();
+      assert.deepStrictEqual(all.map((cookie) => cookie.name).sort(), ["elsewhere", "manual", "visited"]);
+      const visited = all.find((cookie) => cookie.name === "visited");
+      assert.strictEqual(visited?.hostOnly, true);
+      assert.strictEqual(visited?.path, "/");
+
+      const forUrl = await session.getCookies(httpUrl("/cookies"));
+      assert.deepStrictEqual(
+        forUrl.map((cookie) => cookie.name),
+        ["manual", "visited"],
+        "Cookies with longer paths are sent first",
+      );
+
+      const response = await session.fetch(httpUrl("/cookies"), { timeout: 10000 });
+      const body = await response.json<{ cookies: Record<string, string> }>();
+      assert.deepStrictEqual(body.cookies, { manual: "1", visited: "yes" });
+
+      assert.strictEqual(await session.deleteCookies({ domain: "example.com" }), 1);
+      assert.strictEqual(await session.deleteCookies({ name: "manual" }), 1);
+      assert.deepStrictEqual((await session.getCookies()).map((cookie) => cookie.name), ["visited"]);
+
+      await session.setCookie({ name: "visited", value: "", domain: hostname, hostOnly: true, expires: 0 });
+      assert.deepStrictEqual(await session.getCookies(), [], "An expiry in the past deletes the cookie");
+
+      await assert.rejects(
+        session.setCookie({ name: "bad name", value: "1", domain: hostname }),
+        /Invalid cookie name/,
+      );
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should preview the Cookie header a session would send", async () => {
+    const session = await createSession();
+    const { hostname } = new URL(HTTP_TEST_BASE_URL);
+
+    try {
+      assert.strictEqual(await session.getCookieHeader(httpUrl("/cookies")), null);
+
+      await session.fetch(httpUrl("/cookies/set?visited=yes"), { timeout: 10000 });
+      await session.setCookie({ name: "manual", value: "1", domain: hostname, path: "/cookies" });
+      await session.setCookie({ name: "secret", value: "1", domain: hostname, secure: true });
+
+      assert.strictEqual(await session.getCookieHeader(httpUrl("/")), "visited=yes");
+      const header = await session.getCookieHeader(httpUrl("/cookies"));
+      assert.strictEqual(header, "manual=1; visited=yes", "Secure cookies are left out over http");
+
+      const response = await session.fetch(httpUrl("/headers"), { timeout: 10000 });
+      const body = await response.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(body.headers.Cookie, "visited=yes");
+
+      await assert.rejects(session.getCookieHeader("not a url"), RequestError);
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should persist session cookies to a file", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+
+    try {
+      for (const file of ["cookies.json", "cookies.txt"]) {
+        const cookieStorePath = join(dir, file);
+        const first = await createSession({ cookieStorePath });
+        try {
+          await first.fetch(httpUrl("/cookies/set?remember=me"), { timeout: 10000 });
+        } finally {
+          await first.close();
+        }
+
+        const saved = await readFile(cookieStorePath, "utf8");
+        assert.ok(saved.includes("remember"), `${file} should hold the cookie`);
+        if (file.endsWith(".txt")) {
+          assert.ok(saved.startsWith("# Netscape HTTP Cookie File"));
+        }
+
+        const second = await createSession({ cookieStorePath });
+        try {
+          const response = await second.fetch(httpUrl("/cookies"), { timeout: 10000 });
+          const body = await response.json<{ cookies: Record<string, string> }>();
+          assert.strictEqual(body.cookies.remember, "me", "A new session should load the saved cookies");
+        } finally {
+          await second.close();
+        }
+      }
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("session method helpers should share the session cookie jar", async () => {
+    const session = await createSession({ browser: "chrome_142" });
+
+    try {
+      await session.get(httpUrl("/cookies/set?helper=yes"), { timeout: 10000 });
+
+      const posted = await session.post(httpUrl("/post"), "payload", { timeout: 10000 });
+      const postedBody = await posted.json<{ method: string; data: string; headers: Record<string, string> }>();
+
+      assert.strictEqual(postedBody.method, "POST", "post() should send a POST request");
+      assert.strictEqual(postedBody.data, "payload", "post() should send the provided body");
+      assert.ok(postedBody.headers.Cookie?.includes("helper=yes"), "post() should reuse session cookies");
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("session requests can switch proxies without losing cookies", async () => {
+    const session = await createSession({ browser: "chrome_142" });
+    const proxy = new URL(HTTP_TEST_BASE_URL).origin;
+
+    try {
+      await session.get(httpUrl("/cookies/set?rotating=yes"), { timeout: 10000 });
+
+      const proxied = await session.get(httpUrl("/get"), { proxy, timeout: 10000 });
+      const proxiedBody = await proxied.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(proxied.headers.get("x-proxied"), "1", "Request should go through the per-request proxy");
+      assert.ok(proxiedBody.headers.Cookie?.includes("rotating=yes"), "Proxied request should reuse session cookies");
+
+      const direct = await session.get(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(direct.headers.get("x-proxied"), null, "Proxy should only apply to the request that set it");
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should send proxy credentials and headers to the proxy", async () => {
+    const response = await wreqFetch(httpUrl("/get"), {
+      browser: "chrome_142",
+      proxy: new URL(HTTP_TEST_BASE_URL).origin,
+      proxyAuth: { username: "user", password: "p@ss:word" },
+      proxyHeaders: { "X-Proxy-Session": "sticky-1" },
+      timeout: 10000,
+    });
+
+    const body = await response.json<{ headers: Record<string, string> }>();
+    const expected = `Basic ${Buffer.from("user:p@ss:word").toString("base64")}`;
+
+    assert.strictEqual(response.headers.get("x-proxied"), "1", "Request should go through the proxy");
+    assert.strictEqual(body.headers["Proxy-Authorization"], expected, "Should send Basic proxy credentials");
+    assert.strictEqual(body.headers["X-Proxy-Session"], "sticky-1", "Should send custom proxy headers");
+  });
+
+  test("should rotate through a proxy pool and quarantine failing proxies", async () => {
+    const dead = await deadProxyUrl();
+    const live = new URL(HTTP_TEST_BASE_URL).origin;
+    const pool = createProxyPool({ proxies: [dead, live], maxFailures: 1, quarantine: 60_000 });
+
+    try {
+      await assert.rejects(wreqFetch(httpUrl("/get"), { proxyPool: pool, timeout: 10000 }), RequestError);
+
+      for (let i = 0; i < 2; i++) {
+        const response = await wreqFetch(httpUrl("/get"), { proxyPool: pool, timeout: 10000 });
+        assert.strictEqual(response.headers.get("x-proxied"), "1", "Quarantined proxy should be skipped");
+      }
+
+      const [deadStats, liveStats] = pool.stats();
+      assert.strictEqual(deadStats?.failures, 1);
+      assert.ok(deadStats?.quarantinedUntil instanceof Date);
+      assert.strictEqual(liveStats?.successes, 2);
+      assert.strictEqual(liveStats?.quarantinedUntil, null);
+
+      await assert.rejects(
+        wreqFetch(httpUrl("/get"), { proxy: live, proxyPool: pool }),
+        /proxy and proxyPool cannot be combined/,
+      );
+    } finally {
+      pool.close();
+    }
+
+    await assert.rejects(wreqFetch(httpUrl("/get"), { proxyPool: pool }), /closed/);
+    assert.throws(
+      () => createProxyPool({ proxies: [live], strategy: "fastest" as never }),
+      /Unknown proxy pool strategy/,
+    );
+  });
+
+  test("should fail over to another pool proxy after a proxy failure", async () => {
+    const dead = await deadProxyUrl();
+    const live = new URL(HTTP_TEST_BASE_URL).origin;
+    const pool = createProxyPool({ proxies: [dead, live], maxFailures: 5, failover: 1 });
+
+    try {
+      const response = await wreqFetch(httpUrl("/get"), { proxyPool: pool, timeout: 10000 });
+      assert.strictEqual(response.headers.get("x-proxied"), "1");
+      assert.strictEqual(response.proxy, live, "The proxy that answered should be reported");
+      assert.strictEqual(response.attempts, 2);
+
+      const [deadStats] = pool.stats();
+      assert.strictEqual(deadStats?.failures, 1);
+      assert.strictEqual(deadStats?.quarantinedUntil, null, "One failure should not quarantine the proxy");
+    } finally {
+      pool.close();
+    }
+
+    assert.throws(() => createProxyPool({ proxies: [live], failover: -1 }), /failover must be a non-negative integer/);
+  });
+
+  test("should route requests to proxies by hostname", async () => {
+    const dead = await deadProxyUrl();
+    const live = new URL(HTTP_TEST_BASE_URL).origin;
+    const host = new URL(HTTP_TEST_BASE_URL).hostname.replace(/^\[|\]$/g, "");
+
+    try {
+      setProxyRouting({ routes: [{ hosts: [/^no-such-host\./, host], proxy: live }] });
+      const routed = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(routed.headers.get("x-proxied"), "1", "Matching host should go through the routed proxy");
+
+      setProxyRouting({ noProxy: [host], routes: [{ hosts: "*", proxy: live }] });
+      const direct = await wreqFetch(httpUrl("/get"), { proxy: dead, timeout: 10000 });
+      assert.strictEqual(direct.status, 200);
+      assert.strictEqual(direct.headers.get("x-proxied"), null, "noProxy host should connect directly");
+
+      setProxyRouting({ routes: [{ hosts: "*.no-such-host.test", direct: true }] });
+      await assert.rejects(wreqFetch(httpUrl("/get"), { proxy: dead, timeout: 10000 }), RequestError);
+
+      assert.throws(
+        () => setProxyRouting({ routes: [{ hosts: "*", proxy: live, direct: true }] }),
+        /exactly one of proxy, proxyPool or direct/,
+      );
+    } finally {
+      setProxyRouting(null);
+    }
+  });
+
+  test("should check proxies and report failures per proxy", async () => {
+    const dead = await deadProxyUrl();
+    const live = new URL(HTTP_TEST_BASE_URL).origin;
+
+    const [working, failing] = await checkProxies([live, dead], { testUrl: httpUrl("/get"), timeout: 5000 });
+
+    assert.strictEqual(working?.proxy, live);
+    assert.strictEqual(working.ok, true);
+    assert.strictEqual(working.status, 200);
+    assert.ok(typeof working.latency === "number" && working.latency >= 0);
+    assert.ok(working.exitIp !== null, "The echo endpoint should report the exit address");
+    assert.strictEqual(working.error, null);
+
+    assert.strictEqual(failing?.proxy, dead);
+    assert.strictEqual(failing.ok, false);
+    assert.strictEqual(failing.latency, null);
+    assert.ok(failing.error !== null && failing.code !== null, "A refused proxy should carry an error and code");
+
+    await assert.rejects(checkProxies([live], { concurrency: 0 }), /concurrency must be a positive integer/);
+  });
+
+  test("should send Basic and Bearer credentials", async () => {
+    const basic = await wreqFetch(httpUrl("/headers"), {
+      headers: { Authorization: "Basic replaced" },
+      auth: { basic: { user: "jürgen", pass: "pä:ss" } },
+      timeout: 10000,
+    });
+    const basicBody = await basic.json<{ headers: Record<string, string> }>();
+    const expected = `Basic ${Buffer.from("jürgen:pä:ss", "utf8").toString("base64")}`;
+    assert.strictEqual(basicBody.headers.Authorization, expected, "Credentials should be UTF-8 encoded");
+
+    const bearer = await wreqFetch(httpUrl("/headers"), { auth: { bearer: "abc.DEF-123_~+/==" }, timeout: 10000 });
+    const bearerBody = await bearer.json<{ headers: Record<string, string> }>();
+    assert.strictEqual(bearerBody.headers.Authorization, "Bearer abc.DEF-123_~+/==");
+
+    await assert.rejects(wreqFetch(httpUrl("/get"), { auth: { basic: { user: "a:b" } } }), /cannot contain ':'/);
+    await assert.rejects(wreqFetch(httpUrl("/get"), { auth: { bearer: "bad token\r\n" } }), /auth\.bearer/);
+  });
+
+  test("should sign requests with AWS SigV4", async () => {
+    const credentials = { accessKeyId: "AKIDEXAMPLE", secretAccessKey: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY" };
+    const response = await wreqFetch(httpUrl("/headers?b=2&a=1"), {
+      browser: "chrome_142",
+      awsSigV4: { region: "us-east-1", service: "execute-api", ...credentials, sessionToken: "token" },
+      timeout: 10000,
+    });
+    const { rawHeaders } = await response.json<{ rawHeaders: string[] }>();
+
+    const received = new Map<string, string[]>();
+    for (let i = 0; i < rawHeaders.length; i += 2) {
+      const name = (rawHeaders[i] ?? "").toLowerCase();
+      received.set(name, [...(received.get(name) ?? []), (rawHeaders[i + 1] ?? "").trim().replace(/\s+/g, " ")]);
+    }
+
+    const authorization = received.get("authorization")?.[0] ?? "";
+    const match = authorization.match(/^AWS4-HMAC-SHA256 Credential=([^,]+), SignedHeaders=([^,]+), Signature=(\w+)$/);
+    assert.ok(match, `Unexpected Authorization header: ${authorization}`);
+    const [, credential = "", signedHeaders = "", signature] = match;
+    const [, date = "", region = "", service = ""] = credential.split("/");
+    assert.ok(signedHeaders.split(";").includes("user-agent"), "Emulation headers should be signed");
+    assert.deepStrictEqual(received.get("x-amz-security-token"), ["token"]);
+
+    // Recompute the signature from what the server received
+    const sha256 = (value: string) => createHash("sha256").update(value).digest("hex");
+    const canonicalHeaders = signedHeaders
+      .split(";")
+      .map((name) => `${name}:${(received.get(name) ?? []).join(",")}\n`)
+      .join("");
+    const payloadHash = received.get("x-amz-content-sha256")?.[0] ?? "";
+    assert.strictEqual(payloadHash, sha256(""));
+    const canonicalRequest = ["GET", "/headers", "a=1&b=2", canonicalHeaders, signedHeaders, payloadHash].join("\n");
+    const amzDate = received.get("x-amz-date")?.[0] ?? "";
+    const scope = `${date}/${region}/${service}/aws4_request`;
+    const stringToSign = ["AWS4-HMAC-SHA256", amzDate, scope, sha256(canonicalRequest)].join("\n");
+    let key: Buffer = Buffer.from(`AWS4${credentials.secretAccessKey}`);
+    for (const part of [date, region, service, "aws4_request"]) {
+      key = createHmac("sha256", key).update(part).digest();
+    }
+    assert.strictEqual(signature, createHmac("sha256", key).update(stringToSign).digest("hex"));
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { awsSigV4: { region: "us-east-1", service: "", ...credentials } }),
+      /awsSigV4\.service/,
+    );
+  });
+
+  test("should fetch, share and refresh OAuth2 tokens", async () => {
+    const tokens = createTokenManager({
+      tokenUrl: httpUrl("/oauth/token/shared"),
+      clientId: "client",
+      clientSecret: "secret",
+    });
+    const session = await createSession();
+
+    try {
+      session.use(tokens);
+      const responses = await Promise.all([1, 2, 3].map(() => session.get(httpUrl("/headers"), { timeout: 10000 })));
+      for (const response of responses) {
+        const body = await response.json<{ headers: Record<string, string> }>();
+        assert.strictEqual(body.headers.Authorization, "Bearer shared-1", "Concurrent requests should share one token");
+      }
+
+      const own = await session.get(httpUrl("/headers"), { headers: { Authorization: "Bearer mine" }, timeout: 10000 });
+      const ownBody = await own.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(ownBody.headers.Authorization, "Bearer mine", "Explicit Authorization should be kept");
+    } finally {
+      await session.close();
+    }
+
+    // Inside the refresh window the current token is served while a refresh runs
+    const expiring = createTokenManager({
+      tokenUrl: httpUrl("/oauth/token/expiring?expires_in=3600"),
+      clientId: "client",
+      refreshBefore: 3_600_000,
+    });
+    const first = await expiring.getToken();
+    assert.strictEqual(first.accessToken, "expiring-1");
+    assert.strictEqual(first.scope, "client_credentials");
+    assert.ok(first.expiresAt !== undefined && first.expiresAt > Date.now());
+    assert.strictEqual((await expiring.getToken()).accessToken, "expiring-1", "Valid tokens should not block");
+    // Joins the background refresh rather than starting another
+    const refreshed = await expiring.refresh();
+    assert.strictEqual(refreshed.accessToken, "expiring-2");
+    assert.strictEqual((await expiring.getToken()).accessToken, "expiring-2");
+    assert.strictEqual(refreshed.scope, "refresh_token", "Refreshes should use the issued refresh token");
+
+    const rejected = createTokenManager({ tokenUrl: httpUrl("/oauth/token/x"), clientId: "rejected" });
+    await assert.rejects(rejected.getToken(), /invalid_client: Unknown client/);
+    assert.throws(() => createTokenManager({ tokenUrl: "not a url", clientId: "client" }), RequestError);
+  });
+
+  test("should answer Digest challenges", async () => {
+    const anonymous = await wreqFetch(httpUrl("/digest-auth/user/passwd"), { timeout: 10000 });
+    assert.strictEqual(anonymous.status, 401, "Requests without credentials should see the challenge");
+
+    for (const algorithm of ["MD5", "SHA-256"]) {
+      const response = await wreqFetch(httpUrl(`/digest-auth/user/passwd?algorithm=${algorithm}`), {
+        browser: "chrome_142",
+        auth: { type: "digest", username: "user", password: "passwd" },
+        timeout: 10000,
+      });
+      assert.strictEqual(response.status, 200, `Should authenticate with ${algorithm}`);
+      const body = await response.json<{ authenticated: boolean; algorithm: string }>();
+      assert.strictEqual(body.authenticated, true);
+      assert.strictEqual(body.algorithm, algorithm);
+    }
+
+    const rejected = await wreqFetch(httpUrl("/digest-auth/user/passwd"), {
+      auth: { type: "digest", username: "user", password: "wrong" },
+      timeout: 10000,
+    });
+    assert.strictEqual(rejected.status, 401, "Wrong credentials should end with the server's 401");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { auth: { type: "kerberos" as "digest", username: "user" } }),
+      /Unknown auth type/,
+    );
+  });
+
+  test("should route requests through SOCKS5 proxies", async () => {
+    const socks = await startSocks5Proxy();
+    const target = new URL(HTTP_TEST_BASE_URL);
+
+    try {
+      // socks5 resolves the hostname locally and sends the proxy an IP address
+      const local = await wreqFetch(httpUrl("/get"), {
+        browser: "chrome_142",
+        proxy: `socks5://${socks.address}`,
+        timeout: 10000,
+      });
+      assert.strictEqual(local.status, 200);
+      assert.strictEqual(socks.connections[0]?.addressType, "ipv4", "socks5 should send a resolved address");
+
+      // socks5h hands the hostname to the proxy for resolution
+      const remoteUrl = `http://localhost:${target.port}/get`;
+      const remote = await wreqFetch(remoteUrl, {
+        browser: "chrome_142",
+        proxy: `socks5h://${socks.address}`,
+        proxyAuth: { username: "socks-user", password: "secret" },
+        timeout: 10000,
+      });
+      assert.strictEqual(remote.status, 200);
+      assert.deepStrictEqual(
+        socks.connections[1],
+        { host: "localhost", port: Number(target.port), addressType: "domain", username: "socks-user" },
+        "socks5h should resolve the hostname on the proxy",
+      );
+    } finally {
+      await socks.close();
+    }
+  });
+
+  test("should reject unsupported proxy schemes", async () => {
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), { proxy: "ftp://127.0.0.1:21", timeout: 10000 });
+      },
+      (error: unknown) => error instanceof RequestError && /Unsupported proxy scheme/.test(error.message),
+    );
+  });
+
+  test("should load PEM and PKCS#12 client certificates", async () => {
+    const pem = await wreqFetch(httpUrl("/get"), {
+      clientCert: { cert: CLIENT_CERT, key: CLIENT_KEY },
+      timeout: 10000,
+    });
+    assert.strictEqual(pem.status, 200);
+
+    const pkcs12 = await wreqFetch(httpUrl("/get"), {
+      clientCert: { cert: CLIENT_PKCS12, passphrase: CLIENT_PKCS12_PASSPHRASE },
+      timeout: 10000,
+    });
+    assert.strictEqual(pkcs12.status, 200);
+
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), { clientCert: { cert: CLIENT_PKCS12, passphrase: "wrong" }, timeout: 10000 });
+      },
+      (error: unknown) => error instanceof RequestError && /PKCS#12/.test(error.message),
+    );
+
+    await assert.rejects(
+      async () => {
+        await wreqFetch(httpUrl("/get"), { clientCert: { cert: CLIENT_CERT }, timeout: 10000 });
+      },
+      (error: unknown) => error instanceof RequestError && /clientCert.key is required/.test(error.message),
+    );
+  });
+
+  test("should apply connection pool settings", async () => {
+    const pooled = await createSession({ pool: { maxIdlePerHost: 8, idleTimeout: 30_000 } });
+    const unpooled = await createSession({ pool: { maxIdlePerHost: 0 } });
+
+    try {
+      await (await pooled.get(httpUrl("/get"), { timeout: 10000 })).text();
+      const reused = await pooled.get(httpUrl("/get"), { pool: { maxIdlePerHost: 8, idleTimeout: 30_000 } });
+      assert.strictEqual(reused.timings.connect, undefined, "Pooled sessions should reuse the connection");
+
+      await (await unpooled.get(httpUrl("/get"), { timeout: 10000 })).text();
+      const fresh = await unpooled.get(httpUrl("/get"), { timeout: 10000 });
+      assert.ok(fresh.timings.connect !== undefined, "maxIdlePerHost: 0 should open a new connection per request");
+
+      await assert.rejects(
+        pooled.get(httpUrl("/get"), { pool: { maxIdlePerHost: 1 } }),
+        (error: unknown) => error instanceof RequestError && /Session pool cannot be changed/.test(error.message),
+      );
+      await assert.rejects(
+        wreqFetch(httpUrl("/get"), { pool: { idleTimeout: -1 } }),
+        (error: unknown) => error instanceof RequestError && /pool.idleTimeout/.test(error.message),
+      );
+    } finally {
+      await pooled.close();
+      await unpooled.close();
+    }
+  });
+
+  test("should apply happy eyeballs and per-family connect settings", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+
+    for (const pool of [{ happyEyeballs: 50, fallbackTimeout: 2000 }, { happyEyeballs: false }]) {
+      // localhost may resolve to ::1 first; the server only listens on 127.0.0.1
+      const response = await wreqFetch(`http://localhost:${port}/get`, { pool, timeout: 10000 });
+      assert.strictEqual(response.status, 200);
+    }
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { pool: { happyEyeballs: -1 } }),
+      /pool.happyEyeballs must be a boolean or a non-negative integer/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { pool: { fallbackTimeout: 0 } }),
+      /pool.fallbackTimeout must be a positive integer/,
+    );
+  });
+
+  test("should apply TCP socket options", async () => {
+    const pool = {
+      tcpNoDelay: false,
+      sendBufferSize: 64 * 1024,
+      receiveBufferSize: 1024 * 1024,
+      tcpKeepAlive: 5000,
+      tcpKeepAliveInterval: 1000,
+      tcpKeepAliveRetries: 2,
+    };
+    const response = await wreqFetch(httpUrl("/get"), { pool, timeout: 10000 });
+    assert.strictEqual(response.status, 200);
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { pool: { receiveBufferSize: 0 } }),
+      /pool.receiveBufferSize must be a positive integer/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { pool: { tcpNoDelay: "yes" as unknown as boolean } }),
+      /pool.tcpNoDelay must be a boolean/,
+    );
+  });
+
+  test("should queue requests over the concurrency limit", async () => {
+    setConcurrency({ perHost: 1 });
+    try {
+      assert.deepStrictEqual(getConcurrency(), { perHost: 1 });
+
+      const started = Date.now();
+      const responses = await Promise.all([
+        wreqFetch(httpUrl("/delay/1"), { timeout: 10000 }),
+        wreqFetch(httpUrl("/delay/1"), { timeout: 10000 }),
+      ]);
+      const elapsed = Date.now() - started;
+
+      assert.deepStrictEqual(responses.map((response) => response.status), [200, 200]);
+      assert.ok(elapsed >= 1900, `Requests to one host should run one at a time (took ${elapsed}ms)`);
+    } finally {
+      setConcurrency({});
+    }
+
+    assert.deepStrictEqual(getConcurrency(), {});
+    assert.throws(() => setConcurrency({ global: 0 }), /global must be a positive integer/);
+  });
+
+  test("should rate limit requests", async () => {
+    setRateLimit({ requestsPerSecond: 10 });
+    try {
+      const started = Date.now();
+      await Promise.all([0, 1, 2].map(() => wreqFetch(httpUrl("/get"), { timeout: 10000 })));
+      const elapsed = Date.now() - started;
+      assert.ok(elapsed >= 180, `Requests after the first should wait for tokens (took ${elapsed}ms)`);
+
+      setRateLimit({ requestsPerSecond: 0.5, per: "host", failFast: true });
+      assert.deepStrictEqual(getRateLimit(), { requestsPerSecond: 0.5, burst: 1, per: "host", failFast: true });
+
+      await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+      await assert.rejects(
+        wreqFetch(httpUrl("/get"), { timeout: 10000 }),
+        (error: unknown) => error instanceof RequestError && error.code === "ERR_RATE_LIMITED",
+      );
+    } finally {
+      setRateLimit({});
+    }
+
+    assert.deepStrictEqual(getRateLimit(), {});
+  });
+
+  test("should open a host's circuit after repeated failures and close it after a probe", async () => {
+    const key = `circuit-${Date.now()}`;
+    const host = new URL(HTTP_TEST_BASE_URL).hostname.replace(/^\[|\]$/g, "");
+    setCircuitBreaker({ threshold: 2, cooldown: 200, statuses: [503] });
+    try {
+      for (let i = 0; i < 2; i++) {
+        const response = await wreqFetch(httpUrl(`/retry/${key}/2`), { timeout: 10000 });
+        assert.strictEqual(response.status, 503);
+      }
+
+      await assert.rejects(
+        wreqFetch(httpUrl(`/retry/${key}/2`), { timeout: 10000 }),
+        (error: unknown) => error instanceof RequestError && error.code === "ERR_CIRCUIT_OPEN",
+      );
+      const [stats] = circuitBreakerStats();
+      assert.strictEqual(stats?.host, host);
+      assert.strictEqual(stats?.state, "open");
+      assert.strictEqual(stats?.consecutiveFailures, 2);
+      assert.ok(stats?.openUntil instanceof Date);
+
+      await new Promise((resolve) => setTimeout(resolve, 250));
+      const probe = await wreqFetch(httpUrl(`/retry/${key}/2`), { timeout: 10000 });
+      assert.strictEqual(probe.status, 200, "The probe should be let through once the cooldown is over");
+      assert.deepStrictEqual(circuitBreakerStats(), [], "A successful probe should close the circuit");
+    } finally {
+      setCircuitBreaker(null);
+    }
+
+    assert.throws(() => setCircuitBreaker({ threshold: 0 }), /threshold must be a positive integer/);
+  });
+
+  test("should share one request among concurrent identical GETs with dedupe", async () => {
+    const url = httpUrl(`/count/dedupe-${Date.now()}`);
+
+    const responses = await Promise.all([1, 2, 3].map(() => wreqFetch(url, { dedupe: true, timeout: 10000 })));
+    const bodies = await Promise.all(responses.map((response) => response.json() as Promise<{ hits: number }>));
+    assert.deepStrictEqual(
+      bodies.map((body) => body.hits),
+      [1, 1, 1],
+      "Concurrent requests should share one response",
+    );
+
+    const later = (await (await wreqFetch(url, { timeout: 10000 })).json()) as { hits: number };
+    assert.strictEqual(later.hits, 2, "Requests without dedupe should go to the network");
+
+    await assert.rejects(wreqFetch(url, { dedupe: "yes" as unknown as boolean }), /dedupe must be a boolean/);
+  });
+
+  test("should serve and revalidate responses from the HTTP cache", async () => {
+    setHttpCache({ maxSize: 1024 * 1024 });
+    try {
+      const key = `fresh-${Date.now()}`;
+      const first = await wreqFetch(httpUrl(`/cache/${key}`), { timeout: 10000 });
+      const second = await wreqFetch(httpUrl(`/cache/${key}`), { timeout: 10000 });
+      const reloaded = await wreqFetch(httpUrl(`/cache/${key}`), { cache: "reload", timeout: 10000 });
+
+      assert.strictEqual(first.fromCache, false);
+      assert.strictEqual(second.fromCache, true);
+      assert.deepStrictEqual(await second.json(), { hits: 1 });
+      assert.strictEqual(reloaded.fromCache, false);
+      assert.deepStrictEqual(await reloaded.json(), { hits: 2 });
+
+      const stale = httpUrl(`/cache/stale-${Date.now()}?cc=no-cache`);
+      await wreqFetch(stale, { timeout: 10000 });
+      const revalidated = await wreqFetch(stale, { timeout: 10000 });
+
+      assert.strictEqual(revalidated.status, 200);
+      assert.strictEqual(revalidated.fromCache, true);
+      assert.deepStrictEqual(await revalidated.json(), { hits: 1 });
+
+      const refreshed = await wreqFetch(httpUrl(`/cache/${key}`), { followRefresh: true, timeout: 10000 });
+      assert.strictEqual(refreshed.fromCache, true, "Requests following refreshes should share entries too");
+
+      const raw = await wreqFetch(httpUrl(`/cache/${key}`), { decompress: false, timeout: 10000 });
+      assert.strictEqual(raw.fromCache, false, "Raw bodies should be stored apart from decoded ones");
+
+      const signed = httpUrl(`/cache/signed-${Date.now()}`);
+      const credentials = { accessKeyId: "AKIDEXAMPLE", secretAccessKey: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY" };
+      await wreqFetch(signed, { awsSigV4: { region: "us-east-1", service: "s3", ...credentials }, timeout: 10000 });
+      const unsigned = await wreqFetch(signed, { timeout: 10000 });
+      assert.strictEqual(unsigned.fromCache, false, "Responses to signed requests should not be shared");
+      assert.deepStrictEqual(await unsigned.json(), { hits: 2 });
+
+      const challenged = httpUrl(`/cache/digest-${Date.now()}`);
+      await wreqFetch(challenged, { auth: { type: "digest", username: "user", password: "passwd" }, timeout: 10000 });
+      const anonymous = await wreqFetch(challenged, { timeout: 10000 });
+      assert.strictEqual(anonymous.fromCache, false, "Responses to authenticated requests should not be shared");
+
+      const session = await createSession({ browser: "chrome_142" });
+      try {
+        const own = await session.fetch(httpUrl(`/cache/${key}`), { timeout: 10000 });
+        assert.strictEqual(own.fromCache, false, "Sessions should not be served entries stored outside them");
+      } finally {
+        await session.close();
+      }
+    } finally {
+      setHttpCache(null);
+    }
+
+    assert.throws(() => setHttpCache({ store: "disk" }), /dir is required/);
+    await assert.rejects(wreqFetch(httpUrl("/get"), { cache: "bogus" as "default" }), /Unknown cache mode/);
+  });
+
+  test("should revalidate conditional requests with remembered validators", async () => {
+    const url = httpUrl(`/cache/conditional-${Date.now()}?cc=no-cache`);
+
+    const first = await wreqFetch(url, { conditional: true, timeout: 10000 });
+    const second = await wreqFetch(url, { conditional: true, timeout: 10000 });
+    const plain = await wreqFetch(url, { timeout: 10000 });
+
+    assert.strictEqual(first.fromCache, false);
+    assert.strictEqual(second.status, 200, "A 304 should come back as the stored 200");
+    assert.strictEqual(second.fromCache, true);
+    assert.deepStrictEqual(await second.json(), { hits: 1 });
+    assert.deepStrictEqual(await plain.json(), { hits: 3 }, "Requests without conditional send no validators");
+
+    await assert.rejects(
+      wreqFetch(url, { conditional: "yes" as unknown as boolean }),
+      /conditional must be a boolean/,
+    );
+  });
+
+  test("should propagate trace IDs as traceparent headers", async () => {
+    const traceId = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    setTracing({ level: "error", json: true });
+    try {
+      const traced = await wreqFetch(httpUrl("/headers"), { traceId, timeout: 10000 });
+      const { headers } = await traced.json<{ headers: Record<string, string> }>();
+      assert.match(headers.Traceparent ?? "", new RegExp(`^00-${traceId}-[0-9a-f]{16}-01$`));
+
+      const correlated = await wreqFetch(httpUrl("/headers"), { traceId: "order-1234", timeout: 10000 });
+      const correlatedBody = await correlated.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(correlatedBody.headers.Traceparent, undefined, "Only W3C trace IDs are propagated");
+
+      const own = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+      const kept = await wreqFetch(httpUrl("/headers"), { traceId, headers: { traceparent: own }, timeout: 10000 });
+      const keptBody = await kept.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(keptBody.headers.Traceparent, own);
+    } finally {
+      setTracing(null);
+    }
+
+    assert.throws(() => setTracing({ level: "loud" as "info" }), /Unknown tracing level/);
+  });
+
+  test("should aggregate request metrics", async () => {
+    resetMetrics();
+
+    await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+    await wreqFetch(httpUrl("/post"), { method: "POST", body: "hello", timeout: 10000 });
+    await assert.rejects(wreqFetch("http://127.0.0.1:1/", { timeout: 5000 }));
+
+    const metrics = getMetrics();
+    assert.strictEqual(metrics.requests, 3);
+    assert.deepStrictEqual(metrics.responses, { "2xx": 2 });
+    assert.deepStrictEqual(metrics.errors, { ERR_CONNECT: 1 });
+    assert.strictEqual(metrics.latency.count, 3);
+    assert.ok(metrics.latency.p50 !== null && metrics.latency.p99 !== null);
+    assert.ok(metrics.latency.p50 <= metrics.latency.p99);
+    assert.strictEqual(metrics.latency.buckets.at(-1)?.count, 3);
+    assert.strictEqual(metrics.bytesSent, 5);
+    assert.ok(metrics.bytesReceived > 0);
+
+    const text = getMetrics("prometheus");
+    assert.match(text, /^wreq_requests_total\{status_class="2xx"\} 2$/m);
+    assert.match(text, /^wreq_request_errors_total\{code="ERR_CONNECT"\} 1$/m);
+    assert.match(text, /^wreq_request_duration_seconds_bucket\{le="\+Inf"\} 3$/m);
+
+    resetMetrics();
+    assert.strictEqual(getMetrics().requests, 0);
+  });
+
+  test("should parse bodies in Rust", async () => {
+    const page = await wreqFetch(httpUrl("/html"), { parse: "html-meta", timeout: 10000 });
+    assert.strictEqual(page.body, "", "The markup should stay on the Rust side");
+    assert.deepStrictEqual(page.parsed, {
+      title: "Local test page",
+      lang: "en",
+      canonical: httpUrl("/html?canonical=1"),
+      meta: { description: "A page for parser tests", "og:image": "https://example.com/og.png" },
+      links: [httpUrl("/get"), "https://example.com/"],
+    });
+
+    const feed = await wreqFetch(httpUrl("/xml"), { parse: "xml", timeout: 10000 });
+    const entries = (feed.parsed as XmlElement).children as XmlElement[];
+    assert.deepStrictEqual(
+      entries.map((entry) => [entry.attributes.id, entry.children[0]]),
+      [
+        ["1", { name: "title", attributes: {}, children: ["First & best"] }],
+        ["2", { name: "title", attributes: {}, children: ["<Second>"] }],
+      ],
+    );
+
+    const data = await wreqFetch(httpUrl("/json"), { parse: "json", timeout: 10000 });
+    assert.strictEqual((data.parsed as { status: string }).status, "ok");
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/html"), { parse: "json", timeout: 10000 }),
+      (error: unknown) => error instanceof RequestError && error.code === "ERR_BODY_DECODE",
+    );
+    await assert.rejects(wreqFetch(httpUrl("/html"), { parse: "html-meta", stream: true }), /stream/);
+  });
+
+  test("should extract CSS selector matches in Rust", async () => {
+    const response = await wreqFetch(httpUrl("/html"), {
+      extract: {
+        heading: "h1.title",
+        missing: ".nope",
+        links: { selector: "ul.links a", attr: "href", all: true },
+        external: { selector: "a[rel=external]", attr: "href" },
+      },
+      timeout: 10000,
+    });
+    assert.strictEqual(response.body, "");
+    assert.deepStrictEqual(response.extracted, {
+      heading: "Hello",
+      missing: null,
+      links: ["/get", "https://example.com/", "/get", "mailto:team@example.com"],
+      external: "https://example.com/",
+    });
+
+    const plain = await wreqFetch(httpUrl("/html"), { timeout: 10000 });
+    assert.strictEqual(plain.extracted, null);
+
+    await assert.rejects(wreqFetch(httpUrl("/html"), { extract: { broken: "a[" } }), /extract\.broken/);
+  });
+
+  test("should flag bot-protection challenges", async () => {
+    const blocked = await wreqFetch(httpUrl("/challenge"), { timeout: 10000 });
+    assert.deepStrictEqual(blocked.challenge, {
+      provider: "cloudflare",
+      signals: [
+        "header cf-mitigated",
+        "body /cdn-cgi/challenge-platform/",
+        "body <title>Just a moment...</title>",
+      ],
+    });
+
+    const streamed = await wreqFetch(httpUrl("/challenge"), { stream: true, timeout: 10000 });
+    assert.deepStrictEqual(streamed.challenge?.signals, ["header cf-mitigated"], "Streams are judged by headers");
+    await streamed.cancel();
+
+    const page = await wreqFetch(httpUrl("/html"), { timeout: 10000 });
+    assert.strictEqual(page.challenge, null);
+
+    setMocks({
+      routes: [
+        {
+          url: "https://shop.example/",
+          response: { status: 403, body: '<script src="https://ct.captcha-delivery.com/c.js"></script>' },
+        },
+      ],
+    });
+    try {
+      const mocked = await wreqFetch("https://shop.example/");
+      assert.strictEqual(mocked.challenge?.provider, "datadome");
+    } finally {
+      setMocks(null);
+    }
+  });
+
+  test("should tag requests with an ID", async () => {
+    const generated = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+    assert.match(generated.requestId, /^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[0-9a-f]{4}-[0-9a-f]{12}$/);
+    const other = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+    assert.notStrictEqual(other.requestId, generated.requestId, "Each request should get its own ID");
+
+    const events: RequestEvent[] = [];
+    setEventListener((event) => events.push(event));
+    startHarRecording();
+    try {
+      const response = await wreqFetch(httpUrl("/get"), { requestId: "checkout-42", timeout: 10000 });
+      assert.strictEqual(response.requestId, "checkout-42");
+      assert.strictEqual(stopHarRecording()?.log.entries[0]?._requestId, "checkout-42");
+
+      await assert.rejects(
+        wreqFetch("http://127.0.0.1:1/", { requestId: "checkout-43", timeout: 5000 }),
+        (error: unknown) => error instanceof RequestError && error.requestId === "checkout-43",
+      );
+
+      await new Promise((resolve) => setTimeout(resolve, 100));
+      assert.ok(events.some((event) => event.requestId === "checkout-42" && event.type === "complete"));
+      assert.ok(events.some((event) => event.requestId === "checkout-43" && event.type === "error"));
+    } finally {
+      setEventListener(null);
+      stopHarRecording();
+    }
+  });
+
+  test("should report request lifecycle events", async () => {
+    const events: RequestEvent[] = [];
+    const settled = (type: "complete" | "error") =>
+      new Promise<void>((resolve) => {
+        const check = setInterval(() => {
+          if (events.some((event) => event.type === type)) {
+            clearInterval(check);
+            resolve();
+          }
+        }, 10);
+      });
+
+    setEventListener((event) => events.push(event));
+    try {
+      await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+      await settled("complete");
+
+      const types = events.map((event) => event.type);
+      assert.strictEqual(types[0], "request-start");
+      assert.strictEqual(types.at(-1), "complete");
+      assert.ok(types.includes("connected"), "A fresh session should open a connection");
+      assert.ok(events.every((event) => event.requestId === events[0]?.requestId));
+
+      const firstByte = events.find((event) => event.type === "first-byte");
+      assert.strictEqual(firstByte?.status, 200);
+      assert.strictEqual(firstByte?.attempt, 1);
+      assert.ok((events.at(-1)?.bytes ?? 0) > 0);
+
+      events.length = 0;
+      await assert.rejects(wreqFetch("http://127.0.0.1:1/", { timeout: 5000 }));
+      await settled("error");
+      assert.strictEqual(events.find((event) => event.type === "error")?.code, "ERR_CONNECT");
+    } finally {
+      setEventListener(null);
+    }
+  });
+
+  test("should record requests and redirect hops as HAR", async () => {
+    assert.strictEqual(exportHar(), null);
+
+    startHarRecording({ maxBodySize: 8 });
+    try {
+      await wreqFetch(httpUrl("/redirect/1"), { headers: { "X-Har": "yes" }, timeout: 10000 });
+      await wreqFetch(httpUrl("/post"), { method: "POST", body: "hello", timeout: 10000 });
+
+      const snapshot = exportHar();
+      assert.strictEqual(snapshot?.log.entries.length, 3);
+    } finally {
+      const har = stopHarRecording();
+      assert.ok(har);
+      assert.strictEqual(har.log.version, "1.2");
+
+      const [hop, redirected, posted] = har.log.entries;
+      assert.ok(hop && redirected && posted);
+      assert.strictEqual(hop.response.status, 302);
+      assert.strictEqual(hop.request.url, httpUrl("/redirect/1"));
+      assert.strictEqual(redirected.request.url, httpUrl("/get"));
+      assert.strictEqual(redirected.response.status, 200);
+      const sent = redirected.request.headers.find(({ name }) => name.toLowerCase() === "x-har");
+      assert.strictEqual(sent?.value, "yes");
+      assert.ok(!Number.isNaN(Date.parse(redirected.startedDateTime)));
+
+      assert.strictEqual(posted.request.postData?.text, "hello");
+      assert.strictEqual(posted.response.content.text?.length, 8);
+      assert.match(posted.response.content.comment ?? "", /Truncated/);
+    }
+
+    assert.strictEqual(stopHarRecording(), null);
+    assert.throws(() => startHarRecording({ maxEntries: 0 }), /maxEntries/);
+  });
+
+  test("should render requests as curl commands", async () => {
+    const command = toCurl(httpUrl("/post"), {
+      method: "POST",
+      json: { name: "it's" },
+      headers: { "X-Test": "1" },
+      proxy: "http://127.0.0.1:8080",
+      timeout: 5000,
+    });
+
+    assert.ok(command.startsWith(`curl -X POST ${httpUrl("/post")} `));
+    assert.match(command, /-H 'X-Test: 1'/i);
+    assert.match(command, /-H 'user-agent: [^']+'/i, "Emulation headers should be included");
+    assert.match(command, /-H 'content-type: application\/json'/i);
+    assert.ok(command.includes(`--data-raw '{"name":"it'\\''s"}'`));
+    assert.match(command, / --proxy http:\/\/127\.0\.0\.1:8080 /);
+    assert.match(command, / --max-time 5$/);
+
+    const binary = toCurl(httpUrl("/post"), { method: "PUT", body: Buffer.from([0x00, 0xff, 0x25]) });
+    assert.ok(binary.startsWith("printf '\\000\\377%%' | curl -X PUT "));
+    assert.match(binary, /--data-binary @-/);
+
+    const session = await createSession();
+    try {
+      const { hostname } = new URL(HTTP_TEST_BASE_URL);
+      await session.setCookie({ name: "token", value: "abc", domain: hostname });
+      assert.match(toCurl(httpUrl("/get"), { session, disableDefaultHeaders: true }), /-H 'cookie: token=abc'/i);
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should answer requests from mocks", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    setMocks({
+      strict: true,
+      routes: [
+        { url: "https://api.example.test/users/*", response: { json: { id: 1 } } },
+        {
+          url: /\/orders\?page=2$/,
+          method: "POST",
+          response: (request) => ({
+            status: 201,
+            headers: { "X-Echo": request.headers.get("x-test") ?? "" },
+            body: request.body ?? "",
+          }),
+        },
+        { url: "https://api.example.test/once", times: 1, response: { body: "first" } },
+      ],
+    });
+
+    try {
+      const user = await wreqFetch("https://api.example.test/users/1");
+      assert.strictEqual(user.status, 200);
+      assert.strictEqual(user.headers.get("content-type"), "application/json");
+      assert.deepStrictEqual(await user.json(), { id: 1 });
+
+      const order = await wreqFetch("https://api.example.test/orders", {
+        method: "POST",
+        query: { page: 2 },
+        headers: { "X-Test": "1" },
+        json: { item: "book" },
+      });
+      assert.strictEqual(order.status, 201);
+      assert.strictEqual(order.headers.get("x-echo"), "1");
+      assert.strictEqual(await order.text(), '{"item":"book"}', "Handlers should see the serialized body");
+
+      assert.strictEqual(await (await wreqFetch("https://api.example.test/once")).text(), "first");
+      await assert.rejects(
+        wreqFetch("https://api.example.test/once"),
+        (error: unknown) => error instanceof RequestError && /No mock matches GET .*\/once$/.test(error.message),
+        "Strict mode should reject requests no route matches",
+      );
+
+      const result = await download("https://api.example.test/users/2", join(dir, "user.json"));
+      assert.strictEqual(result.bytesWritten, 8);
+      assert.strictEqual(await readFile(join(dir, "user.json"), "utf8"), '{"id":1}');
+
+      setMocks({ routes: [] });
+      const passthrough = await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+      assert.strictEqual(passthrough.status, 200, "Unmatched requests should go out when not strict");
+    } finally {
+      setMocks(null);
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("should preview requests without sending them", async () => {
+    const preview = await wreqFetch(httpUrl("/count/dry-run?page=1"), {
+      browser: "chrome_142",
+      method: "POST",
+      json: { id: 1 },
+      headers: { "X-Test": "1" },
+      dryRun: true,
+    });
+
+    const { host } = new URL(HTTP_TEST_BASE_URL);
+    assert.strictEqual(preview.method, "POST");
+    assert.strictEqual(preview.target, "/count/dry-run?page=1");
+    assert.deepStrictEqual(preview.headers[0], ["host", host], "Host should lead the headers");
+    assert.ok(preview.headers.some(([name]) => name === "user-agent"), "Emulation headers should be included");
+    assert.ok(preview.headers.some(([name, value]) => name === "content-length" && value === "8"));
+    assert.strictEqual(preview.bodyLength, 8);
+    assert.ok(preview.raw.startsWith("POST /count/dry-run?page=1 HTTP/1.1\r\nhost: "));
+    assert.match(preview.raw, /\r\nX-Test: 1\r\n/i);
+
+    const response = await wreqFetch(httpUrl("/count/dry-run"), { browser: "chrome_142", timeout: 10000 });
+    const body = await response.json<{ hits: number }>();
+    assert.strictEqual(body.hits, 1, "The dry run should not have reached the server");
+  });
+
+  test("should connect to overridden addresses", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+    const url = `http://wreq-resolve.test:${port}/get`;
+
+    const response = await wreqFetch(url, { resolve: { "wreq-resolve.test": "127.0.0.1" }, timeout: 10000 });
+    const body = await response.json<{ headers: Record<string, string> }>();
+    assert.strictEqual(body.headers.Host, `wreq-resolve.test:${port}`, "Host header should keep the hostname");
+
+    const session = await createSession({ resolve: { "WREQ-RESOLVE.TEST": ["127.0.0.1:1", "[::1]:1"] } });
+    try {
+      const viaSession = await session.get(url, { timeout: 10000 });
+      assert.strictEqual(viaSession.status, 200, "The URL's port should be used, not the override's");
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(
+      wreqFetch(url, { resolve: { "wreq-resolve.test": "not-an-ip" } }),
+      (error: unknown) => error instanceof RequestError && /Invalid address 'not-an-ip'/.test(error.message),
+    );
+  });
+
+  test("should resolve through the built-in DNS resolver", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+
+    // Overrides apply before the resolver, so no query leaves the machine
+    const session = await createSession({
+      dns: { servers: ["127.0.0.1:1"], cacheSize: 16, negativeTtl: 1000 },
+      resolve: { "wreq-dns.test": "127.0.0.1" },
+    });
+    try {
+      const response = await session.get(`http://wreq-dns.test:${port}/get`, { timeout: 10000 });
+      assert.strictEqual(response.status, 200);
+
+      const sameSettings = { servers: ["127.0.0.1:1"], cacheSize: 16, negativeTtl: 1000 };
+      const again = await session.get(`http://wreq-dns.test:${port}/get`, { dns: sameSettings, timeout: 10000 });
+      assert.strictEqual(again.status, 200, "Equal dns settings should be accepted by the session");
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { dns: { servers: ["1.1.1.1"], doh: "cloudflare" } }),
+      (error: unknown) => error instanceof RequestError && /cannot be combined/.test(error.message),
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { dns: { servers: ["dns.example"] } }),
+      (error: unknown) => error instanceof RequestError && /Invalid nameserver 'dns.example'/.test(error.message),
+    );
+  });
+
+  test("should bind the local address and filter address families", async () => {
+    const port = new URL(HTTP_TEST_BASE_URL).port;
+
+    const bound = await wreqFetch(httpUrl("/get"), { localAddress: "127.0.0.1", timeout: 10000 });
+    assert.strictEqual(bound.status, 200);
+
+    const session = await createSession({ ipStrategy: "ipv4-only", localAddress: "127.0.0.1" });
+    try {
+      const response = await session.get(`http://localhost:${port}/get`, { timeout: 10000 });
+      assert.strictEqual(response.status, 200, "localhost should resolve to its IPv4 address");
+
+      await assert.rejects(
+        session.get(`http://localhost:${port}/get`, { ipStrategy: "prefer-ipv6" }),
+        (error: unknown) => error instanceof RequestError && /ipStrategy cannot be changed/.test(error.message),
+      );
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { localAddress: "not-an-ip" }),
+      (error: unknown) => error instanceof RequestError && /Invalid localAddress/.test(error.message),
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { ipStrategy: "ipv5" as never }),
+      (error: unknown) => error instanceof RequestError && /Unknown ipStrategy 'ipv5'/.test(error.message),
+    );
+  });
+
+  test("should send requests over a Unix domain socket", { skip: process.platform === "win32" }, async () => {
+    const dir = await mkdtemp(join(tmpdir(), "wreq-uds-"));
+    const socketPath = join(dir, "daemon.sock");
+    const server = createHttpServer((req, res) => {
+      res.setHeader("Content-Type", "application/json");
+      res.end(JSON.stringify({ host: req.headers.host, path: req.url }));
+    });
+    await new Promise<void>((resolve) => server.listen(socketPath, resolve));
+
+    try {
+      const response = await wreqFetch("http://docker.local/v1.43/containers/json?all=1", {
+        unixSocket: socketPath,
+        timeout: 10000,
+      });
+      assert.deepStrictEqual(await response.json(), { host: "docker.local", path: "/v1.43/containers/json?all=1" });
+
+      await assert.rejects(
+        wreqFetch("http://docker.local/", { unixSocket: "" }),
+        /unixSocket must be a non-empty path/,
+      );
+    } finally {
+      await new Promise<void>((resolve) => server.close(() => resolve()));
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("sessions should keep their client certificate", async () => {
+    const clientCert = { cert: CLIENT_CERT, key: CLIENT_KEY };
+    const session = await createSession({ clientCert });
+
+    try {
+      const response = await session.fetch(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(response.status, 200);
+
+      await assert.rejects(
+        async () => {
+          await session.fetch(httpUrl("/get"), { clientCert: { cert: CLIENT_PKCS12 }, timeout: 10000 });
+        },
+        (error: unknown) => error instanceof RequestError && /cannot be changed/.test(error.message),
+      );
+    } finally {
+      await session.close();
+    }
+  });
+
+  test("should trust custom CAs and present client certificates", async () => {
+    const server = await startTlsTestServer();
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    const caFile = join(dir, "ca.pem");
+    await writeFile(caFile, CA_CERT);
+
+    try {
+      await assert.rejects(
+        async () => {
+          await wreqFetch(server.url, { timeout: 10000 });
+        },
+        (error: unknown) => error instanceof RequestError && error.code === "ERR_TLS",
+        "An unknown CA should fail verification",
+      );
+
+      const trusted = await wreqFetch(server.url, { ca: CA_CERT, timeout: 10000 });
+      assert.deepStrictEqual(await trusted.json(), { authorized: false });
+
+      const fromFile = await wreqFetch(server.url, {
+        caFile,
+        clientCert: { cert: CLIENT_CERT, key: CLIENT_KEY },
+        timeout: 10000,
+      });
+      assert.deepStrictEqual(await fromFile.json(), { authorized: true, clientSubject: "wreq-js test client" });
+
+      const insecure = await wreqFetch(server.url, { dangerAcceptInvalidCerts: true, timeout: 10000 });
+      assert.strictEqual(insecure.status, 200);
+
+      const session = await createSession({
+        ca: CA_CERT,
+        clientCert: { cert: CLIENT_PKCS12, passphrase: CLIENT_PKCS12_PASSPHRASE },
+      });
+
+      try {
+        const response = await session.fetch(server.url, { timeout: 10000 });
+        assert.deepStrictEqual(await response.json(), { authorized: true, clientSubject: "wreq-js test client" });
+
+        await assert.rejects(
+          async () => {
+            await session.fetch(server.url, { dangerAcceptInvalidCerts: true, timeout: 10000 });
+          },
+          (error: unknown) => error instanceof RequestError && /cannot be changed/.test(error.message),
+        );
+      } finally {
+        await session.close();
+      }
+    } finally {
+      await server.close();
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+
+  test("should expose the server certificate", async () => {
+    const server = await startTlsTestServer();
+
+    try {
+      const response = await wreqFetch(server.url, { ca: CA_CERT, timeout: 10000 });
+      const expected = new X509Certificate(SERVER_CERT);
+
+      assert.ok(response.tls, "HTTPS responses should carry certificate details");
+      assert.strictEqual(response.tls.subject, "CN=localhost");
+      assert.strictEqual(response.tls.issuer, "CN=wreq-js Test CA");
+      assert.deepStrictEqual(response.tls.subjectAltNames, ["localhost", "127.0.0.1"]);
+      assert.strictEqual(response.tls.notBefore.getTime(), new Date(expected.validFrom).getTime());
+      assert.strictEqual(response.tls.notAfter.getTime(), new Date(expected.validTo).getTime());
+      assert.strictEqual(response.tls.fingerprint256, expected.fingerprint256);
+    } finally {
+      await server.close();
+    }
+
+    if (HTTP_TEST_BASE_URL.startsWith("http:")) {
+      const plain = await wreqFetch(httpUrl("/get"), { timeout: 10000 });
+      assert.strictEqual(plain.tls, null, "Plain HTTP responses have no certificate");
+    }
+  });
+
+  test("should enforce certificate pins", async () => {
+    const server = await startTlsTestServer();
+    const cert = new X509Certificate(SERVER_CERT);
+    const spki = cert.publicKey.export({ type: "spki", format: "der" });
+    const publicKeyPin = `sha256/${createHash("sha256").update(spki).digest("base64")}`;
+
+    try {
+      const byCertificate = await wreqFetch(server.url, {
+        ca: CA_CERT,
+        pinnedSha256: [cert.fingerprint256],
+        timeout: 10000,
+      });
+      assert.strictEqual(byCertificate.status, 200);
+
+      const byPublicKey = await wreqFetch(server.url, { ca: CA_CERT, pinnedSha256: [publicKeyPin], timeout: 10000 });
+      assert.strictEqual(byPublicKey.status, 200);
+
+      await assert.rejects(
+        async () => {
+          await wreqFetch(server.url, { ca: CA_CERT, pinnedSha256: ["00".repeat(32)], timeout: 10000 });
+        },
+        (error: unknown) => error instanceof RequestError && error.code === "ERR_PINNING",
+      );
+
+      await assert.rejects(
+        async () => {
+          await wreqFetch(server.url, { ca: CA_CERT, pinnedSha256: ["not-a-hash"], timeout: 10000 });
+        },
+        (error: unknown) => error instanceof RequestError && /Invalid SHA-256 pin/.test(error.message),
+      );
+    } finally {
+      await server.close();
+    }
+  });
+
+  test("should send custom TLS fingerprints", async () => {
+    const server = await startTlsTestServer();
+    const ja3 = "771,4865-4866-4867-49195-49199-49196-49200,0-23-65281-10-11-35-16-5-13-18-51-45-43-27,29-23-24,0";
+
+    try {
+      const fromJa3 = await wreqFetch(server.url, { ca: CA_CERT, tlsFingerprint: { ja3 }, timeout: 10000 });
+      assert.strictEqual(fromJa3.status, 200);
+
+      const explicit = await wreqFetch(server.url, {
+        ca: CA_CERT,
+        tlsFingerprint: {
+          ciphers: ["TLS_AES_128_GCM_SHA256", 0x1302, "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"],
+          curves: ["X25519", "P-256"],
+          alpn: ["http/1.1"],
+        },
+        timeout: 10000,
+      });
+      assert.strictEqual(explicit.status, 200);
+    } finally {
+      await server.close();
+    }
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { tlsFingerprint: { ja4: "t13d1516h2_8daaf6152771_02713d6af862" } }),
+      /can't be reversed/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { tlsFingerprint: { ciphers: ["TLS_NULL_WITH_NULL_NULL"] } }),
+      /Unknown cipher/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { tlsFingerprint: { ja3, ja4: "t13d1516h2_1301_000a_0403" } }),
+      (error: unknown) => error instanceof RequestError && /ja3 or ja4, not both/.test(error.message),
+    );
+  });
+
+  test("should accept custom HTTP/2 settings", async () => {
+    const response = await wreqFetch(httpUrl("/get"), {
+      http2Fingerprint: {
+        akamai: "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p",
+        initialWindowSize: 131072,
+        pseudoHeaderOrder: [":method", ":path", ":authority", ":scheme"],
+        headersPriority: { weight: 256, exclusive: true },
+      },
+      timeout: 10000,
+    });
+    assert.strictEqual(response.status, 200);
+
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { http2Fingerprint: { settingsOrder: [1, 7] } }),
+      /HTTP\/2 setting 7 is not supported/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { http2Fingerprint: { akamai: "1:65536|0|m,a,s,p" } }),
+      /4 \|-separated parts/,
+    );
+    await assert.rejects(
+      wreqFetch(httpUrl("/get"), { http2Fingerprint: { headersPriority: { weight: 0 } } }),
+      (error: unknown) => error instanceof RequestError && /weight must be an integer from 1/.test(error.message),
+    );
+  });
+
+  test("should report the connection fingerprint", async () => {
+    const server = await startTlsTestServer();
+    const ja3 = "771,4865-4866-4867-49195-49199,0-23-65281-10-11-35-16-5-13-18-51-45-43-27,29-23-24,0";
+
+    try {
+      const chrome = await wreqFetch(server.url, {
+        ca: CA_CERT,
+        browser: "chrome_142",
+        inspectFingerprint: true,
+        timeout: 10000,
+      });
+      assert.ok(chrome.fingerprint);
+      assert.match(chrome.fingerprint.ja4 ?? "", /^t13d\d{4}(h2|h1)_[0-9a-f]{12}_[0-9a-f]{12}$/);
+      assert.strictEqual(chrome.fingerprint.ja3, null, "Chrome shuffles its extensions");
+      assert.strictEqual(chrome.fingerprint.alpn, "http/1.1");
+      assert.strictEqual(chrome.fingerprint.http2, null);
+
+      const custom = await wreqFetch(server.url, {
+        ca: CA_CERT,
+        tlsFingerprint: { ja3 },
+        inspectFingerprint: true,
+        timeout: 10000,
+      });
+      assert.ok(custom.fingerprint?.ja3?.startsWith("771,4865-4866-4867-49195-49199,"));
+      assert.match(custom.fingerprint.ja3Hash ?? "", /^[0-9a-f]{32}$/);
+      assert.strictEqual(custom.clone().fingerprint?.ja3, custom.fingerprint.ja3);
+    } finally {
+      await server.close();
+    }
+
+    const plain = await wreqFetch(httpUrl("/get"), { inspectFingerprint: true });
+    assert.ok(plain.fingerprint);
+    assert.strictEqual(plain.fingerprint.ja4, null);
+    assert.strictEqual(plain.fingerprint.alpn, null);
+
+    const unset = await wreqFetch(httpUrl("/get"));
+    assert.strictEqual(unset.fingerprint, null);
+  });
+
+  test("should run middleware around requests", async () => {
+    const order: string[] = [];
+    const removeGlobal = use({
+      onRequest(request) {
+        order.push("global request");
+        request.headers.set("X-Signed", "yes");
+      },
+      onResponse(response) {
+        order.push("global response");
+        return response;
+      },
+    });
+
+    const session = await createSession({ browser: "chrome_142" });
+    const removeSession = session.use({
+      async onRequest(request) {
+        order.push("session request");
+        return { ...request, url: `${request.url}?via=session` };
+      },
+      onResponse() {
+        order.push("session response");
+      },
+    });
+
+    try {
+      const response = await session.get(httpUrl("/get"), { timeout: 10000 });
+      const body = await response.json<{ args: Record<string, string>; headers: Record<string, string> }>();
+
+      assert.strictEqual(body.headers["X-Signed"], "yes", "Global middleware should add headers");
+      assert.strictEqual(body.args.via, "session", "Session middleware should be able to replace the request");
+      assert.deepStrictEqual(order, ["global request", "session request", "session response", "global response"]);
+
+      removeSession();
+      order.length = 0;
+      await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+      assert.deepStrictEqual(order, ["global request", "global response"], "Session middleware should stay scoped");
+    } finally {
+      removeGlobal();
+      await session.close();
+    }
+
+    order.length = 0;
+    await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+    assert.deepStrictEqual(order, [], "Removed middleware should not run");
+  });
+
+  test("should run onSend middleware on every attempt and download", async () => {
+    const sent: string[][] = [];
+    const remove = use({
+      onSend(request) {
+        sent.push(request.headers.map(([name]) => name.toLowerCase()));
+        request.headers.push(["X-Attempt", String(sent.length)]);
+      },
+    });
+
+    const dir = await mkdtemp(join(tmpdir(), "wreq-js-"));
+    try {
+      const retried = await wreqFetch(httpUrl(`/retry/on-send-${Date.now()}/1`), {
+        browser: "chrome_142",
+        retry: { maxAttempts: 2, backoffBase: 1 },
+        timeout: 10000,
+      });
+      assert.strictEqual(retried.status, 200);
+      assert.strictEqual(sent.length, 2, "Each attempt should go through onSend");
+      assert.ok(sent[0]?.includes("user-agent"), "The profile's headers should already be in place");
+
+      sent.length = 0;
+      const echoed = await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+      const body = await echoed.json<{ headers: Record<string, string> }>();
+      assert.strictEqual(body.headers["X-Attempt"], "1", "Headers added by onSend should be sent");
+
+      sent.length = 0;
+      const { response } = await download(httpUrl("/bytes/64"), join(dir, "bytes.bin"), { timeout: 10000 });
+      assert.strictEqual(response.status, 200);
+      assert.strictEqual(sent.length, 1, "Downloads should go through middleware too");
+    } finally {
+      remove();
+      await rm(dir, { recursive: true, force: true });
+    }
+
+    const failure = new Error("refused by middleware");
+    const removeFailing = use({
+      onSend() {
+        throw failure;
+      },
+    });
+    try {
+      await assert.rejects(wreqFetch(httpUrl("/get"), { timeout: 10000 }), (error) => error === failure);
+    } finally {
+      removeFailing();
+    }
+  });
+
+  test("should apply global and session defaults under request options", async () => {
+    setDefaults({ headers: { "X-Default": "global", "X-Layer": "global" }, timeout: 10000 });
+    const session = await createSession({ browser: "chrome_142", headers: { "X-Layer": "session" } });
+
+    try {
+      const plain = await wreqFetch(httpUrl("/get"), { browser: "chrome_142" });
+      const plainHeaders = (await plain.json<{ headers: Record<string, string> }>()).headers;
+      assert.strictEqual(plainHeaders["X-Default"], "global");
+      assert.strictEqual(plainHeaders["X-Layer"], "global");
+
+      const scoped = await session.get(httpUrl("/get"));
+      const scopedHeaders = (await scoped.json<{ headers: Record<string, string> }>()).headers;
+      assert.strictEqual(scopedHeaders["X-Default"], "global", "Session requests should keep global defaults");
+      assert.strictEqual(scopedHeaders["X-Layer"], "session", "Session headers should win over global ones");
+
+      const overridden = await session.get(httpUrl("/get"), { headers: { "X-Layer": "request" } });
+      const overriddenHeaders = (await overridden.json<{ headers: Record<string, string> }>()).headers;
+      assert.strictEqual(overriddenHeaders["X-Layer"], "request", "Request headers should win over defaults");
+
+      assert.throws(() => setDefaults({ retry: { maxAttempts: 0 } }), RequestError);
+    } finally {
+      setDefaults({});
+      await session.close();
+    }
+
+    const cleared = await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 });
+    const clearedHeaders = (await cleared.json<{ headers: Record<string, string> }>()).headers;
+    assert.strictEqual(clearedHeaders["X-Default"], undefined, "Clearing defaults should stop sending them");
+  });
+
+  test("should remove browser profile headers set to null", async () => {
+    const headersOf = async (response: Response) =>
+      (await response.json<{ headers: Record<string, string> }>()).headers;
+
+    const baseline = await headersOf(await wreqFetch(httpUrl("/get"), { browser: "chrome_142", timeout: 10000 }));
+    assert.ok(baseline["Accept-Language"], "The profile should send Accept-Language by default");
+
+    const removed = await headersOf(
+      await wreqFetch(httpUrl("/get"), { browser: "chrome_142", headers: { "Accept-Language": null }, timeout: 10000 }),
+    );
+    assert.strictEqual(removed["Accept-Language"], undefined);
+    assert.strictEqual(removed["User-Agent"], baseline["User-Agent"], "Other profile headers should still be sent");
+
+    setDefaults({ headers: { "X-Layer": "global" } });
+    const session = await createSession({
+      browser: "chrome_142",
+      headers: { "Accept-Language": null, "X-Layer": null },
+      timeout: 10000,
+    });
+    try {
+      const scoped = await headersOf(await session.get(httpUrl("/get")));
+      assert.strictEqual(scoped["Accept-Language"], undefined, "Session nulls should remove profile headers");
+      assert.strictEqual(scoped["X-Layer"], undefined, "Session nulls should remove global defaults");
+
+      const restored = await headersOf(await session.get(httpUrl("/get"), { headers: { "Accept-Language": "de-DE" } }));
+      assert.strictEqual(restored["Accept-Language"], "de-DE", "A request value should win over a session null");
+    } finally {
+      setDefaults({});
+      await session.close();
+    }
+  });
+
+  test("withSession helper should dispose sessions automatically", async () => {
+    let capturedSession: Session | undefined;
+
+    await withSession(async (session) => {
+      capturedSession = session;
+      const response = await session.fetch(httpUrl("/get"), { timeout: 5000 });
+      assert.strictEqual(response.status, 200);
+    });
+
+    const session = capturedSession;
+    assert.ok(session, "withSession should provide a session instance");
+
+    await assert.rejects(
+      async () => {
+        await session.fetch(httpUrl("/get"), { timeout: 5000 });
+      },
+      (error: unknown) => error instanceof RequestError,
+      "Using a closed session should fail",
+    );
+  });
+
+  test("autoReferer should send the last page as Referer and Origin", async () => {
+    const session = await createSession({ browser: "chrome_142", autoReferer: true, timeout: 10000 });
+    type Echo = { headers: Record<string, string> };
+
+    try {
+      const first = await (await session.fetch(httpUrl("/headers"))).json<Echo>();
+      assert.strictEqual(first.headers.Referer, undefined, "Nothing has been visited yet");
+
+      await session.fetch(httpUrl("/headers"));
+      await session.fetch(httpUrl("/html#top"));
+
+      const followed = await (await session.fetch(httpUrl("/headers"))).json<Echo>();
+      assert.strictEqual(followed.headers.Referer, httpUrl("/html"), "JSON responses should not become the referrer");
+      assert.strictEqual(followed.headers.Origin, undefined, "GETs carry no Origin");
+
+      const posted = await (await session.post(httpUrl("/post"), "a=1")).json<Echo>();
+      assert.strictEqual(posted.headers.Origin, new URL(httpUrl("/")).origin);
+
+      const suppressed = await (await session.fetch(httpUrl("/headers"), { headers: { Referer: null } })).json<Echo>();
+      assert.strictEqual(suppressed.headers.Referer, undefined, "A null header should win");
+    } finally {
+      await session.close();
+    }
+
+    await assert.rejects(createSession({ autoReferer: 1 as unknown as boolean }), /autoReferer must be a boolean/);
+  });
+
+  test("prewarm should return ready sessions in target order", async () => {
+    const sessions = await prewarm([
+      { browser: "chrome_142", url: httpUrl("/get"), timeout: 5000 },
+      { browser: "firefox_139", timeout: 5000 },
+    ]);
+
+    try {
+      assert.deepStrictEqual(
+        sessions.map((session) => session.browser),
+        ["chrome_142", "firefox_139"],
+      );
+      const response = await sessions[0]?.fetch(httpUrl("/get"));
+      assert.strictEqual(response?.status, 200);
+    } finally {
+      await Promise.all(sessions.map((session) => session.close()));
+    }
+  });
 });
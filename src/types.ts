@@ -1,6 +1,38 @@
-// Import and re-export the auto-generated BrowserProfile type
-import type { BrowserProfile } from "./generated-types";
-export type { BrowserProfile };
+// Import and re-export the types generated from the Rust sources
+import type {
+  BodyCompression,
+  BodyHashAlgorithm,
+  BodyParser,
+  BrowserProfile,
+  CookiePolicy,
+  EmulationOs,
+  ErrorCode,
+  IpStrategy,
+  NativeConnectionOptions,
+  NativeRequestOptions,
+  NativeResponse,
+  ProxyPoolStrategy,
+  RedirectPolicy,
+  RequestCacheMode,
+  RetryPolicy,
+} from "./generated-types";
+export type {
+  BodyCompression,
+  BodyHashAlgorithm,
+  BodyParser,
+  BrowserProfile,
+  CookiePolicy,
+  EmulationOs,
+  ErrorCode,
+  IpStrategy,
+  NativeConnectionOptions,
+  NativeRequestOptions,
+  NativeResponse,
+  ProxyPoolStrategy,
+  RedirectPolicy,
+  RequestCacheMode,
+  RetryPolicy,
+};
 
 /**
  * Controls how cookies are scoped for a request.
@@ -17,6 +49,13 @@ export interface SessionHandle {
   readonly id: string;
 }
 
+/**
+ * Minimal handle implemented by {@link ProxyPool}, accepted by {@link RequestInit.proxyPool}.
+ */
+export interface ProxyPoolHandle {
+  readonly id: string;
+}
+
 /**
  * A tuple of [name, value] pairs used for initializing headers.
  * Both name and value must be strings.
@@ -67,6 +106,479 @@ export type HeadersInit =
  */
 export type BodyInit = string | ArrayBuffer | ArrayBufferView | URLSearchParams | Buffer;
 
+/**
+ * A single field of a multipart/form-data body: either a text value, or a file
+ * read from disk (`path`) or supplied in memory (`data`).
+ *
+ * @example
+ * ```typescript
+ * const fields: MultipartField[] = [
+ *   { name: 'title', value: 'Quarterly report' },
+ *   { name: 'attachment', path: './report.pdf' },
+ *   { name: 'avatar', data: Buffer.from(png), filename: 'avatar.png', contentType: 'image/png' },
+ * ];
+ * ```
+ */
+export type MultipartField =
+  | { name: string; value: string }
+  | { name: string; path: string; filename?: string; contentType?: string }
+  | { name: string; data: Buffer; filename?: string; contentType?: string };
+
+/**
+ * Credentials for a proxy that requires authentication, sent as Basic
+ * `Proxy-Authorization`. Use this instead of embedding them in the proxy URL
+ * when they contain reserved characters.
+ */
+export interface ProxyAuth {
+  username: string;
+  password?: string;
+}
+
+/**
+ * Credentials for servers that challenge with `401 Unauthorized`. The request is sent
+ * as usual and, when the server asks for the given scheme, answered and sent again:
+ * `digest` supports MD5 and SHA-256 (and their `-sess` variants) with `qop=auth`;
+ * `ntlm` performs an NTLMv2 handshake, which keeps the request on HTTP/1.1 since NTLM
+ * authenticates the connection.
+ */
+export interface ChallengeAuthOptions {
+  type: "digest" | "ntlm";
+  username: string;
+  password?: string;
+  /**
+   * NTLM domain. A `DOMAIN\user` username is split when this is omitted.
+   */
+  domain?: string;
+}
+
+/**
+ * Basic credentials, sent up front as `Authorization: Basic`. Non-ASCII credentials are
+ * encoded as UTF-8, per RFC 7617; `user` cannot contain a colon.
+ */
+export interface BasicAuthOptions {
+  basic: { user: string; pass?: string };
+}
+
+/**
+ * A bearer token, sent up front as `Authorization: Bearer`.
+ */
+export interface BearerAuthOptions {
+  bearer: string;
+}
+
+/**
+ * Request credentials. Basic and Bearer replace any `Authorization` header the request
+ * has; Digest and NTLM only answer the server's challenge.
+ */
+export type AuthOptions = BasicAuthOptions | BearerAuthOptions | ChallengeAuthOptions;
+
+/**
+ * AWS Signature Version 4 settings. Each attempt is signed just before it is sent, over
+ * the headers it goes out with (the browser emulation's included), so S3, API Gateway and
+ * other AWS endpoints can be called through the emulated client. Multipart bodies are
+ * sent with an `UNSIGNED-PAYLOAD` hash, which S3 accepts and most other services don't.
+ */
+export interface AwsSigV4Options {
+  region: string;
+  /**
+   * Signing name of the service, e.g. `"s3"` or `"execute-api"`.
+   */
+  service: string;
+  accessKeyId: string;
+  secretAccessKey: string;
+  /**
+   * Session token for temporary credentials, sent as `X-Amz-Security-Token`.
+   */
+  sessionToken?: string;
+}
+
+/**
+ * Settings for a {@link TokenManager}. Tokens come from the client credentials grant,
+ * or from the refresh token grant when {@link refreshToken} is set; a rotated refresh
+ * token returned by the server replaces the one given here.
+ */
+export interface OAuth2Options {
+  /**
+   * Token endpoint URL.
+   */
+  tokenUrl: string;
+  clientId: string;
+  clientSecret?: string;
+  /**
+   * Use the refresh token grant instead of client credentials.
+   */
+  refreshToken?: string;
+  scope?: string | string[];
+  /**
+   * `audience` parameter some providers (Auth0 and others) require.
+   */
+  audience?: string;
+  /**
+   * How the client authenticates to the token endpoint: HTTP Basic, as RFC 6749
+   * recommends, or `client_id`/`client_secret` form fields.
+   * @default "basic"
+   */
+  clientAuth?: "basic" | "body";
+  /**
+   * Milliseconds before expiry at which a token is refreshed in the background, so
+   * requests never wait on a refresh while the current token is still valid.
+   * @default 60000
+   */
+  refreshBefore?: number;
+  /**
+   * Request options for the token request itself, e.g. `proxy` or `timeout`.
+   */
+  requestInit?: RequestInit;
+}
+
+/**
+ * An access token issued by the token endpoint.
+ */
+export interface AccessToken {
+  accessToken: string;
+  tokenType: string;
+  /**
+   * Expiry in milliseconds since the epoch; absent when the server gave no `expires_in`.
+   */
+  expiresAt?: number;
+  scope?: string;
+}
+
+/**
+ * Client certificate presented during the TLS handshake for mutual TLS.
+ * Either a PEM certificate chain (leaf first) with its PKCS#8 private key, or a
+ * PKCS#12 (`.p12`/`.pfx`) archive passed as `cert` with `key` omitted.
+ */
+export interface ClientCertificate {
+  cert: string | Buffer;
+  key?: string | Buffer;
+  /**
+   * Passphrase for a PKCS#12 archive.
+   */
+  passphrase?: string;
+}
+
+/**
+ * Where a header goes relative to another one, named case-insensitively.
+ */
+export type HeaderPosition = { before: string } | { after: string };
+
+/**
+ * A custom TLS ClientHello. Start from a JA3 string or a raw (unhashed) JA4 string, then
+ * replace parts with explicit lists; whatever is left out keeps the browser profile's
+ * value. Ciphers, curves and signature algorithms take IANA code points or names such as
+ * `"TLS_AES_128_GCM_SHA256"`, `"X25519"` or `"ecdsa_secp256r1_sha256"`.
+ */
+export interface TlsFingerprintOptions {
+  /**
+   * `version,ciphers,extensions,curves,pointFormats`, as printed by JA3 tools.
+   */
+  ja3?: string;
+  /**
+   * The raw JA4_r form, e.g. `t13d1516h2_1301,1302,..._0005,000a,..._0403,0804,...`.
+   * Hashed JA4 strings can't be reversed into a ClientHello.
+   */
+  ja4?: string;
+  ciphers?: Array<number | string>;
+  /**
+   * Extension code points in the order they are sent. Extensions BoringSSL can't send
+   * are rejected rather than silently dropped.
+   */
+  extensions?: number[];
+  curves?: Array<number | string>;
+  sigalgs?: Array<number | string>;
+  alpn?: Array<"h2" | "http/1.1">;
+  /**
+   * Sprinkle GREASE values into the lists, as Chromium-based browsers do.
+   */
+  grease?: boolean;
+}
+
+/**
+ * Custom HTTP/2 connection settings, from an Akamai fingerprint string or given one by
+ * one. Explicit fields replace the matching part of `akamai`; whatever is left out keeps
+ * the browser profile's value.
+ */
+export interface Http2FingerprintOptions {
+  /**
+   * `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`, as printed by fingerprinting
+   * services, e.g. `1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p`. The settings it
+   * lists replace all of the profile's, in the order listed.
+   */
+  akamai?: string;
+  headerTableSize?: number;
+  enablePush?: boolean;
+  maxConcurrentStreams?: number;
+  /**
+   * Stream-level flow control window, at most 2147483647.
+   */
+  initialWindowSize?: number;
+  /**
+   * Between 16384 and 16777215.
+   */
+  maxFrameSize?: number;
+  maxHeaderListSize?: number;
+  /**
+   * SETTINGS parameter ids in the order they are sent, e.g. `[1, 2, 4, 6]`. Parameters
+   * left out follow in the default order.
+   */
+  settingsOrder?: number[];
+  /**
+   * Increment of the connection-level WINDOW_UPDATE sent after the preface; `0` sends none.
+   */
+  windowUpdate?: number;
+  /**
+   * Pseudo-header order, as full names or Akamai's letters: `[":method", ":authority",
+   * ":scheme", ":path"]` or `["m", "a", "s", "p"]`.
+   */
+  pseudoHeaderOrder?: string[];
+  /**
+   * Stream dependency every HEADERS frame carries. `weight` is 1 to 256 and defaults to 16.
+   */
+  headersPriority?: { weight?: number; dependsOn?: number; exclusive?: boolean };
+}
+
+/**
+ * Connection pool and TCP socket tuning for a client. Omitted fields keep the defaults:
+ * unlimited idle connections per host, a 90 second idle timeout, `TCP_NODELAY` on, the
+ * kernel's buffer autotuning, and TCP keepalive probes after 15 seconds of idle time,
+ * every 15 seconds, giving up after 3. Durations are in milliseconds.
+ */
+export interface PoolOptions {
+  /**
+   * Idle connections kept open per host for reuse. `0` disables connection reuse.
+   */
+  maxIdlePerHost?: number;
+  /**
+   * Close pooled connections that have been idle this long.
+   */
+  idleTimeout?: number;
+  /**
+   * Idle time before the first TCP keepalive probe.
+   */
+  tcpKeepAlive?: number;
+  /**
+   * Interval between TCP keepalive probes.
+   */
+  tcpKeepAliveInterval?: number;
+  /**
+   * Unanswered keepalive probes before the connection is considered dead. With
+   * `tcpKeepAlive` and `tcpKeepAliveInterval` it bounds how long a proxy that silently
+   * dropped the connection goes unnoticed.
+   * @default 3
+   */
+  tcpKeepAliveRetries?: number;
+  /**
+   * `TCP_NODELAY`: send small writes immediately instead of batching them (Nagle's
+   * algorithm). Turning it off only saves packets on links that are paid per packet.
+   * @default true
+   */
+  tcpNoDelay?: boolean;
+  /**
+   * `SO_SNDBUF` in bytes. Setting a buffer size turns off the kernel's autotuning for
+   * it, and the kernel may double the value for bookkeeping.
+   */
+  sendBufferSize?: number;
+  /**
+   * `SO_RCVBUF` in bytes. On a high-latency link the receive buffer caps throughput at
+   * about its size per round trip, so large downloads over slow proxies may need more.
+   */
+  receiveBufferSize?: number;
+  /**
+   * Happy Eyeballs (RFC 6555): when a host has both IPv4 and IPv6 addresses, how long
+   * the first family gets before the other is tried in parallel. `false` tries one
+   * family at a time. Lower it where IPv6 is broken and connects stall.
+   * @default 300
+   */
+  happyEyeballs?: boolean | number;
+  /**
+   * Connect time each address family gets, split evenly among its addresses. Without
+   * it, a host whose first A record never answers can use up the whole connect timeout
+   * before the next address is tried.
+   */
+  fallbackTimeout?: number;
+}
+
+/**
+ * Built-in DNS resolver for a client, used instead of the operating system's
+ * resolver. Answers and failures are cached, and sessions with equal settings share
+ * one cache. Omit `servers` and `doh` to query the nameservers from
+ * `/etc/resolv.conf`. TTL bounds are in milliseconds.
+ */
+export interface DnsOptions {
+  /**
+   * Nameservers queried over plain DNS, as `ip` or `ip:port` (port 53 by default).
+   */
+  servers?: string[];
+  /**
+   * Resolve over DNS-over-HTTPS through a public provider. Cannot be combined with `servers`.
+   */
+  doh?: "cloudflare" | "google" | "quad9";
+  /**
+   * Records kept in the cache. `0` disables caching.
+   * @default 32
+   */
+  cacheSize?: number;
+  /**
+   * Keep answers cached at least this long, even when their TTL is shorter.
+   */
+  minTtl?: number;
+  /**
+   * Keep answers cached at most this long, even when their TTL is longer.
+   */
+  maxTtl?: number;
+  /**
+   * Remember failed lookups (unknown host, no records) this long.
+   */
+  negativeTtl?: number;
+}
+
+/**
+ * A content coding taken off a response body, as listed in {@link Response.decodings}.
+ */
+export interface BodyDecoding {
+  encoding: BodyCompression;
+  /**
+   * Found from the body's leading bytes rather than `Content-Encoding`: a mislabelled
+   * coding, or one applied twice or not declared at all.
+   */
+  sniffed: boolean;
+}
+
+/**
+ * Metadata of an HTML page, as returned for `parse: "html-meta"`.
+ */
+export interface HtmlMeta {
+  /**
+   * Text of the `<title>` element with whitespace collapsed, or `null` without one.
+   */
+  title: string | null;
+  /**
+   * The `lang` attribute of `<html>`, or `null`.
+   */
+  lang: string | null;
+  /**
+   * Absolute URL from `<link rel="canonical">`, or `null`.
+   */
+  canonical: string | null;
+  /**
+   * `<meta>` contents keyed by their lowercased `name`, `property`, `http-equiv` or
+   * `itemprop`, e.g. `description` or `og:image`. The first value for a name wins.
+   */
+  meta: Record<string, string>;
+  /**
+   * Absolute http(s) URLs of the page's `<a href>` links, resolved against `<base href>`
+   * and the response URL, in document order without duplicates.
+   */
+  links: string[];
+}
+
+/**
+ * Bot-protection vendors whose challenge pages {@link ChallengeInfo} recognises.
+ */
+export type ChallengeProvider = "cloudflare" | "akamai" | "perimeterx" | "datadome";
+
+/**
+ * Signs that a response is a bot-protection challenge or block page rather than the
+ * page that was asked for, so callers can switch proxy, profile or strategy.
+ */
+export interface ChallengeInfo {
+  /**
+   * Vendor the markers belong to; with markers from several, the one with the most.
+   */
+  provider: ChallengeProvider;
+  /**
+   * What gave it away, e.g. `"header cf-mitigated"` or `"body captcha-delivery.com"`.
+   */
+  signals: string[];
+}
+
+/**
+ * A field for `extract`, when a bare CSS selector isn't enough.
+ */
+export interface ExtractRule {
+  /**
+   * CSS selector to match, e.g. `"article h2 > a"`.
+   */
+  selector: string;
+  /**
+   * Read this attribute instead of the element's text, e.g. `"href"`. Matches without it are skipped.
+   */
+  attr?: string;
+  /**
+   * Return every match as an array instead of the first one, or `null` when nothing matches.
+   * @default false
+   */
+  all?: boolean;
+}
+
+/**
+ * What `extract` found, by field name: the first match's text or attribute (`null` when
+ * nothing matched), or an array of them for fields with `all`.
+ */
+export type ExtractedValues = Record<string, string | string[] | null>;
+
+/**
+ * An element of a document parsed with `parse: "xml"`. Text and CDATA become strings;
+ * whitespace between elements, comments and processing instructions are left out.
+ */
+export interface XmlElement {
+  /**
+   * Qualified name, namespace prefix included.
+   */
+  name: string;
+  attributes: Record<string, string>;
+  children: Array<XmlElement | string>;
+}
+
+/**
+ * A browser profile as described by {@link listEmulations}.
+ */
+export interface EmulationInfo {
+  /**
+   * Profile name, as passed to `browser`.
+   */
+  name: BrowserProfile;
+  /**
+   * Browser the profile imitates: "chrome", "edge", "opera", "safari", "firefox" or "okhttp".
+   */
+  family: string;
+  /**
+   * Browser version, e.g. "142" or "17.4.1".
+   */
+  version: string;
+  /**
+   * Operating system the profile presents as. Desktop profiles present as macOS.
+   */
+  os: "macos" | "ios" | "android";
+}
+
+/**
+ * Relative weights for picking a browser profile at random, e.g.
+ * `{ chrome_142: 60, firefox_139: 30, safari_18: 10 }`. Weights need not add up to 100.
+ */
+export type BrowserWeights = Partial<Record<BrowserProfile, number>>;
+
+/**
+ * A query parameter value. Arrays send the parameter once per element; `null` and
+ * `undefined` leave it out.
+ */
+export type QueryValue = string | number | boolean | null | undefined | Array<string | number | boolean>;
+
+// Keys of the native request shape that RequestInit takes in another form, or that only the library sets
+type RequestInitExcludedKeys =
+  | "url"
+  | "headers"
+  | "body"
+  | "json"
+  | "trailers"
+  | "redirect"
+  | "proxyHeaders"
+  | "proxyPool"
+  | "ephemeral"
+  | "removeHeaders"
+  | "beforeSend";
+
 /**
  * Options for configuring a fetch request. Compatible with the standard Fetch API
  * with additional wreq-specific extensions for browser impersonation, proxies, and timeouts.
@@ -83,110 +595,959 @@ export type BodyInit = string | ArrayBuffer | ArrayBufferView | URLSearchParams
  * };
  * ```
  */
-export interface RequestInit {
+export interface RequestInit extends Omit<NativeRequestOptions, RequestInitExcludedKeys> {
   /**
-   * A string to set request's method.
-   * @default 'GET'
+   * A Headers object, an object literal, or an array of two-item arrays to set request's headers.
+   * In an object literal, a `null` value removes the header: the session's or
+   * {@link setDefaults}' value for it, and the browser profile's default.
+   */
+  headers?: HeadersInit;
+
+  /**
+   * A BodyInit object or null to set request's body.
+   */
+  body?: BodyInit | null;
+
+  /**
+   * Send this value as a JSON body. Sets `Content-Type: application/json` unless
+   * a Content-Type header is given. Cannot be combined with {@link body} or {@link multipart}.
+   */
+  json?: unknown;
+
+  /**
+   * Trailer fields sent after {@link bodyFile}, e.g. a checksum computed while uploading.
+   * The file then goes out chunked, without `Content-Length`, and the names are announced
+   * in a `Trailer` header. Requires {@link bodyFile}.
+   */
+  trailers?: HeadersInit;
+
+  /**
+   * An AbortSignal to set request's signal.
+   */
+  signal?: AbortSignal | null;
+
+  /**
+   * A string indicating whether request follows redirects, results in an error upon
+   * encountering a redirect, or returns the redirect. Unlike browsers, `"manual"` hands
+   * back the 3xx response untouched, with its status, `Location` header and cookies,
+   * so OAuth callbacks and challenge pages can be handled in code.
+   * @default 'follow'
+   */
+  redirect?: "follow" | "manual" | "error";
+
+  /**
+   * Maximum number of redirects to follow before failing with a RequestError.
+   * Only used when {@link redirect} is `"follow"`.
+   * @default 10
+   */
+  maxRedirects?: number;
+
+  /**
+   * Pick the browser profile for this request at random from these weights, to spread
+   * traffic across browsers the way real visitors are. `response.browser` reports the
+   * one used. Cannot be combined with {@link browser}.
+   */
+  browsers?: BrowserWeights;
+
+  /**
+   * Extra headers sent to {@link proxy}, e.g. provider-specific session or routing headers.
+   * They go on the CONNECT request for HTTPS targets and on the request itself for HTTP targets.
+   */
+  proxyHeaders?: HeadersInit;
+
+  /**
+   * Draw the proxy from a pool made with {@link createProxyPool}, afresh for every attempt,
+   * so a retry can go out through a different proxy. {@link proxyAuth} and
+   * {@link proxyHeaders} apply to whichever proxy is drawn. Cannot be combined with
+   * {@link proxy}; inside a session it overrides the session's proxy or pool.
+   */
+  proxyPool?: ProxyPoolHandle;
+
+  /**
+   * Controls how cookies are managed for this call.
+   * - "ephemeral": default when no session/sessionId is provided. Creates an isolated session per request.
+   * - "session": requires an explicit session or sessionId and reuses its cookie jar.
+   */
+  cookieMode?: CookieMode;
+
+  /**
+   * Session instance to bind this request to. When provided, {@link cookieMode}
+   * automatically behaves like `"session"`.
+   */
+  session?: SessionHandle;
+
+  /**
+   * Stream the response body instead of buffering it. The promise resolves as soon
+   * as headers arrive; consume the body with `response.chunks()`, `response.stream()`,
+   * or by iterating the response with `for await`.
+   * @default false
+   */
+  stream?: boolean;
+
+  /**
+   * Build the request without sending it: `fetch()` resolves to a {@link RequestPreview}
+   * of the method, target, headers in their final order (browser profile defaults and
+   * session cookies included) and body length, for checking what a fingerprint looks
+   * like on the wire. Request middleware runs; response middleware does not.
+   * @default false
+   */
+  dryRun?: boolean;
+}
+
+/**
+ * A single event received from a `text/event-stream` response.
+ */
+export interface ServerSentEvent {
+  /**
+   * Event name from the `event:` field.
+   * @default 'message'
+   */
+  type: string;
+  /**
+   * Event payload; multiple `data:` lines are joined with newlines.
+   */
+  data: string;
+  /**
+   * Last event ID seen on the stream so far (from `id:` fields), or an empty string.
+   */
+  lastEventId: string;
+}
+
+/**
+ * Options for {@link eventSource}. Accepts the usual request options except the
+ * method and body, since event streams are always fetched with GET.
+ */
+export interface EventSourceInit extends Omit<RequestInit, "method" | "body" | "multipart" | "stream"> {
+  /**
+   * Reconnect when the connection drops or the server ends the stream.
+   * @default true
+   */
+  reconnect?: boolean;
+  /**
+   * Delay before reconnecting in milliseconds. The server can change it with a `retry:` field.
+   * @default 3000
+   */
+  reconnectDelay?: number;
+  /**
+   * Give up after this many consecutive failed reconnection attempts.
+   * @default Infinity
+   */
+  maxReconnects?: number;
+  /**
+   * Initial `Last-Event-ID` to resume from.
+   */
+  lastEventId?: string;
+}
+
+/**
+ * Options for {@link checkRobots}, used to fetch the robots.txt. Accepts the usual request
+ * options, so it goes out with the same browser profile, proxy and session as the crawl,
+ * except those that set the method, body or query, or change how the response is read.
+ */
+export type RobotsInit = Omit<
+  RequestInit,
+  "method" | "body" | "multipart" | "json" | "form" | "bodyFile" | "query" | "stream" | "parse" | "extract" | "dryRun"
+>;
+
+/**
+ * What an origin's robots.txt says about one URL for one crawler.
+ */
+export interface RobotsResult {
+  /**
+   * Whether the crawler may fetch the URL. Always `true` when the robots.txt is missing
+   * (any other 4xx), and `false` while the server answers it with 429 or a 5xx.
+   */
+  allowed: boolean;
+  /**
+   * Seconds to wait between requests, from the `Crawl-delay` of the group that applies
+   * to the crawler, or `null` when it sets none.
+   */
+  crawlDelay: number | null;
+  /**
+   * URLs from the file's `Sitemap` lines, which apply to every crawler.
+   */
+  sitemaps: string[];
+  /**
+   * HTTP status the robots.txt was fetched with, after redirects.
+   */
+  status: number;
+}
+
+/**
+ * Options for {@link sitemap}, used for every sitemap it fetches. Accepts the usual request
+ * options except those that set the method or body, or change how the response is read.
+ */
+export type SitemapInit = Omit<
+  RequestInit,
+  "method" | "body" | "multipart" | "json" | "form" | "bodyFile" | "stream" | "parse" | "extract" | "dryRun"
+> & {
+  /**
+   * Fetch the sitemaps a sitemap index lists and yield their URLs, rather than yielding
+   * the index's own entries.
+   * @default true
+   */
+  followIndex?: boolean;
+};
+
+/**
+ * One entry of a sitemap: a page from a `<urlset>`, or another sitemap from a
+ * `<sitemapindex>`. Optional fields are left out when the sitemap doesn't set them.
+ */
+export interface SitemapEntry {
+  type: "url" | "sitemap";
+  loc: string;
+  /**
+   * Last modification date as written, e.g. `2024-05-01` or `2024-05-01T12:00:00+00:00`.
+   */
+  lastmod?: string;
+  /**
+   * How often the page changes, lowercased: `always`, `hourly`, `daily`, `weekly`,
+   * `monthly`, `yearly` or `never`. Only pages have one.
+   */
+  changefreq?: string;
+  /**
+   * Priority relative to the site's other pages, from 0.0 to 1.0. Only pages have one.
+   */
+  priority?: number;
+}
+
+/**
+ * Options for {@link grpcWeb}. Accepts the usual request options except those that set
+ * the method or body, which the call frames itself, and those that change how the
+ * response body is read.
+ */
+export type GrpcWebInit = Omit<
+  RequestInit,
+  "method" | "body" | "multipart" | "json" | "form" | "bodyFile" | "stream" | "parse" | "extract" | "dryRun"
+>;
+
+/**
+ * Progress of a {@link download}, reported roughly every 100ms and once more when
+ * the body has been written.
+ */
+export interface DownloadProgress {
+  /**
+   * Size of the file so far: body bytes written to disk, plus the partial file a resumed
+   * download continued.
+   */
+  received: number;
+  /**
+   * Expected size of the whole file, when the server declared one.
+   */
+  total?: number;
+  /**
+   * Average transfer rate since the response headers arrived, in bytes per second.
+   */
+  rate: number;
+}
+
+/**
+ * Options for {@link download}. The body is written straight to disk, so
+ * streaming and abort signals are not available; `timeout` covers the whole
+ * transfer rather than just the response head.
+ */
+export interface DownloadInit extends Omit<RequestInit, "stream" | "signal"> {
+  /**
+   * Called with transfer progress while the body is written.
+   */
+  onProgress?: (progress: DownloadProgress) => void;
+
+  /**
+   * Continue a partial file at the destination instead of starting over. The file is
+   * resumed with a `Range` request guarded by `If-Range`, so if the body has changed
+   * since, the server sends all of it and the file is rewritten. A download that fails
+   * part-way keeps its partial file, with a `<destPath>.resume` file next to it holding
+   * the ETag or Last-Modified date it came with, for the next call to pick up. Bodies
+   * without either validator can't be resumed and are downloaded as usual.
+   * @default false
+   *
+   * @example
+   * ```typescript
+   * // Run again after a dropped connection to fetch only the missing bytes
+   * const { bytesWritten, resumedFrom } = await download(url, './large.iso', { resume: true });
+   * ```
+   */
+  resume?: boolean;
+
+  /**
+   * Fetch the body over this many connections at once, each asking for its own byte range,
+   * when the server supports range requests. A one-byte request probes for support first;
+   * servers without it get an ordinary download. Segmented downloads that fail remove
+   * their file, since its missing parts needn't be at the end.
+   * @default 1
+   *
+   * @example
+   * ```typescript
+   * await download('https://example.com/large.iso', './large.iso', { segments: 4 });
+   * ```
+   */
+  segments?: number;
+}
+
+/**
+ * Configuration for {@link createSession}.
+ */
+export interface CreateSessionOptions {
+  /**
+   * Provide a custom identifier instead of an auto-generated random ID.
+   */
+  sessionId?: string;
+  /**
+   * Browser profile to bind to this session. Defaults to 'chrome_142'.
+   */
+  browser?: BrowserProfile;
+  /**
+   * Pick the session's browser profile once, at random from these weights. Every
+   * request in the session uses it; `session.browser` reports which. Cannot be
+   * combined with {@link browser}.
+   */
+  browsers?: BrowserWeights;
+  /**
+   * Operating system the session's browser profile presents as. Every request in the
+   * session uses it.
+   */
+  emulationOs?: EmulationOs;
+  /**
+   * Default proxy for requests made through the session. Individual requests
+   * may pass their own `proxy` to rotate without creating a new session.
+   */
+  proxy?: string;
+  /**
+   * Credentials for the session's default proxy.
+   */
+  proxyAuth?: ProxyAuth;
+  /**
+   * Extra headers for the session's default proxy.
+   */
+  proxyHeaders?: HeadersInit;
+  /**
+   * Proxy pool requests through the session draw from, unless they name their own
+   * `proxy` or `proxyPool`. Cannot be combined with {@link proxy}.
+   */
+  proxyPool?: ProxyPoolHandle;
+  /**
+   * Client certificate used for every request in the session.
+   */
+  clientCert?: ClientCertificate;
+  /**
+   * Extra root certificates (PEM) trusted by the session.
+   */
+  ca?: string | Buffer;
+  /**
+   * Path to a PEM file of extra root certificates trusted by the session.
+   */
+  caFile?: string;
+  /**
+   * Skip certificate verification for every request in the session.
+   */
+  dangerAcceptInvalidCerts?: boolean;
+  /**
+   * Custom TLS ClientHello used for every connection the session opens.
+   */
+  tlsFingerprint?: TlsFingerprintOptions;
+  /**
+   * Custom HTTP/2 settings used for every connection the session opens.
+   */
+  http2Fingerprint?: Http2FingerprintOptions;
+  /**
+   * Connection reuse and TCP keepalive tuning for the session's client.
+   */
+  pool?: PoolOptions;
+  /**
+   * Fixed addresses for hostnames, bypassing DNS for every request in the session.
+   */
+  resolve?: Record<string, string | string[]>;
+  /**
+   * Built-in caching DNS resolver settings for the session's client.
+   */
+  dns?: DnsOptions;
+  /**
+   * Address family filter or preference for the session's client.
+   */
+  ipStrategy?: IpStrategy;
+  /**
+   * File the session's cookie jar is loaded from on creation and saved to on every change
+   * and on {@link Session.close}. Paths ending in `.txt` use the Netscape `cookies.txt`
+   * format; other paths are JSON. Give each session its own file.
+   */
+  cookieStorePath?: string;
+  /**
+   * Keep the session's client, and with it its open connections and cookies, until
+   * {@link Session.close}. Pinned clients are exempt from the client cache's `idleTtl` and
+   * `maxEntries` and survive {@link clearClientCache}; only {@link shutdown} drops them.
+   * Timeouts are applied per request, so they never need a client of their own.
+   * @default false
+   */
+  pinClient?: boolean;
+  /**
+   * Default local address for the session's connections. Requests may pass their own.
+   */
+  localAddress?: string;
+  /**
+   * Default network interface for the session's connections. Requests may pass their own.
+   */
+  interface?: string;
+  /**
+   * Default timeout applied when {@link Session.fetch} is called without
+   * overriding `timeout`.
+   */
+  timeout?: number;
+  /**
+   * Headers sent with every request in the session. Request headers with the
+   * same name replace them, and replace {@link setDefaults}' headers in turn. A
+   * `null` value in an object literal removes the browser profile's default for
+   * that header, unless the request sets it.
+   */
+  headers?: HeadersInit;
+  /**
+   * Retry policy for requests that do not pass their own `retry`.
+   */
+  retry?: RetryPolicy;
+  /**
+   * Send `Referer` (and `Origin` on methods other than GET and HEAD) from the last HTML page
+   * the session fetched, as a browser navigating between pages would. Follows the default
+   * `strict-origin-when-cross-origin` policy: the full URL within an origin, only the origin
+   * across origins, and nothing from HTTPS to HTTP. Headers the request or session sets,
+   * or removes with `null`, take precedence.
+   * @default false
+   */
+  autoReferer?: boolean;
+}
+
+/**
+ * A session for {@link prewarm} to create ahead of time.
+ */
+export interface PrewarmTarget extends CreateSessionOptions {
+  /**
+   * Open a connection to this URL's origin too, TLS handshake and proxy tunnel included,
+   * so the session's first request finds it in the pool. Bounded by `timeout`.
+   */
+  url?: string | URL;
+}
+
+/**
+ * Defaults for every HTTP request, set with {@link setDefaults}. Request options
+ * win over session defaults, which win over these. Headers are merged by name, and
+ * a `null` header value removes the browser profile's default unless a session or
+ * request sets it.
+ */
+export interface RequestDefaults {
+  headers?: HeadersInit;
+  browser?: BrowserProfile;
+  /**
+   * Weighted browser profiles picked from per request, or once per session, when
+   * neither sets its own {@link browser}.
+   */
+  browsers?: BrowserWeights;
+  proxy?: string;
+  /**
+   * Proxy pool for requests that set neither a proxy nor a pool, nor have a session that does.
+   */
+  proxyPool?: ProxyPoolHandle;
+  timeout?: number;
+  retry?: RetryPolicy;
+}
+
+/**
+ * Caps on requests in flight, set with {@link setConcurrency}. Requests over a cap
+ * wait in a native queue instead of opening more sockets. Omitted fields are unlimited.
+ */
+export interface ConcurrencyOptions {
+  /**
+   * Requests in flight across every session and host.
+   */
+  global?: number;
+  /**
+   * Requests in flight to any one hostname.
+   */
+  perHost?: number;
+}
+
+/**
+ * Token bucket rate limit, set with {@link setRateLimit}. Every attempt of a request,
+ * retries included, takes a token; redirects do not.
+ */
+export interface RateLimitOptions {
+  /**
+   * Sustained request rate. Omit to remove the limit.
+   */
+  requestsPerSecond?: number;
+  /**
+   * Requests that may go out back to back after an idle period.
+   * @default 1
+   */
+  burst?: number;
+  /**
+   * Share one bucket across all requests, or keep one per hostname or per proxy.
+   * Requests without a proxy share one bucket under `"proxy"`.
+   * @default "global"
+   */
+  per?: "global" | "host" | "proxy";
+  /**
+   * Reject with an `ERR_RATE_LIMITED` RequestError instead of waiting for a token.
+   * @default false
+   */
+  failFast?: boolean;
+}
+
+/**
+ * Per-host circuit breaker settings for {@link setCircuitBreaker}.
+ */
+export interface CircuitBreakerOptions {
+  /**
+   * Failures in a row that open a host's circuit. Timeouts, connection, DNS and TLS
+   * errors count, as do responses with one of `statuses`; proxy errors don't.
+   * @default 5
+   */
+  threshold?: number;
+  /**
+   * Milliseconds an open circuit rejects requests before a single probe request is let
+   * through. A probe that succeeds closes the circuit; one that fails opens it again.
+   * @default 30000
+   */
+  cooldown?: number;
+  /**
+   * Response statuses that count as failures.
+   * @default [500, 502, 503, 504]
+   */
+  statuses?: number[];
+}
+
+/**
+ * One host's circuit, from {@link circuitBreakerStats}.
+ */
+export interface CircuitStats {
+  host: string;
+  /**
+   * `"half-open"` once the cooldown is over, until a probe request settles it.
+   */
+  state: "closed" | "open" | "half-open";
+  consecutiveFailures: number;
+  /**
+   * When an open circuit lets a probe through, or `null` when it isn't open.
+   */
+  openUntil: Date | null;
+}
+
+export interface NativeCircuitStats extends Omit<CircuitStats, "openUntil"> {
+  openUntil: number | null;
+}
+
+/**
+ * Proxies and rotation settings for {@link createProxyPool}.
+ */
+export interface ProxyPoolOptions {
+  /**
+   * Proxy URLs, in any scheme {@link RequestInit.proxy} accepts; credentials may be embedded.
+   */
+  proxies: string[];
+  /**
+   * @default "round-robin"
+   */
+  strategy?: ProxyPoolStrategy;
+  /**
+   * Failures in a row that put a proxy in quarantine. Failing to connect to or through
+   * the proxy, timing out, and a `407` response count; errors from the origin don't.
+   * @default 3
+   */
+  maxFailures?: number;
+  /**
+   * Milliseconds a failing proxy sits out. Once released, a single further failure
+   * sends it back, while a success clears its record.
+   * @default 60000
+   */
+  quarantine?: number;
+  /**
+   * After a proxy failure (see `maxFailures`), send the request straight on through up
+   * to this many other proxies of the pool, without waiting for the retry policy or
+   * counting against its attempts. `response.proxy` tells which one it went out through.
+   * @default 0
+   */
+  failover?: number;
+}
+
+/**
+ * One proxy's record in a pool, from {@link ProxyPool.stats}.
+ */
+export interface ProxyStats {
+  proxy: string;
+  successes: number;
+  failures: number;
+  consecutiveFailures: number;
+  /**
+   * When the proxy is drawn again, or `null` when it is not quarantined.
+   */
+  quarantinedUntil: Date | null;
+}
+
+export interface NativeProxyStats extends Omit<ProxyStats, "quarantinedUntil"> {
+  quarantinedUntil: number | null;
+}
+
+/**
+ * Hostname pattern in proxy routing. A string is a glob in which `*` matches any run of
+ * characters; without a `*` it also covers subdomains, as `NO_PROXY` entries do, so
+ * `"example.com"` matches `api.example.com` too. A RegExp is tested against the
+ * lowercase hostname, with only its `i` flag honoured.
+ */
+export type HostPattern = string | RegExp;
+
+/**
+ * Where requests to matching hosts go, for {@link setProxyRouting}. Exactly one of
+ * `proxy`, `proxyPool` and `direct` is set.
+ */
+export interface ProxyRoute {
+  hosts: HostPattern | HostPattern[];
+  proxy?: string;
+  proxyPool?: ProxyPoolHandle;
+  /**
+   * Connect straight to the server, without the request's or the session's proxy.
+   */
+  direct?: boolean;
+}
+
+/**
+ * Routing rules for {@link setProxyRouting}.
+ */
+export interface ProxyRoutingOptions {
+  /**
+   * Checked in order; the first route matching a request's hostname decides its proxy.
+   */
+  routes?: ProxyRoute[];
+  /**
+   * Hosts that always connect directly, checked before `routes`.
+   */
+  noProxy?: HostPattern[];
+}
+
+export interface NativeProxyRoute {
+  hosts: Array<string | { source: string; ignoreCase: boolean }>;
+  proxy?: string;
+  proxyPool?: { id: string };
+  direct?: boolean;
+}
+
+/**
+ * Settings for {@link checkProxies}.
+ */
+export interface ProxyCheckOptions {
+  /**
+   * Echo endpoint each proxy is checked against. It should answer like httpbin's `/get`,
+   * with JSON carrying the caller's address in `origin` (or `ip`) and the request
+   * headers in `headers`, or with the bare address as text.
+   * @default "https://httpbin.org/get"
+   */
+  testUrl?: string;
+  /**
+   * Milliseconds each check may take, connecting included.
+   * @default 10000
+   */
+  timeout?: number;
+  /**
+   * Proxies checked at once.
+   * @default 10
+   */
+  concurrency?: number;
+  /**
+   * Browser profile the checks are sent with.
+   * @default 'chrome_142'
+   */
+  browser?: BrowserProfile;
+}
+
+/**
+ * How a proxy did in {@link checkProxies}.
+ */
+export interface ProxyCheckResult {
+  proxy: string;
+  /**
+   * Whether the test URL answered with a 2xx status through the proxy.
+   */
+  ok: boolean;
+  /**
+   * Status the test URL answered with, `null` when no response arrived.
+   */
+  status: number | null;
+  /**
+   * Milliseconds until the response head arrived, connecting through the proxy included.
+   */
+  latency: number | null;
+  /**
+   * Address the test URL saw the request come from.
    */
-  method?: string;
-
+  exitIp: string | null;
   /**
-   * A Headers object, an object literal, or an array of two-item arrays to set request's headers.
+   * - "transparent": the caller's own address reached the server.
+   * - "anonymous": the address is hidden, but headers such as `Via` or `X-Forwarded-For` show a proxy.
+   * - "elite": nothing marks the request as proxied.
+   *
+   * `null` when the test URL doesn't echo headers or couldn't be reached directly.
    */
-  headers?: HeadersInit;
+  anonymity: "transparent" | "anonymous" | "elite" | null;
+  error: string | null;
+  code: ErrorCode | null;
+}
 
+/**
+ * Storage for the HTTP response cache, set with {@link setHttpCache}.
+ */
+export interface HttpCacheOptions {
   /**
-   * A BodyInit object or null to set request's body.
+   * Keep entries in memory, or as files under {@link dir} so they outlive the process.
+   * @default "memory"
    */
-  body?: BodyInit | null;
-
+  store?: "memory" | "disk";
   /**
-   * An AbortSignal to set request's signal.
+   * Directory for the disk store, created if missing. Required when `store` is `"disk"`.
    */
-  signal?: AbortSignal | null;
-
+  dir?: string;
   /**
-   * A string indicating whether request follows redirects, results in an error upon
-   * encountering a redirect, or returns the redirect (in an opaque fashion).
-   * @default 'follow'
+   * Body bytes the memory store holds before evicting the least recently used entries.
+   * @default 67108864
    */
-  redirect?: "follow" | "manual" | "error";
+  maxSize?: number;
+}
 
+/**
+ * Log output settings for {@link setTracing}.
+ */
+export interface TracingOptions {
   /**
-   * Browser profile to impersonate for this request.
-   * Automatically applies browser-specific headers, TLS fingerprints, and HTTP/2 settings.
-   * @default 'chrome_142'
+   * Most verbose level written. `info` logs one line per request; `debug` adds the
+   * connect, DNS, body-read and retry details.
+   * @default "info"
    */
-  browser?: BrowserProfile;
-
+  level?: "error" | "warn" | "info" | "debug" | "trace";
   /**
-   * Proxy URL to route the request through (e.g., 'http://proxy.example.com:8080').
-   * Supports HTTP and SOCKS5 proxies.
+   * Write one JSON object per line instead of human-readable text.
+   * @default false
    */
-  proxy?: string;
+  json?: boolean;
+}
 
+/**
+ * Settings for {@link startHarRecording}.
+ */
+export interface HarOptions {
   /**
-   * Request timeout in milliseconds. If the request takes longer than this value,
-   * it will be aborted.
-   * @default 30000
+   * Request and response bodies are cut off after this many bytes; `0` leaves them out.
+   * @default 1048576
    */
-  timeout?: number;
-
+  maxBodySize?: number;
   /**
-   * Controls how cookies are managed for this call.
-   * - "ephemeral": default when no session/sessionId is provided. Creates an isolated session per request.
-   * - "session": requires an explicit session or sessionId and reuses its cookie jar.
+   * Entries kept before the oldest are dropped.
+   * @default 10000
    */
-  cookieMode?: CookieMode;
+  maxEntries?: number;
+}
+
+/**
+ * A name/value pair in a HAR entry: a header, query parameter or cookie.
+ */
+export interface HarNameValue {
+  name: string;
+  value: string;
+}
+
+/**
+ * A cookie set by a response, as recorded in a HAR entry.
+ */
+export interface HarCookie extends HarNameValue {
+  path?: string;
+  domain?: string;
+  /** ISO 8601 expiry */
+  expires?: string;
+  httpOnly?: boolean;
+  secure?: boolean;
+}
+
+/**
+ * A request or response body. Binary bodies are base64 encoded.
+ */
+export interface HarContent {
+  /** Body length in bytes, or -1 when it wasn't captured */
+  size: number;
+  mimeType: string;
+  text?: string;
+  encoding?: "base64";
+  comment?: string;
+}
+
+/**
+ * One request and its response, following the HAR 1.2 `entries` format. Every
+ * redirect hop gets its own entry. Requests that failed have a status of `0` and
+ * the error message in `_error`.
+ */
+export interface HarEntry {
+  /** ISO 8601 time the request started */
+  startedDateTime: string;
+  /** Total milliseconds, the sum of the known `timings` */
+  time: number;
+  request: {
+    method: string;
+    url: string;
+    httpVersion: string;
+    cookies: HarNameValue[];
+    headers: HarNameValue[];
+    queryString: HarNameValue[];
+    postData?: Omit<HarContent, "size">;
+    headersSize: number;
+    bodySize: number;
+  };
+  response: {
+    status: number;
+    statusText: string;
+    httpVersion: string;
+    cookies: HarCookie[];
+    headers: HarNameValue[];
+    content: HarContent;
+    redirectURL: string;
+    headersSize: number;
+    bodySize: number;
+    _error?: string;
+    _fromCache?: boolean;
+  };
+  cache: Record<string, never>;
+  /** Phase durations in milliseconds; -1 when a phase didn't happen or isn't known */
+  timings: {
+    blocked: number;
+    dns: number;
+    connect: number;
+    ssl: number;
+    send: number;
+    wait: number;
+    receive: number;
+  };
+  /** ID of the request the entry belongs to, as on its response, events and errors */
+  _requestId: string;
+}
+
+/**
+ * A HAR 1.2 log, as returned by {@link exportHar} and {@link stopHarRecording}.
+ * It can be written to a `.har` file with `JSON.stringify` and opened in browser devtools.
+ */
+export interface Har {
+  log: {
+    version: "1.2";
+    creator: { name: string; version: string };
+    pages: [];
+    entries: HarEntry[];
+  };
+}
 
+/**
+ * Limits for the native client cache. Every session (including the throwaway
+ * ones behind ephemeral requests) holds a client with its own connection pool
+ * and TLS session cache; idle ones are evicted so they release their sockets.
+ * Evicting a session also discards its cookies.
+ */
+export interface ClientCacheOptions {
   /**
-   * Session instance to bind this request to. When provided, {@link cookieMode}
-   * automatically behaves like `"session"`.
+   * Maximum number of cached clients before the least recently used are evicted.
+   * @default 1024
    */
-  session?: SessionHandle;
-
+  maxEntries?: number;
   /**
-   * Identifier of an existing session created elsewhere (e.g., via {@link createSession}).
+   * Milliseconds a client may sit unused before it is evicted.
+   * @default 300000
    */
-  sessionId?: string;
-
+  idleTtl?: number;
   /**
-   * Disable default headers from browser emulation. When enabled, only explicitly
-   * provided headers will be sent with the request, preventing emulation headers
-   * from being automatically added or appended.
-   * @default false
+   * Milliseconds between background sweeps for expired clients.
+   * @default 60000
    */
-  disableDefaultHeaders?: boolean;
+  sweepInterval?: number;
 }
 
 /**
- * Configuration for {@link createSession}.
+ * Snapshot returned by `clientCacheStats()`.
  */
-export interface CreateSessionOptions {
+export interface ClientCacheStats {
+  /** Requests that reused a cached client */
+  hits: number;
+  /** Requests that had to build a new client, ephemeral requests included */
+  misses: number;
+  /** Clients dropped for idling past `idleTtl` or to stay under `maxEntries` */
+  evictions: number;
+  /** Clients currently cached, pinned ones included */
+  size: number;
+  /** Cached clients, most recently used first */
+  entries: ClientCacheEntry[];
+}
+
+export interface ClientCacheEntry {
+  sessionId: string;
+  browser: BrowserProfile;
+  /** Default proxy the session was created with */
+  proxy?: string;
+  /** Milliseconds since the Unix epoch when the client last served a request */
+  lastUsed: number;
+  /** Created with `pinClient`, so never evicted */
+  pinned: boolean;
+}
+
+/**
+ * Settings for the native runtime, applied by {@link configureRuntime} the next time it starts.
+ * Omitted fields keep tokio's defaults.
+ */
+export interface RuntimeOptions {
   /**
-   * Provide a custom identifier instead of an auto-generated random ID.
+   * Threads running requests. Defaults to one per CPU core, which oversubscribes small containers.
    */
-  sessionId?: string;
+  workerThreads?: number;
   /**
-   * Browser profile to bind to this session. Defaults to 'chrome_142'.
+   * Most threads kept for blocking work such as writing cookie files.
+   * @default 512
    */
-  browser?: BrowserProfile;
+  maxBlockingThreads?: number;
   /**
-   * Optional proxy for every request made through the session.
+   * Name given to every runtime thread.
+   * @default "tokio-runtime-worker"
    */
-  proxy?: string;
+  threadName?: string;
   /**
-   * Default timeout applied when {@link Session.fetch} is called without
-   * overriding `timeout`.
+   * Stack size of each thread in bytes, at least 65536.
+   * @default 2097152
    */
-  timeout?: number;
+  threadStackSize?: number;
+}
+
+/**
+ * Settings for {@link shutdown}.
+ */
+export interface ShutdownOptions {
+  /**
+   * Milliseconds to wait for requests in flight before cutting them off.
+   * @default 10000
+   */
+  graceMs?: number;
+}
+
+export interface ShutdownResult {
+  /** Tasks still running when the grace period ran out, rejected with `ERR_SHUTDOWN` */
+  cancelled: number;
 }
 
 /**
- * Standard HTTP request methods supported by wreq.
- * Represents the most commonly used HTTP verbs for RESTful operations.
+ * HTTP protocol versions that can be forced per request.
+ */
+export type HttpVersion = "1.1" | "2";
+
+/**
+ * HTTP request method. The standard verbs are listed for autocompletion, but any
+ * valid method token is accepted, e.g. WebDAV's `PROPFIND` or `REPORT`.
+ * Standard verbs are uppercased; extension methods are sent exactly as given.
  */
-export type HttpMethod = "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD";
+export type HttpMethod =
+  | "GET"
+  | "POST"
+  | "PUT"
+  | "DELETE"
+  | "PATCH"
+  | "HEAD"
+  | "OPTIONS"
+  | "TRACE"
+  | (string & Record<never, never>);
 
 /**
  * Legacy request options interface. This interface is deprecated and will be removed in a future version.
@@ -209,105 +1570,445 @@ export type HttpMethod = "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD";
  * });
  * ```
  */
-export interface RequestOptions {
+export interface RequestOptions
+  extends Omit<
+    NativeRequestOptions,
+    "method" | "json" | "proxyPool" | "ephemeral" | "removeHeaders" | "highWaterMark" | "beforeSend"
+  > {
   /**
-   * The URL to request.
+   * HTTP method to use for the request.
+   * @default 'GET'
    */
-  url: string;
+  method?: HttpMethod;
 
   /**
-   * Browser profile to impersonate.
-   * Automatically applies browser-specific headers, TLS fingerprints, and HTTP/2 settings.
-   * @default 'chrome_142'
+   * Weighted browser profiles to pick this request's profile from.
    */
-  browser?: BrowserProfile;
+  browsers?: BrowserWeights;
 
   /**
-   * HTTP method to use for the request.
-   * @default 'GET'
+   * Value to send as a JSON body. Cannot be combined with {@link body} or {@link multipart}.
    */
-  method?: HttpMethod;
+  json?: unknown;
 
   /**
-   * Additional headers to send with the request.
-   * Browser-specific headers will be automatically added based on the selected browser profile.
+   * Pool to draw the proxy from for every attempt, instead of {@link proxy}.
    */
-  headers?: Record<string, string> | HeaderTuple[];
+  proxyPool?: ProxyPoolHandle;
+}
 
+/**
+ * One response header as it arrived, from {@link Response.rawHeaders}.
+ */
+export interface ResponseHeader {
   /**
-   * Request body data (for POST, PUT, PATCH requests).
+   * Lowercase: HTTP/2 requires it, and HTTP/1 names are normalized when parsed.
    */
-  body?: string;
-
+  name: string;
   /**
-   * Proxy URL to route the request through (e.g., 'http://proxy.example.com:8080').
-   * Supports HTTP and SOCKS5 proxies.
+   * The value as text; one that isn't valid UTF-8 is decoded as Latin-1.
    */
-  proxy?: string;
+  value: string;
+  /**
+   * The value's bytes, present only when they aren't valid UTF-8.
+   */
+  bytes?: Buffer;
+}
 
+/**
+ * A cookie parsed from a single `Set-Cookie` response header.
+ */
+export interface Cookie {
+  name: string;
+  value: string;
+  domain?: string;
+  path?: string;
   /**
-   * Request timeout in milliseconds. If the request takes longer than this value,
-   * it will be aborted.
-   * @default 30000
+   * Absolute expiry from the `Expires` attribute.
    */
-  timeout?: number;
+  expires?: Date;
+  /**
+   * Lifetime in seconds from the `Max-Age` attribute.
+   */
+  maxAge?: number;
+  secure: boolean;
+  httpOnly: boolean;
+  sameSite?: "Strict" | "Lax";
+}
 
+/**
+ * One redirect followed on the way to a response.
+ */
+export interface RedirectHop {
+  /** URL that answered with the redirect */
+  url: string;
+  status: number;
+  /** The `Location` header, as sent */
+  location: string | null;
   /**
-   * Identifier for the session that should handle this request.
-   * @internal
+   * Cookies the redirect set. They are already in the session's jar; responses served
+   * from the HTTP cache report none.
    */
-  sessionId?: string;
+  setCookies: Cookie[];
+}
+
+/**
+ * Redirect hop shape produced by the native binding.
+ *
+ * @internal
+ */
+export interface NativeRedirectHop {
+  url: string;
+  status: number;
+  location?: string;
+  setCookies: NativeCookie[];
+}
+
+/**
+ * Cookie shape produced by the native binding, with `expires` as epoch milliseconds.
+ *
+ * @internal
+ */
+export interface NativeCookie extends Omit<Cookie, "expires"> {
+  expires?: number;
+}
 
+/**
+ * A cookie held in a session's jar, as returned by {@link Session.getCookies}.
+ */
+export interface StoredCookie {
+  name: string;
+  value: string;
   /**
-   * Internal flag indicating whether the session should be discarded once the
-   * request finishes.
-   * @internal
+   * Domain the cookie belongs to, lowercased and without a leading dot.
+   */
+  domain: string;
+  /**
+   * The cookie was set without a `Domain` attribute, so it is only sent to
+   * {@link domain} itself and not to its subdomains.
+   */
+  hostOnly: boolean;
+  path: string;
+  /**
+   * When the cookie expires; absent for session cookies.
    */
-  ephemeral?: boolean;
+  expires?: Date;
+  secure: boolean;
+  httpOnly: boolean;
+  sameSite?: "Strict" | "Lax" | "None";
+}
 
+/**
+ * A cookie to add with {@link Session.setCookie}. It replaces any cookie with the
+ * same name, domain and path; an `expires` in the past deletes that cookie instead.
+ */
+export interface CookieInit {
+  name: string;
+  value: string;
   /**
-   * Disable default headers from browser emulation. When enabled, only explicitly
-   * provided headers will be sent with the request, preventing emulation headers
-   * from being automatically added or appended.
+   * Domain to send the cookie to, subdomains included unless {@link hostOnly} is set.
+   */
+  domain: string;
+  /**
+   * Send the cookie to {@link domain} only, not its subdomains.
    * @default false
    */
-  disableDefaultHeaders?: boolean;
+  hostOnly?: boolean;
+  /**
+   * @default "/"
+   */
+  path?: string;
+  /**
+   * Expiry as a Date or epoch milliseconds. Omit for a session cookie.
+   */
+  expires?: Date | number;
+  secure?: boolean;
+  httpOnly?: boolean;
+  sameSite?: "Strict" | "Lax" | "None";
+}
+
+/**
+ * Selects cookies for {@link Session.deleteCookies}; every field given must match.
+ */
+export interface CookieFilter {
+  name?: string;
+  /**
+   * Matched exactly (a leading dot is ignored), so subdomain cookies are kept.
+   */
+  domain?: string;
+  path?: string;
 }
 
 /**
- * Internal response payload returned from the native Rust binding.
- * This interface represents the raw response data before it's converted
- * to a standard Response object.
+ * Stored cookie shape produced by the native binding, with `expires` as epoch milliseconds.
  *
  * @internal
  */
-export interface NativeResponse {
+export interface NativeStoredCookie extends Omit<StoredCookie, "expires"> {
+  expires?: number;
+}
+
+/**
+ * Phase durations of a request in milliseconds, for diagnosing slow proxies and endpoints.
+ * With retries, these describe the final attempt.
+ */
+export interface Timings {
   /**
-   * HTTP status code (e.g., 200, 404, 500).
+   * DNS lookup. Absent when a pooled connection was reused or the host is an IP address.
    */
-  status: number;
+  dns?: number;
+  /**
+   * TCP connect plus TLS and proxy handshakes. Absent when a pooled connection was reused.
+   */
+  connect?: number;
+  /**
+   * Time from sending the request until response headers arrived, connection setup included.
+   */
+  ttfb: number;
+  /**
+   * Time spent reading the body. Absent for streamed responses.
+   */
+  download?: number;
+  /**
+   * `ttfb + download`. Absent for streamed responses.
+   */
+  total?: number;
+}
+
+/**
+ * Aggregated request metrics returned by {@link getMetrics}, counted since the process
+ * started or the last {@link resetMetrics}. Latencies are in milliseconds.
+ */
+export interface Metrics {
+  /** Requests finished, successfully or not */
+  requests: number;
+  /** Responses by status class, e.g. `{ "2xx": 10, "4xx": 1 }` */
+  responses: Record<string, number>;
+  /** Failed requests by error code */
+  errors: Partial<Record<ErrorCode, number>>;
+  latency: {
+    count: number;
+    sum: number;
+    /** Percentiles over the most recent 4096 requests; `null` before any has finished */
+    p50: number | null;
+    p90: number | null;
+    p99: number | null;
+    /** Cumulative histogram buckets; the last, with `le: null`, counts every request */
+    buckets: { le: number | null; count: number }[];
+  };
+  /** Request body bytes, for bodies whose size is known up front (not multipart) */
+  bytesSent: number;
+  /** Response body bytes, including those streamed to disk by `download` */
+  bytesReceived: number;
+  cacheHits: number;
+  /** Attempts beyond the first */
+  retries: number;
+}
+
+/**
+ * Stage of a request reported to the listener set with {@link setEventListener}.
+ */
+export type RequestEventType =
+  | "request-start"
+  | "dns-resolved"
+  | "connected"
+  | "tls-established"
+  | "first-byte"
+  | "complete"
+  | "error";
+
+/**
+ * A request lifecycle event. Every request gets `request-start` and then either `complete`
+ * or `error`; the stages between only appear when they happen, so a reused connection
+ * reports no `dns-resolved` or `connected`.
+ */
+export interface RequestEvent {
+  type: RequestEventType;
+  /** The request's ID, as on its response, errors and HAR entry */
+  requestId: string;
+  method: string;
+  /** The URL as requested, before redirects */
+  url: string;
+  /** Milliseconds since the Unix epoch */
+  timestamp: number;
+  /** Milliseconds since `request-start` */
+  elapsed: number;
+  /** How long the stage took, for `dns-resolved`, `connected` and `tls-established` */
+  duration?: number;
+  /** Response status, for `first-byte` and `complete` */
+  status?: number;
+  /** Attempt number for `first-byte`; the number of attempts made for `complete` */
+  attempt?: number;
+  /** Body bytes received, for `complete`; absent for streamed responses */
+  bytes?: number;
+  /** Whether the response came from the HTTP cache, for `complete` */
+  fromCache?: boolean;
+  /** Error code, for `error` */
+  code?: ErrorCode;
+  /** Error message, for `error` */
+  error?: string;
+}
+
+/**
+ * The connection a response arrived on, for checking that the client cache and
+ * connection pool actually reuse TCP and TLS sessions.
+ */
+export interface ConnectionInfo {
+  /**
+   * Whether the request went out on a pooled connection. A request that opened any
+   * new connection, e.g. to follow a redirect to another host, is not reused.
+   */
+  reused: boolean;
+  /** Local `ip:port` of the socket, or `null` when unknown */
+  localAddress: string | null;
+  /** Remote `ip:port` of the socket; a proxy's address when going through one */
+  remoteAddress: string | null;
+  /**
+   * What the pool files the connection under: session ID, origin, proxy (without
+   * credentials) and source address or interface. Only requests with the same key
+   * can share connections.
+   */
+  poolKey: string;
+}
+
+/**
+ * Connection details produced by the native binding.
+ *
+ * @internal
+ */
+export interface NativeConnectionInfo {
+  reused: boolean;
+  localAddress?: string;
+  remoteAddress?: string;
+  poolKey: string;
+}
 
+/**
+ * A request as the native client is about to send it, as seen by {@link Middleware.onSend}.
+ * `headers` is in wire order and already holds the browser profile's defaults and the
+ * session's cookies. Hooks may change any field in place or return a replacement.
+ */
+export interface MiddlewareSend {
+  url: string;
+  method: string;
+  headers: HeaderTuple[];
+}
+
+/**
+ * A request as it would be sent, returned by `fetch()` with `dryRun`.
+ */
+export interface RequestPreview {
+  method: string;
+  url: string;
+  /** Path and query, as written in the request line */
+  target: string;
   /**
-   * Response headers as key-value pairs.
-   * Header names are normalized to lowercase.
+   * Headers in the order they would go out. `Host` leads, as over HTTP/1.1; HTTP/2
+   * sends it as `:authority` and lowercases the rest. Challenge auth and SigV4
+   * signatures are added when sending, so they aren't included.
    */
-  headers: Record<string, string>;
+  headers: HeaderTuple[];
+  /** Body size in bytes, or `null` when there is no body or it's only known once sent */
+  bodyLength: number | null;
+  /** The request head as HTTP/1.1 text */
+  raw: string;
+}
+
+/**
+ * Request preview produced by the native binding.
+ *
+ * @internal
+ */
+export interface NativeRequestPreview {
+  method: string;
+  url: string;
+  target: string;
+  headers: HeaderTuple[];
+  bodyLength?: number;
+  raw: string;
+}
 
+/**
+ * The leaf certificate a server presented, for certificate pinning and auditing.
+ */
+export interface TlsInfo {
+  /**
+   * Subject distinguished name, e.g. `CN=example.com, O=Example Inc`.
+   */
+  subject: string;
+  /**
+   * Issuer distinguished name, in the same format as {@link subject}.
+   */
+  issuer: string;
+  /**
+   * DNS names and IP addresses from the subjectAltName extension.
+   */
+  subjectAltNames: string[];
+  notBefore: Date;
+  notAfter: Date;
   /**
-   * Response body as a UTF-8 encoded string.
+   * SHA-256 of the DER certificate as colon-separated uppercase hex, matching
+   * Node's `fingerprint256`.
    */
-  body: string;
+  fingerprint256: string;
+}
+
+/**
+ * Certificate details produced by the native binding, with validity bounds as epoch milliseconds.
+ *
+ * @internal
+ */
+export interface NativeTlsInfo extends Omit<TlsInfo, "notBefore" | "notAfter"> {
+  notBefore: number;
+  notAfter: number;
+}
 
+/**
+ * What a connection presents to fingerprinting services. It is worked out from the
+ * TLS and HTTP/2 settings the client was built with, so BoringSSL's per-connection
+ * extras (padding, and pre_shared_key on resumed sessions) are not part of it.
+ */
+export interface ConnectionFingerprint {
+  /**
+   * Full JA3 string. `null` over plain HTTP, and for profiles that shuffle their TLS
+   * extensions on every connection, as recent Chrome does; compare {@link ja4} instead.
+   */
+  ja3: string | null;
+  /**
+   * MD5 of {@link ja3}, the form most services print.
+   */
+  ja3Hash: string | null;
+  /**
+   * Hashed JA4, e.g. `t13d1516h2_8daaf6152771_d8a2da3f94cd`; `null` over plain HTTP.
+   */
+  ja4: string | null;
+  /**
+   * Protocol the server selected, `null` over plain HTTP.
+   */
+  alpn: "h2" | "http/1.1" | null;
   /**
-   * Cookies set by the server as key-value pairs.
+   * The HTTP/2 connection preface, `null` unless the response came over HTTP/2.
    */
-  cookies: Record<string, string>;
+  http2: Http2Fingerprint | null;
+}
 
+export interface Http2Fingerprint {
   /**
-   * Final URL after following any redirects.
-   * If no redirects occurred, this will match the original request URL.
+   * SETTINGS parameters as `[id, value]`, in the order they are sent.
    */
-  url: string;
+  settings: Array<[number, number]>;
+  /**
+   * Increment of the connection-level WINDOW_UPDATE frame, `0` when none is sent.
+   */
+  windowUpdate: number;
+  /**
+   * Pseudo-header order as Akamai abbreviates it, e.g. `["m", "a", "s", "p"]`.
+   */
+  pseudoHeaderOrder: string[];
+  /**
+   * Akamai HTTP/2 fingerprint: `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADER_ORDER`.
+   */
+  akamai: string;
 }
 
 /**
@@ -345,6 +2046,11 @@ export interface WebSocketOptions {
    */
   browser?: BrowserProfile;
 
+  /**
+   * Operating system the browser profile presents as for the upgrade request.
+   */
+  emulationOs?: EmulationOs;
+
   /**
    * Additional headers to send with the WebSocket upgrade request.
    * Common headers include Authorization, Origin, or custom application headers.
@@ -353,7 +2059,8 @@ export interface WebSocketOptions {
 
   /**
    * Proxy URL to route the connection through (e.g., 'http://proxy.example.com:8080').
-   * Supports HTTP and SOCKS5 proxies.
+   * Supports `http://`, `https://`, `socks4://`, `socks4a://`, `socks5://` and
+   * `socks5h://` (hostname resolved by the proxy).
    */
   proxy?: string;
 
@@ -397,22 +2104,35 @@ export interface NativeWebSocketConnection {
 
 /**
  * Error thrown when a request fails. This can occur due to network errors,
- * timeouts, invalid URLs, or other request-related issues.
+ * timeouts, invalid URLs, or other request-related issues. Use `code` to
+ * branch on the failure class rather than matching the message.
+ *
+ * Requests cancelled through an `AbortSignal` reject with an `AbortError`
+ * instead, as with the standard `fetch`.
  *
  * @example
  * ```typescript
  * try {
  *   const response = await fetch('https://api.example.com');
  * } catch (error) {
- *   if (error instanceof RequestError) {
- *     console.error('Request failed:', error.message);
+ *   if (error instanceof RequestError && error.code === 'ERR_TIMEOUT') {
+ *     console.error('Request timed out:', error.message);
  *   }
  * }
  * ```
  */
 export class RequestError extends Error {
-  constructor(message: string) {
+  readonly code: ErrorCode;
+  /**
+   * ID of the request that failed, matching its log span, events and HAR entry; `undefined`
+   * for errors raised before the request was built, such as invalid options.
+   */
+  readonly requestId: string | undefined;
+
+  constructor(message: string, code: ErrorCode = "ERR_REQUEST", requestId?: string) {
     super(message);
     this.name = "RequestError";
+    this.code = code;
+    this.requestId = requestId;
   }
 }